@@ -0,0 +1,450 @@
+use super::super::lex::token::Span;
+use super::super::parse::ast::*;
+use super::super::parse::visit::{ walk_expression, Visitor };
+use std::collections::HashMap;
+
+pub type NodeId = usize;
+
+/// A local variable, one per declaration
+pub type VarId = usize;
+
+/// What a node does to locals, in evaluation order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A `let`, which leaves the variable unassigned without an initializer
+    Declare(VarId, bool),
+    Read(VarId),
+    Write(VarId),
+}
+
+/// A statement, or the condition or step of a `for`. For `if` and `while` statements the node also
+/// evaluates the condition, for `for` the initializer and for a for-each the iterable. \
+/// A for-each has a second node that takes the next element, a `try` with a `catch` a second node that
+/// binds the caught error. The entry and exit have no statement
+#[derive(Debug)]
+pub struct Node<'a> {
+    pub statement: Option<&'a Statement>,
+    pub span: Span,
+    pub succs: Vec<NodeId>,
+    pub events: Vec<Event>,
+    /// The statement before this one in the same block
+    pub previous: Option<NodeId>,
+}
+
+/// Statement level control flow graph of one function body. \
+/// Nested function declarations are nodes of their own with no effect, and get graphs of their own
+#[derive(Debug)]
+pub struct Cfg<'a> {
+    pub nodes: Vec<Node<'a>>,
+    /// Names of the locals, indexed by `VarId`
+    pub vars: Vec<String>,
+    /// `break` and `continue` statements outside of any loop, which lead nowhere
+    pub stray: Vec<NodeId>,
+    /// Nodes that fall off the end of the body into the exit
+    pub fallthrough: Vec<NodeId>,
+    /// Functions declared in the body, with where they are declared
+    pub functions: Vec<(&'a Declaration, Span)>,
+}
+
+impl<'a> Cfg<'a> {
+    pub const ENTRY: NodeId = 0;
+    pub const EXIT: NodeId = 1;
+
+    pub fn build(arguments: &[Identifier], body: &'a Block, span: Span) -> Self {
+        let mut builder = Builder {
+            cfg: Cfg {
+                nodes: vec![],
+                vars: vec![],
+                stray: vec![],
+                fallthrough: vec![],
+                functions: vec![],
+            },
+            scopes: vec![HashMap::new()],
+            loops: vec![],
+            tries: 0,
+        };
+
+        builder.node(None, span);
+        builder.node(None, span);
+
+        for arg in arguments {
+            let var = builder.declare(arg.name());
+            builder.cfg.nodes[Self::ENTRY].events.push(Event::Declare(var, true));
+        }
+
+        let outs = builder.block(body, vec![Self::ENTRY], span);
+        builder.link(&outs, Self::EXIT);
+        builder.cfg.fallthrough = outs;
+
+        builder.cfg
+    }
+
+    /// Which nodes can be reached from the entry
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut worklist = vec![Self::ENTRY];
+
+        while let Some(n) = worklist.pop() {
+            if !seen[n] {
+                seen[n] = true;
+                worklist.extend(&self.nodes[n].succs);
+            }
+        }
+
+        seen
+    }
+
+    pub fn predecessors(&self) -> Vec<Vec<NodeId>> {
+        let mut preds = vec![vec![]; self.nodes.len()];
+
+        for (n, node) in self.nodes.iter().enumerate() {
+            for succ in &node.succs {
+                preds[*succ].push(n);
+            }
+        }
+
+        preds
+    }
+}
+
+/// Nodes jumping to the next iteration and out of a loop, linked once the loop is done
+struct Loop {
+    continues: Vec<NodeId>,
+    breaks: Vec<NodeId>,
+}
+
+struct Builder<'a> {
+    cfg: Cfg<'a>,
+    scopes: Vec<HashMap<String, VarId>>,
+    loops: Vec<Loop>,
+    /// How many `try` bodies the current statement is in, anything in them may throw to their handler
+    tries: usize,
+}
+
+impl<'a> Builder<'a> {
+    fn node(&mut self, statement: Option<&'a Statement>, span: Span) -> NodeId {
+        self.cfg.nodes.push(Node { statement, span, succs: vec![], events: vec![], previous: None });
+        self.cfg.nodes.len() - 1
+    }
+
+    fn link(&mut self, from: &[NodeId], to: NodeId) {
+        for n in from {
+            self.cfg.nodes[*n].succs.push(to);
+        }
+    }
+
+    fn declare(&mut self, name: String) -> VarId {
+        self.cfg.vars.push(name.clone());
+        let var = self.cfg.vars.len() - 1;
+        self.scopes.last_mut().unwrap().insert(name, var);
+        var
+    }
+
+    /// Links the statements of `block` in sequence after `preds`, returning the nodes that fall out of it
+    fn block(&mut self, block: &'a Block, mut preds: Vec<NodeId>, span: Span) -> Vec<NodeId> {
+        self.scopes.push(HashMap::new());
+
+        let mut previous = None;
+
+        for (i, stmt) in block.statements().iter().enumerate() {
+            let span = block.spans().get(i).copied().unwrap_or(span);
+            let head = self.cfg.nodes.len();
+
+            preds = self.statement(stmt, span, preds);
+            self.cfg.nodes[head].previous = previous;
+            previous = Some(head);
+        }
+
+        self.scopes.pop();
+        preds
+    }
+
+    /// Adds `stmt` after `preds`. Its first node is always the next one created
+    fn statement(&mut self, stmt: &'a Statement, span: Span, preds: Vec<NodeId>) -> Vec<NodeId> {
+        let head = self.node(Some(stmt), span);
+        self.link(&preds, head);
+
+        match stmt {
+            Statement::Expression(_) | Statement::Declaration(Declaration::Variable { .. } | Declaration::Destructure { .. }) => {
+                self.simple(head, stmt);
+                vec![head]
+            },
+            Statement::Declaration(decl) => {
+                self.cfg.functions.push((decl, span));
+                vec![head]
+            },
+            Statement::Return(expr) => {
+                self.expression(head, expr);
+                self.link(&[head], Cfg::EXIT);
+                vec![]
+            },
+            Statement::Assert { condition, message } => {
+                self.expression(head, condition);
+
+                if let Some(message) = message {
+                    self.expression(head, message);
+                }
+                vec![head]
+            },
+            Statement::Throw(expr) => {
+                self.expression(head, expr);
+
+                // Inside a `try` the handler is linked once the body is done
+                if self.tries == 0 {
+                    self.link(&[head], Cfg::EXIT);
+                }
+                vec![]
+            },
+            Statement::Try { body, catch, finally } => self.try_stmt(head, stmt, span, body, catch.as_ref(), finally.as_ref()),
+            Statement::Break => {
+                match self.loops.last_mut() {
+                    Some(l) => l.breaks.push(head),
+                    None => self.cfg.stray.push(head),
+                }
+                vec![]
+            },
+            Statement::Continue => {
+                match self.loops.last_mut() {
+                    Some(l) => l.continues.push(head),
+                    None => self.cfg.stray.push(head),
+                }
+                vec![]
+            },
+            Statement::Block(block) | Statement::Else { body: block } => self.block(block, vec![head], span),
+            Statement::If { condition, body, else_stmt } => {
+                self.expression(head, condition);
+
+                let mut outs = self.block(body, vec![head], span);
+
+                match else_stmt {
+                    Some(stmt) => outs.extend(self.statement(stmt, span, vec![head])),
+                    None => outs.push(head),
+                }
+
+                outs
+            },
+            Statement::While { condition, body } => {
+                self.expression(head, condition);
+
+                self.loops.push(Loop { continues: vec![], breaks: vec![] });
+                let outs = self.block(body, vec![head], span);
+                let l = self.loops.pop().unwrap();
+
+                self.link(&outs, head);
+                self.link(&l.continues, head);
+
+                let mut exits = l.breaks;
+                if !always_true(Some(condition)) {
+                    exits.push(head);
+                }
+                exits
+            },
+            Statement::For { variable, condition, step, body } => {
+                self.scopes.push(HashMap::new());
+
+                if let Some(stmt) = variable {
+                    self.simple(head, stmt);
+                }
+
+                let cond = self.node(Some(stmt), span);
+                self.link(&[head], cond);
+
+                if let Some(condition) = condition {
+                    self.expression(cond, condition);
+                }
+
+                self.loops.push(Loop { continues: vec![], breaks: vec![] });
+                let outs = self.block(body, vec![cond], span);
+                let l = self.loops.pop().unwrap();
+
+                let next = self.node(Some(stmt), span);
+                self.link(&outs, next);
+                self.link(&l.continues, next);
+                self.link(&[next], cond);
+
+                if let Some(step) = step {
+                    self.expression(next, step);
+                }
+
+                self.scopes.pop();
+
+                let mut exits = l.breaks;
+                if !always_true(condition.as_ref()) {
+                    exits.push(cond);
+                }
+                exits
+            },
+            Statement::ForEach { variable, iterable, body } => {
+                self.expression(head, iterable);
+                self.scopes.push(HashMap::new());
+
+                let next = self.node(Some(stmt), span);
+                self.link(&[head], next);
+
+                let var = self.declare(variable.name());
+                self.cfg.nodes[next].events.push(Event::Declare(var, true));
+
+                self.loops.push(Loop { continues: vec![], breaks: vec![] });
+                let outs = self.block(body, vec![next], span);
+                let l = self.loops.pop().unwrap();
+
+                self.link(&outs, next);
+                self.link(&l.continues, next);
+
+                self.scopes.pop();
+
+                let mut exits = l.breaks;
+                exits.push(next);
+                exits
+            },
+        }
+    }
+
+    /// Every node of the `try` body, from `head` on, may throw to the `catch` node. \
+    /// The `finally` follows what completes normally. Only when nothing does does it follow what throws,
+    /// so its effects on the way out of a throw don't leak into the code after the `try`
+    fn try_stmt(&mut self, head: NodeId, stmt: &'a Statement, span: Span, body: &'a Block,
+                catch: Option<&'a (Identifier, Block)>, finally: Option<&'a Block>) -> Vec<NodeId> {
+        self.tries += 1;
+        let mut outs = self.block(body, vec![head], span);
+        self.tries -= 1;
+
+        let mut thrown: Vec<NodeId> = (head..self.cfg.nodes.len()).collect();
+
+        if let Some((identifier, block)) = catch {
+            let node = self.node(Some(stmt), span);
+            self.link(&thrown, node);
+
+            self.scopes.push(HashMap::new());
+            let var = self.declare(identifier.name());
+            self.cfg.nodes[node].events.push(Event::Declare(var, true));
+
+            self.tries += finally.is_some() as usize;
+            outs.extend(self.block(block, vec![node], span));
+            self.tries -= finally.is_some() as usize;
+
+            self.scopes.pop();
+
+            thrown = (node..self.cfg.nodes.len()).collect();
+        }
+
+        let finally = match finally {
+            Some(finally) => finally,
+            None => return outs,
+        };
+
+        match outs.is_empty() {
+            true => {
+                let outs = self.block(finally, thrown, span);
+                self.link(&outs, Cfg::EXIT);
+                vec![]
+            },
+            false => self.block(finally, outs, span),
+        }
+    }
+
+    /// Records the effects of an expression statement or variable declaration on `node`
+    fn simple(&mut self, node: NodeId, stmt: &'a Statement) {
+        match stmt {
+            Statement::Expression(expr) => self.expression(node, expr),
+            Statement::Declaration(Declaration::Variable { identifier, value }) => {
+                if let Some(value) = value {
+                    self.expression(node, value);
+                }
+
+                let var = self.declare(identifier.name());
+                self.cfg.nodes[node].events.push(Event::Declare(var, value.is_some()));
+            },
+            Statement::Declaration(Declaration::Destructure { pattern, value }) => {
+                self.expression(node, value);
+
+                for identifier in pattern.identifiers() {
+                    let var = self.declare(identifier.name());
+                    self.cfg.nodes[node].events.push(Event::Declare(var, true));
+                }
+            },
+            _ => (),
+        }
+    }
+
+    fn expression(&mut self, node: NodeId, expr: &Expression) {
+        let mut events = Events { scopes: &self.scopes, events: &mut self.cfg.nodes[node].events, optional: 0 };
+        events.visit_expression(expr);
+    }
+}
+
+/// Collects the reads and writes of locals in an expression
+struct Events<'b> {
+    scopes: &'b [HashMap<String, VarId>],
+    events: &'b mut Vec<Event>,
+    /// How many enclosing subexpressions might be skipped, their writes don't assign for sure
+    optional: usize,
+}
+
+impl Events<'_> {
+    fn lookup(&self, name: &str) -> Option<VarId> {
+        self.scopes.iter().rev().find_map(|s| s.get(name)).copied()
+    }
+
+    fn write(&mut self, target: &Expression) {
+        if self.optional > 0 {
+            return
+        }
+
+        if let Expression::Value(identifier) = target {
+            if let Some(var) = self.lookup(&identifier.name()) {
+                self.events.push(Event::Write(var));
+            }
+        }
+    }
+
+    fn maybe(&mut self, exprs: &[&Expression]) {
+        self.optional += 1;
+        exprs.iter().for_each(|e| self.visit_expression(e));
+        self.optional -= 1;
+    }
+}
+
+impl Visitor for Events<'_> {
+    fn visit_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Value(identifier) => {
+                if let Some(var) = self.lookup(&identifier.name()) {
+                    self.events.push(Event::Read(var));
+                }
+            },
+            // A compound assignment reads its target before the right hand side
+            Expression::Assignment { lhs, operation, rhs } if matches!(lhs.as_ref(), Expression::Value(_)) => {
+                if operation.is_some() {
+                    self.visit_expression(lhs);
+                }
+
+                self.visit_expression(rhs);
+                self.write(lhs);
+            },
+            Expression::Postfix { operand, .. } if matches!(operand.as_ref(), Expression::Value(_)) => {
+                self.visit_expression(operand);
+                self.write(operand);
+            },
+            Expression::Conditional { condition, then, otherwise } => {
+                self.visit_expression(condition);
+                self.maybe(&[then, otherwise]);
+            },
+            Expression::Logical { lhs: target, rhs: property, .. } | Expression::Coalesce { lhs: target, rhs: property } |
+            Expression::OptionalMember { target, property } => {
+                self.visit_expression(target);
+                self.maybe(&[property]);
+            },
+            Expression::OptionalCall { target, args } => {
+                self.visit_expression(target);
+                self.maybe(&args.iter().flatten().collect::<Vec<_>>());
+            },
+            expr => walk_expression(self, expr),
+        }
+    }
+}
+
+/// A missing `for` condition or a literal `true` keeps a loop going until it breaks
+fn always_true(condition: Option<&Expression>) -> bool {
+    matches!(condition, None | Some(Expression::Literal(Literal::Boolean(true))))
+}
\ No newline at end of file
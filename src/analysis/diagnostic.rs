@@ -0,0 +1,49 @@
+use super::super::lex::token::Span;
+use std::fmt::{ Display, Result, Formatter };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found in a program, pointing at the statement it's about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+    /// The lint rule that produced it, if any
+    pub rule: Option<&'static str>,
+}
+
+impl Diagnostic {
+    pub fn warning(span: Span, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, span, message: message.into(), rule: None }
+    }
+
+    pub fn error(span: Span, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, span, message: message.into(), rule: None }
+    }
+}
+
+/// `line:column: severity: message [rule]`, with a 1-based column
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}:{}: {}: {}", self.span.line, self.span.column + 1, self.severity, self.message)?;
+
+        match self.rule {
+            Some(rule) => write!(f, " [{}]", rule),
+            None => Ok(()),
+        }
+    }
+}
\ No newline at end of file
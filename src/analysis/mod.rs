@@ -0,0 +1,204 @@
+pub mod cfg;
+mod diagnostic;
+
+pub use diagnostic::{ Diagnostic, Severity };
+
+use super::parse::ast::*;
+use super::parse::visit::{ walk_declaration, Visitor };
+use super::lex::token::Span;
+use cfg::{ Cfg, Event };
+
+/// Builds the control flow graph of every function and test and reports: \
+/// code that can never run, functions that return a value on some paths but fall off the end on others,
+/// `break` and `continue` outside of loops, and locals that may be read before they are assigned. \
+/// Also rejects destructuring patterns that can't match the literal they're given
+pub fn check(ast: &AST) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    for (decl, span) in ast.program().iter().zip(ast.spans()) {
+        if let Declaration::Function { .. } = decl {
+            function(decl, *span, &mut diagnostics);
+        }
+    }
+
+    for test in ast.tests() {
+        let cfg = Cfg::build(&[], &test.body, test.span);
+        flow(&cfg, &mut diagnostics);
+    }
+
+    Patterns { diagnostics: &mut diagnostics }.visit_ast(ast);
+
+    diagnostics.sort_by_key(|d| (d.span.line, d.span.column));
+    diagnostics
+}
+
+fn function(decl: &Declaration, span: Span, diagnostics: &mut Vec<Diagnostic>) {
+    let Declaration::Function { identifier, arguments, body } = decl else { return };
+
+    let cfg = Cfg::build(arguments.as_deref().unwrap_or_default(), body, span);
+    let reachable = cfg.reachable();
+
+    let returns = cfg.nodes.iter().enumerate()
+        .any(|(n, node)| reachable[n] && matches!(node.statement, Some(Statement::Return(_))));
+
+    if returns && cfg.fallthrough.iter().any(|n| reachable[*n]) {
+        diagnostics.push(Diagnostic::warning(span,
+            format!("function '{}' does not return a value on every path", identifier.name())));
+    }
+
+    flow(&cfg, diagnostics);
+}
+
+/// The checks shared by functions and tests
+fn flow(cfg: &Cfg, diagnostics: &mut Vec<Diagnostic>) {
+    let reachable = cfg.reachable();
+
+    unreachable_code(cfg, &reachable, diagnostics);
+
+    for node in &cfg.stray {
+        let keyword = if let Some(Statement::Break) = cfg.nodes[*node].statement { "break" } else { "continue" };
+        diagnostics.push(Diagnostic::error(cfg.nodes[*node].span, format!("'{}' used outside of a loop", keyword)));
+    }
+
+    read_before_assignment(cfg, &reachable, diagnostics);
+
+    for (decl, span) in &cfg.functions {
+        function(decl, *span, diagnostics);
+    }
+}
+
+/// Reports the first statement of every unreachable run whose previous statement can be reached
+fn unreachable_code(cfg: &Cfg, reachable: &[bool], diagnostics: &mut Vec<Diagnostic>) {
+    for (n, node) in cfg.nodes.iter().enumerate() {
+        let previous = match node.previous {
+            Some(previous) if !reachable[n] && reachable[previous] => previous,
+            _ => continue,
+        };
+
+        let message = match cfg.nodes[previous].statement {
+            Some(Statement::Return(_)) => "unreachable code after 'return'",
+            Some(Statement::Break) => "unreachable code after 'break'",
+            Some(Statement::Continue) => "unreachable code after 'continue'",
+            Some(Statement::Throw(_)) => "unreachable code after 'throw'",
+            _ => "unreachable code",
+        };
+
+        diagnostics.push(Diagnostic::warning(node.span, message));
+    }
+}
+
+/// Tracks the locals that may still be unassigned on entry to each node until nothing changes,
+/// then reports each local read while it may be unassigned, once
+fn read_before_assignment(cfg: &Cfg, reachable: &[bool], diagnostics: &mut Vec<Diagnostic>) {
+    let preds = cfg.predecessors();
+    let mut outs = vec![vec![false; cfg.vars.len()]; cfg.nodes.len()];
+
+    let transfer = |set: &mut Vec<bool>, events: &[Event], mut read: Option<&mut dyn FnMut(usize)>| {
+        for event in events {
+            match *event {
+                Event::Declare(var, assigned) => set[var] = !assigned,
+                Event::Write(var) => set[var] = false,
+                Event::Read(var) if set[var] => if let Some(read) = read.as_mut() { read(var) },
+                Event::Read(_) => (),
+            }
+        }
+    };
+
+    let input = |outs: &[Vec<bool>], n: usize| {
+        let mut set = vec![false; cfg.vars.len()];
+        for pred in &preds[n] {
+            for (var, unassigned) in outs[*pred].iter().enumerate() {
+                set[var] |= unassigned;
+            }
+        }
+        set
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for n in (0..cfg.nodes.len()).filter(|n| reachable[*n]) {
+            let mut set = input(&outs, n);
+            transfer(&mut set, &cfg.nodes[n].events, None);
+
+            if set != outs[n] {
+                outs[n] = set;
+                changed = true;
+            }
+        }
+    }
+
+    let mut reported = vec![false; cfg.vars.len()];
+
+    for n in (0..cfg.nodes.len()).filter(|n| reachable[*n]) {
+        let mut set = input(&outs, n);
+        let mut read = |var: usize| {
+            if !reported[var] {
+                reported[var] = true;
+                diagnostics.push(Diagnostic::warning(cfg.nodes[n].span,
+                    format!("variable '{}' may be read before it is assigned", cfg.vars[var])));
+            }
+        };
+
+        transfer(&mut set, &cfg.nodes[n].events, Some(&mut read));
+    }
+}
+
+/// Finds destructuring declarations whose value is a literal of the wrong shape
+struct Patterns<'d> {
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl Visitor for Patterns<'_> {
+    fn visit_declaration(&mut self, decl: &Declaration, span: Span) {
+        if let Declaration::Destructure { pattern, value } = decl {
+            if let Some((got, expected)) = mismatch(pattern, value) {
+                self.diagnostics.push(Diagnostic::error(span,
+                    format!("pattern can never match, it destructures {} as {}", got, expected)));
+            }
+        }
+
+        walk_declaration(self, decl, span)
+    }
+}
+
+/// What a literal `value` is and what `pattern` expects instead, if they can't match. \
+/// Only values whose shape is known without running anything are compared
+fn mismatch(pattern: &Pattern, value: &Expression) -> Option<(String, String)> {
+    let (patterns, tuple) = match pattern {
+        Pattern::Identifier(_) => return None,
+        Pattern::Tuple(patterns) => (patterns, true),
+        Pattern::Array(patterns) => (patterns, false),
+    };
+
+    let (elements, is_tuple) = match value {
+        Expression::Tuple(elements) => (elements, true),
+        Expression::Array { elements, .. } if !elements.iter().any(|e| matches!(e, Expression::Spread(_))) =>
+            (elements, false),
+        Expression::Literal(literal) => return Some((literal_type(literal).to_string(), pattern.shape()?)),
+        Expression::Range { .. } => return Some((String::from("Range"), pattern.shape()?)),
+        Expression::Map(_) => return Some((String::from("Map"), pattern.shape()?)),
+        Expression::Set(_) => return Some((String::from("Set"), pattern.shape()?)),
+        Expression::Comprehension { .. } if tuple => return Some((String::from("an array"), pattern.shape()?)),
+        _ => return None,
+    };
+
+    if is_tuple != tuple || elements.len() != patterns.len() {
+        let kind = if is_tuple { "a tuple" } else { "an array" };
+        return Some((format!("{} of {} elements", kind, elements.len()), pattern.shape()?))
+    }
+
+    patterns.iter().zip(elements).find_map(|(p, e)| mismatch(p, e))
+}
+
+fn literal_type(literal: &Literal) -> &'static str {
+    match literal {
+        Literal::String(_) => "String",
+        Literal::Integer(_) => "Integer",
+        Literal::Decimal(_) => "Decimal",
+        Literal::Character(_) => "Character",
+        Literal::Boolean(_) => "Boolean",
+        Literal::Null => "Null",
+    }
+}
\ No newline at end of file
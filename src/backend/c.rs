@@ -0,0 +1,862 @@
+use super::super::lex::token::Token;
+use super::super::parse::ast::*;
+use super::super::parse::visit::{ walk_expression, Visitor };
+use super::super::stdlib::{ self, Builtin };
+use super::{ builtin, place };
+use super::error::BackendError;
+use std::collections::{ BTreeMap, HashMap, HashSet };
+use std::fmt::Write;
+
+type GenResult = Result<(), BackendError>;
+type ExprResult = Result<String, BackendError>;
+
+/// The runtime header every generated file includes
+pub const RUNTIME_HEADER: &str = include_str!("runtime/ult_runtime.h");
+pub const RUNTIME_HEADER_NAME: &str = "ult_runtime.h";
+
+/// Translates an `AST` into a C99 source file, with GNU statement expressions for comprehensions. \
+/// Values are the tagged union `ult_value` from the runtime header, so the output behaves
+/// like the VM. Operands are evaluated in whatever order the C compiler picks
+pub fn generate(ast: &AST) -> Result<String, BackendError> {
+    let mut gen = Generator::new();
+
+    gen.program(ast)?;
+
+    Ok(gen.finish())
+}
+
+const INDENT: &str = "    ";
+
+/// Names that can't be used verbatim in the generated C
+const RESERVED: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else", "enum",
+    "extern", "float", "for", "goto", "if", "inline", "int", "long", "register", "restrict", "return",
+    "short", "signed", "sizeof", "static", "struct", "switch", "typedef", "union", "unsigned", "void",
+    "volatile", "while", "_Bool", "_Complex", "_Imaginary", "main", "NULL", "abs", "exit", "free",
+    "malloc", "realloc", "calloc", "printf", "puts", "putchar", "fputs", "fputc", "stdin", "stdout",
+    "stderr", "pow", "sqrt", "fmod", "floor", "ceil", "sin", "cos", "tan", "log", "exp", "round",
+    "strlen", "strcmp", "strcpy", "memcpy", "memset", "isnan", "isinf", "snprintf", "strtod",
+];
+
+#[derive(Clone)]
+enum Binding {
+    Variable(String),
+    Function(String, usize),
+}
+
+struct Context {
+    scopes: Vec<HashMap<String, Binding>>,
+    /// C names already declared in this function
+    used: HashSet<String>,
+    code: String,
+    indent: usize,
+    loops: usize,
+}
+
+impl Context {
+    fn new() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            used: HashSet::new(),
+            code: String::new(),
+            indent: 1,
+            loops: 0,
+        }
+    }
+}
+
+struct Generator {
+    globals: HashMap<String, Binding>,
+    /// C names taken at file scope
+    file_names: HashSet<String>,
+    global_decls: String,
+    prototypes: String,
+    functions: String,
+    /// Functions referenced as values, by C name, with their arity and Ult name
+    function_values: BTreeMap<String, (usize, String)>,
+    contexts: Vec<Context>,
+    main: Option<String>,
+}
+
+impl Generator {
+    fn new() -> Self {
+        Self {
+            globals: HashMap::new(),
+            file_names: HashSet::new(),
+            global_decls: String::new(),
+            prototypes: String::new(),
+            functions: String::new(),
+            function_values: BTreeMap::new(),
+            contexts: vec![],
+            main: None,
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "/* Generated by ult */").unwrap();
+        writeln!(out, "#include \"{}\"", RUNTIME_HEADER_NAME).unwrap();
+
+        if !self.global_decls.is_empty() {
+            writeln!(out, "\n/* Globals */\n{}", self.global_decls.trim_end()).unwrap();
+        }
+
+        if !self.prototypes.is_empty() {
+            writeln!(out, "\n/* Functions */\n{}", self.prototypes.trim_end()).unwrap();
+        }
+
+        for (cname, (arity, name)) in &self.function_values {
+            let args = (0..*arity).map(|i| format!("args[{}]", i)).collect::<Vec<_>>().join(", ");
+            let unused = if *arity == 0 { "\n    (void)args;" } else { "" };
+
+            writeln!(out, "\nstatic ult_value {}__dyn(const ult_value *args) {{{}\n    return {}({});\n}}",
+                cname, unused, cname, args).unwrap();
+            writeln!(out, "static const ult_function {}__fn = {{ {}__dyn, {}, \"{}\" }};", cname, cname, arity, name).unwrap();
+        }
+
+        out.push_str(&self.functions);
+
+        writeln!(out, "\nint main(void) {{\n    ult_init();").unwrap();
+        match &self.main {
+            Some(main) => writeln!(out, "    return ult_exit_code({}());", main).unwrap(),
+            None => writeln!(out, "    return 0;").unwrap(),
+        }
+        writeln!(out, "}}").unwrap();
+
+        out
+    }
+
+    /// A C name for `name` that doesn't collide with anything in `taken`
+    fn fresh(name: &str, taken: &mut HashSet<String>) -> String {
+        // Names the parser makes up start with '@'
+        let mut base = name.replace('@', "_");
+        if RESERVED.contains(&name) || name.starts_with("ult_") {
+            base.push('_');
+        }
+
+        let mut cname = base.clone();
+        let mut n = 0;
+
+        while taken.contains(&cname) {
+            n += 1;
+            cname = format!("{}_{}", base, n);
+        }
+
+        taken.insert(cname.clone());
+        cname
+    }
+
+    fn program(&mut self, ast: &AST) -> GenResult {
+        let mut cnames = vec![];
+
+        // File scope names first so functions can refer to later declarations
+        for decl in ast.program() {
+            match decl {
+                Declaration::Variable { identifier, .. } => {
+                    let cname = Self::fresh(&identifier.name(), &mut self.file_names);
+                    writeln!(self.global_decls, "static ult_value {};", cname).unwrap();
+                    self.globals.insert(identifier.name(), Binding::Variable(cname.clone()));
+                    cnames.push(cname);
+                },
+                Declaration::Destructure { pattern, .. } => {
+                    for identifier in pattern.identifiers() {
+                        let cname = Self::fresh(&identifier.name(), &mut self.file_names);
+                        writeln!(self.global_decls, "static ult_value {};", cname).unwrap();
+                        self.globals.insert(identifier.name(), Binding::Variable(cname));
+                    }
+                    cnames.push(String::new());
+                },
+                Declaration::Function { identifier, arguments, .. } => {
+                    let cname = Self::fresh(&identifier.name(), &mut self.file_names);
+                    let arity = arguments.as_ref().map_or(0, Vec::len);
+
+                    if identifier.name() == "main" && arity == 0 {
+                        self.main = Some(cname.clone());
+                    }
+
+                    self.globals.insert(identifier.name(), Binding::Function(cname.clone(), arity));
+                    cnames.push(cname);
+                },
+            }
+        }
+
+        // Global initializers run in declaration order from `ult_init`
+        self.contexts.push(Context::new());
+        self.ctx().used = self.file_names.clone();
+
+        for (decl, cname) in ast.program().iter().zip(cnames) {
+            match decl {
+                Declaration::Variable { value, .. } => {
+                    let value = self.expression_or_null(value.as_ref())?;
+                    self.line(format!("{} = {};", cname, value));
+                },
+                Declaration::Destructure { pattern, value } => {
+                    let value = self.expression(value)?;
+                    self.destructure(pattern, value, true);
+                },
+                Declaration::Function { arguments, body, .. } =>
+                    self.function(cname, arguments.as_deref().unwrap_or_default(), body)?,
+            }
+        }
+
+        let init = self.contexts.pop().unwrap();
+        writeln!(self.functions, "\nstatic void ult_init(void) {{\n{}}}", init.code).unwrap();
+
+        Ok(())
+    }
+
+    fn function(&mut self, cname: String, arguments: &[Identifier], body: &Block) -> GenResult {
+        self.contexts.push(Context::new());
+
+        // Parameters must not share a name with any file scope function or global
+        self.ctx().used = self.file_names.clone();
+
+        let mut params = vec![];
+
+        for arg in arguments {
+            let param = Self::fresh(&arg.name(), &mut self.ctx().used);
+            params.push(format!("ult_value {}", param));
+            self.bind(arg.name(), Binding::Variable(param));
+        }
+
+        let params = match params.len() {
+            0 => String::from("void"),
+            _ => params.join(", "),
+        };
+
+        for stmt in body.statements() {
+            self.statement(stmt)?;
+        }
+
+        let ctx = self.contexts.pop().unwrap();
+        let signature = format!("static ult_value {}({})", cname, params);
+        let fallthrough = match body.statements().last() {
+            Some(Statement::Return(_)) => "",
+            _ => "    return ult_null();\n",
+        };
+
+        writeln!(self.prototypes, "{};", signature).unwrap();
+        writeln!(self.functions, "\n{} {{\n{}{}}}", signature, ctx.code, fallthrough).unwrap();
+
+        Ok(())
+    }
+
+    fn ctx(&mut self) -> &mut Context {
+        self.contexts.last_mut().unwrap()
+    }
+
+    fn line(&mut self, line: impl AsRef<str>) {
+        let ctx = self.ctx();
+        ctx.code.push_str(&INDENT.repeat(ctx.indent));
+        ctx.code.push_str(line.as_ref());
+        ctx.code.push('\n');
+    }
+
+    fn bind(&mut self, name: String, binding: Binding) {
+        self.ctx().scopes.last_mut().unwrap().insert(name, binding);
+    }
+
+    fn lookup(&self, name: &str) -> Result<Binding, BackendError> {
+        let (current, enclosing) = self.contexts.split_last().unwrap();
+
+        if let Some(binding) = current.scopes.iter().rev().find_map(|s| s.get(name)) {
+            return Ok(binding.clone())
+        }
+
+        // Hoisted nested functions are reachable from inner functions, locals are not
+        for ctx in enclosing.iter().rev() {
+            match ctx.scopes.iter().rev().find_map(|s| s.get(name)) {
+                Some(Binding::Variable(_)) =>
+                    return Err(BackendError::Unsupported(format!("capturing local variable '{}'", name))),
+                Some(binding) => return Ok(binding.clone()),
+                None => (),
+            }
+        }
+
+        self.globals.get(name).cloned().ok_or_else(|| BackendError::UndefinedVariable(name.to_string()))
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> GenResult {
+        match stmt {
+            Statement::Expression(expr) => {
+                let expr = self.expression_stmt(expr)?;
+                self.line(format!("{};", expr));
+            },
+            Statement::Declaration(Declaration::Variable { identifier, value }) => {
+                let value = self.expression_or_null(value.as_ref())?;
+                let cname = Self::fresh(&identifier.name(), &mut self.ctx().used);
+                self.line(format!("ult_value {} = {};", cname, value));
+                self.bind(identifier.name(), Binding::Variable(cname));
+            },
+            Statement::Declaration(Declaration::Destructure { pattern, value }) => {
+                let value = self.expression(value)?;
+                self.destructure(pattern, value, false);
+            },
+            Statement::Throw(_) | Statement::Try { .. } => return Err(BackendError::Unsupported(String::from("exceptions"))),
+            Statement::Assert { .. } => return Err(BackendError::Unsupported(String::from("assertions"))),
+            Statement::Declaration(Declaration::Function { identifier, arguments, body }) => {
+                let arguments = arguments.as_deref().unwrap_or_default();
+                let cname = Self::fresh(&identifier.name(), &mut self.file_names);
+
+                self.bind(identifier.name(), Binding::Function(cname.clone(), arguments.len()));
+                self.function(cname, arguments, body)?;
+            },
+            Statement::Block(block) | Statement::Else { body: block } => {
+                self.line("{");
+                self.block(block)?;
+                self.line("}");
+            },
+            Statement::Return(expr) => {
+                let expr = self.expression(expr)?;
+                self.line(format!("return {};", expr));
+            },
+            Statement::If { .. } => self.if_chain(stmt, "")?,
+            Statement::While { condition, body } => {
+                let condition = self.expression(condition)?;
+                self.line(format!("while (ult_truthy({})) {{", condition));
+                self.loop_body(body)?;
+                self.line("}");
+            },
+            Statement::For { variable, condition, step, body } => {
+                self.ctx().scopes.push(HashMap::new());
+
+                let init = match variable.as_deref() {
+                    Some(Statement::Declaration(Declaration::Variable { identifier, value })) => {
+                        let value = self.expression_or_null(value.as_ref())?;
+                        let cname = Self::fresh(&identifier.name(), &mut self.ctx().used);
+                        self.bind(identifier.name(), Binding::Variable(cname.clone()));
+                        format!("ult_value {} = {}", cname, value)
+                    },
+                    Some(Statement::Expression(expr)) => self.expression_stmt(expr)?,
+                    Some(_) => return Err(BackendError::Unsupported(String::from("this 'for' initializer"))),
+                    None => String::new(),
+                };
+
+                let condition = match condition {
+                    Some(cond) => format!(" ult_truthy({})", self.expression(cond)?),
+                    None => String::new(),
+                };
+
+                let step = match step {
+                    Some(step) => format!(" {}", self.expression_stmt(step)?),
+                    None => String::new(),
+                };
+
+                self.line(format!("for ({};{};{}) {{", init, condition, step));
+                self.loop_body(body)?;
+                self.line("}");
+
+                self.ctx().scopes.pop();
+            },
+            Statement::ForEach { variable, iterable, body } => {
+                let iterable = self.expression(iterable)?;
+                let name = variable.name();
+                let it = Self::fresh(&format!("{}_iterable", name), &mut self.ctx().used);
+                let cursor = Self::fresh(&format!("{}_cursor", name), &mut self.ctx().used);
+                let cname = Self::fresh(&name, &mut self.ctx().used);
+
+                self.line("{");
+                self.ctx().indent += 1;
+                self.line(format!("ult_value {} = ult_snapshot({});", it, iterable));
+                self.line(format!("int64_t {} = 0;", cursor));
+                self.line(format!("ult_value {};", cname));
+                self.line(format!("while (ult_next({}, &{}, &{})) {{", it, cursor, cname));
+
+                self.ctx().scopes.push(HashMap::new());
+                self.bind(name, Binding::Variable(cname));
+                self.loop_body(body)?;
+                self.ctx().scopes.pop();
+
+                self.line("}");
+                self.ctx().indent -= 1;
+                self.line("}");
+            },
+            Statement::Break => {
+                if self.ctx().loops == 0 {
+                    return Err(BackendError::BreakOutsideLoop)
+                }
+                self.line("break;");
+            },
+            Statement::Continue => {
+                if self.ctx().loops == 0 {
+                    return Err(BackendError::ContinueOutsideLoop)
+                }
+                self.line("continue;");
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Emits `if`/`else if`/`else` chains flat instead of nesting each `else if`
+    /// Binds the elements of `value` to the pattern's names, globals are already declared at file scope. \
+    /// `ult_unpack` checks the shape, so elements are then read straight from the items
+    fn destructure(&mut self, pattern: &Pattern, value: String, global: bool) {
+        let (patterns, tuple) = match pattern {
+            Pattern::Identifier(identifier) if global => {
+                if let Some(Binding::Variable(cname)) = self.globals.get(&identifier.name()).cloned() {
+                    self.line(format!("{} = {};", cname, value));
+                }
+                return
+            },
+            Pattern::Identifier(identifier) => {
+                let cname = Self::fresh(&identifier.name(), &mut self.ctx().used);
+                self.line(format!("ult_value {} = {};", cname, value));
+                self.bind(identifier.name(), Binding::Variable(cname));
+                return
+            },
+            Pattern::Tuple(patterns) => (patterns, true),
+            Pattern::Array(patterns) => (patterns, false),
+        };
+
+        let temp = Self::fresh("pattern", &mut self.ctx().used);
+        self.line(format!("ult_value {} = ult_unpack({}, {}, {});", temp, value, tuple as i32, patterns.len()));
+
+        for (i, pattern) in patterns.iter().enumerate() {
+            self.destructure(pattern, format!("{}.as.array->items[{}]", temp, i), global);
+        }
+    }
+
+    fn if_chain(&mut self, stmt: &Statement, prefix: &str) -> GenResult {
+        match stmt {
+            Statement::If { condition, body, else_stmt } => {
+                let condition = self.expression(condition)?;
+                self.line(format!("{}if (ult_truthy({})) {{", prefix, condition));
+                self.block(body)?;
+
+                match else_stmt.as_deref() {
+                    Some(stmt) => self.if_chain(stmt, "} else "),
+                    None => { self.line("}"); Ok(()) },
+                }
+            },
+            Statement::Block(block) | Statement::Else { body: block } => {
+                self.line(format!("{}{{", prefix));
+                self.block(block)?;
+                self.line("}");
+                Ok(())
+            },
+            stmt => self.statement(stmt),
+        }
+    }
+
+    fn block(&mut self, block: &Block) -> GenResult {
+        let ctx = self.ctx();
+        ctx.scopes.push(HashMap::new());
+        ctx.indent += 1;
+
+        for stmt in block.statements() {
+            self.statement(stmt)?;
+        }
+
+        let ctx = self.ctx();
+        ctx.indent -= 1;
+        ctx.scopes.pop();
+
+        Ok(())
+    }
+
+    fn loop_body(&mut self, body: &Block) -> GenResult {
+        self.ctx().loops += 1;
+        self.block(body)?;
+        self.ctx().loops -= 1;
+        Ok(())
+    }
+
+    fn expression_or_null(&mut self, expr: Option<&Expression>) -> ExprResult {
+        match expr {
+            Some(expr) => self.expression(expr),
+            None => Ok(String::from("ult_null()")),
+        }
+    }
+
+    /// An expression in statement position, where assignments need no parentheses
+    fn expression_stmt(&mut self, expr: &Expression) -> ExprResult {
+        match expr {
+            Expression::Assignment { lhs, operation: None, rhs } => self.assignment(lhs, rhs),
+            expr => self.expression(expr),
+        }
+    }
+
+    fn assignment(&mut self, lhs: &Expression, rhs: &Expression) -> ExprResult {
+        match place(lhs)? {
+            Place::Variable(identifier) => {
+                let cname = self.variable(identifier)?;
+                Ok(format!("{} = {}", cname, self.expression(rhs)?))
+            },
+            Place::Element { target, index } =>
+                self.sequenced(&[target, index, rhs], |ops| format!("ult_set_index({}, {}, {})", ops[0], ops[1], ops[2])),
+        }
+    }
+
+    /// The C variable an assignment stores into
+    fn variable(&mut self, identifier: &Identifier) -> ExprResult {
+        match self.lookup(&identifier.name())? {
+            Binding::Variable(cname) => Ok(cname),
+            Binding::Function(..) => Err(BackendError::Unsupported(String::from("assignment to functions"))),
+        }
+    }
+
+    /// When `rhs` might change the target, it's saved with the runtime first, see `ult_save`, so it's read
+    /// before `rhs` runs
+    fn compound_assignment(&mut self, lhs: &Expression, operation: &Token, rhs: &Expression) -> ExprResult {
+        let func = binary_fn(operation)?;
+
+        match place(lhs)? {
+            Place::Element { target, index } => {
+                let save = self.save_index(target, index)?;
+                Ok(format!("({}, ult_update({}, {}))", save, func, self.expression(rhs)?))
+            },
+            Place::Variable(identifier) if !has_effects(rhs) => {
+                let cname = self.variable(identifier)?;
+                Ok(format!("({} = {}({}, {}))", cname, func, cname, self.expression(rhs)?))
+            },
+            Place::Variable(identifier) => {
+                let cname = self.variable(identifier)?;
+                Ok(format!("(ult_save({}), {} = ult_update({}, {}))", cname, cname, func, self.expression(rhs)?))
+            },
+        }
+    }
+
+    fn save_index(&mut self, target: &Expression, index: &Expression) -> ExprResult {
+        self.sequenced(&[target, index], |ops| format!("ult_save_index({}, {})", ops[0], ops[1]))
+    }
+
+    fn postfix(&mut self, operand: &Expression, operation: &Token) -> ExprResult {
+        let func = match operation {
+            Token::Increment => "ult_add",
+            Token::Decrement => "ult_sub",
+            tok => return Err(BackendError::Unsupported(format!("postfix operator {}", tok))),
+        };
+
+        match place(operand)? {
+            Place::Element { target, index } => {
+                let save = self.save_index(target, index)?;
+                Ok(format!("({}, ult_postfix({}))", save, func))
+            },
+            Place::Variable(identifier) => {
+                let cname = self.variable(identifier)?;
+                Ok(format!("(ult_save({}), {} = {}({}, ult_int(1)), ult_restore().old)", cname, cname, func, cname))
+            },
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) -> ExprResult {
+        let code = match expr {
+            Expression::Literal(lit) => literal(lit),
+            Expression::Value(identifier) => match self.lookup(&identifier.name())? {
+                Binding::Variable(cname) => cname,
+                Binding::Function(cname, arity) => {
+                    self.function_values.insert(cname.clone(), (arity, identifier.name()));
+                    format!("ult_func(&{}__fn)", cname)
+                },
+            },
+            Expression::Assignment { lhs, operation: None, rhs } => format!("({})", self.assignment(lhs, rhs)?),
+            Expression::Assignment { lhs, operation: Some(operation), rhs } => self.compound_assignment(lhs, operation, rhs)?,
+            Expression::Postfix { operand, operation } => self.postfix(operand, operation)?,
+            Expression::Member { target, property } =>
+                self.sequenced(&[target, property], |ops| format!("ult_index({}, {})", ops[0], ops[1]))?,
+            Expression::Array { elements, .. } => self.array(elements)?,
+            Expression::Tuple(elements) if elements.is_empty() => String::from("ult_tuple(0, NULL)"),
+            Expression::Tuple(elements) => self.sequenced(&elements.iter().collect::<Vec<_>>(),
+                |ops| format!("ult_tuple({}, (ult_value[]){{ {} }})", ops.len(), ops.join(", ")))?,
+            Expression::Map(entries) if entries.is_empty() => String::from("ult_map_of(0, NULL)"),
+            Expression::Map(entries) => {
+                let operands = entries.iter().flat_map(|(key, value)| [key, value]).collect::<Vec<_>>();
+                self.sequenced(&operands, |ops| format!("ult_map_of({}, (ult_value[]){{ {} }})", entries.len(), ops.join(", ")))?
+            },
+            Expression::Set(elements) if elements.is_empty() => String::from("ult_set_of(0, NULL)"),
+            Expression::Set(elements) if elements.iter().any(is_spread) => format!("ult_set_from({})", self.array(elements)?),
+            Expression::Set(elements) => self.sequenced(&elements.iter().collect::<Vec<_>>(),
+                |ops| format!("ult_set_of({}, (ult_value[]){{ {} }})", ops.len(), ops.join(", ")))?,
+            Expression::Call { target, args } => self.call(target, args.as_deref().unwrap_or_default())?,
+            Expression::Conditional { condition, then, otherwise } => format!("(ult_truthy({}) ? {} : {})",
+                self.expression(condition)?, self.expression(then)?, self.expression(otherwise)?),
+            Expression::Coalesce { lhs, rhs } => format!("(ult_hold({}), ult_is_null(ult_held(0)) ? ult_drop(1, {}) : ult_drop(1, ult_held(0)))",
+                self.expression(lhs)?, self.expression(rhs)?),
+            Expression::OptionalMember { target, property } =>
+                self.optional(target, &[property], |ops| format!("ult_index({}, {})", ops[0], ops[1]))?,
+            Expression::OptionalCall { target, args } => self.optional_call(target, args.as_deref().unwrap_or_default())?,
+            Expression::Unary { prefix, operand } => {
+                let operand = self.expression(operand)?;
+
+                match prefix {
+                    Some(Token::Minus) => format!("ult_neg({})", operand),
+                    Some(Token::Not) => format!("ult_not({})", operand),
+                    Some(Token::BinaryNegate) => format!("ult_bnot({})", operand),
+                    Some(tok) => return Err(BackendError::Unsupported(format!("unary operator {}", tok))),
+                    None => operand,
+                }
+            },
+            Expression::Binary { lhs, operation, rhs } => {
+                let func = binary_fn(operation)?;
+                self.sequenced(&[lhs, rhs], |ops| format!("{}({}, {})", func, ops[0], ops[1]))?
+            },
+            Expression::Logical { lhs, operation, rhs } => format!("ult_bool(ult_truthy({}) {} ult_truthy({}))",
+                self.expression(lhs)?, logical_op(operation)?, self.expression(rhs)?),
+            Expression::Range { start, end, inclusive } =>
+                self.sequenced(&[start, end], |ops| format!("ult_range({}, {}, {})", ops[0], ops[1], *inclusive as i32))?,
+            Expression::Spread(_) => return Err(BackendError::Unsupported(String::from("'...' outside of arrays, sets and calls"))),
+            Expression::Comprehension { result, loops, .. } => self.comprehension(result, loops)?,
+            Expression::Append { target, value } => {
+                let array = self.expression(&Expression::Value(target.clone()))?;
+                format!("ult_array_push({}.as.array, {})", array, self.expression(value)?)
+            },
+        };
+
+        Ok(code)
+    }
+
+    /// A GNU statement expression, `({ ... })`, so the loops only run where the comprehension is evaluated,
+    /// even inside `&&`, `?:` or `??`
+    fn comprehension(&mut self, result: &Identifier, loops: &Statement) -> ExprResult {
+        let cname = Self::fresh(&result.name(), &mut self.ctx().used);
+        let outer = std::mem::take(&mut self.ctx().code);
+        let indent = self.ctx().indent;
+
+        self.ctx().indent += 1;
+        self.line(format!("ult_value {} = ult_array_of(0, NULL);", cname));
+
+        self.ctx().scopes.push(HashMap::new());
+        self.bind(result.name(), Binding::Variable(cname.clone()));
+        self.statement(loops)?;
+        self.ctx().scopes.pop();
+
+        self.line(format!("{};", cname));
+        self.ctx().indent = indent;
+        let code = std::mem::replace(&mut self.ctx().code, outer);
+
+        Ok(format!("({{\n{}{}}})", code, INDENT.repeat(indent)))
+    }
+
+    /// Spread elements are flagged for `ult_array_spread` to iterate into the array
+    fn array(&mut self, elements: &[Expression]) -> ExprResult {
+        let operands: Vec<_> = elements.iter().map(|e| match e {
+            Expression::Spread(iterable) => iterable.as_ref(),
+            e => e,
+        }).collect();
+
+        if elements.is_empty() {
+            return Ok(String::from("ult_array_of(0, NULL)"))
+        }
+
+        if !elements.iter().any(is_spread) {
+            return self.sequenced(&operands, |ops| format!("ult_array_of({}, (ult_value[]){{ {} }})", ops.len(), ops.join(", ")))
+        }
+
+        let flags = elements.iter().map(|e| (is_spread(e) as i32).to_string()).collect::<Vec<_>>().join(", ");
+
+        self.sequenced(&operands, |ops| {
+            format!("ult_array_spread({}, (ult_value[]){{ {} }}, (int[]){{ {} }})", ops.len(), ops.join(", "), flags)
+        })
+    }
+
+    /// Generates `operands` and passes them to `apply` to build a call. C leaves the order function arguments
+    /// are evaluated in unspecified, so if any operand has side effects each one is held with the runtime in
+    /// turn, see `ult_hold`, and the call reads them back with `ult_held`
+    fn sequenced(&mut self, operands: &[&Expression], apply: impl FnOnce(&[String]) -> String) -> ExprResult {
+        let mut codes = vec![];
+
+        for operand in operands {
+            codes.push(self.expression(operand)?);
+        }
+
+        if codes.len() < 2 || !operands.iter().any(|e| has_effects(e)) {
+            return Ok(apply(&codes))
+        }
+
+        let n = codes.len();
+        let holds: Vec<_> = codes.iter().map(|code| format!("ult_hold({})", code)).collect();
+        let held: Vec<_> = (0..n).map(|i| format!("ult_held({})", n - 1 - i)).collect();
+
+        Ok(format!("({}, ult_drop({}, {}))", holds.join(", "), n, apply(&held)))
+    }
+
+    /// Holds `target` and `operands` and applies them unless `target` is null, which skips the operands
+    fn optional(&mut self, target: &Expression, operands: &[&Expression], apply: impl FnOnce(&[String]) -> String) -> ExprResult {
+        let target = self.expression(target)?;
+        let mut holds = vec![];
+
+        for operand in operands {
+            holds.push(format!("ult_hold({})", self.expression(operand)?));
+        }
+
+        let n = operands.len();
+        let held: Vec<_> = (0..=n).map(|i| format!("ult_held({})", n - i)).collect();
+        holds.push(format!("ult_drop({}, {})", n + 1, apply(&held)));
+
+        Ok(format!("(ult_hold({}), ult_is_null(ult_held(0)) ? ult_drop(1, ult_null()) : ({}))", target, holds.join(", ")))
+    }
+
+    /// Functions are never null, so only calls of values check
+    fn optional_call(&mut self, target: &Expression, args: &[Expression]) -> ExprResult {
+        if let Expression::Value(identifier) = target {
+            if !matches!(self.lookup(&identifier.name()), Ok(Binding::Variable(_))) {
+                return self.call(target, args)
+            }
+        }
+
+        if args.iter().any(is_spread) {
+            let array = Expression::Array { scope: Scope::Global, elements: args.to_vec() };
+            return self.optional(target, &[&array], |ops| format!("ult_apply({}, {})", ops[0], ops[1]))
+        }
+
+        let operands: Vec<_> = args.iter().collect();
+        self.optional(target, &operands, |ops| format!("ult_call({}, {})", ops[0], arg_array(&ops[1..])))
+    }
+
+    fn call(&mut self, target: &Expression, args: &[Expression]) -> ExprResult {
+        if args.iter().any(is_spread) {
+            return self.spread_call(target, args)
+        }
+
+        let mut operands: Vec<_> = args.iter().collect();
+
+        if let Expression::Value(identifier) = target {
+            let name = identifier.name();
+
+            match self.lookup(&name) {
+                Ok(Binding::Function(cname, arity)) => {
+                    if arity != args.len() {
+                        return Err(BackendError::ArityMismatch(name, arity, args.len()))
+                    }
+
+                    return self.sequenced(&operands, |ops| format!("{}({})", cname, ops.join(", ")))
+                },
+                Err(BackendError::UndefinedVariable(_)) => return match builtin(&name, args.len())? {
+                    Some(_) => self.sequenced(&operands, |ops| format!("ult_std_{}({})", name, arg_array(ops))),
+                    None => Err(BackendError::UndefinedFunction(name)),
+                },
+                Err(e) => return Err(e),
+                Ok(Binding::Variable(_)) => (),
+            }
+        }
+
+        operands.insert(0, target);
+        self.sequenced(&operands, |ops| format!("ult_call({}, {})", ops[0], arg_array(&ops[1..])))
+    }
+
+    /// The arguments are gathered into an array and passed by the runtime, which checks their count
+    fn spread_call(&mut self, target: &Expression, args: &[Expression]) -> ExprResult {
+        let array = Expression::Array { scope: Scope::Global, elements: args.to_vec() };
+
+        if let Expression::Value(identifier) = target {
+            let name = identifier.name();
+
+            match self.lookup(&name) {
+                Ok(Binding::Function(cname, arity)) => {
+                    self.function_values.insert(cname.clone(), (arity, name));
+                    return Ok(format!("ult_apply(ult_func(&{}__fn), {})", cname, self.expression(&array)?))
+                },
+                Err(BackendError::UndefinedVariable(_)) => return match stdlib::lookup(&name) {
+                    Some(Builtin { arity, .. }) => Ok(format!("ult_apply_std(\"{}\", {}, ult_std_{}, {})",
+                        name, arity.map_or(-1, |a| a as i64), name, self.expression(&array)?)),
+                    None => Err(BackendError::UndefinedFunction(name)),
+                },
+                Err(e) => return Err(e),
+                Ok(Binding::Variable(_)) => (),
+            }
+        }
+
+        self.sequenced(&[target, &array], |ops| format!("ult_apply({}, {})", ops[0], ops[1]))
+    }
+}
+
+fn is_spread(expr: &Expression) -> bool {
+    matches!(expr, Expression::Spread(_))
+}
+
+/// The arguments of a call through the runtime, a count and an array
+fn arg_array(args: &[String]) -> String {
+    match args.len() {
+        0 => String::from("0, NULL"),
+        n => format!("{}, (ult_value[]){{ {} }}", n, args.join(", ")),
+    }
+}
+
+/// Whether evaluating `expr` might assign to anything, which calls might do too. \
+/// `??` and `?.` count as well, as they use the hold stack themselves
+fn has_effects(expr: &Expression) -> bool {
+    struct Effects(bool);
+
+    impl Visitor for Effects {
+        fn visit_expression(&mut self, expr: &Expression) {
+            match expr {
+                Expression::Assignment { .. } | Expression::Postfix { .. } | Expression::Call { .. } |
+                Expression::OptionalCall { .. } | Expression::Coalesce { .. } | Expression::OptionalMember { .. } => self.0 = true,
+                expr => walk_expression(self, expr),
+            }
+        }
+    }
+
+    let mut effects = Effects(false);
+    effects.visit_expression(expr);
+    effects.0
+}
+
+fn literal(lit: &Literal) -> String {
+    match lit {
+        Literal::Null => String::from("ult_null()"),
+        Literal::Boolean(b) => format!("ult_bool({})", *b as i32),
+        Literal::Integer(i) => format!("ult_int({})", i),
+        Literal::Decimal(d) => format!("ult_dec({:?})", d),
+        Literal::Character(c) => match c {
+            ' '..='~' if *c != '\'' && *c != '\\' => format!("ult_char('{}')", c),
+            c => format!("ult_char({})", *c as u32),
+        },
+        Literal::String(s) => format!("ult_str(\"{}\")", escape(s)),
+    }
+}
+
+fn binary_fn(tok: &Token) -> Result<&'static str, BackendError> {
+    let func = match tok {
+        Token::Plus => "ult_add",
+        Token::Minus => "ult_sub",
+        Token::Multiply => "ult_mul",
+        Token::Divide => "ult_div",
+        Token::Modulo => "ult_mod",
+        Token::Exponentiate => "ult_pow",
+        Token::BinaryAnd => "ult_band",
+        Token::BinaryOr => "ult_bor",
+        Token::Xor => "ult_bxor",
+        Token::ShiftLeft => "ult_shl",
+        Token::ShiftRight => "ult_shr",
+        Token::Equals => "ult_eq",
+        Token::NotEquals => "ult_ne",
+        Token::In => "ult_in",
+        Token::LessThan => "ult_lt",
+        Token::LessEquals => "ult_le",
+        Token::GreaterThan => "ult_gt",
+        Token::GreaterEquals => "ult_ge",
+        tok => return Err(BackendError::Unsupported(format!("binary operator {}", tok))),
+    };
+
+    Ok(func)
+}
+
+/// C's own `&&` and `||` already skip the right side
+fn logical_op(tok: &Token) -> Result<&'static str, BackendError> {
+    match tok {
+        Token::LogicalAnd => Ok("&&"),
+        Token::LogicalOr => Ok("||"),
+        tok => Err(BackendError::Unsupported(format!("logical operator {}", tok))),
+    }
+}
+
+/// Escapes a string for a C string literal. Non-ASCII bytes become octal escapes
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\t' => out.push_str("\\t"),
+            b'\r' => out.push_str("\\r"),
+            b'?' => out.push_str("\\?"),
+            b' '..=b'~' => out.push(byte as char),
+            b => write!(out, "\\{:03o}", b).unwrap(),
+        }
+    }
+    out
+}
\ No newline at end of file
@@ -0,0 +1,66 @@
+use super::super::ir::{ LowerError, VerifyError };
+use std::error::Error;
+use std::fmt::{ Display, Result, Formatter };
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BackendError {
+    Unsupported(String),
+    UndefinedVariable(String),
+    UndefinedFunction(String),
+    ArityMismatch(String, usize, usize),
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
+    UnknownTarget(String),
+    /// A known target whose `backend-*` feature is off
+    Disabled(&'static str),
+    Toolchain(String),
+    Io(std::io::Error),
+    /// The SSA IR broke an invariant, a bug in lowering or a pass
+    InvalidIr(VerifyError),
+}
+
+impl Display for BackendError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        use BackendError::*;
+        match self {
+            Unsupported(what) => write!(f, "Unsupported by this backend: {}", what),
+            UndefinedVariable(name) => write!(f, "Undefined variable '{}'", name),
+            UndefinedFunction(name) => write!(f, "Undefined function '{}'", name),
+            ArityMismatch(name, e, g) => write!(f, "Function '{}' expected {} arguments but got {}", name, e, g),
+            BreakOutsideLoop => write!(f, "'break' used outside of a loop"),
+            ContinueOutsideLoop => write!(f, "'continue' used outside of a loop"),
+            UnknownTarget(name) => write!(f, "Unknown target '{}'", name),
+            Disabled(name) => write!(f, "Target '{}' is not enabled in this build of ult", name),
+            Toolchain(msg) => write!(f, "Toolchain error: {}", msg),
+            Io(e) => write!(f, "IO error: {}", e),
+            InvalidIr(e) => write!(f, "Invalid IR: {}", e),
+        }
+    }
+}
+
+impl Error for BackendError {}
+
+impl From<std::io::Error> for BackendError {
+    fn from(e: std::io::Error) -> Self {
+        BackendError::Io(e)
+    }
+}
+
+impl From<LowerError> for BackendError {
+    fn from(e: LowerError) -> Self {
+        match e {
+            LowerError::UndefinedVariable(name) => BackendError::UndefinedVariable(name),
+            LowerError::ArityMismatch(name, expected, got) => BackendError::ArityMismatch(name, expected, got),
+            LowerError::BreakOutsideLoop => BackendError::BreakOutsideLoop,
+            LowerError::ContinueOutsideLoop => BackendError::ContinueOutsideLoop,
+            e => BackendError::Unsupported(e.to_string()),
+        }
+    }
+}
+
+impl From<VerifyError> for BackendError {
+    fn from(e: VerifyError) -> Self {
+        BackendError::InvalidIr(e)
+    }
+}
\ No newline at end of file
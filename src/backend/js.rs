@@ -0,0 +1,806 @@
+use super::super::lex::token::{ Span, Token };
+use super::super::parse::ast::*;
+use super::super::parse::util::binop_symbol;
+use super::super::stdlib::{ self, Builtin };
+use super::{ builtin, place };
+use super::error::BackendError;
+use std::collections::{ HashMap, HashSet };
+use std::fmt::Write;
+
+type GenResult = Result<(), BackendError>;
+type ExprResult = Result<String, BackendError>;
+
+/// Runtime prelude placed at the top of every generated file
+pub const RUNTIME: &str = include_str!("runtime/ult_runtime.js");
+
+/// Generated JavaScript and its Source Map v3 JSON
+pub struct Transpiled {
+    pub code: String,
+    pub source_map: String,
+}
+
+/// Transpiles an `AST` to ES2020 JavaScript for Node. \
+/// Integers are BigInts checked against the `i64` range, and operators go through a small runtime
+/// that mirrors the VM's semantics. Declarations become `let`, and any declaration that would
+/// clash with a visible name is renamed so shadowing behaves as in Ult. `source` and `file` name
+/// the Ult input and the JavaScript output in the source map
+pub fn generate(ast: &AST, source: &str, file: &str) -> Result<Transpiled, BackendError> {
+    let mut gen = Generator::new();
+
+    gen.program(ast)?;
+
+    writeln!(gen.out, "//# sourceMappingURL={}.map", file).unwrap();
+
+    let source_map = format!("{{\"version\":3,\"file\":{},\"sources\":[{}],\"names\":[],\"mappings\":\"{}\"}}\n",
+        quote(file), quote(source), gen.mappings());
+
+    Ok(Transpiled { code: gen.out, source_map })
+}
+
+/// Words that can't be used as binding names in strict mode JavaScript
+const RESERVED: [&str; 38] = [
+    "arguments", "await", "case", "catch", "class", "const", "debugger", "default", "delete", "do",
+    "enum", "eval", "export", "extends", "finally", "function", "implements", "import", "in",
+    "instanceof", "interface", "new", "package", "private", "protected", "public", "static", "super",
+    "switch", "this", "throw", "try", "typeof", "var", "void", "with", "yield", "undefined",
+];
+
+#[derive(Clone)]
+enum Binding {
+    Variable(String),
+    Function(String, usize),
+}
+
+struct Generator {
+    out: String,
+    /// Line of `out` being written
+    line: usize,
+    indent: usize,
+    /// Generated `(line, column)` mapped to a source span
+    spans: Vec<(usize, usize, Span)>,
+    scopes: Vec<HashMap<String, Binding>>,
+    /// Suffix counters, so every renamed binding is unique in the file
+    renames: HashMap<String, usize>,
+    /// Loops enclosing the current statement within its function
+    loops: usize,
+}
+
+impl Generator {
+    fn new() -> Self {
+        Self {
+            out: String::new(),
+            line: 0,
+            indent: 0,
+            spans: vec![],
+            scopes: vec![HashMap::new()],
+            renames: HashMap::new(),
+            loops: 0,
+        }
+    }
+
+    fn program(&mut self, ast: &AST) -> GenResult {
+        self.write("\"use strict\";\n// Generated by ult\n");
+        self.write(RUNTIME);
+        self.write("\n");
+
+        // Register top level names first so functions can refer to later declarations.
+        // Redeclared globals share one binding like they do in the VM
+        let mut declared = HashSet::new();
+        for decl in ast.program() {
+            let bindings = match decl {
+                Declaration::Variable { identifier, .. } => vec![(identifier.name(), Binding::Variable(js_name(&identifier.name())))],
+                Declaration::Destructure { pattern, .. } => pattern.identifiers().into_iter()
+                    .map(|identifier| (identifier.name(), Binding::Variable(js_name(&identifier.name()))))
+                    .collect(),
+                Declaration::Function { identifier, arguments, .. } =>
+                    vec![(identifier.name(), Binding::Function(js_name(&identifier.name()), arguments.as_ref().map_or(0, Vec::len)))],
+            };
+
+            for (name, binding) in bindings {
+                self.scopes[0].insert(name, binding);
+            }
+        }
+
+        self.line_start(None);
+        self.write("$.run(() => {\n");
+        self.indent += 1;
+
+        for (decl, span) in ast.program().iter().zip(ast.spans()) {
+            match decl {
+                Declaration::Variable { identifier, value } => {
+                    let value = self.expression_or_null(value.as_ref())?;
+                    self.destructure(&Pattern::Identifier(identifier.clone()), value, Some(*span), Some(&mut declared));
+                },
+                Declaration::Destructure { pattern, value } => {
+                    let value = self.expression(value)?;
+                    self.destructure(pattern, value, Some(*span), Some(&mut declared));
+                },
+                Declaration::Function { identifier, arguments, body } => {
+                    declared.insert(js_name(&identifier.name()));
+                    self.function(js_name(&identifier.name()), arguments.as_deref().unwrap_or_default(), body, *span)?
+                },
+            }
+        }
+
+        self.line_start(None);
+        match self.scopes[0].get("main").cloned() {
+            Some(Binding::Function(name, 0)) => writeln!(self, "return {}();", name),
+            Some(Binding::Function(_, n)) => return Err(BackendError::ArityMismatch(String::from("main"), 0, n)),
+            _ => writeln!(self, "return null;"),
+        }
+
+        self.indent -= 1;
+        self.write("});\n");
+
+        Ok(())
+    }
+
+    fn function(&mut self, name: String, arguments: &[Identifier], body: &Block, span: Span) -> GenResult {
+        self.scopes.push(HashMap::new());
+        let loops = std::mem::replace(&mut self.loops, 0);
+
+        // Parameters only need renaming when repeated
+        let mut params = vec![];
+        for arg in arguments {
+            let param = match self.scopes.last().unwrap().contains_key(&arg.name()) {
+                true => self.declare(&arg.name()),
+                false => js_name(&arg.name()),
+            };
+
+            self.scopes.last_mut().unwrap().insert(arg.name(), Binding::Variable(param.clone()));
+            params.push(param);
+        }
+
+        self.line_start(Some(span));
+        writeln!(self, "function {}({}) {{", name, params.join(", "));
+
+        self.statements(body)?;
+
+        if !matches!(body.statements().last(), Some(Statement::Return(_))) {
+            self.indent += 1;
+            self.line_start(None);
+            writeln!(self, "return null;");
+            self.indent -= 1;
+        }
+
+        self.line_start(None);
+        writeln!(self, "}}");
+
+        self.loops = loops;
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn write(&mut self, text: &str) {
+        self.line += text.matches('\n').count();
+        self.out.push_str(text);
+    }
+
+    fn write_fmt(&mut self, args: std::fmt::Arguments) {
+        self.write(&args.to_string());
+    }
+
+    /// Indents a new line, mapping it back to `span` when there is one
+    fn line_start(&mut self, span: Option<Span>) {
+        let column = self.indent * 4;
+
+        if let Some(span) = span {
+            self.spans.push((self.line, column, span));
+        }
+
+        self.write(&" ".repeat(column));
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|s| s.get(name))
+    }
+
+    /// Binds `name` in the innermost scope. A name that is already visible gets a unique
+    /// `$<n>` suffix, so initializers still see the outer binding and nothing is redeclared
+    fn declare(&mut self, name: &str) -> String {
+        let base = js_name(name);
+
+        let js = if self.lookup(name).is_some() {
+            let n = self.renames.entry(base.clone()).or_insert(0);
+            *n += 1;
+            format!("{}${}", base.trim_end_matches('$'), n)
+        }
+        else {
+            base
+        };
+
+        self.scopes.last_mut().unwrap().insert(name.to_string(), Binding::Variable(js.clone()));
+        js
+    }
+
+    /// Binds the elements of `value` to the pattern's names, with `$.unpack` checking each level's shape. \
+    /// Globals are passed the names declared so far, a global declared again is assigned to instead
+    fn destructure(&mut self, pattern: &Pattern, value: String, span: Option<Span>, mut globals: Option<&mut HashSet<String>>) {
+        let (patterns, tuple) = match pattern {
+            Pattern::Identifier(identifier) => {
+                let (keyword, name) = match globals {
+                    Some(declared) => {
+                        let name = js_name(&identifier.name());
+                        (if declared.insert(name.clone()) { "let " } else { "" }, name)
+                    },
+                    None => ("let ", self.declare(&identifier.name())),
+                };
+
+                self.line_start(span);
+                writeln!(self, "{}{} = {};", keyword, name, value);
+                return
+            },
+            Pattern::Tuple(patterns) => (patterns, true),
+            Pattern::Array(patterns) => (patterns, false),
+        };
+
+        let temp = self.declare("@pattern");
+        self.line_start(span);
+        writeln!(self, "const {} = $.unpack({}, {}, {});", temp, value, tuple, patterns.len());
+
+        for (i, pattern) in patterns.iter().enumerate() {
+            self.destructure(pattern, format!("{}[{}]", temp, i), span, globals.as_deref_mut());
+        }
+    }
+
+    fn statements(&mut self, block: &Block) -> GenResult {
+        self.indent += 1;
+
+        for (stmt, span) in block.statements().iter().zip(block.spans()) {
+            self.statement(stmt, Some(*span))?;
+        }
+
+        self.indent -= 1;
+        Ok(())
+    }
+
+    /// Writes `block`'s statements in a new scope, without the braces
+    fn block(&mut self, block: &Block) -> GenResult {
+        self.scopes.push(HashMap::new());
+        let result = self.statements(block);
+        self.scopes.pop();
+        result
+    }
+
+    fn statement(&mut self, stmt: &Statement, span: Option<Span>) -> GenResult {
+        match stmt {
+            Statement::Expression(expr) => {
+                let expr = self.statement_expression(expr)?;
+                self.line_start(span);
+                writeln!(self, "{};", expr);
+            },
+            Statement::Declaration(Declaration::Variable { identifier, value }) => {
+                // The initializer is generated before the name is bound, it may refer to a shadowed one
+                let value = self.expression_or_null(value.as_ref())?;
+                let name = self.declare(&identifier.name());
+
+                self.line_start(span);
+                writeln!(self, "let {} = {};", name, value);
+            },
+            Statement::Declaration(Declaration::Destructure { pattern, value }) => {
+                let value = self.expression(value)?;
+                self.destructure(pattern, value, span, None);
+            },
+            Statement::Declaration(Declaration::Function { identifier, arguments, body }) => {
+                let arguments = arguments.as_deref().unwrap_or_default();
+                let name = self.declare(&identifier.name());
+
+                // Nested functions are callable directly, like top level ones
+                self.scopes.last_mut().unwrap()
+                    .insert(identifier.name(), Binding::Function(name.clone(), arguments.len()));
+                self.function(name, arguments, body, span.unwrap_or_default())?;
+            },
+            Statement::Block(block) | Statement::Else { body: block } => {
+                self.line_start(span);
+                writeln!(self, "{{");
+                self.block(block)?;
+                self.line_start(None);
+                writeln!(self, "}}");
+            },
+            Statement::Return(expr) => {
+                let expr = self.expression(expr)?;
+                self.line_start(span);
+                writeln!(self, "return {};", expr);
+            },
+            Statement::If { condition, body, else_stmt } => {
+                let condition = self.condition(condition)?;
+                self.line_start(span);
+                writeln!(self, "if ({}) {{", condition);
+                self.block(body)?;
+                self.else_chain(else_stmt.as_deref())?;
+            },
+            Statement::While { condition, body } => {
+                let condition = self.condition(condition)?;
+                self.line_start(span);
+                writeln!(self, "while ({}) {{", condition);
+                self.loop_body(body)?;
+                self.line_start(None);
+                writeln!(self, "}}");
+            },
+            Statement::For { variable, condition, step, body } => {
+                self.scopes.push(HashMap::new());
+
+                let init = match variable.as_deref() {
+                    Some(Statement::Declaration(Declaration::Variable { identifier, value })) => {
+                        let value = self.expression_or_null(value.as_ref())?;
+                        format!("let {} = {}", self.declare(&identifier.name()), value)
+                    },
+                    Some(Statement::Expression(expr)) => self.statement_expression(expr)?,
+                    Some(_) => return Err(BackendError::Unsupported(String::from("this for loop initializer"))),
+                    None => String::new(),
+                };
+
+                let condition = match condition {
+                    Some(cond) => self.condition(cond)?,
+                    None => String::new(),
+                };
+
+                let step = match step {
+                    Some(step) => self.statement_expression(step)?,
+                    None => String::new(),
+                };
+
+                self.line_start(span);
+                writeln!(self, "for ({}; {}; {}) {{", init, condition, step);
+                self.loop_body(body)?;
+                self.line_start(None);
+                writeln!(self, "}}");
+
+                self.scopes.pop();
+            },
+            Statement::ForEach { variable, iterable, body } => {
+                let iterable = self.expression(iterable)?;
+
+                self.scopes.push(HashMap::new());
+                let name = self.declare(&variable.name());
+
+                self.line_start(span);
+                writeln!(self, "for (let {} of $.iterate({})) {{", name, iterable);
+                self.loop_body(body)?;
+                self.line_start(None);
+                writeln!(self, "}}");
+
+                self.scopes.pop();
+            },
+            Statement::Throw(expr) => {
+                let expr = self.expression(expr)?;
+                self.line_start(span);
+                writeln!(self, "throw $.thrown({});", expr);
+            },
+            Statement::Assert { condition, message } => {
+                // The operands are held so a failure can show them
+                let (values, check, detail) = match condition {
+                    Expression::Binary { lhs, operation, rhs } => (
+                        format!("$lhs = {}, $rhs = {}", self.expression(lhs)?, self.expression(rhs)?),
+                        format!("$.{}($lhs, $rhs)", binary_fn(operation)?),
+                        format!("$lhs, \"{}\", $rhs", binop_symbol(operation)),
+                    ),
+                    condition => (
+                        format!("$value = {}", self.expression(condition)?),
+                        String::from("$value"),
+                        String::from("$value"),
+                    ),
+                };
+                let message = self.expression_or_null(message.as_ref())?;
+
+                self.line_start(span);
+                writeln!(self, "{{");
+                self.indent += 1;
+                self.line_start(None);
+                writeln!(self, "const {};", values);
+                self.line_start(None);
+                writeln!(self, "if (!$.truthy({})) $.assertFailed({}, {});", check, message, detail);
+                self.indent -= 1;
+                self.line_start(None);
+                writeln!(self, "}}");
+            },
+            Statement::Try { body, catch, finally } => {
+                self.line_start(span);
+                writeln!(self, "try {{");
+                self.block(body)?;
+
+                if let Some((identifier, block)) = catch {
+                    // Runtime errors are caught as their message, like in the VM
+                    self.scopes.push(HashMap::new());
+                    let error = self.declare("@error");
+                    let name = self.declare(&identifier.name());
+
+                    self.line_start(None);
+                    writeln!(self, "}} catch ({}) {{", error);
+                    self.indent += 1;
+                    self.line_start(None);
+                    writeln!(self, "let {} = $.caught({});", name, error);
+                    self.indent -= 1;
+                    self.statements(block)?;
+
+                    self.scopes.pop();
+                }
+
+                if let Some(block) = finally {
+                    self.line_start(None);
+                    writeln!(self, "}} finally {{");
+                    self.block(block)?;
+                }
+
+                self.line_start(None);
+                writeln!(self, "}}");
+            },
+            Statement::Break => {
+                if self.loops == 0 {
+                    return Err(BackendError::BreakOutsideLoop)
+                }
+                self.line_start(span);
+                writeln!(self, "break;");
+            },
+            Statement::Continue => {
+                if self.loops == 0 {
+                    return Err(BackendError::ContinueOutsideLoop)
+                }
+                self.line_start(span);
+                writeln!(self, "continue;");
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Closes an `if` body, continuing with `else if` or `else` when there is one
+    fn else_chain(&mut self, else_stmt: Option<&Statement>) -> GenResult {
+        match else_stmt {
+            Some(Statement::If { condition, body, else_stmt }) => {
+                let condition = self.condition(condition)?;
+                self.line_start(None);
+                writeln!(self, "}} else if ({}) {{", condition);
+                self.block(body)?;
+                self.else_chain(else_stmt.as_deref())
+            },
+            Some(Statement::Block(body)) | Some(Statement::Else { body }) => {
+                self.line_start(None);
+                writeln!(self, "}} else {{");
+                self.block(body)?;
+                self.else_chain(None)
+            },
+            Some(_) => Err(BackendError::Unsupported(String::from("this else branch"))),
+            None => {
+                self.line_start(None);
+                writeln!(self, "}}");
+                Ok(())
+            },
+        }
+    }
+
+    fn loop_body(&mut self, body: &Block) -> GenResult {
+        self.loops += 1;
+        let result = self.block(body);
+        self.loops -= 1;
+        result
+    }
+
+    fn condition(&mut self, expr: &Expression) -> ExprResult {
+        Ok(format!("$.truthy({})", self.expression(expr)?))
+    }
+
+    fn expression_or_null(&mut self, expr: Option<&Expression>) -> ExprResult {
+        match expr {
+            Some(expr) => self.expression(expr),
+            None => Ok(String::from("null")),
+        }
+    }
+
+    /// An expression whose value is unused, so assignments need no parentheses
+    fn statement_expression(&mut self, expr: &Expression) -> ExprResult {
+        match expr {
+            Expression::Assignment { lhs, operation: None, rhs } => self.assignment(lhs, rhs),
+            expr => self.expression(expr),
+        }
+    }
+
+    fn assignment(&mut self, lhs: &Expression, rhs: &Expression) -> ExprResult {
+        match place(lhs)? {
+            Place::Variable(identifier) => Ok(format!("{} = {}", self.variable(identifier)?, self.expression(rhs)?)),
+            Place::Element { target, index } => Ok(format!("$.setIndex({}, {}, {})",
+                self.expression(target)?, self.expression(index)?, self.expression(rhs)?)),
+        }
+    }
+
+    /// The JavaScript variable an assignment stores into
+    fn variable(&mut self, identifier: &Identifier) -> ExprResult {
+        match self.lookup(&identifier.name()) {
+            Some(Binding::Variable(js) | Binding::Function(js, _)) => Ok(js.clone()),
+            None => Err(BackendError::UndefinedVariable(identifier.name())),
+        }
+    }
+
+    /// Elements are held with `$.hold` so their target and index are evaluated once, JavaScript's left to
+    /// right order reads variables before `rhs` runs
+    fn compound_assignment(&mut self, lhs: &Expression, operation: &Token, rhs: &Expression) -> ExprResult {
+        let func = binary_fn(operation)?;
+
+        match place(lhs)? {
+            Place::Element { target, index } => {
+                let place = format!("$.hold({}, {})", self.expression(target)?, self.expression(index)?);
+                Ok(format!("$.update({}, $.{}, {})", place, func, self.expression(rhs)?))
+            },
+            Place::Variable(identifier) => {
+                let js = self.variable(identifier)?;
+                Ok(format!("({} = $.{}({}, {}))", js, func, js, self.expression(rhs)?))
+            },
+        }
+    }
+
+    fn postfix(&mut self, operand: &Expression, operation: &Token) -> ExprResult {
+        let func = match operation {
+            Token::Increment => "add",
+            Token::Decrement => "sub",
+            tok => return Err(BackendError::Unsupported(format!("postfix operator {}", tok))),
+        };
+
+        match place(operand)? {
+            Place::Element { target, index } => {
+                let place = format!("$.hold({}, {})", self.expression(target)?, self.expression(index)?);
+                Ok(format!("$.postfix({}, $.{})", place, func))
+            },
+            Place::Variable(identifier) => {
+                let js = self.variable(identifier)?;
+                Ok(format!("$.first({}, {} = $.{}({}, 1n))", js, js, func, js))
+            },
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) -> ExprResult {
+        match expr {
+            Expression::Literal(lit) => Ok(literal(lit)),
+            Expression::Value(identifier) => match self.lookup(&identifier.name()) {
+                Some(Binding::Variable(js) | Binding::Function(js, _)) => Ok(js.clone()),
+                None => Err(BackendError::UndefinedVariable(identifier.name())),
+            },
+            Expression::Member { target, property } =>
+                Ok(format!("$.index({}, {})", self.expression(target)?, self.expression(property)?)),
+            Expression::Array { elements, .. } => Ok(format!("[{}]", self.elements(elements)?.join(", "))),
+            Expression::Tuple(elements) => {
+                let elements = elements.iter().map(|e| self.expression(e)).collect::<Result<Vec<_>, _>>()?;
+                Ok(format!("$.tuple({})", elements.join(", ")))
+            },
+            Expression::Map(entries) => {
+                let mut operands = vec![];
+                for (key, value) in entries {
+                    operands.push(self.expression(key)?);
+                    operands.push(self.expression(value)?);
+                }
+                Ok(format!("$.map({})", operands.join(", ")))
+            },
+            Expression::Set(elements) => Ok(format!("$.set({})", self.elements(elements)?.join(", "))),
+            Expression::Assignment { lhs, operation: None, rhs } => Ok(format!("({})", self.assignment(lhs, rhs)?)),
+            Expression::Assignment { lhs, operation: Some(operation), rhs } => self.compound_assignment(lhs, operation, rhs),
+            Expression::Postfix { operand, operation } => self.postfix(operand, operation),
+            Expression::Call { target, args } => self.call(target, args.as_deref().unwrap_or_default()),
+            Expression::Conditional { condition, then, otherwise } =>
+                Ok(format!("({} ? {} : {})", self.condition(condition)?, self.expression(then)?, self.expression(otherwise)?)),
+            Expression::Coalesce { lhs, rhs } => Ok(format!("({} ?? {})", self.expression(lhs)?, self.expression(rhs)?)),
+            Expression::OptionalMember { target, property } =>
+                Ok(format!("$.optional({}, $v => $.index($v, {}))", self.expression(target)?, self.expression(property)?)),
+            Expression::OptionalCall { target, args } => self.optional_call(target, args.as_deref().unwrap_or_default()),
+            Expression::Unary { prefix, operand } => {
+                let operand = self.expression(operand)?;
+
+                let func = match prefix {
+                    None => return Ok(operand),
+                    Some(Token::Minus) => "neg",
+                    Some(Token::Not) => "not",
+                    Some(Token::BinaryNegate) => "bnot",
+                    Some(tok) => return Err(BackendError::Unsupported(format!("unary operator {}", tok))),
+                };
+
+                Ok(format!("$.{}({})", func, operand))
+            },
+            Expression::Binary { lhs, operation, rhs } =>
+                Ok(format!("$.{}({}, {})", binary_fn(operation)?, self.expression(lhs)?, self.expression(rhs)?)),
+            Expression::Logical { lhs, operation, rhs } =>
+                Ok(format!("({} {} {})", self.condition(lhs)?, logical_op(operation)?, self.condition(rhs)?)),
+            Expression::Range { start, end, inclusive } =>
+                Ok(format!("$.range({}, {}, {})", self.expression(start)?, self.expression(end)?, inclusive)),
+            Expression::Spread(_) => Err(BackendError::Unsupported(String::from("'...' outside of arrays, sets and calls"))),
+            Expression::Comprehension { result, loops, .. } => {
+                // An arrow function called in place, so the loops only run where the comprehension is evaluated.
+                // Its statements are joined onto the line of the enclosing statement, which the source map points at
+                let (out, line, spans) = (std::mem::take(&mut self.out), self.line, self.spans.len());
+
+                self.scopes.push(HashMap::new());
+                let name = self.declare(&result.name());
+                self.statement(loops, None)?;
+                self.scopes.pop();
+
+                let body = std::mem::replace(&mut self.out, out);
+                self.line = line;
+                self.spans.truncate(spans);
+
+                let body = body.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+                Ok(format!("(() => {{ const {} = []; {} return {}; }})()", name, body, name))
+            },
+            Expression::Append { target, value } => {
+                let array = self.variable(target)?;
+                Ok(format!("{}.push({})", array, self.expression(value)?))
+            },
+        }
+    }
+
+    /// Array elements or call arguments, spreading iterables with the runtime's iteration protocol
+    fn elements(&mut self, elements: &[Expression]) -> Result<Vec<String>, BackendError> {
+        elements.iter().map(|e| match e {
+            Expression::Spread(iterable) => Ok(format!("...$.iterate({})", self.expression(iterable)?)),
+            e => self.expression(e),
+        }).collect()
+    }
+
+    /// The access happens in a function `$.optional` only calls for non-null values, which Ult names can't
+    /// shadow as they have no `$`. Functions are never null, so calling them needs no check
+    fn optional_call(&mut self, target: &Expression, args: &[Expression]) -> ExprResult {
+        if let Expression::Value(identifier) = target {
+            if !matches!(self.lookup(&identifier.name()), Some(Binding::Variable(_))) {
+                return self.call(target, args)
+            }
+        }
+
+        let mut call_args = vec![String::from("$v")];
+        call_args.extend(self.elements(args)?);
+
+        Ok(format!("$.optional({}, $v => $.call({}))", self.expression(target)?, call_args.join(", ")))
+    }
+
+    fn call(&mut self, target: &Expression, args: &[Expression]) -> ExprResult {
+        let spread = args.iter().any(|a| matches!(a, Expression::Spread(_)));
+        let args = self.elements(args)?;
+
+        if let Expression::Value(identifier) = target {
+            let name = identifier.name();
+
+            // How many arguments a spread passes is only known at runtime, where `$.call` checks it
+            match self.lookup(&name) {
+                Some(Binding::Function(js, _)) if spread => return Ok(format!("$.call({}, {})", js, args.join(", "))),
+                None if spread => return match stdlib::lookup(&name) {
+                    Some(Builtin { arity: Some(_), .. }) => Ok(format!("$.call($.std.{}, {})", name, args.join(", "))),
+                    Some(_) => Ok(format!("$.std.{}({})", name, args.join(", "))),
+                    None => Err(BackendError::UndefinedFunction(name)),
+                },
+                Some(Binding::Function(js, arity)) if *arity == args.len() =>
+                    return Ok(format!("{}({})", js, args.join(", "))),
+                Some(Binding::Function(_, arity)) =>
+                    return Err(BackendError::ArityMismatch(name, *arity, args.len())),
+                Some(Binding::Variable(_)) => (),
+                None => return match builtin(&name, args.len())? {
+                    Some(_) => Ok(format!("$.std.{}({})", name, args.join(", "))),
+                    None => Err(BackendError::UndefinedFunction(name)),
+                },
+            }
+        }
+
+        // Anything else is checked at runtime
+        let mut call_args = vec![self.expression(target)?];
+        call_args.extend(args);
+
+        Ok(format!("$.call({})", call_args.join(", ")))
+    }
+
+    /// Encodes the recorded spans as Source Map v3 `mappings`
+    fn mappings(&self) -> String {
+        let mut out = String::new();
+        let mut line = 0;
+        let mut column = 0;
+        let (mut src_line, mut src_column) = (0, 0);
+
+        for (gen_line, gen_column, span) in &self.spans {
+            if line < *gen_line {
+                out.push_str(&";".repeat(gen_line - line));
+                line = *gen_line;
+                column = 0;
+            }
+            else if !out.is_empty() {
+                out.push(',');
+            }
+
+            let (orig_line, orig_column) = (span.line as i64 - 1, span.column as i64);
+
+            vlq(&mut out, *gen_column as i64 - column);
+            vlq(&mut out, 0);
+            vlq(&mut out, orig_line - src_line);
+            vlq(&mut out, orig_column - src_column);
+
+            column = *gen_column as i64;
+            src_line = orig_line;
+            src_column = orig_column;
+        }
+
+        out
+    }
+}
+
+/// Names the parser makes up start with '@', which becomes a `$` no Ult name has
+fn js_name(name: &str) -> String {
+    if RESERVED.contains(&name) { format!("{}$", name) } else { name.replace('@', "$") }
+}
+
+/// Name of the runtime function applying a binary operator
+fn binary_fn(tok: &Token) -> Result<&'static str, BackendError> {
+    let func = match tok {
+        Token::Plus => "add",
+        Token::Minus => "sub",
+        Token::Multiply => "mul",
+        Token::Divide => "div",
+        Token::Modulo => "mod",
+        Token::Exponentiate => "pow",
+        Token::BinaryAnd => "band",
+        Token::BinaryOr => "bor",
+        Token::Xor => "bxor",
+        Token::ShiftLeft => "shl",
+        Token::ShiftRight => "shr",
+        Token::Equals => "eq",
+        Token::NotEquals => "ne",
+        Token::In => "in",
+        Token::LessThan => "lt",
+        Token::LessEquals => "le",
+        Token::GreaterThan => "gt",
+        Token::GreaterEquals => "ge",
+        tok => return Err(BackendError::Unsupported(format!("binary operator {}", tok))),
+    };
+
+    Ok(func)
+}
+
+/// JS's own `&&` and `||` already skip the right side
+fn logical_op(tok: &Token) -> Result<&'static str, BackendError> {
+    match tok {
+        Token::LogicalAnd => Ok("&&"),
+        Token::LogicalOr => Ok("||"),
+        tok => Err(BackendError::Unsupported(format!("logical operator {}", tok))),
+    }
+}
+
+fn literal(lit: &Literal) -> String {
+    match lit {
+        Literal::String(s) => quote(s),
+        Literal::Integer(i) => format!("{}n", i),
+        Literal::Decimal(d) => format!("{:?}", d),
+        Literal::Character(c) => format!("$.char({})", quote(&c.to_string())),
+        Literal::Boolean(b) => b.to_string(),
+        Literal::Null => String::from("null"),
+    }
+}
+
+/// A double quoted JavaScript (and JSON) string literal
+fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\u{2028}' || c == '\u{2029}' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Appends a Base64 VLQ number
+fn vlq(out: &mut String, value: i64) {
+    let mut v = if value < 0 { ((-value) << 1) | 1 } else { value << 1 };
+
+    loop {
+        let mut digit = v & 31;
+        v >>= 5;
+        if v > 0 {
+            digit |= 32;
+        }
+
+        out.push(BASE64[digit as usize] as char);
+
+        if v == 0 {
+            break
+        }
+    }
+}
\ No newline at end of file
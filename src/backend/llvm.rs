@@ -0,0 +1,582 @@
+use super::super::ir::{ self, BinOp, Callee, Constant, Function, Inst, Module, Terminator, Type, UnOp, Value };
+use super::super::ir::opt::{ OptLevel, PassManager };
+use super::super::parse::ast::AST;
+use super::error::BackendError;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+type GenResult = Result<(), BackendError>;
+type ValueResult = Result<String, BackendError>;
+
+/// Lowers an `AST` to textual LLVM IR, by way of the SSA IR optimized at `-O2`. \
+/// Like the x86-64 backend every value is an `i64`. IR values become registers named `%vN`,
+/// blocks keep their `bbN` labels and phis stay phis, so only globals live in memory.
+/// Printing goes through `printf`. Pointers are opaque (`ptr`), the default from LLVM 15. LLVM 14 tools need
+/// `-opaque-pointers`, which `ult build` passes to an older clang
+pub fn generate(ast: &AST) -> Result<String, BackendError> {
+    let mut module = ir::lower::lower(ast)?;
+
+    ir::verify::verify(&module)?;
+    PassManager::for_level(OptLevel::O2).run(&mut module, None)?;
+
+    let mut gen = Generator::new();
+
+    gen.module(&module)?;
+
+    Ok(gen.finish())
+}
+
+struct Generator {
+    data: String,
+    constants: String,
+    functions: String,
+    /// Body of the function being generated
+    code: String,
+    /// Numbers temporaries `%t.N`, apart from values `%vN` and parameters `%pN`
+    counter: usize,
+    strings: HashMap<String, String>,
+}
+
+impl Generator {
+    fn new() -> Self {
+        Self {
+            data: String::new(),
+            constants: String::new(),
+            functions: String::new(),
+            code: String::new(),
+            counter: 0,
+            strings: HashMap::new(),
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "; Generated by ult").unwrap();
+        out.push_str(&self.data);
+        out.push_str(&self.constants);
+        out.push_str(&self.functions);
+        out.push_str(RUNTIME);
+
+        out
+    }
+
+    fn module(&mut self, module: &Module) -> GenResult {
+        for global in &module.globals {
+            writeln!(self.data, "@ult_var_{} = internal global i64 0", global).unwrap();
+        }
+
+        if !self.data.is_empty() {
+            self.data.push('\n');
+        }
+
+        for function in &module.functions {
+            self.function(function)?;
+        }
+
+        // `main` runs the global initializers before the program's `main`
+        writeln!(self.functions, "define i32 @main() {{\nentry:\n  call i64 {}()", symbol(Module::INIT)).unwrap();
+
+        match module.function("main") {
+            Some(main) if main.params == 0 => writeln!(self.functions,
+                "  %result = call i64 {}()\n  %code = trunc i64 %result to i32\n  ret i32 %code", symbol("main")).unwrap(),
+            Some(main) => return Err(BackendError::ArityMismatch(String::from("main"), 0, main.params)),
+            None => writeln!(self.functions, "  ret i32 0").unwrap(),
+        }
+
+        writeln!(self.functions, "}}\n").unwrap();
+
+        Ok(())
+    }
+
+    fn function(&mut self, f: &Function) -> GenResult {
+        self.code.clear();
+
+        for (id, block) in f.block_ids().zip(&f.blocks) {
+            if id != Function::ENTRY {
+                self.code.push('\n');
+            }
+            writeln!(self.code, "{}:", id).unwrap();
+
+            for &value in &block.insts {
+                self.inst(f, value)?;
+            }
+
+            match &block.terminator {
+                Terminator::Jump(target) => self.instr(format!("br label %{}", target)),
+                // A branch with both edges to one block must stay a single edge for its phis
+                Terminator::Branch(_, then, otherwise) if then == otherwise => self.instr(format!("br label %{}", then)),
+                Terminator::Branch(cond, then, otherwise) => {
+                    let cond = self.operand(f, *cond)?;
+                    let flag = self.temp();
+                    self.instr(format!("{} = icmp ne i64 {}, 0", flag, cond));
+                    self.instr(format!("br i1 {}, label %{}, label %{}", flag, then, otherwise));
+                },
+                Terminator::Return(value) => {
+                    let value = self.operand(f, *value)?;
+                    self.instr(format!("ret i64 {}", value));
+                },
+                Terminator::Unreachable => self.instr("unreachable"),
+            }
+        }
+
+        let params = (0..f.params).map(|i| format!("i64 %p{}", i)).collect::<Vec<_>>();
+
+        writeln!(self.functions, "define internal i64 {}({}) {{", symbol(&f.name), params.join(", ")).unwrap();
+        self.functions.push_str(&self.code);
+        writeln!(self.functions, "}}\n").unwrap();
+
+        Ok(())
+    }
+
+    fn temp(&mut self) -> String {
+        self.counter += 1;
+        format!("%t.{}", self.counter)
+    }
+
+    fn instr(&mut self, line: impl AsRef<str>) {
+        self.code.push_str("  ");
+        self.code.push_str(line.as_ref());
+        self.code.push('\n');
+    }
+
+    /// The `i64` operand standing for `value`. \
+    /// Constants, parameters and copies are used in place, instructions without a result are zero
+    fn operand(&self, f: &Function, value: Value) -> ValueResult {
+        match f.inst(value) {
+            Inst::Const(constant) => literal(constant),
+            Inst::Param(i) => Ok(format!("%p{}", i)),
+            Inst::Copy(v) => self.operand(f, *v),
+            Inst::SetGlobal(..) => Ok(String::from("0")),
+            Inst::Call(Callee::Builtin(name), _) if name == "print" || name == "println" => Ok(String::from("0")),
+            _ => Ok(register(value)),
+        }
+    }
+
+    fn inst(&mut self, f: &Function, value: Value) -> GenResult {
+        let dest = register(value);
+
+        match f.inst(value) {
+            Inst::Const(_) | Inst::Param(_) | Inst::Copy(_) => (),
+            Inst::Phi(incoming) => {
+                let mut edges = vec![];
+                for (block, v) in incoming {
+                    edges.push(format!("[ {}, %{} ]", self.operand(f, *v)?, block));
+                }
+                self.instr(format!("{} = phi i64 {}", dest, edges.join(", ")));
+            },
+            Inst::Unary(op, v) => {
+                let v = self.operand(f, *v)?;
+                match op {
+                    UnOp::Neg => self.instr(format!("{} = call i64 @ult_sub(i64 0, i64 {})", dest, v)),
+                    UnOp::BitNot => self.instr(format!("{} = xor i64 {}, -1", dest, v)),
+                    UnOp::Not => {
+                        let flag = self.temp();
+                        self.instr(format!("{} = icmp eq i64 {}, 0", flag, v));
+                        self.instr(format!("{} = zext i1 {} to i64", dest, flag));
+                    },
+                }
+            },
+            Inst::Binary(op, lhs, rhs) => {
+                let lhs = self.operand(f, *lhs)?;
+                let rhs = self.operand(f, *rhs)?;
+                self.binary(&dest, *op, &lhs, &rhs)?;
+            },
+            Inst::Call(Callee::Direct(name), args) => {
+                let mut values = vec![];
+                for arg in args {
+                    values.push(format!("i64 {}", self.operand(f, *arg)?));
+                }
+                self.instr(format!("{} = call i64 {}({})", dest, symbol(name), values.join(", ")));
+            },
+            Inst::Call(Callee::Builtin(name), args) if name == "print" || name == "println" =>
+                self.print(f, args, name == "println")?,
+            Inst::Call(Callee::Builtin(name), args) => self.builtin(f, &dest, name, args)?,
+            Inst::Call(Callee::Indirect(_), _) => return Err(BackendError::Unsupported(String::from("indirect calls"))),
+            Inst::GetGlobal(name) => self.instr(format!("{} = load i64, ptr @ult_var_{}", dest, name)),
+            Inst::SetGlobal(name, v) => {
+                let v = self.operand(f, *v)?;
+                self.instr(format!("store i64 {}, ptr @ult_var_{}", v, name));
+            },
+            Inst::Array(_) => return Err(BackendError::Unsupported(String::from("arrays"))),
+            Inst::Tuple(_) => return Err(BackendError::Unsupported(String::from("tuples"))),
+            Inst::Map(_) => return Err(BackendError::Unsupported(String::from("maps"))),
+            Inst::Set(_) => return Err(BackendError::Unsupported(String::from("sets"))),
+            Inst::Unpack(..) => return Err(BackendError::Unsupported(String::from("destructuring"))),
+            Inst::Index(..) | Inst::SetIndex(..) => return Err(BackendError::Unsupported(String::from("indexing"))),
+        }
+
+        Ok(())
+    }
+
+    fn binary(&mut self, dest: &str, op: BinOp, lhs: &str, rhs: &str) -> GenResult {
+        let instr = match op {
+            BinOp::BitAnd => "and",
+            BinOp::BitOr => "or",
+            BinOp::BitXor => "xor",
+            // Operations that can fail are checked by the runtime, so blocks never need splitting
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod | BinOp::Pow | BinOp::Shl | BinOp::Shr => {
+                let helper = match op {
+                    BinOp::Add => "@ult_add",
+                    BinOp::Sub => "@ult_sub",
+                    BinOp::Mul => "@ult_mul",
+                    BinOp::Div => "@ult_div",
+                    BinOp::Mod => "@ult_rem",
+                    BinOp::Shl => "@ult_shl",
+                    BinOp::Shr => "@ult_shr",
+                    _ => "@ult_pow",
+                };
+                self.instr(format!("{} = call i64 {}(i64 {}, i64 {})", dest, helper, lhs, rhs));
+                return Ok(())
+            },
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                let cmp = match op {
+                    BinOp::Eq => "eq",
+                    BinOp::Ne => "ne",
+                    BinOp::Lt => "slt",
+                    BinOp::Le => "sle",
+                    BinOp::Gt => "sgt",
+                    _ => "sge",
+                };
+                let flag = self.temp();
+                self.instr(format!("{} = icmp {} i64 {}, {}", flag, cmp, lhs, rhs));
+                self.instr(format!("{} = zext i1 {} to i64", dest, flag));
+                return Ok(())
+            },
+            BinOp::In => return Err(BackendError::Unsupported(String::from("'in'"))),
+        };
+
+        self.instr(format!("{} = {} i64 {}, {}", dest, instr, lhs, rhs));
+        Ok(())
+    }
+
+    /// The standard library functions that only need integers: `abs`, `min`, `max` and `pow`. \
+    /// Their arity was checked when lowering
+    fn builtin(&mut self, f: &Function, dest: &str, name: &str, args: &[Value]) -> GenResult {
+        let mut values = vec![];
+        for arg in args {
+            values.push(self.operand(f, *arg)?);
+        }
+
+        match name {
+            "pow" => self.binary(dest, BinOp::Pow, &values[0], &values[1])?,
+            "abs" => {
+                let negated = self.temp();
+                let negative = self.temp();
+                self.instr(format!("{} = call i64 @ult_sub(i64 0, i64 {})", negated, values[0]));
+                self.instr(format!("{} = icmp slt i64 {}, 0", negative, values[0]));
+                self.select(dest, &negative, &negated, &values[0]);
+            },
+            // Like the VM, `min` only picks `b` when it's smaller and `max` when `a` is
+            "min" | "max" => {
+                let (a, b) = (&values[0], &values[1]);
+                let (lhs, rhs) = if name == "min" { (b, a) } else { (a, b) };
+                let less = self.temp();
+                self.instr(format!("{} = icmp slt i64 {}, {}", less, lhs, rhs));
+                self.select(dest, &less, b, a);
+            },
+            _ => return Err(BackendError::Unsupported(format!("standard library function '{}'", name))),
+        }
+
+        Ok(())
+    }
+
+    fn select(&mut self, dest: &str, flag: &str, then: &str, otherwise: &str) {
+        self.instr(format!("{} = select i1 {}, i64 {}, i64 {}", dest, flag, then, otherwise));
+    }
+
+    /// Prints the arguments separated by spaces with a single `printf` call. \
+    /// Constants are written into the format, other values are formatted by their IR type
+    fn print(&mut self, f: &Function, args: &[Value], newline: bool) -> GenResult {
+        let mut format = String::new();
+        let mut values = vec![];
+
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                format.push(' ');
+            }
+
+            match constant(f, *arg) {
+                Some(Constant::Str(s)) => format.push_str(&s.replace('%', "%%")),
+                Some(Constant::Char('%')) => format.push_str("%%"),
+                Some(Constant::Char(c)) => format.push(*c),
+                Some(Constant::Bool(b)) => format.push_str(if *b { "true" } else { "false" }),
+                Some(Constant::Null) => format.push_str("null"),
+                Some(Constant::Int(n)) => write!(format, "{}", n).unwrap(),
+                _ => {
+                    let value = self.operand(f, *arg)?;
+
+                    match f.ty(*arg) {
+                        Type::Bool => {
+                            let flag = self.temp();
+                            let temp = self.temp();
+                            self.instr(format!("{} = icmp ne i64 {}, 0", flag, value));
+                            self.instr(format!("{} = select i1 {}, ptr @.str.true, ptr @.str.false", temp, flag));
+                            format.push_str("%s");
+                            values.push(format!("ptr {}", temp));
+                        },
+                        Type::Char => {
+                            let temp = self.temp();
+                            self.instr(format!("{} = trunc i64 {} to i32", temp, value));
+                            format.push_str("%c");
+                            values.push(format!("i32 {}", temp));
+                        },
+                        _ => {
+                            format.push_str("%lld");
+                            values.push(format!("i64 {}", value));
+                        },
+                    }
+                },
+            }
+        }
+
+        if newline {
+            format.push('\n');
+        }
+
+        let constant = self.string(&format);
+        values.insert(0, format!("ptr {}", constant));
+
+        self.instr(format!("call i32 (ptr, ...) @printf({})", values.join(", ")));
+        Ok(())
+    }
+
+    /// Returns a constant holding the NUL terminated `s`
+    fn string(&mut self, s: &str) -> String {
+        if let Some(name) = self.strings.get(s) {
+            return name.clone()
+        }
+
+        let name = format!("@.str.{}", self.strings.len());
+        writeln!(self.constants, "{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"\n",
+            name, s.len() + 1, escape(s)).unwrap();
+
+        self.strings.insert(s.to_string(), name.clone());
+        name
+    }
+}
+
+/// `__init` becomes `ult_init`, user functions, nested ones named `outer.inner`, get a prefix
+fn symbol(name: &str) -> String {
+    if name == Module::INIT {
+        String::from("@ult_init")
+    }
+    else {
+        format!("@ult_fn_{}", name)
+    }
+}
+
+fn register(value: Value) -> String {
+    format!("%v{}", value.0)
+}
+
+/// The constant `value` holds, looking through copies
+fn constant(f: &Function, value: Value) -> Option<&Constant> {
+    match f.inst(value) {
+        Inst::Const(constant) => Some(constant),
+        Inst::Copy(v) => constant(f, *v),
+        _ => None,
+    }
+}
+
+fn literal(constant: &Constant) -> ValueResult {
+    match constant {
+        Constant::Int(i) => Ok(i.to_string()),
+        Constant::Bool(b) => Ok((*b as i64).to_string()),
+        Constant::Char(c) => Ok((*c as u32).to_string()),
+        Constant::Null => Ok(String::from("0")),
+        Constant::Dec(_) => Err(BackendError::Unsupported(String::from("decimals"))),
+        Constant::Str(_) => Err(BackendError::Unsupported(String::from("strings outside of print"))),
+        Constant::Func(_) => Err(BackendError::Unsupported(String::from("functions as values"))),
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'"' | b'\\' => write!(out, "\\{:02X}", byte).unwrap(),
+            b' '..=b'~' => out.push(byte as char),
+            b => write!(out, "\\{:02X}", b).unwrap(),
+        }
+    }
+    out
+}
+
+/// libc declarations and helpers shared by every module. \
+/// Like the VM, arithmetic that overflows, divides by zero or shifts by a count outside 0..=63 is a runtime error
+const RUNTIME: &str = r#"declare i32 @printf(ptr, ...)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @exit(i32) noreturn
+declare { i64, i1 } @llvm.sadd.with.overflow.i64(i64, i64)
+declare { i64, i1 } @llvm.ssub.with.overflow.i64(i64, i64)
+declare { i64, i1 } @llvm.smul.with.overflow.i64(i64, i64)
+
+@.str.true = private unnamed_addr constant [5 x i8] c"true\00"
+@.str.false = private unnamed_addr constant [6 x i8] c"false\00"
+@.str.div_zero = private unnamed_addr constant [18 x i8] c"Division by zero\0A\00"
+@.str.overflow = private unnamed_addr constant [18 x i8] c"Integer overflow\0A\00"
+@.str.negative_exponent = private unnamed_addr constant [44 x i8] c"Negative exponents need decimal arithmetic\0A\00"
+
+; Writes the message to stderr and exits with status 1
+define internal void @ult_fail(ptr %message) noreturn {
+entry:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr %message)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal i64 @ult_add(i64 %lhs, i64 %rhs) {
+entry:
+  %pair = call { i64, i1 } @llvm.sadd.with.overflow.i64(i64 %lhs, i64 %rhs)
+  %overflow = extractvalue { i64, i1 } %pair, 1
+  br i1 %overflow, label %error, label %ok
+
+error:
+  call void @ult_fail(ptr @.str.overflow)
+  unreachable
+
+ok:
+  %value = extractvalue { i64, i1 } %pair, 0
+  ret i64 %value
+}
+
+define internal i64 @ult_sub(i64 %lhs, i64 %rhs) {
+entry:
+  %pair = call { i64, i1 } @llvm.ssub.with.overflow.i64(i64 %lhs, i64 %rhs)
+  %overflow = extractvalue { i64, i1 } %pair, 1
+  br i1 %overflow, label %error, label %ok
+
+error:
+  call void @ult_fail(ptr @.str.overflow)
+  unreachable
+
+ok:
+  %value = extractvalue { i64, i1 } %pair, 0
+  ret i64 %value
+}
+
+define internal i64 @ult_mul(i64 %lhs, i64 %rhs) {
+entry:
+  %pair = call { i64, i1 } @llvm.smul.with.overflow.i64(i64 %lhs, i64 %rhs)
+  %overflow = extractvalue { i64, i1 } %pair, 1
+  br i1 %overflow, label %error, label %ok
+
+error:
+  call void @ult_fail(ptr @.str.overflow)
+  unreachable
+
+ok:
+  %value = extractvalue { i64, i1 } %pair, 0
+  ret i64 %value
+}
+
+; `sdiv` and `srem` of the minimum by -1 are undefined, so -1 negates instead
+define internal i64 @ult_div(i64 %lhs, i64 %rhs) {
+entry:
+  %zero = icmp eq i64 %rhs, 0
+  br i1 %zero, label %error, label %nonzero
+
+error:
+  call void @ult_fail(ptr @.str.div_zero)
+  unreachable
+
+nonzero:
+  %minus_one = icmp eq i64 %rhs, -1
+  br i1 %minus_one, label %negate, label %divide
+
+negate:
+  %negated = call i64 @ult_sub(i64 0, i64 %lhs)
+  ret i64 %negated
+
+divide:
+  %value = sdiv i64 %lhs, %rhs
+  ret i64 %value
+}
+
+define internal i64 @ult_rem(i64 %lhs, i64 %rhs) {
+entry:
+  %zero = icmp eq i64 %rhs, 0
+  br i1 %zero, label %error, label %nonzero
+
+error:
+  call void @ult_fail(ptr @.str.div_zero)
+  unreachable
+
+nonzero:
+  %minus_one = icmp eq i64 %rhs, -1
+  br i1 %minus_one, label %negate, label %divide
+
+negate:
+  call i64 @ult_sub(i64 0, i64 %lhs)
+  ret i64 0
+
+divide:
+  %value = srem i64 %lhs, %rhs
+  ret i64 %value
+}
+
+define internal i64 @ult_shl(i64 %lhs, i64 %rhs) {
+entry:
+  %out = icmp ugt i64 %rhs, 63
+  br i1 %out, label %error, label %ok
+
+error:
+  call void @ult_fail(ptr @.str.overflow)
+  unreachable
+
+ok:
+  %value = shl i64 %lhs, %rhs
+  ret i64 %value
+}
+
+define internal i64 @ult_shr(i64 %lhs, i64 %rhs) {
+entry:
+  %out = icmp ugt i64 %rhs, 63
+  br i1 %out, label %error, label %ok
+
+error:
+  call void @ult_fail(ptr @.str.overflow)
+  unreachable
+
+ok:
+  %value = ashr i64 %lhs, %rhs
+  ret i64 %value
+}
+
+; Integer power by squaring. Negative exponents would give decimals, so they're errors
+define internal i64 @ult_pow(i64 %base, i64 %exp) {
+entry:
+  %negative = icmp slt i64 %exp, 0
+  br i1 %negative, label %error, label %loop
+
+error:
+  call void @ult_fail(ptr @.str.negative_exponent)
+  unreachable
+
+loop:
+  %result = phi i64 [ 1, %entry ], [ %result.next, %square ]
+  %b = phi i64 [ %base, %entry ], [ %b.next, %square ]
+  %e = phi i64 [ %exp, %entry ], [ %e.next, %square ]
+  %bit = and i64 %e, 1
+  %odd = icmp ne i64 %bit, 0
+  br i1 %odd, label %multiply, label %next
+
+multiply:
+  %product = call i64 @ult_mul(i64 %result, i64 %b)
+  br label %next
+
+next:
+  %result.next = phi i64 [ %product, %multiply ], [ %result, %loop ]
+  %e.next = lshr i64 %e, 1
+  %more = icmp ne i64 %e.next, 0
+  br i1 %more, label %square, label %done
+
+square:
+  %b.next = call i64 @ult_mul(i64 %b, i64 %b)
+  br label %loop
+
+done:
+  ret i64 %result.next
+}
+"#;
\ No newline at end of file
@@ -0,0 +1,203 @@
+#[cfg(feature = "backend-c")]
+pub mod c;
+#[cfg(feature = "backend-js")]
+pub mod js;
+#[cfg(feature = "backend-llvm")]
+pub mod llvm;
+#[cfg(feature = "backend-wasm")]
+pub mod wasm;
+#[cfg(feature = "backend-x86_64")]
+pub mod x86_64;
+mod error;
+
+pub use error::BackendError;
+
+use super::parse::ast::AST;
+#[cfg(any(feature = "backend-x86_64", feature = "backend-c", feature = "backend-wasm", feature = "backend-llvm", feature = "backend-js"))]
+use super::parse::ast::{ Expression, Place };
+#[cfg(any(feature = "backend-c", feature = "backend-js"))]
+use super::stdlib::{ self, Builtin };
+use std::path::Path;
+#[cfg(any(feature = "backend-x86_64", feature = "backend-c", feature = "backend-llvm"))]
+use std::process::Command;
+
+/// Native and source-to-source compilation targets for `ult build`. \
+/// Every target is listed whichever `backend-*` features are enabled, see `Target::enabled`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Target {
+    X86_64Linux,
+    C,
+    Wasm32,
+    Llvm,
+    Js,
+}
+
+impl Target {
+    pub fn from_name(name: &str) -> Result<Target, BackendError> {
+        match name {
+            "x86_64-linux" => Ok(Target::X86_64Linux),
+            "c" => Ok(Target::C),
+            "wasm32" => Ok(Target::Wasm32),
+            "llvm" => Ok(Target::Llvm),
+            "js" => Ok(Target::Js),
+            _ => Err(BackendError::UnknownTarget(name.to_string())),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Target::X86_64Linux => "x86_64-linux",
+            Target::C => "c",
+            Target::Wasm32 => "wasm32",
+            Target::Llvm => "llvm",
+            Target::Js => "js",
+        }
+    }
+
+    /// Whether the backend for this target was compiled in
+    pub fn enabled(&self) -> bool {
+        match self {
+            Target::X86_64Linux => cfg!(feature = "backend-x86_64"),
+            Target::C => cfg!(feature = "backend-c"),
+            Target::Wasm32 => cfg!(feature = "backend-wasm"),
+            Target::Llvm => cfg!(feature = "backend-llvm"),
+            Target::Js => cfg!(feature = "backend-js"),
+        }
+    }
+}
+
+/// Compiles `ast`, parsed from `input`, for `target` and writes the result to `output`. \
+/// With `emit_only` the generated source is written without invoking any external toolchain
+#[allow(unused_variables)] // With backends disabled some parameters go unused
+pub fn build(ast: &AST, target: Target, input: &Path, output: &Path, emit_only: bool) -> Result<(), BackendError> {
+    match target {
+        #[cfg(feature = "backend-x86_64")]
+        Target::X86_64Linux => {
+            let asm = x86_64::generate(ast)?;
+
+            if emit_only {
+                return Ok(std::fs::write(output, asm)?)
+            }
+
+            let asm_path = output.with_extension("s");
+            let obj_path = output.with_extension("o");
+
+            std::fs::write(&asm_path, asm)?;
+
+            run_tool(Command::new("as").arg(&asm_path).arg("-o").arg(&obj_path))?;
+            run_tool(Command::new("ld").arg(&obj_path).arg("-o").arg(output))?;
+
+            std::fs::remove_file(&obj_path)?;
+
+            Ok(())
+        },
+        #[cfg(feature = "backend-c")]
+        Target::C => {
+            let source = c::generate(ast)?;
+            let c_path = if emit_only { output.to_path_buf() } else { output.with_extension("c") };
+
+            std::fs::write(&c_path, source)?;
+            std::fs::write(c_path.with_file_name(c::RUNTIME_HEADER_NAME), c::RUNTIME_HEADER)?;
+
+            if emit_only {
+                return Ok(())
+            }
+
+            let cc = std::env::var("CC").unwrap_or_else(|_| String::from("cc"));
+
+            run_tool(Command::new(cc).args(["-std=gnu99", "-O2", "-o"]).arg(output).arg(&c_path).arg("-lm"))
+        },
+        #[cfg(feature = "backend-wasm")]
+        Target::Wasm32 => {
+            let module = wasm::generate(ast)?;
+
+            // The binary is encoded directly, `--emit-only` gives the text format instead
+            if emit_only {
+                return Ok(std::fs::write(output, module.to_wat())?)
+            }
+
+            Ok(std::fs::write(output, module.to_binary())?)
+        },
+        #[cfg(feature = "backend-llvm")]
+        Target::Llvm => {
+            let ir = llvm::generate(ast)?;
+            let ll_path = if emit_only { output.to_path_buf() } else { output.with_extension("ll") };
+
+            std::fs::write(&ll_path, ir)?;
+
+            if emit_only {
+                return Ok(())
+            }
+
+            let clang = std::env::var("CLANG").unwrap_or_else(|_| String::from("clang"));
+            let mut cmd = Command::new(&clang);
+
+            // Opaque pointers became the default in LLVM 15
+            if clang_version(&clang).is_some_and(|major| major < 15) {
+                cmd.args(["-Xclang", "-opaque-pointers"]);
+            }
+
+            run_tool(cmd.args(["-O2", "-o"]).arg(output).arg(&ll_path))
+        },
+        #[cfg(feature = "backend-js")]
+        Target::Js => {
+            let js_path = if output.extension().is_none() { output.with_extension("js") } else { output.to_path_buf() };
+            let map_path = js_path.with_extension("js.map");
+            let file = js_path.file_name().unwrap_or_default().to_string_lossy();
+
+            // The map sits beside the script, so a source in the same directory is referenced by name
+            let source = match input.parent() == js_path.parent() {
+                true => input.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+                false => std::fs::canonicalize(input)?.to_string_lossy().into_owned(),
+            };
+
+            let js::Transpiled { code, source_map } = js::generate(ast, &source, &file)?;
+
+            std::fs::write(&js_path, code)?;
+            Ok(std::fs::write(map_path, source_map)?)
+        },
+        #[allow(unreachable_patterns)]
+        target => Err(BackendError::Disabled(target.name())),
+    }
+}
+
+/// Runs an external tool, turning a failed exit status into an error with its stderr
+#[cfg(any(feature = "backend-x86_64", feature = "backend-c", feature = "backend-llvm"))]
+fn run_tool(cmd: &mut Command) -> Result<(), BackendError> {
+    let out = cmd.output()
+        .map_err(|e| BackendError::Toolchain(format!("could not run {:?}: {}", cmd.get_program(), e)))?;
+
+    if !out.status.success() {
+        return Err(BackendError::Toolchain(format!("{:?} failed:\n{}",
+            cmd.get_program(), String::from_utf8_lossy(&out.stderr))))
+    }
+
+    Ok(())
+}
+
+/// Major version of `clang`, if it runs
+#[cfg(feature = "backend-llvm")]
+fn clang_version(clang: &str) -> Option<u32> {
+    let out = Command::new(clang).arg("--version").output().ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let version = text.split("version ").nth(1)?;
+
+    version.split('.').next()?.trim().parse().ok()
+}
+
+/// Where an assignment to `lhs` stores, which the parser made sure it has
+#[cfg(any(feature = "backend-x86_64", feature = "backend-c", feature = "backend-wasm", feature = "backend-llvm", feature = "backend-js"))]
+fn place(lhs: &Expression) -> Result<Place<'_>, BackendError> {
+    lhs.place().ok_or_else(|| BackendError::Unsupported(String::from("assignment to values")))
+}
+
+/// The standard library function called `name`, if there is one, checking it takes `argc` arguments
+#[cfg(any(feature = "backend-c", feature = "backend-js"))]
+fn builtin(name: &str, argc: usize) -> Result<Option<&'static Builtin>, BackendError> {
+    match stdlib::lookup(name) {
+        Some(Builtin { arity: Some(arity), .. }) if *arity != argc =>
+            Err(BackendError::ArityMismatch(name.to_string(), *arity, argc)),
+        builtin => Ok(builtin),
+    }
+}
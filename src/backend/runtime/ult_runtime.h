@@ -0,0 +1,1272 @@
+/* Runtime support for C code generated by ult. Header only, C99. */
+#ifndef ULT_RUNTIME_H
+#define ULT_RUNTIME_H
+
+#include <errno.h>
+#include <math.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef enum {
+    ULT_NULL,
+    ULT_INTEGER,
+    ULT_DECIMAL,
+    ULT_BOOLEAN,
+    ULT_CHARACTER,
+    ULT_STRING,
+    ULT_ARRAY,
+    ULT_TUPLE,
+    ULT_MAP,
+    ULT_SET,
+    ULT_RANGE,
+    ULT_FUNCTION
+} ult_tag;
+
+struct ult_value;
+struct ult_array;
+struct ult_table;
+
+/* Functions used as values are called through a wrapper taking an argument array */
+typedef struct ult_value (*ult_fn)(const struct ult_value *args);
+
+typedef struct {
+    ult_fn fn;
+    int arity;
+    const char *name;
+} ult_function;
+
+typedef struct ult_value {
+    ult_tag tag;
+    union {
+        int64_t integer;
+        double decimal;
+        int boolean;
+        uint32_t character;
+        const char *string;
+        /* Arrays and tuples, which are never changed */
+        struct ult_array *array;
+        /* Maps, and sets which are never changed */
+        struct ult_table *table;
+        /* `start..end` with the end excluded */
+        struct { int64_t start, end; } range;
+        const ult_function *function;
+    } as;
+} ult_value;
+
+typedef struct ult_array {
+    size_t len;
+    ult_value *items;
+} ult_array;
+
+/* Entries in the order their keys were first inserted, found by hash through `slots`, an open addressing
+ * table of entry positions plus one where 0 is empty. Sets leave every value null */
+typedef struct ult_table {
+    size_t len, cap, slots_len;
+    ult_value *keys, *values;
+    size_t *slots;
+} ult_table;
+
+/* ---- Errors ---- */
+
+static inline void ult_panic(const char *msg) {
+    fprintf(stderr, "Runtime error: %s\n", msg);
+    exit(1);
+}
+
+static inline void ult_type_error(const char *op, ult_value a, ult_value b);
+
+/* ---- Constructors ---- */
+
+static inline ult_value ult_null(void) { ult_value v; v.tag = ULT_NULL; v.as.integer = 0; return v; }
+static inline ult_value ult_int(int64_t i) { ult_value v; v.tag = ULT_INTEGER; v.as.integer = i; return v; }
+static inline ult_value ult_dec(double d) { ult_value v; v.tag = ULT_DECIMAL; v.as.decimal = d; return v; }
+static inline ult_value ult_bool(int b) { ult_value v; v.tag = ULT_BOOLEAN; v.as.boolean = b != 0; return v; }
+static inline ult_value ult_char(uint32_t c) { ult_value v; v.tag = ULT_CHARACTER; v.as.character = c; return v; }
+static inline ult_value ult_str(const char *s) { ult_value v; v.tag = ULT_STRING; v.as.string = s; return v; }
+
+static inline ult_value ult_func(const ult_function *f) {
+    ult_value v;
+    v.tag = ULT_FUNCTION;
+    v.as.function = f;
+    return v;
+}
+
+static inline void *ult_alloc(size_t size) {
+    void *p = malloc(size ? size : 1);
+    if (!p) ult_panic("Out of memory");
+    return p;
+}
+
+/* Builds an array by copying `len` values */
+static inline ult_value ult_array_of(size_t len, const ult_value *items) {
+    ult_value v;
+    ult_array *a = ult_alloc(sizeof(ult_array));
+    a->len = len;
+    a->items = ult_alloc(len * sizeof(ult_value));
+    if (len) memcpy(a->items, items, len * sizeof(ult_value));
+    v.tag = ULT_ARRAY;
+    v.as.array = a;
+    return v;
+}
+
+static inline ult_value ult_tuple(size_t len, const ult_value *items) {
+    ult_value v = ult_array_of(len, items);
+    v.tag = ULT_TUPLE;
+    return v;
+}
+
+static inline void ult_array_push(ult_array *a, ult_value v) {
+    a->items = realloc(a->items, (a->len + 1) * sizeof(ult_value));
+    if (!a->items) ult_panic("Out of memory");
+    a->items[a->len++] = v;
+}
+
+static inline const char *ult_type_name(ult_value v) {
+    switch (v.tag) {
+    case ULT_NULL: return "Null";
+    case ULT_INTEGER: return "Integer";
+    case ULT_DECIMAL: return "Decimal";
+    case ULT_BOOLEAN: return "Boolean";
+    case ULT_CHARACTER: return "Character";
+    case ULT_STRING: return "String";
+    case ULT_ARRAY: return "Array";
+    case ULT_TUPLE: return "Tuple";
+    case ULT_MAP: return "Map";
+    case ULT_SET: return "Set";
+    case ULT_RANGE: return "Range";
+    case ULT_FUNCTION: return "Function";
+    }
+    return "?";
+}
+
+/* ---- Formatting ---- */
+
+typedef struct {
+    char *data;
+    size_t len, cap;
+} ult_buf;
+
+static inline void ult_buf_push(ult_buf *b, const char *s, size_t n) {
+    if (b->len + n + 1 > b->cap) {
+        b->cap = (b->len + n + 1) * 2;
+        b->data = realloc(b->data, b->cap);
+        if (!b->data) ult_panic("Out of memory");
+    }
+    memcpy(b->data + b->len, s, n);
+    b->len += n;
+    b->data[b->len] = '\0';
+}
+
+static inline void ult_buf_str(ult_buf *b, const char *s) { ult_buf_push(b, s, strlen(s)); }
+
+static inline void ult_buf_utf8(ult_buf *b, uint32_t c) {
+    char out[4];
+    size_t n;
+    if (c < 0x80) { out[0] = (char)c; n = 1; }
+    else if (c < 0x800) { out[0] = (char)(0xC0 | (c >> 6)); out[1] = (char)(0x80 | (c & 0x3F)); n = 2; }
+    else if (c < 0x10000) {
+        out[0] = (char)(0xE0 | (c >> 12)); out[1] = (char)(0x80 | ((c >> 6) & 0x3F));
+        out[2] = (char)(0x80 | (c & 0x3F)); n = 3;
+    }
+    else {
+        out[0] = (char)(0xF0 | (c >> 18)); out[1] = (char)(0x80 | ((c >> 12) & 0x3F));
+        out[2] = (char)(0x80 | ((c >> 6) & 0x3F)); out[3] = (char)(0x80 | (c & 0x3F)); n = 4;
+    }
+    ult_buf_push(b, out, n);
+}
+
+/* Shortest representation that reads back to the same double, always with a decimal point */
+static inline void ult_buf_decimal(ult_buf *b, double d) {
+    char tmp[40];
+    int precision;
+    if (isnan(d)) { ult_buf_str(b, "NaN"); return; }
+    if (isinf(d)) { ult_buf_str(b, d < 0 ? "-inf" : "inf"); return; }
+    for (precision = 1; precision <= 17; precision++) {
+        snprintf(tmp, sizeof tmp, "%.*g", precision, d);
+        if (strtod(tmp, NULL) == d) break;
+    }
+    ult_buf_str(b, tmp);
+    if (!strpbrk(tmp, ".e")) ult_buf_str(b, ".0");
+}
+
+static inline void ult_buf_quoted(ult_buf *b, const char *s, char quote) {
+    char q[2] = { quote, '\0' };
+    ult_buf_str(b, q);
+    for (; *s; s++) {
+        switch (*s) {
+        case '\n': ult_buf_str(b, "\\n"); break;
+        case '\t': ult_buf_str(b, "\\t"); break;
+        case '\r': ult_buf_str(b, "\\r"); break;
+        case '\\': ult_buf_str(b, "\\\\"); break;
+        default:
+            if (*s == quote) { ult_buf_str(b, "\\"); }
+            ult_buf_push(b, s, 1);
+        }
+    }
+    ult_buf_str(b, q);
+}
+
+static inline void ult_buf_value(ult_buf *b, ult_value v, int nested) {
+    char tmp[32];
+    size_t i;
+    switch (v.tag) {
+    case ULT_NULL: ult_buf_str(b, "null"); break;
+    case ULT_INTEGER: snprintf(tmp, sizeof tmp, "%lld", (long long)v.as.integer); ult_buf_str(b, tmp); break;
+    case ULT_DECIMAL: ult_buf_decimal(b, v.as.decimal); break;
+    case ULT_BOOLEAN: ult_buf_str(b, v.as.boolean ? "true" : "false"); break;
+    case ULT_CHARACTER:
+        if (nested) {
+            ult_buf tmpbuf = { NULL, 0, 0 };
+            ult_buf_utf8(&tmpbuf, v.as.character);
+            ult_buf_quoted(b, tmpbuf.data, '\'');
+            free(tmpbuf.data);
+        }
+        else {
+            ult_buf_utf8(b, v.as.character);
+        }
+        break;
+    case ULT_STRING:
+        if (nested) ult_buf_quoted(b, v.as.string, '"');
+        else ult_buf_str(b, v.as.string);
+        break;
+    case ULT_ARRAY:
+        ult_buf_str(b, "[");
+        for (i = 0; i < v.as.array->len; i++) {
+            if (i) ult_buf_str(b, ", ");
+            ult_buf_value(b, v.as.array->items[i], 1);
+        }
+        ult_buf_str(b, "]");
+        break;
+    case ULT_TUPLE:
+        ult_buf_str(b, "(");
+        for (i = 0; i < v.as.array->len; i++) {
+            if (i) ult_buf_str(b, ", ");
+            ult_buf_value(b, v.as.array->items[i], 1);
+        }
+        ult_buf_str(b, v.as.array->len == 1 ? ",)" : ")");
+        break;
+    case ULT_MAP:
+        ult_buf_str(b, "{");
+        for (i = 0; i < v.as.table->len; i++) {
+            if (i) ult_buf_str(b, ", ");
+            ult_buf_value(b, v.as.table->keys[i], 1);
+            ult_buf_str(b, ": ");
+            ult_buf_value(b, v.as.table->values[i], 1);
+        }
+        ult_buf_str(b, "}");
+        break;
+    case ULT_SET:
+        /* Like the literal, `{,}` is the empty set and `{}` the empty map */
+        ult_buf_str(b, v.as.table->len ? "{" : "{,");
+        for (i = 0; i < v.as.table->len; i++) {
+            if (i) ult_buf_str(b, ", ");
+            ult_buf_value(b, v.as.table->keys[i], 1);
+        }
+        ult_buf_str(b, "}");
+        break;
+    case ULT_RANGE:
+        snprintf(tmp, sizeof tmp, "%lld..", (long long)v.as.range.start);
+        ult_buf_str(b, tmp);
+        snprintf(tmp, sizeof tmp, "%lld", (long long)v.as.range.end);
+        ult_buf_str(b, tmp);
+        break;
+    case ULT_FUNCTION:
+        ult_buf_str(b, "<func ");
+        ult_buf_str(b, v.as.function->name);
+        ult_buf_str(b, ">");
+        break;
+    }
+}
+
+static inline const char *ult_to_string(ult_value v) {
+    ult_buf b = { NULL, 0, 0 };
+    ult_buf_value(&b, v, 0);
+    if (!b.data) ult_buf_str(&b, "");
+    return b.data;
+}
+
+static inline void ult_type_error(const char *op, ult_value a, ult_value b) {
+    char msg[128];
+    snprintf(msg, sizeof msg, "Type error: cannot apply '%s' to %s and %s", op, ult_type_name(a), ult_type_name(b));
+    ult_panic(msg);
+}
+
+/* ---- Operators ---- */
+
+static inline int ult_truthy(ult_value v) {
+    switch (v.tag) {
+    case ULT_NULL: return 0;
+    case ULT_BOOLEAN: return v.as.boolean;
+    case ULT_INTEGER: return v.as.integer != 0;
+    case ULT_DECIMAL: return v.as.decimal != 0.0;
+    default: return 1;
+    }
+}
+
+static inline int ult_is_null(ult_value v) {
+    return v.tag == ULT_NULL;
+}
+
+static inline int ult_is_number(ult_value v) { return v.tag == ULT_INTEGER || v.tag == ULT_DECIMAL; }
+
+static inline double ult_as_decimal(ult_value v) {
+    return v.tag == ULT_INTEGER ? (double)v.as.integer : v.as.decimal;
+}
+
+static inline ult_value ult_overflow(void) {
+    ult_panic("Integer overflow");
+    return ult_null();
+}
+
+static inline ult_value ult_add(ult_value a, ult_value b) {
+    if (a.tag == ULT_INTEGER && b.tag == ULT_INTEGER) {
+        int64_t x = a.as.integer, y = b.as.integer;
+        if ((y > 0 && x > INT64_MAX - y) || (y < 0 && x < INT64_MIN - y)) return ult_overflow();
+        return ult_int(x + y);
+    }
+    if (a.tag == ULT_STRING || b.tag == ULT_STRING) {
+        ult_buf buf = { NULL, 0, 0 };
+        ult_buf_value(&buf, a, 0);
+        ult_buf_value(&buf, b, 0);
+        if (!buf.data) ult_buf_str(&buf, "");
+        return ult_str(buf.data);
+    }
+    if (ult_is_number(a) && ult_is_number(b)) return ult_dec(ult_as_decimal(a) + ult_as_decimal(b));
+    ult_type_error("+", a, b);
+    return ult_null();
+}
+
+static inline ult_value ult_sub(ult_value a, ult_value b) {
+    if (a.tag == ULT_INTEGER && b.tag == ULT_INTEGER) {
+        int64_t x = a.as.integer, y = b.as.integer;
+        if ((y < 0 && x > INT64_MAX + y) || (y > 0 && x < INT64_MIN + y)) return ult_overflow();
+        return ult_int(x - y);
+    }
+    if (ult_is_number(a) && ult_is_number(b)) return ult_dec(ult_as_decimal(a) - ult_as_decimal(b));
+    ult_type_error("-", a, b);
+    return ult_null();
+}
+
+static inline ult_value ult_mul(ult_value a, ult_value b) {
+    if (a.tag == ULT_INTEGER && b.tag == ULT_INTEGER) {
+        int64_t x = a.as.integer, y = b.as.integer;
+        if (x != 0 && y != 0) {
+            if ((x > 0 && y > 0 && x > INT64_MAX / y) || (x < 0 && y < 0 && x < INT64_MAX / y) ||
+                (x > 0 && y < 0 && y < INT64_MIN / x) || (x < 0 && y > 0 && x < INT64_MIN / y))
+                return ult_overflow();
+        }
+        return ult_int(x * y);
+    }
+    if (ult_is_number(a) && ult_is_number(b)) return ult_dec(ult_as_decimal(a) * ult_as_decimal(b));
+    ult_type_error("*", a, b);
+    return ult_null();
+}
+
+static inline ult_value ult_div(ult_value a, ult_value b) {
+    if (a.tag == ULT_INTEGER && b.tag == ULT_INTEGER) {
+        if (b.as.integer == 0) ult_panic("Division by zero");
+        if (a.as.integer == INT64_MIN && b.as.integer == -1) return ult_overflow();
+        return ult_int(a.as.integer / b.as.integer);
+    }
+    if (ult_is_number(a) && ult_is_number(b)) return ult_dec(ult_as_decimal(a) / ult_as_decimal(b));
+    ult_type_error("/", a, b);
+    return ult_null();
+}
+
+static inline ult_value ult_mod(ult_value a, ult_value b) {
+    if (a.tag == ULT_INTEGER && b.tag == ULT_INTEGER) {
+        if (b.as.integer == 0) ult_panic("Division by zero");
+        if (a.as.integer == INT64_MIN && b.as.integer == -1) return ult_overflow();
+        return ult_int(a.as.integer % b.as.integer);
+    }
+    if (ult_is_number(a) && ult_is_number(b)) return ult_dec(fmod(ult_as_decimal(a), ult_as_decimal(b)));
+    ult_type_error("%", a, b);
+    return ult_null();
+}
+
+static inline ult_value ult_pow(ult_value a, ult_value b) {
+    if (a.tag == ULT_INTEGER && b.tag == ULT_INTEGER && b.as.integer >= 0) {
+        ult_value result = ult_int(1), base = a;
+        int64_t exp = b.as.integer;
+        while (exp > 0) {
+            if (exp & 1) result = ult_mul(result, base);
+            exp >>= 1;
+            if (exp > 0) base = ult_mul(base, base);
+        }
+        return result;
+    }
+    if (ult_is_number(a) && ult_is_number(b)) return ult_dec(pow(ult_as_decimal(a), ult_as_decimal(b)));
+    ult_type_error("**", a, b);
+    return ult_null();
+}
+
+#define ULT_INTEGER_OP(name, sym, expr)                                  \
+    static inline ult_value name(ult_value a, ult_value b) {                    \
+        int64_t x, y;                                                    \
+        if (a.tag != ULT_INTEGER || b.tag != ULT_INTEGER) {              \
+            ult_type_error(sym, a, b);                                   \
+        }                                                                \
+        x = a.as.integer;                                                \
+        y = b.as.integer;                                                \
+        return expr;                                                     \
+    }
+
+ULT_INTEGER_OP(ult_band, "&", ult_int(x & y))
+ULT_INTEGER_OP(ult_bor, "|", ult_int(x | y))
+ULT_INTEGER_OP(ult_bxor, "^", ult_int(x ^ y))
+ULT_INTEGER_OP(ult_shl, "<<", (y < 0 || y > 63) ? ult_overflow() : ult_int((int64_t)((uint64_t)x << y)))
+ULT_INTEGER_OP(ult_shr, ">>", (y < 0 || y > 63) ? ult_overflow() : ult_int(x >> y))
+
+static inline size_t ult_table_find(const ult_table *t, ult_value key);
+
+static inline int ult_equal(ult_value a, ult_value b) {
+    size_t i, j;
+    if (ult_is_number(a) && ult_is_number(b)) {
+        if (a.tag == ULT_INTEGER && b.tag == ULT_INTEGER) return a.as.integer == b.as.integer;
+        return ult_as_decimal(a) == ult_as_decimal(b);
+    }
+    if (a.tag != b.tag) return 0;
+    switch (a.tag) {
+    case ULT_NULL: return 1;
+    case ULT_BOOLEAN: return a.as.boolean == b.as.boolean;
+    case ULT_CHARACTER: return a.as.character == b.as.character;
+    case ULT_STRING: return strcmp(a.as.string, b.as.string) == 0;
+    case ULT_FUNCTION: return a.as.function == b.as.function;
+    case ULT_ARRAY:
+    case ULT_TUPLE:
+        if (a.as.array == b.as.array) return 1;
+        if (a.as.array->len != b.as.array->len) return 0;
+        for (i = 0; i < a.as.array->len; i++) {
+            if (!ult_equal(a.as.array->items[i], b.as.array->items[i])) return 0;
+        }
+        return 1;
+    case ULT_MAP:
+    case ULT_SET:
+        /* The same keys with equal values, in any order */
+        if (a.as.table == b.as.table) return 1;
+        if (a.as.table->len != b.as.table->len) return 0;
+        for (i = 0; i < a.as.table->len; i++) {
+            j = ult_table_find(b.as.table, a.as.table->keys[i]);
+            if (j == b.as.table->len || !ult_equal(a.as.table->values[i], b.as.table->values[j])) return 0;
+        }
+        return 1;
+    case ULT_RANGE: return a.as.range.start == b.as.range.start && a.as.range.end == b.as.range.end;
+    default: return 0;
+    }
+}
+
+/* ---- Maps and sets ---- */
+
+static inline uint64_t ult_mix(uint64_t h, uint64_t x) {
+    return (h ^ x) * 0x100000001B3ull;
+}
+
+/* Collections nested deeper than this only hash their type */
+#define ULT_HASH_DEPTH 4
+
+/* Hashes a value by its content, agreeing with `ult_equal`. Integers hash as the decimal they equal, and
+ * maps and sets add up their entries' hashes so order doesn't matter */
+static inline uint64_t ult_hash_nested(ult_value v, int depth) {
+    uint64_t h = 0xCBF29CE484222325ull, bits, sum = 0;
+    const unsigned char *s;
+    double d;
+    size_t i;
+    if (depth == ULT_HASH_DEPTH && (v.tag == ULT_ARRAY || v.tag == ULT_TUPLE || v.tag == ULT_MAP || v.tag == ULT_SET))
+        return ult_mix(h, v.tag);
+    switch (v.tag) {
+    case ULT_INTEGER:
+    case ULT_DECIMAL:
+        d = ult_as_decimal(v);
+        /* -0.0 is the same key as 0.0, and every NaN the same as any other */
+        if (d == 0.0) d = 0.0;
+        if (d != d) return ult_mix(h, ULT_DECIMAL);
+        memcpy(&bits, &d, sizeof bits);
+        return ult_mix(ult_mix(h, ULT_DECIMAL), bits);
+    case ULT_BOOLEAN: return ult_mix(ult_mix(h, v.tag), (uint64_t)v.as.boolean);
+    case ULT_CHARACTER: return ult_mix(ult_mix(h, v.tag), v.as.character);
+    case ULT_STRING:
+        h = ult_mix(h, v.tag);
+        for (s = (const unsigned char *)v.as.string; *s; s++) h = ult_mix(h, *s);
+        return h;
+    case ULT_ARRAY:
+    case ULT_TUPLE:
+        h = ult_mix(ult_mix(h, v.tag), v.as.array->len);
+        for (i = 0; i < v.as.array->len; i++) h = ult_mix(h, ult_hash_nested(v.as.array->items[i], depth + 1));
+        return h;
+    case ULT_MAP:
+    case ULT_SET:
+        for (i = 0; i < v.as.table->len; i++)
+            sum += ult_hash_nested(v.as.table->keys[i], depth + 1) ^ (ult_hash_nested(v.as.table->values[i], depth + 1) << 1);
+        return ult_mix(ult_mix(ult_mix(h, v.tag), v.as.table->len), sum);
+    case ULT_RANGE:
+        return ult_mix(ult_mix(ult_mix(h, v.tag), (uint64_t)v.as.range.start), (uint64_t)v.as.range.end);
+    case ULT_FUNCTION: return ult_mix(ult_mix(h, v.tag), (uint64_t)(uintptr_t)v.as.function);
+    default: return ult_mix(h, v.tag);
+    }
+}
+
+static inline uint64_t ult_hash(ult_value v) { return ult_hash_nested(v, 0); }
+
+/* Keys match like `==` does, except that NaN matches itself so it can be found again */
+static inline int ult_same_key(ult_value a, ult_value b) {
+    if (a.tag == ULT_DECIMAL && b.tag == ULT_DECIMAL && a.as.decimal != a.as.decimal) return b.as.decimal != b.as.decimal;
+    return ult_equal(a, b);
+}
+
+/* Like in the VM, arrays and maps can't be keys or set elements, as they could change after they were
+ * inserted and then not be found again, and neither can tuples holding them */
+static inline void ult_hashable(ult_value v) {
+    char msg[64];
+    size_t i;
+    if (v.tag == ULT_ARRAY || v.tag == ULT_MAP) {
+        snprintf(msg, sizeof msg, "Type error: unhashable %s", ult_type_name(v));
+        ult_panic(msg);
+    }
+    if (v.tag == ULT_TUPLE) for (i = 0; i < v.as.array->len; i++) ult_hashable(v.as.array->items[i]);
+}
+
+/* Position of `key` among the entries, or `t->len` when it's missing */
+static inline size_t ult_table_find(const ult_table *t, ult_value key) {
+    size_t mask, i;
+    ult_hashable(key);
+    if (!t->slots_len) return t->len;
+    mask = t->slots_len - 1;
+    for (i = ult_hash(key) & mask; t->slots[i]; i = (i + 1) & mask) {
+        if (ult_same_key(t->keys[t->slots[i] - 1], key)) return t->slots[i] - 1;
+    }
+    return t->len;
+}
+
+static inline void ult_table_slot(ult_table *t, size_t entry) {
+    size_t mask = t->slots_len - 1, i = ult_hash(t->keys[entry]) & mask;
+    while (t->slots[i]) i = (i + 1) & mask;
+    t->slots[i] = entry + 1;
+}
+
+/* Sets the value of `key`, a key that is already there keeps its place */
+static inline void ult_table_insert(ult_table *t, ult_value key, ult_value value) {
+    size_t i = ult_table_find(t, key);
+    if (i < t->len) {
+        t->values[i] = value;
+        return;
+    }
+    if (t->len == t->cap) {
+        t->cap = t->cap ? t->cap * 2 : 8;
+        t->keys = realloc(t->keys, t->cap * sizeof(ult_value));
+        t->values = realloc(t->values, t->cap * sizeof(ult_value));
+        if (!t->keys || !t->values) ult_panic("Out of memory");
+    }
+    t->keys[t->len] = key;
+    t->values[t->len++] = value;
+    /* Slots stay at most half full */
+    if (2 * t->len <= t->slots_len) {
+        ult_table_slot(t, t->len - 1);
+        return;
+    }
+    free(t->slots);
+    t->slots_len = t->slots_len ? t->slots_len * 2 : 16;
+    t->slots = calloc(t->slots_len, sizeof(size_t));
+    if (!t->slots) ult_panic("Out of memory");
+    for (i = 0; i < t->len; i++) ult_table_slot(t, i);
+}
+
+static inline ult_value ult_table_value(ult_tag tag) {
+    ult_value v;
+    v.tag = tag;
+    v.as.table = ult_alloc(sizeof(ult_table));
+    memset(v.as.table, 0, sizeof(ult_table));
+    return v;
+}
+
+/* Builds a map from `len` keys each followed by its value */
+static inline ult_value ult_map_of(size_t len, const ult_value *items) {
+    ult_value v = ult_table_value(ULT_MAP);
+    size_t i;
+    for (i = 0; i < len; i++) ult_table_insert(v.as.table, items[2 * i], items[2 * i + 1]);
+    return v;
+}
+
+static inline ult_value ult_set_of(size_t len, const ult_value *items) {
+    ult_value v = ult_table_value(ULT_SET);
+    size_t i;
+    for (i = 0; i < len; i++) ult_table_insert(v.as.table, items[i], ult_null());
+    return v;
+}
+
+/* A set literal with spreads, from the array `ult_array_spread` built of its elements */
+static inline ult_value ult_set_from(ult_value array) {
+    return ult_set_of(array.as.array->len, array.as.array->items);
+}
+
+/* `item in collection`: whether a map has the key or a set the element, an array or tuple has an element
+ * equal to it, a string contains it as a substring or character, or a range the integer */
+static inline ult_value ult_in(ult_value item, ult_value collection) {
+    ult_buf b = { NULL, 0, 0 };
+    size_t i;
+    int found;
+    switch (collection.tag) {
+    case ULT_MAP:
+    case ULT_SET:
+        return ult_bool(ult_table_find(collection.as.table, item) < collection.as.table->len);
+    case ULT_ARRAY:
+    case ULT_TUPLE:
+        for (i = 0; i < collection.as.array->len; i++) {
+            if (ult_equal(collection.as.array->items[i], item)) return ult_bool(1);
+        }
+        return ult_bool(0);
+    case ULT_STRING:
+        if (item.tag == ULT_STRING) return ult_bool(strstr(collection.as.string, item.as.string) != NULL);
+        if (item.tag != ULT_CHARACTER) break;
+        ult_buf_utf8(&b, item.as.character);
+        found = strstr(collection.as.string, b.data) != NULL;
+        free(b.data);
+        return ult_bool(found);
+    case ULT_RANGE:
+        if (item.tag == ULT_INTEGER)
+            return ult_bool(collection.as.range.start <= item.as.integer && item.as.integer < collection.as.range.end);
+        if (item.tag == ULT_DECIMAL) return ult_bool(0);
+        break;
+    default:
+        break;
+    }
+    ult_type_error("in", item, collection);
+    return ult_null();
+}
+
+/* Orders two values as -1, 0 or 1, or 2 when unordered because of NaN */
+static inline int ult_compare(const char *op, ult_value a, ult_value b) {
+    if (a.tag == ULT_INTEGER && b.tag == ULT_INTEGER)
+        return (a.as.integer > b.as.integer) - (a.as.integer < b.as.integer);
+    if (a.tag == ULT_STRING && b.tag == ULT_STRING) {
+        int c = strcmp(a.as.string, b.as.string);
+        return (c > 0) - (c < 0);
+    }
+    if (a.tag == ULT_CHARACTER && b.tag == ULT_CHARACTER)
+        return (a.as.character > b.as.character) - (a.as.character < b.as.character);
+    if (ult_is_number(a) && ult_is_number(b)) {
+        double x = ult_as_decimal(a), y = ult_as_decimal(b);
+        if (x != x || y != y) return 2;
+        return (x > y) - (x < y);
+    }
+    ult_type_error(op, a, b);
+    return 2;
+}
+
+static inline ult_value ult_eq(ult_value a, ult_value b) { return ult_bool(ult_equal(a, b)); }
+static inline ult_value ult_ne(ult_value a, ult_value b) { return ult_bool(!ult_equal(a, b)); }
+static inline ult_value ult_lt(ult_value a, ult_value b) { return ult_bool(ult_compare("<", a, b) == -1); }
+static inline ult_value ult_le(ult_value a, ult_value b) { int c = ult_compare("<=", a, b); return ult_bool(c == -1 || c == 0); }
+static inline ult_value ult_gt(ult_value a, ult_value b) { return ult_bool(ult_compare(">", a, b) == 1); }
+static inline ult_value ult_ge(ult_value a, ult_value b) { int c = ult_compare(">=", a, b); return ult_bool(c == 1 || c == 0); }
+
+static inline ult_value ult_neg(ult_value a) {
+    char msg[64];
+    if (a.tag == ULT_INTEGER) return a.as.integer == INT64_MIN ? ult_overflow() : ult_int(-a.as.integer);
+    if (a.tag == ULT_DECIMAL) return ult_dec(-a.as.decimal);
+    snprintf(msg, sizeof msg, "Type error: cannot negate %s", ult_type_name(a));
+    ult_panic(msg);
+    return ult_null();
+}
+
+static inline ult_value ult_not(ult_value a) { return ult_bool(!ult_truthy(a)); }
+
+static inline ult_value ult_bnot(ult_value a) {
+    char msg[64];
+    if (a.tag == ULT_INTEGER) return ult_int(~a.as.integer);
+    snprintf(msg, sizeof msg, "Type error: cannot apply '~' to %s", ult_type_name(a));
+    ult_panic(msg);
+    return ult_null();
+}
+
+static inline size_t ult_bounded(int64_t i, size_t len) {
+    char msg[96];
+    if (i < 0 || (uint64_t)i >= len) {
+        snprintf(msg, sizeof msg, "Index %lld out of bounds for length %lu", (long long)i, (unsigned long)len);
+        ult_panic(msg);
+    }
+    return (size_t)i;
+}
+
+static inline ult_value ult_index(ult_value target, ult_value idx) {
+    char msg[64];
+    if (target.tag == ULT_MAP) {
+        ult_buf b = { NULL, 0, 0 };
+        size_t i = ult_table_find(target.as.table, idx);
+        if (i < target.as.table->len) return target.as.table->values[i];
+        ult_buf_str(&b, "Key ");
+        ult_buf_value(&b, idx, 1);
+        ult_buf_str(&b, " not found in map");
+        ult_panic(b.data);
+    }
+    if (idx.tag != ULT_INTEGER) {
+        snprintf(msg, sizeof msg, "Type error: cannot index with %s", ult_type_name(idx));
+        ult_panic(msg);
+    }
+    if (target.tag == ULT_ARRAY || target.tag == ULT_TUPLE) {
+        return target.as.array->items[ult_bounded(idx.as.integer, target.as.array->len)];
+    }
+    if (target.tag == ULT_STRING) {
+        /* Index by character, not byte */
+        const unsigned char *s = (const unsigned char *)target.as.string;
+        size_t len = 0, i, want;
+        for (i = 0; s[i]; i++) if ((s[i] & 0xC0) != 0x80) len++;
+        want = ult_bounded(idx.as.integer, len);
+        for (i = 0; s[i]; i++) {
+            if ((s[i] & 0xC0) == 0x80) continue;
+            if (want-- == 0) {
+                uint32_t c = s[i];
+                int extra = c >= 0xF0 ? 3 : c >= 0xE0 ? 2 : c >= 0xC0 ? 1 : 0;
+                c &= extra ? (0x3F >> extra) : 0x7F;
+                while (extra--) c = (c << 6) | (s[++i] & 0x3F);
+                return ult_char(c);
+            }
+        }
+    }
+    snprintf(msg, sizeof msg, "Type error: cannot index into %s", ult_type_name(target));
+    ult_panic(msg);
+    return ult_null();
+}
+
+/* Stores into an array element or a map's key, returning the value stored */
+static inline ult_value ult_set_index(ult_value target, ult_value idx, ult_value v) {
+    char msg[64];
+    if (target.tag == ULT_MAP) {
+        ult_table_insert(target.as.table, idx, v);
+        return v;
+    }
+    if (idx.tag != ULT_INTEGER) {
+        snprintf(msg, sizeof msg, "Type error: cannot index with %s", ult_type_name(idx));
+        ult_panic(msg);
+    }
+    if (target.tag != ULT_ARRAY) {
+        snprintf(msg, sizeof msg, "Type error: cannot assign into %s", ult_type_name(target));
+        ult_panic(msg);
+    }
+    return target.as.array->items[ult_bounded(idx.as.integer, target.as.array->len)] = v;
+}
+
+/* ---- Iteration ---- */
+
+/* `a..b`, or `a..=b` stored as `a..b + 1` */
+static inline ult_value ult_range(ult_value a, ult_value b, int inclusive) {
+    ult_value v;
+    if (a.tag != ULT_INTEGER || b.tag != ULT_INTEGER) ult_type_error(inclusive ? "..=" : "..", a, b);
+    if (inclusive && b.as.integer == INT64_MAX) return ult_overflow();
+    v.tag = ULT_RANGE;
+    v.as.range.start = a.as.integer;
+    v.as.range.end = b.as.integer + (inclusive != 0);
+    return v;
+}
+
+/* The iteration protocol: stores the element at `*cursor` in `*out` and moves the cursor past it, or
+ * returns 0 once done. Cursors start at 0 and count elements, or bytes for strings, which yield characters.
+ * Maps yield `(key, value)` tuples. Arrays and maps are read live, loops walk an `ult_snapshot` of them */
+static inline int ult_next(ult_value it, int64_t *cursor, ult_value *out) {
+    char msg[64];
+    ult_value entry[2];
+    switch (it.tag) {
+    case ULT_MAP:
+        if ((uint64_t)*cursor >= it.as.table->len) return 0;
+        entry[0] = it.as.table->keys[*cursor];
+        entry[1] = it.as.table->values[(*cursor)++];
+        *out = ult_tuple(2, entry);
+        return 1;
+    case ULT_SET:
+        if ((uint64_t)*cursor >= it.as.table->len) return 0;
+        *out = it.as.table->keys[(*cursor)++];
+        return 1;
+    case ULT_ARRAY:
+    case ULT_TUPLE:
+        if ((uint64_t)*cursor >= it.as.array->len) return 0;
+        *out = it.as.array->items[(*cursor)++];
+        return 1;
+    case ULT_RANGE:
+        if (it.as.range.start + *cursor >= it.as.range.end) return 0;
+        *out = ult_int(it.as.range.start + (*cursor)++);
+        return 1;
+    case ULT_STRING: {
+        const unsigned char *s = (const unsigned char *)it.as.string + *cursor;
+        uint32_t c = *s;
+        int extra = c >= 0xF0 ? 3 : c >= 0xE0 ? 2 : c >= 0xC0 ? 1 : 0, i;
+        if (!c) return 0;
+        c &= extra ? (0x3F >> extra) : 0x7F;
+        for (i = 1; i <= extra; i++) c = (c << 6) | (s[i] & 0x3F);
+        *cursor += extra + 1;
+        *out = ult_char(c);
+        return 1;
+    }
+    default:
+        snprintf(msg, sizeof msg, "Type error: cannot iterate over %s", ult_type_name(it));
+        ult_panic(msg);
+        return 0;
+    }
+}
+
+/* What a for-each loop walks, like the VM: arrays and maps become tuples of their elements and entries as
+ * they are when the loop starts, so changing them in the loop neither adds iterations nor skips any */
+static inline ult_value ult_snapshot(ult_value it) {
+    ult_value snapshot, entry[2];
+    size_t i;
+    switch (it.tag) {
+    case ULT_ARRAY:
+        return ult_tuple(it.as.array->len, it.as.array->items);
+    case ULT_MAP:
+        snapshot = ult_tuple(0, NULL);
+        for (i = 0; i < it.as.table->len; i++) {
+            entry[0] = it.as.table->keys[i];
+            entry[1] = it.as.table->values[i];
+            ult_array_push(snapshot.as.array, ult_tuple(2, entry));
+        }
+        return snapshot;
+    default:
+        return it;
+    }
+}
+
+/* Checks that `v` is a tuple, or an array, of `len` elements before a pattern takes it apart */
+static inline ult_value ult_unpack(ult_value v, int tuple, size_t len) {
+    char msg[128];
+    const char *shape = tuple ? "a tuple" : "an array";
+    if (v.tag != (tuple ? ULT_TUPLE : ULT_ARRAY)) {
+        snprintf(msg, sizeof msg, "Cannot destructure %s as %s of %lu elements", ult_type_name(v), shape, (unsigned long)len);
+        ult_panic(msg);
+    }
+    if (v.as.array->len != len) {
+        snprintf(msg, sizeof msg, "Cannot destructure %s of %lu elements as %s of %lu elements",
+            ult_type_name(v), (unsigned long)v.as.array->len, shape, (unsigned long)len);
+        ult_panic(msg);
+    }
+    return v;
+}
+
+/* An array literal with spreads: the elements of `items` whose `spread` flag is set are iterated into it */
+static inline ult_value ult_array_spread(size_t n, const ult_value *items, const int *spread) {
+    ult_value result = ult_array_of(0, NULL), element;
+    int64_t cursor;
+    size_t i;
+    for (i = 0; i < n; i++) {
+        if (!spread[i]) {
+            ult_array_push(result.as.array, items[i]);
+            continue;
+        }
+        cursor = 0;
+        while (ult_next(items[i], &cursor, &element)) ult_array_push(result.as.array, element);
+    }
+    return result;
+}
+
+/* ---- Evaluation order ---- */
+
+/* C leaves the order function arguments are evaluated in unspecified, so generated code holds operands
+ * with side effects here one after another, in expressions sequenced by commas, and reads them back */
+static ult_value *ult_holds;
+static size_t ult_holds_len, ult_holds_cap;
+
+static inline void ult_hold(ult_value v) {
+    if (ult_holds_len == ult_holds_cap) {
+        ult_holds_cap = ult_holds_cap ? ult_holds_cap * 2 : 16;
+        ult_holds = realloc(ult_holds, ult_holds_cap * sizeof(ult_value));
+        if (!ult_holds) ult_panic("Out of memory");
+    }
+    ult_holds[ult_holds_len++] = v;
+}
+
+/* The value held `depth` holds ago */
+static inline ult_value ult_held(size_t depth) {
+    return ult_holds[ult_holds_len - 1 - depth];
+}
+
+/* Releases the last `n` holds once `v`, which used them, is computed */
+static inline ult_value ult_drop(size_t n, ult_value v) {
+    ult_holds_len -= n;
+    return v;
+}
+
+/* Compound assignments and `++`/`--` save what they update first, so the old value is read before the
+ * right hand side runs and an element's target and index are evaluated once. Saves nest like the
+ * expressions doing them */
+typedef ult_value (*ult_binary)(ult_value, ult_value);
+
+typedef struct {
+    int element;
+    ult_value target, idx, old;
+} ult_place;
+
+static ult_place *ult_places;
+static size_t ult_places_len, ult_places_cap;
+
+static inline void ult_save_place(ult_place p) {
+    if (ult_places_len == ult_places_cap) {
+        ult_places_cap = ult_places_cap ? ult_places_cap * 2 : 16;
+        ult_places = realloc(ult_places, ult_places_cap * sizeof(ult_place));
+        if (!ult_places) ult_panic("Out of memory");
+    }
+    ult_places[ult_places_len++] = p;
+}
+
+/* Saves the value of a variable */
+static inline void ult_save(ult_value v) {
+    ult_place p;
+    p.element = 0;
+    p.target = p.idx = ult_null();
+    p.old = v;
+    ult_save_place(p);
+}
+
+/* Saves an element, returning its value */
+static inline ult_value ult_save_index(ult_value target, ult_value idx) {
+    ult_place p;
+    p.element = 1;
+    p.target = target;
+    p.idx = idx;
+    p.old = ult_index(target, idx);
+    ult_save_place(p);
+    return p.old;
+}
+
+static inline ult_place ult_restore(void) {
+    return ult_places[--ult_places_len];
+}
+
+/* Applies `op` to the saved value and `rhs`, storing the result if an element was saved */
+static inline ult_value ult_update(ult_binary op, ult_value rhs) {
+    ult_place p = ult_restore();
+    ult_value v = op(p.old, rhs);
+    if (p.element) ult_set_index(p.target, p.idx, v);
+    return v;
+}
+
+/* `++` or `--` on a saved element, returning the old value */
+static inline ult_value ult_postfix(ult_binary op) {
+    ult_place p = ult_restore();
+    ult_set_index(p.target, p.idx, op(p.old, ult_int(1)));
+    return p.old;
+}
+
+/* Calls a function value with `argc` arguments */
+static inline ult_value ult_call(ult_value callee, int argc, const ult_value *args) {
+    char msg[128];
+    if (callee.tag != ULT_FUNCTION) {
+        snprintf(msg, sizeof msg, "Value of type %s is not callable", ult_type_name(callee));
+        ult_panic(msg);
+    }
+    if (callee.as.function->arity != argc) {
+        snprintf(msg, sizeof msg, "Function '%s' expected %d arguments but got %d",
+            callee.as.function->name, callee.as.function->arity, argc);
+        ult_panic(msg);
+    }
+    return callee.as.function->fn(args);
+}
+
+/* Calls a function value with the elements of an array as arguments, for calls with spreads */
+static inline ult_value ult_apply(ult_value callee, ult_value args) {
+    return ult_call(callee, (int)args.as.array->len, args.as.array->items);
+}
+
+/* Calls a standard library function with spread arguments, whose count is only known now. `arity` is -1
+ * for functions taking any number */
+static inline ult_value ult_apply_std(const char *name, int arity, ult_value (*fn)(int, const ult_value *), ult_value args) {
+    char msg[128];
+    int argc = (int)args.as.array->len;
+    if (arity >= 0 && arity != argc) {
+        snprintf(msg, sizeof msg, "Function '%s' expected %d arguments but got %d", name, arity, argc);
+        ult_panic(msg);
+    }
+    return fn(argc, args.as.array->items);
+}
+
+/* ---- Standard library ---- */
+
+/* Called as ult_std_<name>(argc, args), arities are checked when compiling */
+
+static inline void ult_arg_error(const char *name, int position, const char *what, ult_value v) {
+    char msg[160];
+    snprintf(msg, sizeof msg, "Type error: argument %d of '%s': expected %s, got %s", position, name, what, ult_type_name(v));
+    ult_panic(msg);
+}
+
+static inline void ult_native_error(const char *name, const char *error) {
+    char msg[256];
+    snprintf(msg, sizeof msg, "Error in native function '%s': %s", name, error);
+    ult_panic(msg);
+}
+
+/* Strings and characters, which string functions accept alike */
+static inline const char *ult_text(const char *name, int position, ult_value v) {
+    if (v.tag == ULT_STRING) return v.as.string;
+    if (v.tag != ULT_CHARACTER) ult_arg_error(name, position, "String", v);
+    return ult_to_string(v);
+}
+
+static inline ult_array *ult_array_arg(const char *name, int position, ult_value v) {
+    if (v.tag != ULT_ARRAY) ult_arg_error(name, position, "Array", v);
+    return v.as.array;
+}
+
+static inline double ult_decimal_arg(const char *name, int position, ult_value v) {
+    if (!ult_is_number(v)) ult_arg_error(name, position, "Decimal", v);
+    return ult_as_decimal(v);
+}
+
+static inline size_t ult_utf8_len(const char *s) {
+    size_t len = 0;
+    for (; *s; s++) if ((*s & 0xC0) != 0x80) len++;
+    return len;
+}
+
+/* Byte offset of the n-th character */
+static inline size_t ult_utf8_offset(const char *s, size_t n) {
+    size_t i = 0;
+    for (; s[i]; i++) {
+        if ((s[i] & 0xC0) != 0x80 && n-- == 0) break;
+    }
+    return i;
+}
+
+static inline ult_value ult_std_print(int argc, const ult_value *args) {
+    int i;
+    for (i = 0; i < argc; i++) {
+        if (i) fputc(' ', stdout);
+        fputs(ult_to_string(args[i]), stdout);
+    }
+    fflush(stdout);
+    return ult_null();
+}
+
+static inline ult_value ult_std_println(int argc, const ult_value *args) {
+    ult_std_print(argc, args);
+    fputc('\n', stdout);
+    fflush(stdout);
+    return ult_null();
+}
+
+static inline ult_value ult_std_read_line(int argc, const ult_value *args) {
+    ult_buf b = { NULL, 0, 0 };
+    int c;
+    char ch;
+    (void)argc; (void)args;
+    while ((c = fgetc(stdin)) != EOF && c != '\n') {
+        ch = (char)c;
+        ult_buf_push(&b, &ch, 1);
+    }
+    if (c == EOF && !b.data) return ult_null();
+    if (!b.data) ult_buf_str(&b, "");
+    if (b.len && b.data[b.len - 1] == '\r') b.data[--b.len] = '\0';
+    return ult_str(b.data);
+}
+
+static inline ult_value ult_std_read_file(int argc, const ult_value *args) {
+    ult_buf b = { NULL, 0, 0 };
+    char chunk[4096];
+    size_t n;
+    FILE *file;
+    (void)argc;
+    if (args[0].tag != ULT_STRING) ult_arg_error("read_file", 1, "String", args[0]);
+    file = fopen(args[0].as.string, "rb");
+    if (!file) ult_native_error("read_file", strerror(errno));
+    while ((n = fread(chunk, 1, sizeof chunk, file)) > 0) ult_buf_push(&b, chunk, n);
+    fclose(file);
+    if (!b.data) ult_buf_str(&b, "");
+    return ult_str(b.data);
+}
+
+static inline ult_value ult_std_write_file(int argc, const ult_value *args) {
+    const char *text;
+    FILE *file;
+    (void)argc;
+    if (args[0].tag != ULT_STRING) ult_arg_error("write_file", 1, "String", args[0]);
+    text = ult_to_string(args[1]);
+    file = fopen(args[0].as.string, "wb");
+    if (!file) ult_native_error("write_file", strerror(errno));
+    fputs(text, file);
+    if (fclose(file) != 0) ult_native_error("write_file", strerror(errno));
+    return ult_null();
+}
+
+static inline ult_value ult_std_len(int argc, const ult_value *args) {
+    (void)argc;
+    if (args[0].tag == ULT_STRING) return ult_int((int64_t)ult_utf8_len(args[0].as.string));
+    if (args[0].tag == ULT_ARRAY || args[0].tag == ULT_TUPLE) return ult_int((int64_t)args[0].as.array->len);
+    if (args[0].tag == ULT_MAP || args[0].tag == ULT_SET) return ult_int((int64_t)args[0].as.table->len);
+    ult_arg_error("len", 1, "String, Array, Tuple, Map or Set", args[0]);
+    return ult_null();
+}
+
+static inline ult_value ult_std_slice(int argc, const ult_value *args) {
+    char msg[128];
+    int64_t start, end;
+    size_t len;
+    (void)argc;
+    if (args[1].tag != ULT_INTEGER) ult_arg_error("slice", 2, "Integer", args[1]);
+    if (args[2].tag != ULT_INTEGER) ult_arg_error("slice", 3, "Integer", args[2]);
+    if (args[0].tag == ULT_STRING) len = ult_utf8_len(args[0].as.string);
+    else if (args[0].tag == ULT_ARRAY) len = args[0].as.array->len;
+    else { ult_arg_error("slice", 1, "String or Array", args[0]); return ult_null(); }
+    start = args[1].as.integer;
+    end = args[2].as.integer;
+    if (start < 0 || start > end || (uint64_t)end > len) {
+        snprintf(msg, sizeof msg, "range %lld..%lld out of bounds for length %lu", (long long)start, (long long)end, (unsigned long)len);
+        ult_native_error("slice", msg);
+    }
+    if (args[0].tag == ULT_ARRAY) {
+        return ult_array_of((size_t)(end - start), args[0].as.array->items + start);
+    }
+    else {
+        const char *s = args[0].as.string;
+        size_t from = ult_utf8_offset(s, (size_t)start), to = ult_utf8_offset(s, (size_t)end);
+        ult_buf b = { NULL, 0, 0 };
+        ult_buf_push(&b, s + from, to - from);
+        return ult_str(b.data);
+    }
+}
+
+static inline ult_value ult_std_split(int argc, const ult_value *args) {
+    const char *s = ult_text("split", 1, args[0]), *sep = ult_text("split", 2, args[1]), *next;
+    size_t count = 0, cap = 8, seplen = strlen(sep), n;
+    ult_value *parts = ult_alloc(cap * sizeof(ult_value)), result;
+    ult_buf b;
+    (void)argc;
+    for (;;) {
+        if (seplen) {
+            next = strstr(s, sep);
+            n = next ? (size_t)(next - s) : strlen(s);
+        }
+        else {
+            if (!*s) break;
+            n = ult_utf8_offset(s, 1);
+            next = s + n;
+        }
+        if (count == cap) parts = realloc(parts, (cap *= 2) * sizeof(ult_value));
+        if (!parts) ult_panic("Out of memory");
+        b.data = NULL; b.len = 0; b.cap = 0;
+        ult_buf_push(&b, s, n);
+        parts[count++] = ult_str(b.data);
+        if (!seplen) s = next;
+        else if (next) s = next + seplen;
+        else break;
+    }
+    result = ult_array_of(count, parts);
+    free(parts);
+    return result;
+}
+
+static inline ult_value ult_std_find(int argc, const ult_value *args) {
+    const char *s = ult_text("find", 1, args[0]), *found = strstr(s, ult_text("find", 2, args[1]));
+    size_t chars = 0;
+    (void)argc;
+    if (!found) return ult_int(-1);
+    for (; s < found; s++) if ((*s & 0xC0) != 0x80) chars++;
+    return ult_int((int64_t)chars);
+}
+
+/* Only changes the case of ASCII letters */
+static inline ult_value ult_change_case(const char *name, ult_value v, int upper) {
+    char *out;
+    size_t i;
+    if (v.tag != ULT_STRING) ult_arg_error(name, 1, "String", v);
+    out = ult_alloc(strlen(v.as.string) + 1);
+    for (i = 0; v.as.string[i]; i++) {
+        char c = v.as.string[i];
+        if (upper && c >= 'a' && c <= 'z') c = (char)(c - 'a' + 'A');
+        if (!upper && c >= 'A' && c <= 'Z') c = (char)(c - 'A' + 'a');
+        out[i] = c;
+    }
+    out[i] = '\0';
+    return ult_str(out);
+}
+
+static inline ult_value ult_std_upper(int argc, const ult_value *args) { (void)argc; return ult_change_case("upper", args[0], 1); }
+static inline ult_value ult_std_lower(int argc, const ult_value *args) { (void)argc; return ult_change_case("lower", args[0], 0); }
+
+static inline ult_value ult_std_sqrt(int argc, const ult_value *args) { (void)argc; return ult_dec(sqrt(ult_decimal_arg("sqrt", 1, args[0]))); }
+static inline ult_value ult_std_sin(int argc, const ult_value *args) { (void)argc; return ult_dec(sin(ult_decimal_arg("sin", 1, args[0]))); }
+static inline ult_value ult_std_cos(int argc, const ult_value *args) { (void)argc; return ult_dec(cos(ult_decimal_arg("cos", 1, args[0]))); }
+static inline ult_value ult_std_tan(int argc, const ult_value *args) { (void)argc; return ult_dec(tan(ult_decimal_arg("tan", 1, args[0]))); }
+static inline ult_value ult_std_pow(int argc, const ult_value *args) { (void)argc; return ult_pow(args[0], args[1]); }
+
+/* Comparing like `<` keeps the type of whichever argument is picked */
+static inline ult_value ult_std_min(int argc, const ult_value *args) {
+    (void)argc;
+    return ult_compare("<", args[1], args[0]) == -1 ? args[1] : args[0];
+}
+
+static inline ult_value ult_std_max(int argc, const ult_value *args) {
+    (void)argc;
+    return ult_compare("<", args[0], args[1]) == -1 ? args[1] : args[0];
+}
+
+static inline ult_value ult_std_abs(int argc, const ult_value *args) {
+    (void)argc;
+    if (args[0].tag == ULT_INTEGER) {
+        if (args[0].as.integer == INT64_MIN) return ult_overflow();
+        return ult_int(args[0].as.integer < 0 ? -args[0].as.integer : args[0].as.integer);
+    }
+    if (args[0].tag == ULT_DECIMAL) return ult_dec(fabs(args[0].as.decimal));
+    ult_arg_error("abs", 1, "Integer or Decimal", args[0]);
+    return ult_null();
+}
+
+static inline ult_value ult_std_push(int argc, const ult_value *args) {
+    ult_array *a = ult_array_arg("push", 1, args[0]);
+    (void)argc;
+    ult_array_push(a, args[1]);
+    return ult_null();
+}
+
+static inline ult_value ult_std_pop(int argc, const ult_value *args) {
+    ult_array *a = ult_array_arg("pop", 1, args[0]);
+    (void)argc;
+    return a->len ? a->items[--a->len] : ult_null();
+}
+
+/* The callbacks run on a copy of the elements, so they can change the array itself */
+static inline ult_value ult_std_map(int argc, const ult_value *args) {
+    ult_array *a = ult_array_arg("map", 1, args[0]);
+    ult_value copy = ult_array_of(a->len, a->items);
+    size_t i;
+    (void)argc;
+    for (i = 0; i < copy.as.array->len; i++) {
+        copy.as.array->items[i] = ult_call(args[1], 1, &copy.as.array->items[i]);
+    }
+    return copy;
+}
+
+static inline ult_value ult_std_filter(int argc, const ult_value *args) {
+    ult_array *a = ult_array_arg("filter", 1, args[0]);
+    ult_value copy = ult_array_of(a->len, a->items);
+    size_t i, kept = 0;
+    (void)argc;
+    for (i = 0; i < copy.as.array->len; i++) {
+        ult_value element = copy.as.array->items[i];
+        if (ult_truthy(ult_call(args[1], 1, &element))) copy.as.array->items[kept++] = element;
+    }
+    copy.as.array->len = kept;
+    return copy;
+}
+
+/* Total order agreeing with `<`, with NaN after every other number */
+static inline int ult_sort_order(const void *pa, const void *pb) {
+    ult_value a = *(const ult_value *)pa, b = *(const ult_value *)pb;
+    int c = ult_compare("<", a, b);
+    if (c == 2) return (isnan(ult_as_decimal(a)) != 0) - (isnan(ult_as_decimal(b)) != 0);
+    return c;
+}
+
+static inline ult_value ult_std_sort(int argc, const ult_value *args) {
+    ult_array *a = ult_array_arg("sort", 1, args[0]);
+    size_t i;
+    (void)argc;
+    /* Anything `<` can't compare is an error before the array is touched */
+    for (i = 1; i < a->len; i++) ult_compare("<", a->items[0], a->items[i]);
+    if (a->len > 1) qsort(a->items, a->len, sizeof(ult_value), ult_sort_order);
+    return ult_null();
+}
+
+/* Process exit status for a value returned from `main` */
+static inline int ult_exit_code(ult_value v) {
+    return v.tag == ULT_INTEGER ? (int)(v.as.integer & 0xFF) : 0;
+}
+
+#endif
\ No newline at end of file
@@ -0,0 +1,423 @@
+const $ = (() => {
+    class UltError extends Error {}
+
+    /** A value raised by `throw` */
+    class Thrown extends UltError {
+        constructor(value) { super(format(value)); this.value = value; }
+    }
+
+    /** Characters are interned so `===` works on them */
+    class Char {
+        constructor(c) { this.c = c; }
+        toString() { return this.c; }
+    }
+
+    const chars = new Map();
+    const char = c => {
+        let v = chars.get(c);
+        if (v === undefined) chars.set(c, v = new Char(c));
+        return v;
+    };
+
+    /** `start..end` with the end excluded, `start..=end` is stored as `start..end + 1` */
+    class Range {
+        constructor(start, end) { this.start = start; this.end = end; }
+        toString() { return `${this.start}..${this.end}`; }
+    }
+
+    /** Tuples wrap their elements, which never change */
+    class Tuple {
+        constructor(items) { this.items = Object.freeze(items); }
+    }
+
+    /** Maps and sets, whose entries stay in the order their keys were first inserted. `buckets` finds the
+     * entries whose keys hash alike, and keys match like `==` except that NaN matches itself. Sets leave every
+     * value null and are never changed */
+    class Table {
+        constructor(set) { this.set = set; this.keys = []; this.values = []; this.buckets = new Map(); }
+
+        /** Position of the key among the entries, -1 when it's missing */
+        find(key) {
+            hashable(key);
+            const bucket = this.buckets.get(hash(key)) ?? [];
+            return bucket.find(i => sameKey(this.keys[i], key)) ?? -1;
+        }
+
+        /** A key that is already there keeps its place */
+        insert(key, value) {
+            const i = this.find(key);
+            if (i >= 0) return this.values[i] = value;
+
+            const h = hash(key);
+            if (!this.buckets.has(h)) this.buckets.set(h, []);
+            this.buckets.get(h).push(this.keys.length);
+            this.keys.push(key);
+            this.values.push(value);
+            return value;
+        }
+    }
+
+    /** Like in the VM, arrays and maps can't be keys or set elements, as they could change after they were
+     * inserted and then not be found again, and neither can tuples holding them */
+    const hashable = v => {
+        if (Array.isArray(v) || (v instanceof Table && !v.set)) typeError(`unhashable ${typeName(v)}`);
+        if (v instanceof Tuple) v.items.forEach(hashable);
+    };
+
+    /** Functions hash by identity */
+    const ids = new WeakMap();
+    let lastId = 0;
+
+    /** A string equal for values that are `==`, so integers hash as the decimal they equal, and maps and sets
+     * sort their entries' hashes so order doesn't matter. Collections nested deeper than `MAX_DEPTH` only hash
+     * their type */
+    const MAX_DEPTH = 4;
+    const hash = (v, depth = 0) => {
+        const nested = e => hash(e, depth + 1);
+        if (depth === MAX_DEPTH && (Array.isArray(v) || v instanceof Tuple || v instanceof Table)) return typeName(v);
+        if (typeof v === "bigint" || typeof v === "number") {
+            const d = Number(v);
+            return Number.isNaN(d) ? "NaN" : `${d === 0 ? 0 : d}`;
+        }
+        if (typeof v === "string") return JSON.stringify(v);
+        if (v instanceof Char) return `'${v.c}`;
+        if (Array.isArray(v)) return `[${v.map(nested).join(",")}]`;
+        if (v instanceof Tuple) return `(${v.items.map(nested).join(",")})`;
+        if (v instanceof Table) return `{${v.keys.map((k, i) => `${nested(k)}:${nested(v.values[i])}`).sort().join(",")}}`;
+        if (v instanceof Range) return `${v.start}..${v.end}`;
+        if (typeof v === "function") {
+            if (!ids.has(v)) ids.set(v, ++lastId);
+            return `<${ids.get(v)}>`;
+        }
+        return String(v);
+    };
+
+    const sameKey = (a, b) => eq(a, b) || (Number.isNaN(a) && Number.isNaN(b));
+
+    const fail = message => { throw new UltError(message); };
+    const overflow = () => fail("Integer overflow");
+
+    const typeName = v =>
+        v === null ? "Null" :
+        typeof v === "bigint" ? "Integer" :
+        typeof v === "number" ? "Decimal" :
+        typeof v === "boolean" ? "Boolean" :
+        v instanceof Char ? "Character" :
+        typeof v === "string" ? "String" :
+        Array.isArray(v) ? "Array" :
+        v instanceof Tuple ? "Tuple" :
+        v instanceof Table ? (v.set ? "Set" : "Map") :
+        v instanceof Range ? "Range" : "Function";
+
+    const typeError = message => fail(`Type error: ${message}`);
+    const mismatch = (a, b, op) => typeError(`cannot apply '${op}' to ${typeName(a)} and ${typeName(b)}`);
+
+    /** Integers are BigInts checked against the i64 range */
+    const int = v => BigInt.asIntN(64, v) === v ? v : overflow();
+    const MIN = -(2n ** 63n);
+    const ints = (a, b) => typeof a === "bigint" && typeof b === "bigint";
+    const decimal = v => typeof v === "bigint" ? Number(v) : typeof v === "number" ? v : undefined;
+
+    const decimalOp = (a, b, op, f) => {
+        const x = decimal(a), y = decimal(b);
+        return x === undefined || y === undefined ? mismatch(a, b, op) : f(x, y);
+    };
+
+    const intOp = (a, b, op, f) => ints(a, b) ? f(a, b) : mismatch(a, b, op);
+    const shift = (a, b, f) => b < 0n || b >= 64n ? overflow() : f(a, b);
+
+    /** Generated names carry a `$` suffix when renamed */
+    const nameOf = f => f.name.replace(/\$\d*$/, "");
+
+    const formatDecimal = d => {
+        if (Number.isNaN(d)) return "NaN";
+        if (!Number.isFinite(d)) return d > 0 ? "inf" : "-inf";
+        if (Object.is(d, -0)) return "-0.0";
+
+        const abs = Math.abs(d);
+        if (abs !== 0 && (abs < 1e-4 || abs >= 1e16)) return d.toExponential().replace("e+", "e");
+
+        return Number.isInteger(d) ? `${d}.0` : String(d);
+    };
+
+    const quoteChar = c => `'${c === "'" ? "\\'" : c === "\\" ? "\\\\" : c === "\n" ? "\\n" : c}'`;
+
+    /** Elements of collections show strings and characters quoted */
+    const element = e => typeof e === "string" ? JSON.stringify(e) : e instanceof Char ? quoteChar(e.c) : format(e);
+
+    const format = v =>
+        v === null ? "null" :
+        typeof v === "number" ? formatDecimal(v) :
+        Array.isArray(v) ? `[${v.map(element).join(", ")}]` :
+        v instanceof Tuple ? `(${v.items.map(element).join(", ")}${v.items.length === 1 ? "," : ""})` :
+        // Like the literal, `{,}` is the empty set and `{}` the empty map
+        v instanceof Table && v.set ? (v.keys.length ? `{${v.keys.map(element).join(", ")}}` : "{,}") :
+        v instanceof Table ? `{${v.keys.map((k, i) => `${element(k)}: ${element(v.values[i])}`).join(", ")}}` :
+        typeof v === "function" ? `<func ${nameOf(v)}>` :
+        String(v);
+
+    const truthy = v =>
+        v === null ? false :
+        typeof v === "boolean" ? v :
+        typeof v === "bigint" ? v !== 0n :
+        typeof v === "number" ? v !== 0 : true;
+
+    /** Three way comparison, NaN when unordered */
+    const compare = (a, b, op) => {
+        if (ints(a, b) || (typeof a === "string" && typeof b === "string")) return a < b ? -1 : a > b ? 1 : 0;
+        if (a instanceof Char && b instanceof Char) return a.c < b.c ? -1 : a.c > b.c ? 1 : 0;
+
+        return decimalOp(a, b, op, (x, y) => x < y ? -1 : x > y ? 1 : x === y ? 0 : NaN);
+    };
+
+    const eq = (a, b) => {
+        if (a === b) return true;
+        if (ints(a, b)) return false;
+        if (Array.isArray(a) && Array.isArray(b)) return a.length === b.length && a.every((v, i) => eq(v, b[i]));
+        if (a instanceof Tuple && b instanceof Tuple) return eq(a.items, b.items);
+        if (a instanceof Table && b instanceof Table) {
+            // The same keys with equal values, in any order
+            return a.set === b.set && a.keys.length === b.keys.length && a.keys.every((k, i) => {
+                const j = b.find(k);
+                return j >= 0 && eq(a.values[i], b.values[j]);
+            });
+        }
+        if (a instanceof Range && b instanceof Range) return a.start === b.start && a.end === b.end;
+
+        const x = decimal(a), y = decimal(b);
+        return x !== undefined && y !== undefined && x === y;
+    };
+
+    const bounded = (i, len) => i >= 0n && i < BigInt(len) ? Number(i) : fail(`Index ${i} out of bounds for length ${len}`);
+
+    const index = (v, i) => {
+        if (v instanceof Table && !v.set) {
+            const found = v.find(i);
+            return found >= 0 ? v.values[found] : fail(`Key ${element(i)} not found in map`);
+        }
+        if (typeof i !== "bigint") return typeError(`cannot index with ${typeName(i)}`);
+        if (Array.isArray(v)) return v[bounded(i, v.length)];
+        if (v instanceof Tuple) return v.items[bounded(i, v.items.length)];
+        if (typeof v === "string") {
+            const cs = [...v];
+            return char(cs[bounded(i, cs.length)]);
+        }
+        return typeError(`cannot index into ${typeName(v)}`);
+    };
+
+    const setIndex = (v, i, value) => {
+        if (v instanceof Table && !v.set) return v.insert(i, value);
+        if (typeof i !== "bigint") return typeError(`cannot index with ${typeName(i)}`);
+        if (!Array.isArray(v)) return typeError(`cannot assign into ${typeName(v)}`);
+        return v[bounded(i, v.length)] = value;
+    };
+
+    /** The iteration protocol, maps yield `(key, value)` tuples. Like the VM, arrays and maps are copied when
+     * iteration starts, so changing them in a loop doesn't change what it walks */
+    function* iterate(v) {
+        if (Array.isArray(v)) yield* v.slice();
+        else if (v instanceof Table && v.set) yield* v.keys;
+        else if (v instanceof Table) yield* v.keys.map((k, i) => new Tuple([k, v.values[i]]));
+        else if (v instanceof Tuple) yield* v.items;
+        else if (v instanceof Range) for (let i = v.start; i < v.end; i++) yield i;
+        else if (typeof v === "string") for (const c of v) yield char(c);
+        else typeError(`cannot iterate over ${typeName(v)}`);
+    }
+
+    const print = args => process.stdout.write(args.map(format).join(" "));
+
+    /** Typed access to standard library arguments */
+    const argError = (name, position, what, v) => typeError(`argument ${position} of '${name}': expected ${what}, got ${typeName(v)}`);
+    const nativeError = (name, message) => fail(`Error in native function '${name}': ${message}`);
+    const text = (name, position, v) => typeof v === "string" ? v : v instanceof Char ? v.c : argError(name, position, "String", v);
+    const array = (name, position, v) => Array.isArray(v) ? v : argError(name, position, "Array", v);
+    const number = (name, position, v) => decimal(v) ?? argError(name, position, "Decimal", v);
+    const fs = () => require("fs");
+
+    /** Like Rust's io errors, which the VM reports */
+    const ioError = (name, e) => nativeError(name, e.code === "ENOENT" ? "No such file or directory (os error 2)" : e.message);
+
+    /** Total order agreeing with `<`, with NaN after every other number */
+    const order = (a, b) => {
+        const c = compare(a, b, "<");
+        return Number.isNaN(c) ? Number.isNaN(decimal(a)) - Number.isNaN(decimal(b)) : c;
+    };
+
+    /** The standard library, see `BUILTINS` in src/stdlib */
+    const std = {
+        print: (...args) => { print(args); return null; },
+        println: (...args) => { print(args); process.stdout.write("\n"); return null; },
+        read_line: () => {
+            const bytes = [], byte = Buffer.alloc(1);
+            let read;
+            while ((read = fs().readSync(0, byte, 0, 1, null)) === 1 && byte[0] !== 10) bytes.push(byte[0]);
+            if (read === 0 && bytes.length === 0) return null;
+            return Buffer.from(bytes).toString("utf8").replace(/\r$/, "");
+        },
+        read_file: path => {
+            try { return fs().readFileSync(text("read_file", 1, path), "utf8"); }
+            catch (e) { return e instanceof UltError ? fail(e.message) : ioError("read_file", e); }
+        },
+        write_file: (path, v) => {
+            try { fs().writeFileSync(text("write_file", 1, path), format(v)); return null; }
+            catch (e) { return e instanceof UltError ? fail(e.message) : ioError("write_file", e); }
+        },
+
+        len: v => typeof v === "string" ? BigInt([...v].length) :
+            Array.isArray(v) ? BigInt(v.length) :
+            v instanceof Tuple ? BigInt(v.items.length) :
+            v instanceof Table ? BigInt(v.keys.length) : argError("len", 1, "String, Array, Tuple, Map or Set", v),
+        slice: (v, start, end) => {
+            if (typeof start !== "bigint") return argError("slice", 2, "Integer", start);
+            if (typeof end !== "bigint") return argError("slice", 3, "Integer", end);
+            const items = typeof v === "string" ? [...v] : Array.isArray(v) ? v : argError("slice", 1, "String or Array", v);
+            if (start < 0n || start > end || end > BigInt(items.length)) {
+                return nativeError("slice", `range ${start}..${end} out of bounds for length ${items.length}`);
+            }
+            const sliced = items.slice(Number(start), Number(end));
+            return typeof v === "string" ? sliced.join("") : sliced;
+        },
+        split: (s, sep) => {
+            s = text("split", 1, s);
+            sep = text("split", 2, sep);
+            return sep === "" ? [...s] : s.split(sep);
+        },
+        find: (s, needle) => {
+            s = text("find", 1, s);
+            const i = s.indexOf(text("find", 2, needle));
+            return i < 0 ? -1n : BigInt([...s.slice(0, i)].length);
+        },
+        upper: s => typeof s === "string" ? s.toUpperCase() : argError("upper", 1, "String", s),
+        lower: s => typeof s === "string" ? s.toLowerCase() : argError("lower", 1, "String", s),
+
+        sqrt: x => Math.sqrt(number("sqrt", 1, x)),
+        pow: (x, y) => $.pow(x, y),
+        sin: x => Math.sin(number("sin", 1, x)),
+        cos: x => Math.cos(number("cos", 1, x)),
+        tan: x => Math.tan(number("tan", 1, x)),
+        min: (a, b) => compare(b, a, "<") < 0 ? b : a,
+        max: (a, b) => compare(a, b, "<") < 0 ? b : a,
+        abs: x => typeof x === "bigint" ? int(x < 0n ? -x : x) :
+            typeof x === "number" ? Math.abs(x) : argError("abs", 1, "Integer or Decimal", x),
+
+        push: (a, v) => { array("push", 1, a).push(v); return null; },
+        pop: a => array("pop", 1, a).pop() ?? null,
+        /** The callbacks run on a copy of the elements, so they can change the array itself */
+        map: (a, f) => [...array("map", 1, a)].map(e => $.call(f, e)),
+        filter: (a, f) => [...array("filter", 1, a)].filter(e => truthy($.call(f, e))),
+        sort: a => {
+            array("sort", 1, a).forEach(e => compare(a[0], e, "<"));
+            a.sort(order);
+            return null;
+        },
+    };
+
+    return {
+        char,
+        truthy,
+        add: (a, b) => ints(a, b) ? int(a + b) :
+            typeof a === "string" || typeof b === "string" ? format(a) + format(b) :
+            decimalOp(a, b, "+", (x, y) => x + y),
+        sub: (a, b) => ints(a, b) ? int(a - b) : decimalOp(a, b, "-", (x, y) => x - y),
+        mul: (a, b) => ints(a, b) ? int(a * b) : decimalOp(a, b, "*", (x, y) => x * y),
+        div: (a, b) => ints(a, b) ? (b === 0n ? fail("Division by zero") : int(a / b)) :
+            decimalOp(a, b, "/", (x, y) => x / y),
+        mod: (a, b) => ints(a, b) ? (b === 0n ? fail("Division by zero") : b === -1n && a === MIN ? overflow() : a % b) :
+            decimalOp(a, b, "%", (x, y) => x % y),
+        pow: (a, b) => ints(a, b) && b >= 0n ?
+            (b >= 64n && (a > 1n || a < -1n) ? overflow() : int(a ** b)) :
+            decimalOp(a, b, "**", (x, y) => x ** y),
+        band: (a, b) => intOp(a, b, "&", (x, y) => x & y),
+        bor: (a, b) => intOp(a, b, "|", (x, y) => x | y),
+        bxor: (a, b) => intOp(a, b, "^", (x, y) => x ^ y),
+        shl: (a, b) => intOp(a, b, "<<", (x, y) => shift(x, y, (x, y) => BigInt.asIntN(64, x << y))),
+        shr: (a, b) => intOp(a, b, ">>", (x, y) => shift(x, y, (x, y) => x >> y)),
+        eq,
+        ne: (a, b) => !eq(a, b),
+        lt: (a, b) => compare(a, b, "<") < 0,
+        le: (a, b) => compare(a, b, "<=") <= 0,
+        gt: (a, b) => compare(a, b, ">") > 0,
+        ge: (a, b) => compare(a, b, ">=") >= 0,
+        neg: a => typeof a === "bigint" ? int(-a) : typeof a === "number" ? -a : typeError(`cannot negate ${typeName(a)}`),
+        not: a => !truthy(a),
+        bnot: a => typeof a === "bigint" ? ~a : typeError(`cannot apply '~' to ${typeName(a)}`),
+        index,
+        setIndex,
+        range: (a, b, inclusive) => ints(a, b) ? new Range(a, inclusive ? int(b + 1n) : b) : mismatch(a, b, inclusive ? "..=" : ".."),
+        iterate,
+        tuple: (...items) => new Tuple(items),
+        /** Keys each followed by their value */
+        map: (...entries) => {
+            const map = new Table(false);
+            for (let i = 0; i < entries.length; i += 2) map.insert(entries[i], entries[i + 1]);
+            return map;
+        },
+        set: (...elements) => {
+            const set = new Table(true);
+            elements.forEach(e => set.insert(e, null));
+            return set;
+        },
+        /** `item in collection`: whether a map has the key or a set the element, an array or tuple has an
+         * element equal to it, a string contains it as a substring or character, or a range the integer */
+        in: (item, v) => {
+            if (v instanceof Table) return v.find(item) >= 0;
+            if (Array.isArray(v)) return v.some(e => eq(e, item));
+            if (v instanceof Tuple) return v.items.some(e => eq(e, item));
+            if (typeof v === "string" && (typeof item === "string" || item instanceof Char)) return v.includes(String(item));
+            if (v instanceof Range && typeof item === "bigint") return v.start <= item && item < v.end;
+            if (v instanceof Range && typeof item === "number") return false;
+            return mismatch(item, v, "in");
+        },
+        /** The elements of a tuple, or an array, of `n` elements that a pattern takes apart */
+        unpack: (v, tuple, n) => {
+            const shape = `${tuple ? "a tuple" : "an array"} of ${n} elements`;
+            const items = tuple ? (v instanceof Tuple ? v.items : null) : (Array.isArray(v) ? v : null);
+            if (items === null) return fail(`Cannot destructure ${typeName(v)} as ${shape}`);
+            return items.length === n ? items : fail(`Cannot destructure ${typeName(v)} of ${items.length} elements as ${shape}`);
+        },
+        /** Compound assignments to elements hold the element first, so its old value is read before the
+         * right hand side runs */
+        hold: (v, i) => ({ v, i, old: index(v, i) }),
+        update: (place, f, rhs) => setIndex(place.v, place.i, f(place.old, rhs)),
+        postfix: (place, f) => { setIndex(place.v, place.i, f(place.old, 1n)); return place.old; },
+        /** The first of values evaluated in order, which is the old value for `x++` */
+        first: v => v,
+        /** `v?.[i]` and `v?.(args)`, where `f` does the access unless `v` is null */
+        optional: (v, f) => v === null ? null : f(v),
+        call: (f, ...args) => {
+            if (typeof f !== "function") fail(`Value of type ${typeName(f)} is not callable`);
+            if (f.length !== args.length) fail(`Function '${nameOf(f)}' expected ${f.length} arguments but got ${args.length}`);
+            return f(...args);
+        },
+        std,
+        thrown: v => new Thrown(v),
+        /** Fails an `assert` with its message, if any, and its condition's value or operands and operator */
+        assertFailed: (message, ...values) => {
+            const detail = values.length === 3 ? `${element(values[0])} ${values[1]} ${element(values[2])}` : `got ${element(values[0])}`;
+            fail(`Assertion failed: ${message === null ? detail : `${format(message)} (${detail})`}`);
+        },
+        /** What `catch` binds: the thrown value, or the message of a runtime error */
+        caught: e => {
+            if (e instanceof Thrown) return e.value;
+            if (e instanceof UltError) return e.message;
+            if (e instanceof RangeError && e.message.includes("call stack")) return "Stack overflow";
+            throw e;
+        },
+        /** Runs the program, exiting with `main`'s integer result or reporting a runtime error */
+        run: program => {
+            try {
+                const result = program();
+                process.exitCode = typeof result === "bigint" ? Number(BigInt.asUintN(8, result)) : 0;
+            }
+            catch (e) {
+                let message;
+                if (e instanceof UltError) message = e.message;
+                else if (e instanceof RangeError && e.message.includes("call stack")) message = "Stack overflow";
+                else throw e;
+
+                process.stderr.write(`Runtime error: ${message}\n`);
+                process.exitCode = 1;
+            }
+        },
+    };
+})();
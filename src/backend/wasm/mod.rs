@@ -0,0 +1,1250 @@
+pub mod module;
+
+use super::super::lex::token::{ Span, Token };
+use super::super::parse::ast::*;
+use super::super::parse::visit::{ walk_declaration, Visitor };
+use super::super::stdlib;
+use super::error::BackendError;
+use super::place;
+use module::{ Func, FuncType, Global, Import, Instr, Label, Module, ValType };
+use std::collections::{ HashMap, HashSet };
+
+type GenResult = Result<(), BackendError>;
+type TyResult = Result<Ty, BackendError>;
+
+/// Lowers an `AST` to a WebAssembly module. \
+/// Integers, booleans, characters and `null` become `i64`, decimals `f64`. Types are inferred
+/// from initializers, assignments, call arguments and returns by regenerating the module until
+/// no variable or signature has to be widened any further. Printing goes through host imports
+/// from the `env` module, and `main` is exported. Integer arithmetic is checked like in the VM,
+/// and runtime errors are passed to the `fail` import, which should print them and stop
+pub fn generate(ast: &AST) -> Result<Module, BackendError> {
+    let mut types = Types::default();
+
+    for _ in 0..MAX_PASSES {
+        let mut gen = Generator::new(ast, &mut types);
+
+        gen.program(ast)?;
+
+        if !gen.changed {
+            return Ok(gen.module)
+        }
+    }
+
+    Err(BackendError::Unsupported(String::from("programs whose types don't settle")))
+}
+
+const MAX_PASSES: usize = 16;
+
+/// Host functions every module imports, in function index order
+const IMPORTS: [(&str, &[ValType]); 6] = [
+    ("print_i64", &[ValType::I64]),
+    ("print_f64", &[ValType::F64]),
+    ("print_bool", &[ValType::I64]),
+    ("print_char", &[ValType::I64]),
+    ("print_str", &[ValType::I32, ValType::I32]),
+    ("fail", &[ValType::I32, ValType::I32]),
+];
+
+const PRINT_I64: u32 = 0;
+const PRINT_F64: u32 = 1;
+const PRINT_BOOL: u32 = 2;
+const PRINT_CHAR: u32 = 3;
+const PRINT_STR: u32 = 4;
+const FAIL: u32 = 5;
+
+/// Checked integer operations, added after `__init` in the order they're first used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Helper {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    Pow,
+}
+
+impl Helper {
+    fn name(self) -> &'static str {
+        match self {
+            Helper::Add => "ult_add",
+            Helper::Sub => "ult_sub",
+            Helper::Mul => "ult_mul",
+            Helper::Div => "ult_div",
+            Helper::Rem => "ult_rem",
+            Helper::Shl => "ult_shl",
+            Helper::Shr => "ult_shr",
+            Helper::Pow => "ult_pow",
+        }
+    }
+
+    const ALL: [Helper; 8] =
+        [Helper::Add, Helper::Sub, Helper::Mul, Helper::Div, Helper::Rem, Helper::Shl, Helper::Shr, Helper::Pow];
+}
+
+/// Static types. Everything but `Dec` is an `i64` at runtime, the rest only picks how it prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ty {
+    Int,
+    Bool,
+    Char,
+    Dec,
+}
+
+impl Ty {
+    fn val(self) -> ValType {
+        match self {
+            Ty::Dec => ValType::F64,
+            _ => ValType::I64,
+        }
+    }
+
+    /// The narrowest type that holds both
+    fn join(self, other: Ty) -> Ty {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Ty::Dec, _) | (_, Ty::Dec) => Ty::Dec,
+            _ => Ty::Int,
+        }
+    }
+}
+
+fn join(known: Option<Ty>, ty: Ty) -> Ty {
+    known.map_or(ty, |k| k.join(ty))
+}
+
+/// Types learned so far, carried between passes
+#[derive(Default)]
+struct Types {
+    params: HashMap<u32, Vec<Ty>>,
+    returns: HashMap<u32, Ty>,
+    /// Keyed by function index (or `u32::MAX` for globals) and declaration order
+    variables: HashMap<(u32, usize), Ty>,
+}
+
+#[derive(Clone, Copy)]
+enum Binding {
+    Local(u32, Ty, (u32, usize)),
+    Global(u32, Ty, (u32, usize)),
+    Function(u32),
+}
+
+struct Context {
+    index: u32,
+    scopes: Vec<HashMap<String, Binding>>,
+    params: Vec<ValType>,
+    locals: Vec<ValType>,
+    local_names: Vec<String>,
+    body: Vec<Instr>,
+    /// `(continue, break)` labels of enclosing loops
+    loops: Vec<(Label, Label)>,
+    labels: usize,
+    declarations: usize,
+    result: Ty,
+}
+
+struct FuncInfo {
+    name: String,
+    arity: usize,
+}
+
+const GLOBALS: u32 = u32::MAX;
+
+struct Generator<'t> {
+    types: &'t mut Types,
+    changed: bool,
+    module: Module,
+    /// User functions in declaration pre-order, then `__init`, then helpers
+    funcs: Vec<FuncInfo>,
+    built: Vec<Option<Func>>,
+    next_func: usize,
+    globals: HashMap<String, Binding>,
+    global_count: usize,
+    strings: HashMap<String, (i32, i32)>,
+    contexts: Vec<Context>,
+    helpers: Vec<Helper>,
+}
+
+impl<'t> Generator<'t> {
+    fn new(ast: &AST, types: &'t mut Types) -> Self {
+        let mut funcs = vec![];
+        let mut taken = IMPORTS.iter().map(|(n, _)| n.to_string()).collect::<HashSet<_>>();
+        taken.insert(String::from("__init"));
+        taken.extend(Helper::ALL.iter().map(|h| h.name().to_string()));
+
+        FunctionNames { prefix: String::new(), funcs: &mut funcs, taken: &mut taken }.visit_ast(ast);
+
+        let mut module = Module::default();
+
+        for (name, params) in IMPORTS {
+            module.imports.push(Import {
+                module: String::from("env"),
+                name: name.to_string(),
+                ty: FuncType { params: params.to_vec(), results: vec![] },
+            });
+        }
+
+        // Space and newline for `print` live at the start of memory
+        module.data.extend_from_slice(b" \n");
+
+        Self {
+            types,
+            changed: false,
+            module,
+            built: (0..funcs.len() + 1).map(|_| None).collect(),
+            funcs,
+            next_func: 0,
+            globals: HashMap::new(),
+            global_count: 0,
+            strings: HashMap::new(),
+            contexts: vec![],
+            helpers: vec![],
+        }
+    }
+
+    fn func_index(&self, local: usize) -> u32 {
+        (IMPORTS.len() + local) as u32
+    }
+
+    fn init_index(&self) -> u32 {
+        self.func_index(self.funcs.len())
+    }
+
+    /// The index of a checked integer operation, adding it to the module the first time
+    fn helper(&mut self, helper: Helper) -> u32 {
+        // `ult_pow` multiplies through `ult_mul`
+        if helper == Helper::Pow {
+            self.helper(Helper::Mul);
+        }
+
+        let position = match self.helpers.iter().position(|&h| h == helper) {
+            Some(position) => position,
+            None => {
+                self.helpers.push(helper);
+                self.helpers.len() - 1
+            },
+        };
+
+        self.func_index(self.funcs.len() + 1 + position)
+    }
+
+    fn program(&mut self, ast: &AST) -> GenResult {
+        // Register top level names first so functions can refer to later declarations
+        let mut next = 0;
+        for decl in ast.program() {
+            match decl {
+                Declaration::Variable { identifier, .. } => {
+                    let key = (GLOBALS, self.global_count);
+                    let ty = self.types.variables.get(&key).copied().unwrap_or(Ty::Int);
+
+                    self.module.globals.push(Global { name: identifier.name(), ty: ty.val() });
+                    self.globals.insert(identifier.name(), Binding::Global(self.global_count as u32, ty, key));
+                    self.global_count += 1;
+                },
+                Declaration::Destructure { .. } => return Err(BackendError::Unsupported(String::from("destructuring"))),
+                Declaration::Function { .. } => {
+                    self.globals.insert(self.funcs[next].name.clone(), Binding::Function(self.func_index(next)));
+                    next += count_functions(decl);
+                },
+            }
+        }
+
+        self.begin_function(self.init_index(), vec![], Ty::Int);
+
+        let mut global = 0;
+        for decl in ast.program() {
+            match decl {
+                Declaration::Variable { value, .. } => {
+                    let key = (GLOBALS, global);
+                    let ty = self.expression_or_null(value.as_ref())?;
+                    let declared = self.widen(key, ty);
+
+                    self.coerce(ty, declared);
+                    self.emit(Instr::GlobalSet(global as u32));
+                    global += 1;
+                },
+                Declaration::Destructure { .. } => return Err(BackendError::Unsupported(String::from("destructuring"))),
+                Declaration::Function { arguments, body, .. } =>
+                    self.function(arguments.as_deref().unwrap_or_default(), body)?,
+            }
+        }
+
+        let init = self.end_function(String::from("__init"), vec![], false);
+        self.built[self.funcs.len()] = Some(init);
+        self.module.start = Some(self.init_index());
+
+        let main = self.funcs.iter().position(|f| f.name == "main" && f.arity == 0);
+
+        for (i, func) in std::mem::take(&mut self.built).into_iter().enumerate() {
+            if let Some(mut func) = func {
+                if Some(i) == main {
+                    func.export = Some(String::from("main"));
+                }
+                self.module.funcs.push(func);
+            }
+        }
+
+        for i in 0..self.helpers.len() {
+            let func = self.helper_function(self.helpers[i]);
+            self.module.funcs.push(func);
+        }
+
+        Ok(())
+    }
+
+    fn begin_function(&mut self, index: u32, params: Vec<ValType>, result: Ty) {
+        self.contexts.push(Context {
+            index,
+            scopes: vec![HashMap::new()],
+            params,
+            locals: vec![],
+            local_names: vec![],
+            body: vec![],
+            loops: vec![],
+            labels: 0,
+            declarations: 0,
+            result,
+        });
+    }
+
+    fn end_function(&mut self, name: String, param_names: Vec<String>, has_result: bool) -> Func {
+        let ctx = self.contexts.pop().unwrap();
+
+        let mut local_names = param_names;
+        local_names.extend(ctx.local_names);
+
+        Func {
+            name,
+            ty: FuncType {
+                params: ctx.params,
+                results: if has_result { vec![ctx.result.val()] } else { vec![] },
+            },
+            local_names,
+            locals: ctx.locals,
+            body: ctx.body,
+            export: None,
+        }
+    }
+
+    fn function(&mut self, arguments: &[Identifier], body: &Block) -> GenResult {
+        let local = self.next_func;
+        self.next_func += 1;
+
+        let index = self.func_index(local);
+        let params = self.types.params.entry(index).or_insert_with(|| vec![Ty::Int; arguments.len()]).clone();
+        let result = self.types.returns.get(&index).copied().unwrap_or(Ty::Int);
+
+        self.begin_function(index, params.iter().map(|t| t.val()).collect(), result);
+
+        let mut names = vec![];
+        for (i, (arg, ty)) in arguments.iter().zip(&params).enumerate() {
+            names.push(self.unique_local_name(&arg.name(), &names));
+            self.bind(arg.name(), Binding::Local(i as u32, *ty, (index, usize::MAX - i)));
+        }
+
+        for stmt in body.statements() {
+            self.statement(stmt)?;
+        }
+
+        // Falling off the end returns zero
+        self.zero(result);
+
+        let func = self.end_function(self.funcs[local].name.clone(), names, true);
+        self.built[local] = Some(func);
+
+        Ok(())
+    }
+
+    fn unique_local_name(&self, name: &str, taken: &[String]) -> String {
+        let in_ctx = self.contexts.last().map(|c| &c.local_names);
+        let used = |n: &String| taken.contains(n) || in_ctx.is_some_and(|c| c.contains(n));
+
+        let mut unique = name.to_string();
+        let mut n = 0;
+        while used(&unique) {
+            n += 1;
+            unique = format!("{}.{}", name, n);
+        }
+        unique
+    }
+
+    fn ctx(&mut self) -> &mut Context {
+        self.contexts.last_mut().unwrap()
+    }
+
+    fn emit(&mut self, instr: Instr) {
+        self.ctx().body.push(instr);
+    }
+
+    fn label(&mut self) -> Label {
+        let ctx = self.ctx();
+        ctx.labels += 1;
+        ctx.labels
+    }
+
+    fn bind(&mut self, name: String, binding: Binding) {
+        self.ctx().scopes.last_mut().unwrap().insert(name, binding);
+    }
+
+    fn lookup(&self, name: &str) -> Result<Binding, BackendError> {
+        let (current, enclosing) = self.contexts.split_last().unwrap();
+
+        if let Some(binding) = current.scopes.iter().rev().find_map(|s| s.get(name)) {
+            return Ok(*binding)
+        }
+
+        // Nested functions are hoisted, so they are reachable from inner functions
+        for ctx in enclosing.iter().rev() {
+            match ctx.scopes.iter().rev().find_map(|s| s.get(name)) {
+                Some(Binding::Local(..)) =>
+                    return Err(BackendError::Unsupported(format!("capturing local variable '{}'", name))),
+                Some(binding) => return Ok(*binding),
+                None => (),
+            }
+        }
+
+        self.globals.get(name).copied().ok_or_else(|| BackendError::UndefinedVariable(name.to_string()))
+    }
+
+    /// Records that the variable `key` must hold `ty`, returning its widened type
+    fn widen(&mut self, key: (u32, usize), ty: Ty) -> Ty {
+        let known = self.types.variables.get(&key).copied();
+        let widened = join(known, ty);
+
+        if known != Some(widened) {
+            self.types.variables.insert(key, widened);
+            // Only a change in representation or printing needs another pass
+            if known.unwrap_or(Ty::Int) != widened {
+                self.changed = true;
+            }
+        }
+
+        widened
+    }
+
+    /// Converts the value on top of the stack from `from` to `to`
+    fn coerce(&mut self, from: Ty, to: Ty) {
+        if to == Ty::Dec && from != Ty::Dec {
+            self.emit(Instr::Op("f64.convert_i64_s", 0xB9));
+        }
+        else if from == Ty::Dec && to != Ty::Dec {
+            // Not representable yet, the widened type is picked up by the next pass
+            self.changed = true;
+            self.emit(Instr::Op("i64.trunc_f64_s", 0xB0));
+        }
+    }
+
+    fn zero(&mut self, ty: Ty) {
+        match ty {
+            Ty::Dec => self.emit(Instr::F64Const(0.0)),
+            _ => self.emit(Instr::I64Const(0)),
+        }
+    }
+
+    /// Turns the value on top of the stack into an `i32` truth value
+    fn truthy(&mut self, ty: Ty) {
+        match ty {
+            Ty::Dec => {
+                self.emit(Instr::F64Const(0.0));
+                self.emit(Instr::Op("f64.ne", 0x62));
+            },
+            _ => {
+                self.emit(Instr::Op("i64.eqz", 0x50));
+                self.emit(Instr::Op("i32.eqz", 0x45));
+            },
+        }
+    }
+
+    fn declare_local(&mut self, name: String, ty: Ty, key: (u32, usize)) -> u32 {
+        let unique = self.unique_local_name(&name, &[]);
+        let ctx = self.ctx();
+        let index = (ctx.params.len() + ctx.locals.len()) as u32;
+
+        ctx.locals.push(ty.val());
+        ctx.local_names.push(unique);
+
+        self.bind(name, Binding::Local(index, ty, key));
+        index
+    }
+
+    /// A local for an intermediate value, not bound to any name
+    fn temp_local(&mut self, ty: Ty) -> u32 {
+        let unique = self.unique_local_name("@temp", &[]);
+        let ctx = self.ctx();
+        let index = (ctx.params.len() + ctx.locals.len()) as u32;
+
+        ctx.locals.push(ty.val());
+        ctx.local_names.push(unique);
+        index
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> GenResult {
+        match stmt {
+            Statement::Expression(expr) => {
+                self.expression(expr)?;
+                self.emit(Instr::Drop);
+            },
+            Statement::Declaration(Declaration::Variable { identifier, value }) => {
+                let key = (self.ctx().index, self.ctx().declarations);
+                self.ctx().declarations += 1;
+
+                let ty = self.expression_or_null(value.as_ref())?;
+                let declared = self.widen(key, ty);
+
+                self.coerce(ty, declared);
+                let index = self.declare_local(identifier.name(), declared, key);
+                self.emit(Instr::LocalSet(index));
+            },
+            Statement::Declaration(Declaration::Destructure { .. }) => return Err(BackendError::Unsupported(String::from("destructuring"))),
+            Statement::Throw(_) | Statement::Try { .. } => return Err(BackendError::Unsupported(String::from("exceptions"))),
+            Statement::Assert { .. } => return Err(BackendError::Unsupported(String::from("assertions"))),
+            Statement::Declaration(Declaration::Function { identifier, arguments, body }) => {
+                let index = self.func_index(self.next_func);
+                self.bind(identifier.name(), Binding::Function(index));
+                self.function(arguments.as_deref().unwrap_or_default(), body)?;
+            },
+            Statement::Block(block) | Statement::Else { body: block } => self.block(block)?,
+            Statement::Return(expr) => {
+                let ty = self.expression(expr)?;
+                let index = self.ctx().index;
+                let known = self.types.returns.get(&index).copied();
+                let widened = join(known, ty);
+
+                if known != Some(widened) {
+                    self.types.returns.insert(index, widened);
+                    if known.unwrap_or(Ty::Int) != widened {
+                        self.changed = true;
+                    }
+                }
+
+                let result = self.ctx().result;
+                self.coerce(ty, result);
+                self.emit(Instr::Return);
+            },
+            Statement::If { condition, body, else_stmt } => {
+                let ty = self.expression(condition)?;
+                self.truthy(ty);
+
+                let label = self.label();
+                self.emit(Instr::If(label));
+                self.block(body)?;
+
+                if let Some(stmt) = else_stmt {
+                    self.emit(Instr::Else);
+                    self.statement(stmt)?;
+                }
+
+                self.emit(Instr::End);
+            },
+            Statement::While { condition, body } => {
+                let exit = self.label();
+                let top = self.label();
+
+                self.emit(Instr::Block(exit));
+                self.emit(Instr::Loop(top));
+                self.exit_unless(condition, exit)?;
+                self.loop_body(body, top, exit)?;
+                self.emit(Instr::Br(top));
+                self.emit(Instr::End);
+                self.emit(Instr::End);
+            },
+            Statement::For { variable, condition, step, body } => {
+                let exit = self.label();
+                let top = self.label();
+                let next = self.label();
+
+                self.ctx().scopes.push(HashMap::new());
+
+                if let Some(stmt) = variable {
+                    self.statement(stmt)?;
+                }
+
+                self.emit(Instr::Block(exit));
+                self.emit(Instr::Loop(top));
+
+                if let Some(cond) = condition {
+                    self.exit_unless(cond, exit)?;
+                }
+
+                // `continue` breaks out of this inner block onto the step
+                self.emit(Instr::Block(next));
+                self.loop_body(body, next, exit)?;
+                self.emit(Instr::End);
+
+                if let Some(step) = step {
+                    self.expression(step)?;
+                    self.emit(Instr::Drop);
+                }
+
+                self.emit(Instr::Br(top));
+                self.emit(Instr::End);
+                self.emit(Instr::End);
+
+                self.ctx().scopes.pop();
+            },
+            Statement::ForEach { variable, iterable, body } => {
+                let (first, last, inclusive) = match iterable {
+                    Expression::Range { start, end, inclusive } => (start, end, *inclusive),
+                    _ => return Err(BackendError::Unsupported(String::from("iterating over values other than ranges"))),
+                };
+                let exit = self.label();
+                let top = self.label();
+                let next = self.label();
+
+                self.ctx().scopes.push(HashMap::new());
+
+                // The counter is hidden so assigning to the variable doesn't change the iteration
+                let mut hidden = vec![];
+                for (name, bound) in [("@index", first), ("@end", last)] {
+                    let key = (self.ctx().index, self.ctx().declarations);
+                    self.ctx().declarations += 1;
+
+                    if self.expression(bound)? == Ty::Dec {
+                        return Err(BackendError::Unsupported(String::from("decimal ranges")))
+                    }
+
+                    self.widen(key, Ty::Int);
+                    let index = self.declare_local(name.to_string(), Ty::Int, key);
+                    self.emit(Instr::LocalSet(index));
+                    hidden.push(index);
+                }
+
+                let key = (self.ctx().index, self.ctx().declarations);
+                self.ctx().declarations += 1;
+                let declared = self.widen(key, Ty::Int);
+                let slot = self.declare_local(variable.name(), declared, key);
+
+                self.emit(Instr::Block(exit));
+                self.emit(Instr::Loop(top));
+                self.emit(Instr::LocalGet(hidden[0]));
+                self.emit(Instr::LocalGet(hidden[1]));
+                if inclusive {
+                    self.emit(Instr::Op("i64.gt_s", 0x55));
+                }
+                else {
+                    self.emit(Instr::Op("i64.ge_s", 0x59));
+                }
+                self.emit(Instr::BrIf(exit));
+                self.emit(Instr::LocalGet(hidden[0]));
+                self.coerce(Ty::Int, declared);
+                self.emit(Instr::LocalSet(slot));
+
+                // `continue` breaks out of this inner block onto the increment
+                self.emit(Instr::Block(next));
+                self.loop_body(body, next, exit)?;
+                self.emit(Instr::End);
+
+                let add = self.helper(Helper::Add);
+                self.emit(Instr::LocalGet(hidden[0]));
+                self.emit(Instr::I64Const(1));
+                self.emit(Instr::Call(add));
+                self.emit(Instr::LocalSet(hidden[0]));
+                self.emit(Instr::Br(top));
+                self.emit(Instr::End);
+                self.emit(Instr::End);
+
+                self.ctx().scopes.pop();
+            },
+            Statement::Break => {
+                let (_, exit) = self.ctx().loops.last().copied().ok_or(BackendError::BreakOutsideLoop)?;
+                self.emit(Instr::Br(exit));
+            },
+            Statement::Continue => {
+                let (next, _) = self.ctx().loops.last().copied().ok_or(BackendError::ContinueOutsideLoop)?;
+                self.emit(Instr::Br(next));
+            },
+        }
+
+        Ok(())
+    }
+
+    fn exit_unless(&mut self, condition: &Expression, exit: Label) -> GenResult {
+        let ty = self.expression(condition)?;
+        self.truthy(ty);
+        self.emit(Instr::Op("i32.eqz", 0x45));
+        self.emit(Instr::BrIf(exit));
+        Ok(())
+    }
+
+    fn block(&mut self, block: &Block) -> GenResult {
+        self.ctx().scopes.push(HashMap::new());
+
+        for stmt in block.statements() {
+            self.statement(stmt)?;
+        }
+
+        self.ctx().scopes.pop();
+        Ok(())
+    }
+
+    fn loop_body(&mut self, body: &Block, next: Label, exit: Label) -> GenResult {
+        self.ctx().loops.push((next, exit));
+        self.block(body)?;
+        self.ctx().loops.pop();
+        Ok(())
+    }
+
+    fn expression_or_null(&mut self, expr: Option<&Expression>) -> TyResult {
+        match expr {
+            Some(expr) => self.expression(expr),
+            None => { self.emit(Instr::I64Const(0)); Ok(Ty::Int) },
+        }
+    }
+
+    /// Generates `expr` into a separate instruction list
+    fn capture(&mut self, expr: &Expression) -> Result<(Vec<Instr>, Ty), BackendError> {
+        let saved = std::mem::take(&mut self.ctx().body);
+        let ty = self.expression(expr);
+        let code = std::mem::replace(&mut self.ctx().body, saved);
+
+        Ok((code, ty?))
+    }
+
+    /// Pushes the value of `expr` and returns its type
+    fn expression(&mut self, expr: &Expression) -> TyResult {
+        match expr {
+            Expression::Literal(lit) => self.literal(lit),
+            Expression::Value(identifier) => match self.lookup(&identifier.name())? {
+                Binding::Local(index, ty, _) => { self.emit(Instr::LocalGet(index)); Ok(ty) },
+                Binding::Global(index, ty, _) => { self.emit(Instr::GlobalGet(index)); Ok(ty) },
+                Binding::Function(_) => Err(BackendError::Unsupported(String::from("functions as values"))),
+            },
+            Expression::Assignment { lhs, operation, rhs } => {
+                let binding = self.binding(lhs)?;
+
+                let ty = match operation {
+                    Some(operation) => self.binary(lhs, operation, rhs)?,
+                    None => self.expression(rhs)?,
+                };
+
+                self.store(binding, ty)
+            },
+            Expression::Postfix { operand, operation } => {
+                let binding = self.binding(operand)?;
+                let op = if *operation == Token::Increment { Token::Plus } else { Token::Minus };
+
+                // The old value stays on the stack under the stored one
+                let old = self.expression(operand)?;
+                let ty = self.binary(operand, &op, &Expression::Literal(Literal::Integer(1)))?;
+                self.store(binding, ty)?;
+                self.emit(Instr::Drop);
+
+                Ok(old)
+            },
+            Expression::Call { target, args } => self.call(target, args.as_deref().unwrap_or_default()),
+            Expression::Unary { prefix, operand } => self.unary(prefix.as_ref(), operand),
+            Expression::Binary { lhs, operation, rhs } => self.binary(lhs, operation, rhs),
+            Expression::Conditional { condition, then, otherwise } => {
+                let cond = self.expression(condition)?;
+                self.truthy(cond);
+
+                let (then_code, then_ty) = self.capture(then)?;
+                let (otherwise_code, otherwise_ty) = self.capture(otherwise)?;
+                let ty = then_ty.join(otherwise_ty);
+
+                let label = self.label();
+                self.emit(Instr::IfValue(label, ty.val()));
+                self.ctx().body.extend(then_code);
+                self.coerce(then_ty, ty);
+                self.emit(Instr::Else);
+                self.ctx().body.extend(otherwise_code);
+                self.coerce(otherwise_ty, ty);
+                self.emit(Instr::End);
+
+                Ok(ty)
+            },
+            Expression::Logical { lhs, operation, rhs } => self.logical(lhs, operation, rhs),
+            // Null is zero here, so it can't be told apart
+            Expression::Coalesce { .. } => Err(BackendError::Unsupported(String::from("null coalescing"))),
+            Expression::OptionalMember { .. } | Expression::OptionalCall { .. } =>
+                Err(BackendError::Unsupported(String::from("optional chaining"))),
+            Expression::Member { .. } => Err(BackendError::Unsupported(String::from("indexing"))),
+            Expression::Array { .. } => Err(BackendError::Unsupported(String::from("arrays"))),
+            Expression::Range { .. } => Err(BackendError::Unsupported(String::from("ranges as values"))),
+            Expression::Spread(_) => Err(BackendError::Unsupported(String::from("spread"))),
+            Expression::Tuple(_) => Err(BackendError::Unsupported(String::from("tuples"))),
+            Expression::Map(_) => Err(BackendError::Unsupported(String::from("maps"))),
+            Expression::Set(_) => Err(BackendError::Unsupported(String::from("sets"))),
+            Expression::Comprehension { .. } | Expression::Append { .. } =>
+                Err(BackendError::Unsupported(String::from("comprehensions"))),
+        }
+    }
+
+    /// The variable an assignment stores into
+    fn binding(&mut self, lhs: &Expression) -> Result<Binding, BackendError> {
+        match place(lhs)? {
+            Place::Variable(identifier) => match self.lookup(&identifier.name())? {
+                Binding::Function(_) => Err(BackendError::Unsupported(String::from("assignment to functions"))),
+                binding => Ok(binding),
+            },
+            Place::Element { .. } => Err(BackendError::Unsupported(String::from("indexing"))),
+        }
+    }
+
+    /// Stores the value of type `ty` on top of the stack into a variable, leaving the stored value
+    fn store(&mut self, binding: Binding, ty: Ty) -> TyResult {
+        match binding {
+            Binding::Local(index, declared, key) => {
+                self.widen(key, ty);
+                self.coerce(ty, declared);
+                self.emit(Instr::LocalSet(index));
+                self.emit(Instr::LocalGet(index));
+                Ok(declared)
+            },
+            Binding::Global(index, declared, key) => {
+                self.widen(key, ty);
+                self.coerce(ty, declared);
+                self.emit(Instr::GlobalSet(index));
+                self.emit(Instr::GlobalGet(index));
+                Ok(declared)
+            },
+            Binding::Function(_) => Err(BackendError::Unsupported(String::from("assignment to functions"))),
+        }
+    }
+
+    fn literal(&mut self, lit: &Literal) -> TyResult {
+        let (instr, ty) = match lit {
+            Literal::Integer(i) => (Instr::I64Const(*i), Ty::Int),
+            Literal::Decimal(d) => (Instr::F64Const(*d), Ty::Dec),
+            Literal::Boolean(b) => (Instr::I64Const(*b as i64), Ty::Bool),
+            Literal::Character(c) => (Instr::I64Const(*c as i64), Ty::Char),
+            Literal::Null => (Instr::I64Const(0), Ty::Int),
+            Literal::String(_) => return Err(BackendError::Unsupported(String::from("strings outside of print"))),
+        };
+
+        self.emit(instr);
+        Ok(ty)
+    }
+
+    fn unary(&mut self, prefix: Option<&Token>, operand: &Expression) -> TyResult {
+        let (code, ty) = self.capture(operand)?;
+
+        match prefix {
+            Some(Token::Minus) if ty == Ty::Dec => {
+                self.ctx().body.extend(code);
+                self.emit(Instr::Op("f64.neg", 0x9A));
+                Ok(Ty::Dec)
+            },
+            Some(Token::Minus) => {
+                let sub = self.helper(Helper::Sub);
+                self.emit(Instr::I64Const(0));
+                self.ctx().body.extend(code);
+                self.emit(Instr::Call(sub));
+                Ok(Ty::Int)
+            },
+            Some(Token::Not) => {
+                self.ctx().body.extend(code);
+                self.truthy(ty);
+                self.emit(Instr::Op("i32.eqz", 0x45));
+                self.emit(Instr::Op("i64.extend_i32_u", 0xAD));
+                Ok(Ty::Bool)
+            },
+            Some(Token::BinaryNegate) if ty != Ty::Dec => {
+                self.ctx().body.extend(code);
+                self.emit(Instr::I64Const(-1));
+                self.emit(Instr::Op("i64.xor", 0x85));
+                Ok(Ty::Int)
+            },
+            Some(tok) => Err(BackendError::Unsupported(format!("unary operator {} on {:?}", tok, ty))),
+            None => {
+                self.ctx().body.extend(code);
+                Ok(ty)
+            },
+        }
+    }
+
+    /// An `if` that only evaluates `rhs` in the branch `lhs` doesn't decide
+    fn logical(&mut self, lhs: &Expression, op: &Token, rhs: &Expression) -> TyResult {
+        let lty = self.expression(lhs)?;
+        self.truthy(lty);
+
+        let (rcode, rty) = self.capture(rhs)?;
+        let label = self.label();
+        self.emit(Instr::IfValue(label, ValType::I32));
+
+        match op {
+            Token::LogicalAnd => {
+                self.ctx().body.extend(rcode);
+                self.truthy(rty);
+                self.emit(Instr::Else);
+                self.emit(Instr::I32Const(0));
+            },
+            Token::LogicalOr => {
+                self.emit(Instr::I32Const(1));
+                self.emit(Instr::Else);
+                self.ctx().body.extend(rcode);
+                self.truthy(rty);
+            },
+            tok => return Err(BackendError::Unsupported(format!("logical operator {}", tok))),
+        }
+
+        self.emit(Instr::End);
+        self.emit(Instr::Op("i64.extend_i32_u", 0xAD));
+        Ok(Ty::Bool)
+    }
+
+    fn binary(&mut self, lhs: &Expression, op: &Token, rhs: &Expression) -> TyResult {
+        let (lcode, lty) = self.capture(lhs)?;
+        let (rcode, rty) = self.capture(rhs)?;
+
+        let decimal = lty == Ty::Dec || rty == Ty::Dec;
+        let operand = if decimal { Ty::Dec } else { Ty::Int };
+
+        self.ctx().body.extend(lcode);
+        self.coerce(lty, operand);
+        self.ctx().body.extend(rcode);
+        self.coerce(rty, operand);
+
+        let unsupported = || Err(BackendError::Unsupported(format!("operator {} on decimals", op)));
+
+        let (instr, ty) = match op {
+            Token::Plus if decimal => (Instr::Op("f64.add", 0xA0), Ty::Dec),
+            Token::Minus if decimal => (Instr::Op("f64.sub", 0xA1), Ty::Dec),
+            Token::Multiply if decimal => (Instr::Op("f64.mul", 0xA2), Ty::Dec),
+            Token::Divide if decimal => (Instr::Op("f64.div", 0xA3), Ty::Dec),
+            Token::Equals if decimal => (Instr::Op("f64.eq", 0x61), Ty::Bool),
+            Token::NotEquals if decimal => (Instr::Op("f64.ne", 0x62), Ty::Bool),
+            Token::LessThan if decimal => (Instr::Op("f64.lt", 0x63), Ty::Bool),
+            Token::GreaterThan if decimal => (Instr::Op("f64.gt", 0x64), Ty::Bool),
+            Token::LessEquals if decimal => (Instr::Op("f64.le", 0x65), Ty::Bool),
+            Token::GreaterEquals if decimal => (Instr::Op("f64.ge", 0x66), Ty::Bool),
+            _ if decimal => return unsupported(),
+
+            Token::Plus => (Instr::Call(self.helper(Helper::Add)), Ty::Int),
+            Token::Minus => (Instr::Call(self.helper(Helper::Sub)), Ty::Int),
+            Token::Multiply => (Instr::Call(self.helper(Helper::Mul)), Ty::Int),
+            Token::Divide => (Instr::Call(self.helper(Helper::Div)), Ty::Int),
+            Token::Modulo => (Instr::Call(self.helper(Helper::Rem)), Ty::Int),
+            Token::BinaryAnd => (Instr::Op("i64.and", 0x83), Ty::Int),
+            Token::BinaryOr => (Instr::Op("i64.or", 0x84), Ty::Int),
+            Token::Xor => (Instr::Op("i64.xor", 0x85), Ty::Int),
+            Token::ShiftLeft => (Instr::Call(self.helper(Helper::Shl)), Ty::Int),
+            Token::ShiftRight => (Instr::Call(self.helper(Helper::Shr)), Ty::Int),
+            Token::Exponentiate => (Instr::Call(self.helper(Helper::Pow)), Ty::Int),
+            Token::Equals => (Instr::Op("i64.eq", 0x51), Ty::Bool),
+            Token::NotEquals => (Instr::Op("i64.ne", 0x52), Ty::Bool),
+            Token::LessThan => (Instr::Op("i64.lt_s", 0x53), Ty::Bool),
+            Token::GreaterThan => (Instr::Op("i64.gt_s", 0x55), Ty::Bool),
+            Token::LessEquals => (Instr::Op("i64.le_s", 0x57), Ty::Bool),
+            Token::GreaterEquals => (Instr::Op("i64.ge_s", 0x59), Ty::Bool),
+            tok => return Err(BackendError::Unsupported(format!("binary operator {}", tok))),
+        };
+
+        self.emit(instr);
+
+        // Comparisons produce an i32
+        if ty == Ty::Bool {
+            self.emit(Instr::Op("i64.extend_i32_u", 0xAD));
+        }
+
+        Ok(ty)
+    }
+
+    fn call(&mut self, target: &Expression, args: &[Expression]) -> TyResult {
+        let name = match target {
+            Expression::Value(identifier) => identifier.name(),
+            _ => return Err(BackendError::Unsupported(String::from("indirect calls"))),
+        };
+
+        let index = match self.lookup(&name) {
+            Ok(Binding::Function(index)) => index,
+            Ok(_) => return Err(BackendError::Unsupported(String::from("calling non-function values"))),
+            Err(BackendError::UndefinedVariable(_)) if name == "print" || name == "println" =>
+                return self.print(args, name == "println"),
+            Err(BackendError::UndefinedVariable(_)) if stdlib::lookup(&name).is_some() =>
+                return self.builtin(&name, args),
+            Err(BackendError::UndefinedVariable(_)) => return Err(BackendError::UndefinedFunction(name)),
+            Err(e) => return Err(e),
+        };
+
+        let local = (index - IMPORTS.len() as u32) as usize;
+        let arity = self.funcs[local].arity;
+
+        if arity != args.len() {
+            return Err(BackendError::ArityMismatch(name, arity, args.len()))
+        }
+
+        let mut params = self.types.params.get(&index).cloned().unwrap_or_else(|| vec![Ty::Int; arity]);
+
+        for (i, arg) in args.iter().enumerate() {
+            let ty = self.expression(arg)?;
+            let widened = params[i].join(ty);
+
+            if widened != params[i] {
+                params[i] = widened;
+                self.changed = true;
+            }
+
+            self.coerce(ty, params[i]);
+        }
+
+        self.types.params.insert(index, params);
+        self.emit(Instr::Call(index));
+
+        Ok(self.types.returns.get(&index).copied().unwrap_or(Ty::Int))
+    }
+
+    /// The standard library functions on numbers: `abs`, `min`, `max`, `pow` and `sqrt`
+    fn builtin(&mut self, name: &str, args: &[Expression]) -> TyResult {
+        if let Some(arity) = stdlib::lookup(name).and_then(|b| b.arity).filter(|&arity| arity != args.len()) {
+            return Err(BackendError::ArityMismatch(name.to_string(), arity, args.len()))
+        }
+
+        match name {
+            "pow" => self.binary(&args[0], &Token::Exponentiate, &args[1]),
+            "sqrt" => {
+                let ty = self.expression(&args[0])?;
+                self.coerce(ty, Ty::Dec);
+                self.emit(Instr::Op("f64.sqrt", 0x9F));
+                Ok(Ty::Dec)
+            },
+            "abs" => match self.expression(&args[0])? {
+                Ty::Dec => {
+                    self.emit(Instr::Op("f64.abs", 0x99));
+                    Ok(Ty::Dec)
+                },
+                ty => {
+                    let value = self.temp_local(ty);
+                    let sub = self.helper(Helper::Sub);
+                    self.emit(Instr::LocalSet(value));
+                    self.emit(Instr::I64Const(0));
+                    self.emit(Instr::LocalGet(value));
+                    self.emit(Instr::Call(sub));
+                    self.emit(Instr::LocalGet(value));
+                    self.emit(Instr::LocalGet(value));
+                    self.emit(Instr::I64Const(0));
+                    self.emit(Instr::Op("i64.lt_s", 0x53));
+                    self.emit(Instr::Op("select", 0x1B));
+                    Ok(Ty::Int)
+                },
+            },
+            // Like the VM, `min` only picks `b` when it's smaller and `max` when `a` is. The result keeps
+            // the type of the argument picked, which a mix of integers and decimals can't do statically
+            "min" | "max" => {
+                let aty = self.expression(&args[0])?;
+                let bty = self.expression(&args[1])?;
+
+                if (aty == Ty::Dec) != (bty == Ty::Dec) {
+                    return Err(BackendError::Unsupported(format!("'{}' of an integer and a decimal", name)))
+                }
+
+                let ty = aty.join(bty);
+                let (a, b) = (self.temp_local(ty), self.temp_local(ty));
+                let (lhs, rhs) = if name == "min" { (b, a) } else { (a, b) };
+
+                self.emit(Instr::LocalSet(b));
+                self.emit(Instr::LocalSet(a));
+                self.emit(Instr::LocalGet(b));
+                self.emit(Instr::LocalGet(a));
+                self.emit(Instr::LocalGet(lhs));
+                self.emit(Instr::LocalGet(rhs));
+                self.emit(match ty {
+                    Ty::Dec => Instr::Op("f64.lt", 0x63),
+                    _ => Instr::Op("i64.lt_s", 0x53),
+                });
+                self.emit(Instr::Op("select", 0x1B));
+                Ok(ty)
+            },
+            _ => Err(BackendError::Unsupported(format!("standard library function '{}'", name))),
+        }
+    }
+
+    /// Prints each argument separated by spaces through the host imports
+    fn print(&mut self, args: &[Expression], newline: bool) -> TyResult {
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.print_bytes(0, 1);
+            }
+
+            if let Expression::Literal(Literal::String(s)) = arg {
+                let (ptr, len) = self.string(s);
+                self.print_bytes(ptr, len);
+                continue
+            }
+
+            let ty = self.expression(arg)?;
+            self.emit(Instr::Call(match ty {
+                Ty::Int => PRINT_I64,
+                Ty::Dec => PRINT_F64,
+                Ty::Bool => PRINT_BOOL,
+                Ty::Char => PRINT_CHAR,
+            }));
+        }
+
+        if newline {
+            self.print_bytes(1, 1);
+        }
+
+        self.emit(Instr::I64Const(0));
+        Ok(Ty::Int)
+    }
+
+    fn print_bytes(&mut self, ptr: i32, len: i32) {
+        self.emit(Instr::I32Const(ptr));
+        self.emit(Instr::I32Const(len));
+        self.emit(Instr::Call(PRINT_STR));
+    }
+
+    /// Places a string in the data segment, returning its offset and length
+    fn string(&mut self, s: &str) -> (i32, i32) {
+        if let Some(loc) = self.strings.get(s) {
+            return *loc
+        }
+
+        let loc = (self.module.data.len() as i32, s.len() as i32);
+        self.module.data.extend_from_slice(s.as_bytes());
+        self.strings.insert(s.to_string(), loc);
+        loc
+    }
+
+    /// Passes `message` to the `fail` import, which doesn't return
+    fn fail(&mut self, message: &str) -> Vec<Instr> {
+        let (ptr, len) = self.string(message);
+        vec![Instr::I32Const(ptr), Instr::I32Const(len), Instr::Call(FAIL), Instr::Op("unreachable", 0x00)]
+    }
+
+    /// `helper(a, b)` with the errors of the VM: overflow, division by zero, shift counts outside
+    /// of 0 to 63 and negative exponents, which need decimals. `ult_pow` squares until the last bit
+    /// of the exponent, so it only fails when the result overflows
+    fn helper_function(&mut self, helper: Helper) -> Func {
+        use Instr::*;
+
+        let (a, b, result) = (0, 1, 2);
+        let (check, minus_one, done, top, odd) = (1, 2, 3, 4, 5);
+        let overflow = self.fail("Integer overflow");
+
+        // `a * -1` and `a / -1` are `-a`, `a % -1` is 0, and all of them overflow for the minimum
+        let by_minus_one = |then: Vec<Instr>| [
+            vec![LocalGet(b), I64Const(-1), Op("i64.eq", 0x51), If(minus_one)],
+            vec![LocalGet(a), I64Const(i64::MIN), Op("i64.eq", 0x51), If(check)], overflow.clone(), vec![End],
+            then,
+            vec![Return, End],
+        ].concat();
+        let zero_divisor = [
+            vec![LocalGet(b), Op("i64.eqz", 0x50), If(check)], self.fail("Division by zero"), vec![End],
+        ].concat();
+        let shift_count = [
+            vec![LocalGet(b), I64Const(63), Op("i64.gt_u", 0x56), If(check)], overflow.clone(), vec![End],
+        ].concat();
+
+        let body = match helper {
+            // The sum has a different sign from both operands exactly when it overflows
+            Helper::Add => [
+                vec![LocalGet(a), LocalGet(b), Op("i64.add", 0x7C), LocalSet(result)],
+                vec![LocalGet(a), LocalGet(result), Op("i64.xor", 0x85), LocalGet(b), LocalGet(result), Op("i64.xor", 0x85)],
+                vec![Op("i64.and", 0x83), I64Const(0), Op("i64.lt_s", 0x53), If(check)], overflow, vec![End],
+                vec![LocalGet(result)],
+            ].concat(),
+            Helper::Sub => [
+                vec![LocalGet(a), LocalGet(b), Op("i64.sub", 0x7D), LocalSet(result)],
+                vec![LocalGet(a), LocalGet(b), Op("i64.xor", 0x85), LocalGet(a), LocalGet(result), Op("i64.xor", 0x85)],
+                vec![Op("i64.and", 0x83), I64Const(0), Op("i64.lt_s", 0x53), If(check)], overflow, vec![End],
+                vec![LocalGet(result)],
+            ].concat(),
+            // Otherwise the product overflowed if dividing it doesn't give `a` back
+            Helper::Mul => [
+                by_minus_one(vec![I64Const(0), LocalGet(a), Op("i64.sub", 0x7D)]),
+                vec![LocalGet(a), LocalGet(b), Op("i64.mul", 0x7E), LocalSet(result)],
+                vec![LocalGet(b), Op("i64.eqz", 0x50), Op("i32.eqz", 0x45), If(done)],
+                vec![LocalGet(result), LocalGet(b), Op("i64.div_s", 0x7F), LocalGet(a), Op("i64.ne", 0x52), If(check)],
+                overflow,
+                vec![End, End, LocalGet(result)],
+            ].concat(),
+            Helper::Div => [
+                zero_divisor,
+                by_minus_one(vec![I64Const(0), LocalGet(a), Op("i64.sub", 0x7D)]),
+                vec![LocalGet(a), LocalGet(b), Op("i64.div_s", 0x7F)],
+            ].concat(),
+            Helper::Rem => [
+                zero_divisor,
+                by_minus_one(vec![I64Const(0)]),
+                vec![LocalGet(a), LocalGet(b), Op("i64.rem_s", 0x81)],
+            ].concat(),
+            Helper::Shl => [shift_count, vec![LocalGet(a), LocalGet(b), Op("i64.shl", 0x86)]].concat(),
+            Helper::Shr => [shift_count, vec![LocalGet(a), LocalGet(b), Op("i64.shr_s", 0x87)]].concat(),
+            Helper::Pow => {
+                let mul = self.helper(Helper::Mul);
+                let (base, exp) = (a, b);
+
+                [
+                    vec![LocalGet(exp), I64Const(0), Op("i64.lt_s", 0x53), If(check)],
+                    self.fail("Negative exponents need decimal arithmetic"),
+                    vec![End],
+                    vec![I64Const(1), LocalSet(result), Block(done), Loop(top)],
+                    vec![LocalGet(exp), Op("i64.eqz", 0x50), BrIf(done)],
+                    vec![LocalGet(exp), I64Const(1), Op("i64.and", 0x83), Op("i64.eqz", 0x50), Op("i32.eqz", 0x45)],
+                    vec![If(odd), LocalGet(result), LocalGet(base), Call(mul), LocalSet(result), End],
+                    vec![LocalGet(exp), I64Const(1), Op("i64.shr_u", 0x88), LocalSet(exp)],
+                    vec![LocalGet(exp), Op("i64.eqz", 0x50), BrIf(done)],
+                    vec![LocalGet(base), LocalGet(base), Call(mul), LocalSet(base)],
+                    vec![Br(top), End, End, LocalGet(result)],
+                ].concat()
+            },
+        };
+
+        let names = if helper == Helper::Pow { ["base", "exp", "result"] } else { ["a", "b", "result"] };
+        let locals = match helper {
+            Helper::Add | Helper::Sub | Helper::Mul | Helper::Pow => vec![ValType::I64],
+            _ => vec![],
+        };
+
+        Func {
+            name: helper.name().to_string(),
+            ty: FuncType { params: vec![ValType::I64, ValType::I64], results: vec![ValType::I64] },
+            local_names: names[..2 + locals.len()].iter().map(|n| n.to_string()).collect(),
+            locals,
+            body,
+            export: None,
+        }
+    }
+}
+
+/// Assigns every function declaration a unique name, in pre-order
+struct FunctionNames<'a> {
+    /// Name of the enclosing function followed by a dot, empty at the top level
+    prefix: String,
+    funcs: &'a mut Vec<FuncInfo>,
+    taken: &'a mut HashSet<String>,
+}
+
+impl Visitor for FunctionNames<'_> {
+    fn visit_declaration(&mut self, decl: &Declaration, span: Span) {
+        let Declaration::Function { identifier, arguments, body } = decl else { return };
+
+        let base = format!("{}{}", self.prefix, identifier.name());
+        let mut name = base.clone();
+        let mut n = 0;
+
+        while self.taken.contains(&name) {
+            n += 1;
+            name = format!("{}.{}", base, n);
+        }
+
+        self.taken.insert(name.clone());
+        self.funcs.push(FuncInfo { name: name.clone(), arity: arguments.as_ref().map_or(0, Vec::len) });
+
+        let outer = std::mem::replace(&mut self.prefix, format!("{}.", name));
+        self.visit_block(body, span);
+        self.prefix = outer;
+    }
+}
+
+/// The function itself and every function nested in it
+fn count_functions(decl: &Declaration) -> usize {
+    struct Count(usize);
+
+    impl Visitor for Count {
+        fn visit_declaration(&mut self, decl: &Declaration, span: Span) {
+            if let Declaration::Function { .. } = decl {
+                self.0 += 1;
+            }
+            walk_declaration(self, decl, span);
+        }
+    }
+
+    let mut count = Count(0);
+    count.visit_declaration(decl, Span::default());
+    count.0
+}
\ No newline at end of file
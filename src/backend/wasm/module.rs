@@ -0,0 +1,400 @@
+use std::fmt::Write;
+
+/// Value types used by generated modules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValType {
+    I32,
+    I64,
+    F64,
+}
+
+impl ValType {
+    fn name(&self) -> &'static str {
+        match self {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+            ValType::F64 => "f64",
+        }
+    }
+
+    fn byte(&self) -> u8 {
+        match self {
+            ValType::I32 => 0x7F,
+            ValType::I64 => 0x7E,
+            ValType::F64 => 0x7C,
+        }
+    }
+}
+
+/// Labels are numbered per function and resolved to relative depths when encoding
+pub type Label = usize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr {
+    /// A plain instruction without immediates, as its text name and opcode
+    Op(&'static str, u8),
+    I32Const(i32),
+    I64Const(i64),
+    F64Const(f64),
+    LocalGet(u32),
+    LocalSet(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    Call(u32),
+    Block(Label),
+    Loop(Label),
+    If(Label),
+    /// An `if` whose branches both leave a value of the type
+    IfValue(Label, ValType),
+    Else,
+    End,
+    Br(Label),
+    BrIf(Label),
+    Return,
+    Drop,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncType {
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
+
+#[derive(Debug)]
+pub struct Import {
+    pub module: String,
+    pub name: String,
+    pub ty: FuncType,
+}
+
+#[derive(Debug)]
+pub struct Func {
+    pub name: String,
+    pub ty: FuncType,
+    /// Names of params followed by locals, used for the text format
+    pub local_names: Vec<String>,
+    pub locals: Vec<ValType>,
+    pub body: Vec<Instr>,
+    pub export: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Global {
+    pub name: String,
+    pub ty: ValType,
+}
+
+/// A WebAssembly module with one memory, mutable zero-initialized globals and an optional
+/// start function. Functions are indexed after imports
+#[derive(Debug, Default)]
+pub struct Module {
+    pub imports: Vec<Import>,
+    pub funcs: Vec<Func>,
+    pub globals: Vec<Global>,
+    pub data: Vec<u8>,
+    pub start: Option<u32>,
+}
+
+impl Module {
+    fn func_name(&self, idx: u32) -> &str {
+        let idx = idx as usize;
+        match self.imports.get(idx) {
+            Some(import) => &import.name,
+            None => &self.funcs[idx - self.imports.len()].name,
+        }
+    }
+
+    /// Renders the module in the WebAssembly text format
+    pub fn to_wat(&self) -> String {
+        let mut out = String::from("(module\n");
+
+        for import in &self.imports {
+            writeln!(out, "  (import \"{}\" \"{}\" (func ${}{}))",
+                import.module, import.name, import.name, signature(&import.ty, &[])).unwrap();
+        }
+
+        writeln!(out, "  (memory (export \"memory\") 1)").unwrap();
+
+        for global in &self.globals {
+            writeln!(out, "  (global ${} (mut {}) ({}.const 0))", global.name, global.ty.name(), global.ty.name()).unwrap();
+        }
+
+        for func in &self.funcs {
+            self.func_wat(func, &mut out);
+        }
+
+        for (i, func) in self.funcs.iter().enumerate() {
+            if let Some(export) = &func.export {
+                writeln!(out, "  (export \"{}\" (func ${}))", export, self.func_name((self.imports.len() + i) as u32)).unwrap();
+            }
+        }
+
+        if let Some(start) = self.start {
+            writeln!(out, "  (start ${})", self.func_name(start)).unwrap();
+        }
+
+        if !self.data.is_empty() {
+            let mut bytes = String::new();
+            for b in &self.data {
+                match b {
+                    b' '..=b'~' if *b != b'"' && *b != b'\\' => bytes.push(*b as char),
+                    b => write!(bytes, "\\{:02x}", b).unwrap(),
+                }
+            }
+            writeln!(out, "  (data (i32.const 0) \"{}\")", bytes).unwrap();
+        }
+
+        out.push_str(")\n");
+        out
+    }
+
+    fn func_wat(&self, func: &Func, out: &mut String) {
+        writeln!(out, "  (func ${}{}", func.name, signature(&func.ty, &func.local_names)).unwrap();
+
+        let params = func.ty.params.len();
+        for (i, ty) in func.locals.iter().enumerate() {
+            writeln!(out, "    (local ${} {})", func.local_names[params + i], ty.name()).unwrap();
+        }
+
+        let mut depth = 2;
+        for instr in &func.body {
+            if matches!(instr, Instr::End | Instr::Else) {
+                depth -= 1;
+            }
+
+            out.push_str(&"  ".repeat(depth));
+
+            match instr {
+                Instr::Op(name, _) => out.push_str(name),
+                Instr::I32Const(v) => write!(out, "i32.const {}", v).unwrap(),
+                Instr::I64Const(v) => write!(out, "i64.const {}", v).unwrap(),
+                Instr::F64Const(v) => write!(out, "f64.const {:?}", v).unwrap(),
+                Instr::LocalGet(i) => write!(out, "local.get ${}", func.local_names[*i as usize]).unwrap(),
+                Instr::LocalSet(i) => write!(out, "local.set ${}", func.local_names[*i as usize]).unwrap(),
+                Instr::GlobalGet(i) => write!(out, "global.get ${}", self.globals[*i as usize].name).unwrap(),
+                Instr::GlobalSet(i) => write!(out, "global.set ${}", self.globals[*i as usize].name).unwrap(),
+                Instr::Call(f) => write!(out, "call ${}", self.func_name(*f)).unwrap(),
+                Instr::Block(l) => write!(out, "block $L{}", l).unwrap(),
+                Instr::Loop(l) => write!(out, "loop $L{}", l).unwrap(),
+                Instr::If(l) => write!(out, "if $L{}", l).unwrap(),
+                Instr::IfValue(l, ty) => write!(out, "if $L{} (result {})", l, ty.name()).unwrap(),
+                Instr::Else => out.push_str("else"),
+                Instr::End => out.push_str("end"),
+                Instr::Br(l) => write!(out, "br $L{}", l).unwrap(),
+                Instr::BrIf(l) => write!(out, "br_if $L{}", l).unwrap(),
+                Instr::Return => out.push_str("return"),
+                Instr::Drop => out.push_str("drop"),
+            }
+
+            out.push('\n');
+
+            if matches!(instr, Instr::Block(_) | Instr::Loop(_) | Instr::If(_) | Instr::IfValue(..) | Instr::Else) {
+                depth += 1;
+            }
+        }
+
+        out.push_str("  )\n");
+    }
+
+    /// Encodes the module in the WebAssembly binary format
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut out = vec![0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+
+        // Every import and function gets its own type entry, in function index order
+        let types = self.imports.iter().map(|i| &i.ty).chain(self.funcs.iter().map(|f| &f.ty)).collect::<Vec<_>>();
+
+        section(&mut out, 1, types.len(), |buf| {
+            for ty in &types {
+                buf.push(0x60);
+                vec_of(buf, &ty.params, |buf, t| buf.push(t.byte()));
+                vec_of(buf, &ty.results, |buf, t| buf.push(t.byte()));
+            }
+        });
+
+        section(&mut out, 2, self.imports.len(), |buf| {
+            for (i, import) in self.imports.iter().enumerate() {
+                name(buf, &import.module);
+                name(buf, &import.name);
+                buf.push(0x00);
+                uleb(buf, i as u64);
+            }
+        });
+
+        section(&mut out, 3, self.funcs.len(), |buf| {
+            for i in 0..self.funcs.len() {
+                uleb(buf, (self.imports.len() + i) as u64);
+            }
+        });
+
+        // One memory of one page, no maximum
+        section(&mut out, 5, 1, |buf| buf.extend_from_slice(&[0x00, 0x01]));
+
+        section(&mut out, 6, self.globals.len(), |buf| {
+            for global in &self.globals {
+                buf.push(global.ty.byte());
+                buf.push(0x01);
+                match global.ty {
+                    ValType::I32 => buf.extend_from_slice(&[0x41, 0x00]),
+                    ValType::I64 => buf.extend_from_slice(&[0x42, 0x00]),
+                    ValType::F64 => { buf.push(0x44); buf.extend_from_slice(&0f64.to_le_bytes()); },
+                }
+                buf.push(0x0B);
+            }
+        });
+
+        let exports = self.funcs.iter().enumerate()
+            .filter_map(|(i, f)| f.export.as_ref().map(|e| (e, self.imports.len() + i)))
+            .collect::<Vec<_>>();
+
+        section(&mut out, 7, exports.len() + 1, |buf| {
+            name(buf, "memory");
+            buf.extend_from_slice(&[0x02, 0x00]);
+            for (export, idx) in &exports {
+                name(buf, export);
+                buf.push(0x00);
+                uleb(buf, *idx as u64);
+            }
+        });
+
+        if let Some(start) = self.start {
+            let mut body = vec![];
+            uleb(&mut body, start as u64);
+            out.push(8);
+            uleb(&mut out, body.len() as u64);
+            out.extend(body);
+        }
+
+        section(&mut out, 10, self.funcs.len(), |buf| {
+            for func in &self.funcs {
+                let body = encode_body(func);
+                uleb(buf, body.len() as u64);
+                buf.extend(body);
+            }
+        });
+
+        if !self.data.is_empty() {
+            section(&mut out, 11, 1, |buf| {
+                buf.extend_from_slice(&[0x00, 0x41, 0x00, 0x0B]);
+                uleb(buf, self.data.len() as u64);
+                buf.extend_from_slice(&self.data);
+            });
+        }
+
+        out
+    }
+}
+
+fn signature(ty: &FuncType, names: &[String]) -> String {
+    let mut out = String::new();
+    for (i, param) in ty.params.iter().enumerate() {
+        match names.get(i) {
+            Some(name) => write!(out, " (param ${} {})", name, param.name()).unwrap(),
+            None => write!(out, " (param {})", param.name()).unwrap(),
+        }
+    }
+    for result in &ty.results {
+        write!(out, " (result {})", result.name()).unwrap();
+    }
+    out
+}
+
+fn encode_body(func: &Func) -> Vec<u8> {
+    let mut buf = vec![];
+
+    // Locals are run length encoded by type
+    let mut runs: Vec<(u32, ValType)> = vec![];
+    for ty in &func.locals {
+        match runs.last_mut() {
+            Some((n, t)) if t == ty => *n += 1,
+            _ => runs.push((1, *ty)),
+        }
+    }
+
+    vec_of(&mut buf, &runs, |buf, (n, ty)| {
+        uleb(buf, *n as u64);
+        buf.push(ty.byte());
+    });
+
+    let mut labels: Vec<Label> = vec![];
+    let depth = |labels: &Vec<Label>, l: &Label| {
+        (labels.len() - 1 - labels.iter().rposition(|x| x == l).expect("branch to unknown label")) as u64
+    };
+
+    for instr in &func.body {
+        match instr {
+            Instr::Op(_, op) => buf.push(*op),
+            Instr::I32Const(v) => { buf.push(0x41); sleb(&mut buf, *v as i64); },
+            Instr::I64Const(v) => { buf.push(0x42); sleb(&mut buf, *v); },
+            Instr::F64Const(v) => { buf.push(0x44); buf.extend_from_slice(&v.to_le_bytes()); },
+            Instr::LocalGet(i) => { buf.push(0x20); uleb(&mut buf, *i as u64); },
+            Instr::LocalSet(i) => { buf.push(0x21); uleb(&mut buf, *i as u64); },
+            Instr::GlobalGet(i) => { buf.push(0x23); uleb(&mut buf, *i as u64); },
+            Instr::GlobalSet(i) => { buf.push(0x24); uleb(&mut buf, *i as u64); },
+            Instr::Call(f) => { buf.push(0x10); uleb(&mut buf, *f as u64); },
+            Instr::Block(l) => { buf.extend_from_slice(&[0x02, 0x40]); labels.push(*l); },
+            Instr::Loop(l) => { buf.extend_from_slice(&[0x03, 0x40]); labels.push(*l); },
+            Instr::If(l) => { buf.extend_from_slice(&[0x04, 0x40]); labels.push(*l); },
+            Instr::IfValue(l, ty) => { buf.extend_from_slice(&[0x04, ty.byte()]); labels.push(*l); },
+            Instr::Else => buf.push(0x05),
+            Instr::End => { buf.push(0x0B); labels.pop(); },
+            Instr::Br(l) => { buf.push(0x0C); uleb(&mut buf, depth(&labels, l)); },
+            Instr::BrIf(l) => { buf.push(0x0D); uleb(&mut buf, depth(&labels, l)); },
+            Instr::Return => buf.push(0x0F),
+            Instr::Drop => buf.push(0x1A),
+        }
+    }
+
+    buf.push(0x0B);
+    buf
+}
+
+fn section(out: &mut Vec<u8>, id: u8, count: usize, f: impl FnOnce(&mut Vec<u8>)) {
+    if count == 0 {
+        return
+    }
+
+    let mut body = vec![];
+    uleb(&mut body, count as u64);
+    f(&mut body);
+
+    out.push(id);
+    uleb(out, body.len() as u64);
+    out.extend(body);
+}
+
+fn vec_of<T>(buf: &mut Vec<u8>, items: &[T], f: impl Fn(&mut Vec<u8>, &T)) {
+    uleb(buf, items.len() as u64);
+    for item in items {
+        f(buf, item);
+    }
+}
+
+fn name(buf: &mut Vec<u8>, s: &str) {
+    uleb(buf, s.len() as u64);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn uleb(buf: &mut Vec<u8>, mut v: u64) {
+    loop {
+        let byte = (v & 0x7F) as u8;
+        v >>= 7;
+        if v == 0 {
+            buf.push(byte);
+            return
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn sleb(buf: &mut Vec<u8>, mut v: i64) {
+    loop {
+        let byte = (v & 0x7F) as u8;
+        v >>= 7;
+        let done = (v == 0 && byte & 0x40 == 0) || (v == -1 && byte & 0x40 != 0);
+        if done {
+            buf.push(byte);
+            return
+        }
+        buf.push(byte | 0x80);
+    }
+}
\ No newline at end of file
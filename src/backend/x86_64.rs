@@ -0,0 +1,952 @@
+use super::super::lex::token::Token;
+use super::super::parse::ast::*;
+use super::super::stdlib;
+use super::error::BackendError;
+use super::place;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+type GenResult = Result<(), BackendError>;
+
+/// Integer argument registers of the System V AMD64 calling convention
+const ARG_REGS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+/// Lowers an `AST` to x86-64 assembly in GNU as (AT&T) syntax for Linux. \
+/// Every value is a 64-bit integer: booleans are `0`/`1`, characters their code point and
+/// `null` is `0`. Decimals, arrays and first-class functions are not supported
+pub fn generate(ast: &AST) -> Result<String, BackendError> {
+    let mut gen = Generator::new();
+
+    gen.program(ast)?;
+
+    Ok(gen.finish())
+}
+
+#[derive(Clone)]
+enum Binding {
+    /// Offset from `%rbp`
+    Local(i64),
+    Global(String),
+    Function(String, usize),
+}
+
+struct Context {
+    symbol: String,
+    code: String,
+    scopes: Vec<HashMap<String, Binding>>,
+    /// Bytes of stack used by locals so far, what was used when each scope began,
+    /// and the most ever used
+    used: i64,
+    scope_used: Vec<i64>,
+    frame: i64,
+    /// `(continue, break)` labels of enclosing loops
+    loops: Vec<(String, String)>,
+    /// Values pushed on the stack as temporaries, tracked for call alignment
+    pushes: usize,
+    ret_label: String,
+}
+
+impl Context {
+    fn new(symbol: String, ret_label: String) -> Self {
+        Self {
+            symbol,
+            code: String::new(),
+            scopes: vec![HashMap::new()],
+            used: 0,
+            scope_used: vec![],
+            frame: 0,
+            loops: vec![],
+            pushes: 0,
+            ret_label,
+        }
+    }
+}
+
+struct Generator {
+    globals: HashMap<String, Binding>,
+    data: String,
+    rodata: String,
+    functions: String,
+    contexts: Vec<Context>,
+    labels: usize,
+}
+
+impl Generator {
+    fn new() -> Self {
+        Self {
+            globals: HashMap::new(),
+            data: String::new(),
+            rodata: String::new(),
+            functions: String::new(),
+            contexts: vec![],
+            labels: 0,
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "# Generated by ult").unwrap();
+        writeln!(out, "\t.text").unwrap();
+        out.push_str(&self.functions);
+        out.push_str(RUNTIME);
+        writeln!(out, "\n\t.data").unwrap();
+        out.push_str(&self.data);
+        writeln!(out, "\n\t.section .rodata").unwrap();
+        out.push_str(RUNTIME_RODATA);
+        out.push_str(&self.rodata);
+        writeln!(out, "\n\t.section .note.GNU-stack,\"\",@progbits").unwrap();
+
+        out
+    }
+
+    fn program(&mut self, ast: &AST) -> GenResult {
+        // Register every global first so functions can refer to later declarations
+        for decl in ast.program() {
+            match decl {
+                Declaration::Variable { identifier, .. } => {
+                    let symbol = format!("ult_var_{}", identifier.name());
+                    writeln!(self.data, "{}:\n\t.quad 0", symbol).unwrap();
+                    self.globals.insert(identifier.name(), Binding::Global(symbol));
+                },
+                Declaration::Destructure { .. } => return Err(BackendError::Unsupported(String::from("destructuring"))),
+                Declaration::Function { identifier, arguments, .. } => {
+                    let arity = arguments.as_ref().map_or(0, Vec::len);
+                    let symbol = format!("ult_fn_{}", identifier.name());
+                    self.globals.insert(identifier.name(), Binding::Function(symbol, arity));
+                },
+            }
+        }
+
+        // `_start` initializes globals in order, then hands control to `main`
+        self.contexts.push(Context::new(String::from("_start"), String::new()));
+
+        for decl in ast.program() {
+            match decl {
+                Declaration::Variable { identifier, value } => {
+                    self.expression_or_zero(value.as_ref())?;
+                    self.emit(format!("movq %rax, ult_var_{}(%rip)", identifier.name()));
+                },
+                Declaration::Destructure { .. } => return Err(BackendError::Unsupported(String::from("destructuring"))),
+                Declaration::Function { identifier, arguments, body } => {
+                    let symbol = format!("ult_fn_{}", identifier.name());
+                    self.function(symbol, arguments.as_deref().unwrap_or_default(), body)?;
+                },
+            }
+        }
+
+        match self.globals.get("main") {
+            Some(Binding::Function(symbol, 0)) => self.emit(format!("call {}", symbol)),
+            Some(Binding::Function(_, n)) => return Err(BackendError::ArityMismatch(String::from("main"), 0, *n)),
+            _ => self.emit("xorl %eax, %eax"),
+        }
+
+        self.emit("movq %rax, %rdi");
+        self.emit("movl $60, %eax");
+        self.emit("syscall");
+
+        let start = self.contexts.pop().unwrap();
+        writeln!(self.functions, "\n\t.globl _start\n_start:").unwrap();
+        self.functions.push_str(&start.code);
+
+        Ok(())
+    }
+
+    fn function(&mut self, symbol: String, arguments: &[Identifier], body: &Block) -> GenResult {
+        let ret_label = self.label();
+        self.contexts.push(Context::new(symbol, ret_label));
+
+        for (i, arg) in arguments.iter().enumerate() {
+            let offset = match ARG_REGS.get(i) {
+                Some(reg) => {
+                    let offset = self.alloc_slot();
+                    self.emit(format!("movq {}, {}(%rbp)", reg, offset));
+                    offset
+                },
+                // Stack arguments sit above the return address and saved `%rbp`
+                None => 16 + 8 * (i - ARG_REGS.len()) as i64,
+            };
+
+            self.bind(arg.name(), Binding::Local(offset));
+        }
+
+        for stmt in body.statements() {
+            self.statement(stmt)?;
+        }
+
+        let ctx = self.contexts.pop().unwrap();
+        let frame = (ctx.frame + 15) / 16 * 16;
+
+        writeln!(self.functions, "\n{}:", ctx.symbol).unwrap();
+        writeln!(self.functions, "\tpushq %rbp\n\tmovq %rsp, %rbp").unwrap();
+        if frame > 0 {
+            writeln!(self.functions, "\tsubq ${}, %rsp", frame).unwrap();
+        }
+        self.functions.push_str(&ctx.code);
+        writeln!(self.functions, "\txorl %eax, %eax\n{}:\n\tleave\n\tret", ctx.ret_label).unwrap();
+
+        Ok(())
+    }
+
+    fn ctx(&mut self) -> &mut Context {
+        self.contexts.last_mut().unwrap()
+    }
+
+    fn emit(&mut self, line: impl AsRef<str>) {
+        let code = &mut self.ctx().code;
+        code.push('\t');
+        code.push_str(line.as_ref());
+        code.push('\n');
+    }
+
+    fn place(&mut self, label: &str) {
+        writeln!(self.ctx().code, "{}:", label).unwrap();
+    }
+
+    fn label(&mut self) -> String {
+        self.labels += 1;
+        format!(".L{}", self.labels)
+    }
+
+    fn push(&mut self) {
+        self.emit("pushq %rax");
+        self.ctx().pushes += 1;
+    }
+
+    fn pop(&mut self, reg: &str) {
+        self.emit(format!("popq {}", reg));
+        self.ctx().pushes -= 1;
+    }
+
+    fn alloc_slot(&mut self) -> i64 {
+        let ctx = self.ctx();
+        ctx.used += 8;
+        ctx.frame = ctx.frame.max(ctx.used);
+        -ctx.used
+    }
+
+    fn bind(&mut self, name: String, binding: Binding) {
+        self.ctx().scopes.last_mut().unwrap().insert(name, binding);
+    }
+
+    fn begin_scope(&mut self) {
+        let ctx = self.ctx();
+        ctx.scopes.push(HashMap::new());
+        ctx.scope_used.push(ctx.used);
+    }
+
+    /// Drops a scope's bindings, letting later scopes reuse its stack slots
+    fn end_scope(&mut self) {
+        let ctx = self.ctx();
+        ctx.scopes.pop();
+        ctx.used = ctx.scope_used.pop().unwrap();
+    }
+
+    fn lookup(&self, name: &str) -> Result<Binding, BackendError> {
+        let (current, enclosing) = self.contexts.split_last().unwrap();
+
+        if let Some(binding) = current.scopes.iter().rev().find_map(|s| s.get(name)) {
+            return Ok(binding.clone())
+        }
+
+        // Enclosing functions' nested functions are reachable, their locals are not
+        for ctx in enclosing.iter().rev() {
+            match ctx.scopes.iter().rev().find_map(|s| s.get(name)) {
+                Some(Binding::Local(_)) =>
+                    return Err(BackendError::Unsupported(format!("capturing local variable '{}'", name))),
+                Some(binding) => return Ok(binding.clone()),
+                None => (),
+            }
+        }
+
+        self.globals.get(name).cloned().ok_or_else(|| BackendError::UndefinedVariable(name.to_string()))
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> GenResult {
+        match stmt {
+            Statement::Expression(expr) => self.expression(expr),
+            Statement::Declaration(Declaration::Variable { identifier, value }) => {
+                self.expression_or_zero(value.as_ref())?;
+                let offset = self.alloc_slot();
+                self.emit(format!("movq %rax, {}(%rbp)", offset));
+                self.bind(identifier.name(), Binding::Local(offset));
+                Ok(())
+            },
+            Statement::Declaration(Declaration::Destructure { .. }) => Err(BackendError::Unsupported(String::from("destructuring"))),
+            Statement::Throw(_) | Statement::Try { .. } => Err(BackendError::Unsupported(String::from("exceptions"))),
+            Statement::Assert { .. } => Err(BackendError::Unsupported(String::from("assertions"))),
+            Statement::Declaration(Declaration::Function { identifier, arguments, body }) => {
+                let arguments = arguments.as_deref().unwrap_or_default();
+                let symbol = format!("{}.{}", self.ctx().symbol, identifier.name());
+
+                self.bind(identifier.name(), Binding::Function(symbol.clone(), arguments.len()));
+                self.function(symbol, arguments, body)
+            },
+            Statement::Block(block) | Statement::Else { body: block } => self.block(block),
+            Statement::Return(expr) => {
+                self.expression(expr)?;
+                let ret = self.ctx().ret_label.clone();
+                self.emit(format!("jmp {}", ret));
+                Ok(())
+            },
+            Statement::If { condition, body, else_stmt } => {
+                let otherwise = self.label();
+                let end = self.label();
+
+                self.condition(condition, &otherwise)?;
+                self.block(body)?;
+
+                if let Some(stmt) = else_stmt {
+                    self.emit(format!("jmp {}", end));
+                    self.place(&otherwise);
+                    self.statement(stmt)?;
+                }
+                else {
+                    self.place(&otherwise);
+                }
+
+                self.place(&end);
+                Ok(())
+            },
+            Statement::While { condition, body } => {
+                let start = self.label();
+                let end = self.label();
+
+                self.place(&start);
+                self.condition(condition, &end)?;
+                self.loop_body(body, &start, &end)?;
+                self.emit(format!("jmp {}", start));
+                self.place(&end);
+                Ok(())
+            },
+            Statement::For { variable, condition, step, body } => {
+                let start = self.label();
+                let next = self.label();
+                let end = self.label();
+
+                self.begin_scope();
+
+                if let Some(stmt) = variable {
+                    self.statement(stmt)?;
+                }
+
+                self.place(&start);
+
+                if let Some(cond) = condition {
+                    self.condition(cond, &end)?;
+                }
+
+                self.loop_body(body, &next, &end)?;
+                self.place(&next);
+
+                if let Some(step) = step {
+                    self.expression(step)?;
+                }
+
+                self.emit(format!("jmp {}", start));
+                self.place(&end);
+                self.end_scope();
+                Ok(())
+            },
+            Statement::ForEach { variable, iterable, body } => {
+                let (first, last, inclusive) = match iterable {
+                    Expression::Range { start, end, inclusive } => (start, end, *inclusive),
+                    _ => return Err(BackendError::Unsupported(String::from("iterating over values other than ranges"))),
+                };
+                let start = self.label();
+                let next = self.label();
+                let end = self.label();
+
+                self.begin_scope();
+
+                // The counter is hidden so assigning to the variable doesn't change the iteration
+                self.expression(first)?;
+                let counter = self.alloc_slot();
+                self.emit(format!("movq %rax, {}(%rbp)", counter));
+                self.expression(last)?;
+                let limit = self.alloc_slot();
+                self.emit(format!("movq %rax, {}(%rbp)", limit));
+                let slot = self.alloc_slot();
+                self.bind(variable.name(), Binding::Local(slot));
+
+                self.place(&start);
+                self.emit(format!("movq {}(%rbp), %rax", counter));
+                self.emit(format!("cmpq {}(%rbp), %rax", limit));
+                self.emit(format!("{} {}", if inclusive { "jg" } else { "jge" }, end));
+                self.emit(format!("movq %rax, {}(%rbp)", slot));
+                self.loop_body(body, &next, &end)?;
+                self.place(&next);
+                self.emit(format!("addq $1, {}(%rbp)", counter));
+                self.emit("jo ult_overflow");
+                self.emit(format!("jmp {}", start));
+                self.place(&end);
+                self.end_scope();
+                Ok(())
+            },
+            Statement::Break => {
+                let (_, end) = self.ctx().loops.last().cloned().ok_or(BackendError::BreakOutsideLoop)?;
+                self.emit(format!("jmp {}", end));
+                Ok(())
+            },
+            Statement::Continue => {
+                let (next, _) = self.ctx().loops.last().cloned().ok_or(BackendError::ContinueOutsideLoop)?;
+                self.emit(format!("jmp {}", next));
+                Ok(())
+            },
+        }
+    }
+
+    fn block(&mut self, block: &Block) -> GenResult {
+        self.begin_scope();
+
+        for stmt in block.statements() {
+            self.statement(stmt)?;
+        }
+
+        self.end_scope();
+        Ok(())
+    }
+
+    fn loop_body(&mut self, body: &Block, next: &str, end: &str) -> GenResult {
+        self.ctx().loops.push((next.to_string(), end.to_string()));
+        self.block(body)?;
+        self.ctx().loops.pop();
+        Ok(())
+    }
+
+    /// Evaluates a condition and jumps to `otherwise` if it is zero
+    fn condition(&mut self, cond: &Expression, otherwise: &str) -> GenResult {
+        self.expression(cond)?;
+        self.emit("testq %rax, %rax");
+        self.emit(format!("je {}", otherwise));
+        Ok(())
+    }
+
+    fn expression_or_zero(&mut self, expr: Option<&Expression>) -> GenResult {
+        match expr {
+            Some(expr) => self.expression(expr),
+            None => { self.emit("xorl %eax, %eax"); Ok(()) },
+        }
+    }
+
+    /// Evaluates an expression into `%rax`
+    fn expression(&mut self, expr: &Expression) -> GenResult {
+        match expr {
+            Expression::Literal(lit) => self.literal(lit),
+            Expression::Value(identifier) => match self.lookup(&identifier.name())? {
+                Binding::Local(offset) => { self.emit(format!("movq {}(%rbp), %rax", offset)); Ok(()) },
+                Binding::Global(symbol) => { self.emit(format!("movq {}(%rip), %rax", symbol)); Ok(()) },
+                Binding::Function(..) => Err(BackendError::Unsupported(String::from("functions as values"))),
+            },
+            Expression::Assignment { lhs, operation, rhs } => {
+                let location = self.location(lhs)?;
+
+                match operation {
+                    Some(operation) => {
+                        self.emit(format!("movq {}, %rax", location));
+                        self.push();
+                        self.expression(rhs)?;
+                        self.emit("movq %rax, %rcx");
+                        self.pop("%rax");
+                        self.apply(operation)?;
+                    },
+                    None => self.expression(rhs)?,
+                }
+
+                self.emit(format!("movq %rax, {}", location));
+                Ok(())
+            },
+            Expression::Postfix { operand, operation } => {
+                let location = self.location(operand)?;
+                let op = if *operation == Token::Increment { "addq" } else { "subq" };
+
+                self.emit(format!("movq {}, %rax", location));
+                self.emit(format!("{} $1, {}", op, location));
+                self.emit("jo ult_overflow");
+                Ok(())
+            },
+            Expression::Call { target, args } => self.call(target, args.as_deref().unwrap_or_default()),
+            Expression::Unary { prefix, operand } => {
+                self.expression(operand)?;
+
+                match prefix {
+                    Some(Token::Minus) => {
+                        self.emit("negq %rax");
+                        self.emit("jo ult_overflow");
+                    },
+                    Some(Token::BinaryNegate) => self.emit("notq %rax"),
+                    Some(Token::Not) => {
+                        self.emit("testq %rax, %rax");
+                        self.emit("sete %al");
+                        self.emit("movzbq %al, %rax");
+                    },
+                    Some(tok) => return Err(BackendError::Unsupported(format!("unary operator {}", tok))),
+                    None => (),
+                }
+
+                Ok(())
+            },
+            Expression::Binary { lhs, operation, rhs } => self.binary(lhs, operation, rhs),
+            Expression::Logical { lhs, operation, rhs } => self.logical(lhs, operation, rhs),
+            Expression::Conditional { condition, then, otherwise } => {
+                let otherwise_label = self.label();
+                let end = self.label();
+
+                self.condition(condition, &otherwise_label)?;
+                self.expression(then)?;
+                self.emit(format!("jmp {}", end));
+                self.place(&otherwise_label);
+                self.expression(otherwise)?;
+                self.place(&end);
+                Ok(())
+            },
+            // Null is zero here, so it can't be told apart
+            Expression::Coalesce { .. } => Err(BackendError::Unsupported(String::from("null coalescing"))),
+            Expression::OptionalMember { .. } | Expression::OptionalCall { .. } =>
+                Err(BackendError::Unsupported(String::from("optional chaining"))),
+            Expression::Member { .. } => Err(BackendError::Unsupported(String::from("indexing"))),
+            Expression::Array { .. } => Err(BackendError::Unsupported(String::from("arrays"))),
+            Expression::Range { .. } => Err(BackendError::Unsupported(String::from("ranges as values"))),
+            Expression::Spread(_) => Err(BackendError::Unsupported(String::from("spread"))),
+            Expression::Tuple(_) => Err(BackendError::Unsupported(String::from("tuples"))),
+            Expression::Map(_) => Err(BackendError::Unsupported(String::from("maps"))),
+            Expression::Set(_) => Err(BackendError::Unsupported(String::from("sets"))),
+            Expression::Comprehension { .. } | Expression::Append { .. } =>
+                Err(BackendError::Unsupported(String::from("comprehensions"))),
+        }
+    }
+
+    /// The memory operand of the variable an assignment stores into
+    fn location(&mut self, lhs: &Expression) -> Result<String, BackendError> {
+        let name = match place(lhs)? {
+            Place::Variable(identifier) => identifier.name(),
+            Place::Element { .. } => return Err(BackendError::Unsupported(String::from("indexing"))),
+        };
+
+        match self.lookup(&name)? {
+            Binding::Local(offset) => Ok(format!("{}(%rbp)", offset)),
+            Binding::Global(symbol) => Ok(format!("{}(%rip)", symbol)),
+            Binding::Function(..) => Err(BackendError::Unsupported(String::from("assignment to functions"))),
+        }
+    }
+
+    fn literal(&mut self, lit: &Literal) -> GenResult {
+        let value = match lit {
+            Literal::Integer(i) => *i,
+            Literal::Boolean(b) => *b as i64,
+            Literal::Character(c) => *c as i64,
+            Literal::Null => 0,
+            Literal::Decimal(_) => return Err(BackendError::Unsupported(String::from("decimals"))),
+            Literal::String(_) => return Err(BackendError::Unsupported(String::from("strings outside of print"))),
+        };
+
+        match value {
+            0 => self.emit("xorl %eax, %eax"),
+            v if i32::try_from(v).is_ok() => self.emit(format!("movq ${}, %rax", v)),
+            v => self.emit(format!("movabsq ${}, %rax", v)),
+        }
+
+        Ok(())
+    }
+
+    fn binary(&mut self, lhs: &Expression, op: &Token, rhs: &Expression) -> GenResult {
+        self.expression(lhs)?;
+        self.push();
+        self.expression(rhs)?;
+        self.emit("movq %rax, %rcx");
+        self.pop("%rax");
+        self.apply(op)
+    }
+
+    /// Jumps past `rhs` when `lhs` alone decides the result, which is 0 or 1
+    fn logical(&mut self, lhs: &Expression, op: &Token, rhs: &Expression) -> GenResult {
+        let (jump, decided) = match op {
+            Token::LogicalAnd => ("je", "xorl %eax, %eax"),
+            Token::LogicalOr => ("jne", "movl $1, %eax"),
+            tok => return Err(BackendError::Unsupported(format!("logical operator {}", tok))),
+        };
+        let skip = self.label();
+        let end = self.label();
+
+        self.expression(lhs)?;
+        self.emit("testq %rax, %rax");
+        self.emit(format!("{} {}", jump, skip));
+        self.expression(rhs)?;
+        self.emit("testq %rax, %rax");
+        self.emit("setne %al");
+        self.emit("movzbq %al, %rax");
+        self.emit(format!("jmp {}", end));
+        self.place(&skip);
+        self.emit(decided);
+        self.place(&end);
+        Ok(())
+    }
+
+    /// Applies a binary operator to `%rax` and `%rcx`, leaving the result in `%rax`. \
+    /// Like the VM, overflow, division by zero and shifts outside 0..=63 are runtime errors
+    fn apply(&mut self, op: &Token) -> GenResult {
+        match op {
+            Token::Plus | Token::Minus | Token::Multiply => {
+                let instr = match op {
+                    Token::Plus => "addq",
+                    Token::Minus => "subq",
+                    _ => "imulq",
+                };
+                self.emit(format!("{} %rcx, %rax", instr));
+                self.emit("jo ult_overflow");
+            },
+            Token::Divide | Token::Modulo => {
+                let divide = self.label();
+                let end = self.label();
+
+                self.emit("testq %rcx, %rcx");
+                self.emit("jz ult_div_zero");
+                // `idivq` faults on the minimum divided by -1, which overflows like negating it
+                self.emit("cmpq $-1, %rcx");
+                self.emit(format!("jne {}", divide));
+                self.emit("negq %rax");
+                self.emit("jo ult_overflow");
+                if *op == Token::Modulo {
+                    self.emit("xorl %eax, %eax");
+                }
+                self.emit(format!("jmp {}", end));
+                self.place(&divide);
+                self.emit("cqto");
+                self.emit("idivq %rcx");
+                if *op == Token::Modulo {
+                    self.emit("movq %rdx, %rax");
+                }
+                self.place(&end);
+            },
+            Token::Exponentiate => {
+                self.emit("movq %rax, %rdi");
+                self.emit("movq %rcx, %rsi");
+                self.aligned_call("ult_pow");
+            },
+            Token::BinaryAnd => self.emit("andq %rcx, %rax"),
+            Token::BinaryOr => self.emit("orq %rcx, %rax"),
+            Token::Xor => self.emit("xorq %rcx, %rax"),
+            Token::ShiftLeft | Token::ShiftRight => {
+                // Unsigned, so negative counts are out of range too
+                self.emit("cmpq $63, %rcx");
+                self.emit("ja ult_overflow");
+                self.emit(if *op == Token::ShiftLeft { "salq %cl, %rax" } else { "sarq %cl, %rax" });
+            },
+            cmp => {
+                let set = match cmp {
+                    Token::Equals => "sete",
+                    Token::NotEquals => "setne",
+                    Token::LessThan => "setl",
+                    Token::LessEquals => "setle",
+                    Token::GreaterThan => "setg",
+                    Token::GreaterEquals => "setge",
+                    tok => return Err(BackendError::Unsupported(format!("binary operator {}", tok))),
+                };
+
+                self.emit("cmpq %rcx, %rax");
+                self.emit(format!("{} %al", set));
+                self.emit("movzbq %al, %rax");
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Calls a runtime helper that takes only register arguments with `%rsp` 16-byte aligned
+    fn aligned_call(&mut self, symbol: &str) {
+        let misaligned = self.ctx().pushes % 2 == 1;
+
+        if misaligned {
+            self.emit("subq $8, %rsp");
+        }
+
+        self.emit(format!("call {}", symbol));
+
+        if misaligned {
+            self.emit("addq $8, %rsp");
+        }
+    }
+
+    fn call(&mut self, target: &Expression, args: &[Expression]) -> GenResult {
+        let name = match target {
+            Expression::Value(identifier) => identifier.name(),
+            _ => return Err(BackendError::Unsupported(String::from("indirect calls"))),
+        };
+
+        let (symbol, arity) = match self.lookup(&name) {
+            Ok(Binding::Function(symbol, arity)) => (symbol, arity),
+            Ok(_) => return Err(BackendError::Unsupported(String::from("calling non-function values"))),
+            Err(BackendError::UndefinedVariable(_)) if name == "print" || name == "println" =>
+                return self.print(args, name == "println"),
+            Err(BackendError::UndefinedVariable(_)) if stdlib::lookup(&name).is_some() =>
+                return self.builtin(&name, args),
+            Err(BackendError::UndefinedVariable(_)) => return Err(BackendError::UndefinedFunction(name)),
+            Err(e) => return Err(e),
+        };
+
+        if arity != args.len() {
+            return Err(BackendError::ArityMismatch(name, arity, args.len()))
+        }
+
+        // Evaluate left to right, parking every argument on the stack
+        for arg in args {
+            self.expression(arg)?;
+            self.push();
+        }
+
+        let stack_args = args.len().saturating_sub(ARG_REGS.len());
+        let padding = (self.ctx().pushes + stack_args) % 2 == 1;
+
+        if padding {
+            self.emit("subq $8, %rsp");
+            self.ctx().pushes += 1;
+        }
+
+        // Copy arguments beyond the sixth into place, last one first
+        for (copied, i) in (ARG_REGS.len()..args.len()).rev().enumerate() {
+            let offset = 8 * (args.len() - 1 - i + copied + padding as usize);
+            self.emit(format!("pushq {}(%rsp)", offset));
+        }
+
+        for (i, reg) in ARG_REGS.iter().enumerate().take(args.len()) {
+            let offset = 8 * (args.len() - 1 - i + stack_args + padding as usize);
+            self.emit(format!("movq {}(%rsp), {}", offset, reg));
+        }
+
+        self.emit(format!("call {}", symbol));
+
+        let temporaries = args.len() + padding as usize;
+        let cleanup = 8 * (temporaries + stack_args);
+        if cleanup > 0 {
+            self.emit(format!("addq ${}, %rsp", cleanup));
+        }
+        self.ctx().pushes -= temporaries;
+
+        Ok(())
+    }
+
+    /// The standard library functions that only need integers: `abs`, `min`, `max` and `pow`
+    fn builtin(&mut self, name: &str, args: &[Expression]) -> GenResult {
+        if let Some(arity) = stdlib::lookup(name).and_then(|b| b.arity).filter(|&arity| arity != args.len()) {
+            return Err(BackendError::ArityMismatch(name.to_string(), arity, args.len()))
+        }
+
+        match name {
+            "pow" => self.binary(&args[0], &Token::Exponentiate, &args[1]),
+            "abs" => {
+                self.expression(&args[0])?;
+                self.emit("movq %rax, %rcx");
+                self.emit("negq %rax");
+                self.emit("jo ult_overflow");
+                self.emit("cmovsq %rcx, %rax");
+                Ok(())
+            },
+            // Like the VM, `min` only picks `b` when it's smaller and `max` when `a` is
+            "min" | "max" => {
+                self.expression(&args[0])?;
+                self.push();
+                self.expression(&args[1])?;
+                self.emit("movq %rax, %rcx");
+                self.pop("%rax");
+                self.emit(if name == "min" { "cmpq %rax, %rcx" } else { "cmpq %rcx, %rax" });
+                self.emit("cmovlq %rcx, %rax");
+                Ok(())
+            },
+            _ => Err(BackendError::Unsupported(format!("standard library function '{}'", name))),
+        }
+    }
+
+    /// Prints each argument separated by spaces. String literals are printed as text,
+    /// comparisons and boolean literals as `true`/`false`, everything else as an integer
+    fn print(&mut self, args: &[Expression], newline: bool) -> GenResult {
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.aligned_call("ult_print_space");
+            }
+
+            match arg {
+                Expression::Literal(Literal::String(s)) => {
+                    let label = self.string(s);
+                    self.emit(format!("leaq {}(%rip), %rdi", label));
+                    self.emit(format!("movq ${}, %rsi", s.len()));
+                    self.aligned_call("ult_print_str");
+                },
+                arg => {
+                    self.expression(arg)?;
+                    self.emit("movq %rax, %rdi");
+                    let func = if is_boolean(arg) { "ult_print_bool" } else { "ult_print_int" };
+                    self.aligned_call(func);
+                },
+            }
+        }
+
+        if newline {
+            self.aligned_call("ult_print_newline");
+        }
+
+        self.emit("xorl %eax, %eax");
+        Ok(())
+    }
+
+    fn string(&mut self, s: &str) -> String {
+        let label = self.label();
+        writeln!(self.rodata, "{}:\n\t.ascii \"{}\"", label, escape(s)).unwrap();
+        label
+    }
+}
+
+fn is_boolean(expr: &Expression) -> bool {
+    use Token::*;
+    match expr {
+        Expression::Literal(Literal::Boolean(_)) => true,
+        Expression::Unary { prefix: Some(Not), .. } => true,
+        Expression::Logical { .. } => true,
+        Expression::Binary { operation, .. } => matches!(operation,
+            Equals | NotEquals | LessThan | LessEquals | GreaterThan | GreaterEquals),
+        _ => false,
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b' '..=b'~' => out.push(byte as char),
+            b => write!(out, "\\{:03o}", b).unwrap(),
+        }
+    }
+    out
+}
+
+/// Printing and arithmetic helpers, implemented directly on Linux syscalls so no libc is needed
+const RUNTIME: &str = r#"
+# ---- ult runtime ----
+
+# ult_print_int(value): writes a signed integer in decimal to stdout
+ult_print_int:
+	pushq %rbp
+	movq %rsp, %rbp
+	subq $32, %rsp
+	movq %rdi, %rax
+	movq %rdi, %r8
+	leaq -1(%rbp), %rsi
+	movq $10, %rcx
+	testq %rax, %rax
+	jns 1f
+	negq %rax
+1:
+	xorl %edx, %edx
+	divq %rcx
+	addb $'0', %dl
+	movb %dl, (%rsi)
+	decq %rsi
+	testq %rax, %rax
+	jnz 1b
+	testq %r8, %r8
+	jns 2f
+	movb $'-', (%rsi)
+	decq %rsi
+2:
+	incq %rsi
+	movq %rbp, %rdx
+	subq %rsi, %rdx
+	movl $1, %edi
+	movl $1, %eax
+	syscall
+	leave
+	ret
+
+# ult_print_str(ptr, len): writes raw bytes to stdout
+ult_print_str:
+	movq %rsi, %rdx
+	movq %rdi, %rsi
+	movl $1, %edi
+	movl $1, %eax
+	syscall
+	ret
+
+# ult_print_bool(value): writes `true` or `false` to stdout
+ult_print_bool:
+	testq %rdi, %rdi
+	jz 1f
+	leaq ult_true(%rip), %rdi
+	movq $4, %rsi
+	jmp ult_print_str
+1:
+	leaq ult_false(%rip), %rdi
+	movq $5, %rsi
+	jmp ult_print_str
+
+ult_print_space:
+	leaq ult_space(%rip), %rdi
+	movq $1, %rsi
+	jmp ult_print_str
+
+ult_print_newline:
+	leaq ult_newline(%rip), %rdi
+	movq $1, %rsi
+	jmp ult_print_str
+
+# ult_pow(base, exp): integer power by squaring. Negative exponents would give decimals, so they're errors
+ult_pow:
+	movl $1, %eax
+	testq %rsi, %rsi
+	js ult_negative_exponent
+1:
+	testq $1, %rsi
+	jz 2f
+	imulq %rdi, %rax
+	jo ult_overflow
+2:
+	shrq %rsi
+	jz 3f
+	imulq %rdi, %rdi
+	jo ult_overflow
+	jmp 1b
+3:
+	ret
+
+# ult_error(message, len): writes the message to stderr and exits with status 1
+ult_error:
+	movq %rsi, %rdx
+	movq %rdi, %rsi
+	movl $2, %edi
+	movl $1, %eax
+	syscall
+	movl $1, %edi
+	movl $60, %eax
+	syscall
+
+ult_div_zero:
+	leaq ult_div_zero_msg(%rip), %rdi
+	movq $17, %rsi
+	jmp ult_error
+
+ult_overflow:
+	leaq ult_overflow_msg(%rip), %rdi
+	movq $17, %rsi
+	jmp ult_error
+
+ult_negative_exponent:
+	leaq ult_negative_exponent_msg(%rip), %rdi
+	movq $43, %rsi
+	jmp ult_error
+"#;
+
+const RUNTIME_RODATA: &str = r#"ult_true:
+	.ascii "true"
+ult_false:
+	.ascii "false"
+ult_space:
+	.ascii " "
+ult_newline:
+	.ascii "\n"
+ult_div_zero_msg:
+	.ascii "Division by zero\n"
+ult_overflow_msg:
+	.ascii "Integer overflow\n"
+ult_negative_exponent_msg:
+	.ascii "Negative exponents need decimal arithmetic\n"
+"#;
\ No newline at end of file
@@ -0,0 +1,300 @@
+use super::value::Value;
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// Bytecode instructions. \
+/// Operands follow the opcode inline, `u16` operands are little endian
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCode {
+    Constant,       // u16 constant index
+    Null,
+    True,
+    False,
+    Pop,
+    PopN,           // u16 count
+    Dup,
+    Dup2,           // duplicates the top two values
+    Bury,           // u8 depth, moves the top value below the `depth` values under it
+    GetLocal,       // u16 slot
+    SetLocal,       // u16 slot
+    GetGlobal,      // u16 global index
+    SetGlobal,      // u16 global index
+    DefineGlobal,   // u16 global index
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Negate,
+    Not,
+    BitNot,
+    Jump,           // u16 forward offset
+    JumpIfFalse,    // u16 forward offset, pops the condition
+    JumpIfNull,     // u16 forward offset, leaves the value
+    JumpIfNotNull,  // u16 forward offset, leaves the value
+    Loop,           // u16 backward offset
+    Call,           // u8 argument count
+    Return,
+    Array,          // u16 element count
+    Index,
+    SetIndex,       // stores the top value into target[index] below it, leaving the value
+    Range,          // u8 1 if inclusive
+    IterNext,       // u16 forward offset, advances the cursor on top past the iterable below it and pushes
+                    // the element, or jumps once there are none left
+    Snapshot,       // replaces the iterable on top with what a loop over it walks, see `Value::snapshot`
+    Extend,         // pops an iterable and appends its elements to the array below it
+    Apply,          // pops an array of arguments and calls the function below it with them
+    Tuple,          // u16 element count
+    UnpackTuple,    // u16 element count, replaces a tuple of exactly that many elements with its elements
+    UnpackArray,    // u16 element count, the same for an array
+    Map,            // u16 entry count, pops a key and a value for each
+    Set,            // u16 element count
+    SetOf,          // pops an iterable and pushes a set of its elements
+    In,             // pops a collection and a value, pushes whether the collection contains it
+    Append,         // pops a value and appends it to the array below it
+    Try,            // u16 forward offset to the handler, which starts with the error on top of the stack
+    EndTry,         // leaves the innermost handler
+    Throw,          // pops a value and throws it
+    Rethrow,        // the same, keeping the trace of the error it rethrows
+    Assert,         // u8 1 for a binary operation, pops a message and the value of a failed condition, or its
+                    // operands and operator, and fails
+    Stash,          // u16 local count, sets aside the values above the frame's first `count` slots
+    Unstash,        // puts the values set aside by the last `Stash` back below the top value
+}
+
+impl OpCode {
+    const ALL: [OpCode; 64] = {
+        use OpCode::*;
+        [
+            Constant, Null, True, False, Pop, PopN, Dup, Dup2, Bury, GetLocal, SetLocal, GetGlobal,
+            SetGlobal, DefineGlobal, Add, Subtract, Multiply, Divide, Modulo, Power, BitAnd, BitOr,
+            BitXor, ShiftLeft, ShiftRight, Equal, NotEqual, Less, LessEqual, Greater, GreaterEqual,
+            Negate, Not, BitNot, Jump, JumpIfFalse, JumpIfNull, JumpIfNotNull, Loop, Call, Return,
+            Array, Index, SetIndex, Range, IterNext, Snapshot, Extend, Apply, Tuple, UnpackTuple, UnpackArray,
+            Map, Set, SetOf, In, Append, Try, EndTry, Throw, Rethrow, Assert, Stash, Unstash,
+        ]
+    };
+
+    /// Decodes an opcode from its byte representation
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OpCode::ALL.get(byte as usize).copied()
+    }
+
+    /// Number of operand bytes following the opcode
+    pub fn operand_len(&self) -> usize {
+        use OpCode::*;
+        match self {
+            Constant | PopN | GetLocal | SetLocal | GetGlobal | SetGlobal | DefineGlobal |
+            Jump | JumpIfFalse | JumpIfNull | JumpIfNotNull | Loop | Array | IterNext |
+            Tuple | UnpackTuple | UnpackArray | Map | Set | Try | Stash => 2,
+            Call | Bury | Range | Assert => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn mnemonic(&self) -> String {
+        let mut name = String::new();
+        for (i, c) in format!("{:?}", self).chars().enumerate() {
+            if c.is_uppercase() && i > 0 { name.push('_'); }
+            name.push(c.to_ascii_uppercase());
+        }
+        name
+    }
+}
+
+// Decoding indexes into `OpCode::ALL`, so it has to stay in declaration order
+const _: () = {
+    let mut i = 0;
+    while i < OpCode::ALL.len() {
+        assert!(OpCode::ALL[i] as usize == i);
+        i += 1;
+    }
+};
+
+/// A sequence of bytecode with its own constant pool
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Value>,
+    /// Source lines, as the offset each one's code starts at
+    pub lines: Vec<(usize, i32)>,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.code.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.code.is_empty()
+    }
+
+    pub fn write_op(&mut self, op: OpCode) {
+        self.code.push(op as u8);
+    }
+
+    pub fn write_u8(&mut self, byte: u8) {
+        self.code.push(byte);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.code.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    /// Attributes the code written from now on to `line`
+    pub fn mark_line(&mut self, line: i32) {
+        if self.lines.last().is_none_or(|&(_, last)| last != line) {
+            self.lines.push((self.code.len(), line));
+        }
+    }
+
+    /// The source line of the instruction at `offset`, if any code before it was marked
+    pub fn line_at(&self, offset: usize) -> Option<i32> {
+        let idx = self.lines.partition_point(|&(start, _)| start <= offset);
+        idx.checked_sub(1).map(|i| self.lines[i].1)
+    }
+
+    /// Overwrites a previously written `u16` operand, used to back-patch jumps
+    pub fn patch_u16(&mut self, offset: usize, value: u16) {
+        self.code[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Adds a value to the constant pool, reusing an equal existing entry
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        let existing = self.constants.iter().position(|c| {
+            c.type_name() == value.type_name() && !matches!(c, Value::Function(_)) && *c == value
+        });
+
+        existing.unwrap_or_else(|| {
+            self.constants.push(value);
+            self.constants.len() - 1
+        })
+    }
+}
+
+/// A compiled function
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub arity: usize,
+    pub chunk: Chunk,
+}
+
+/// A whole compiled program. \
+/// `script` runs the top level declarations and then calls `main` if there is one, unless compiled `without_main`.
+/// `tests` are the program's `test` blocks by name, to be called after its top level has run
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub script: Rc<Function>,
+    pub globals: Vec<String>,
+    pub tests: Vec<(String, Rc<Function>)>,
+}
+
+impl Program {
+    /// Disassembles every function in the program into a human readable listing
+    pub fn disassemble(&self) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "== globals ==").unwrap();
+        for (i, name) in self.globals.iter().enumerate() {
+            writeln!(out, "{:>4} {}", i, name).unwrap();
+        }
+
+        disassemble_function(&self.script, self, &mut out);
+
+        for (_, test) in &self.tests {
+            disassemble_function(test, self, &mut out);
+        }
+
+        out
+    }
+}
+
+fn disassemble_function(func: &Function, program: &Program, out: &mut String) {
+    writeln!(out, "\n== {} ({} args) ==", func.name, func.arity).unwrap();
+
+    let chunk = &func.chunk;
+    let mut offset = 0;
+
+    while offset < chunk.len() {
+        offset = disassemble_instruction(chunk, program, offset, out);
+    }
+
+    for constant in &chunk.constants {
+        if let Value::Function(f) = constant {
+            disassemble_function(f, program, out);
+        }
+    }
+}
+
+/// Writes a single instruction at `offset` and returns the offset of the next one
+pub fn disassemble_instruction(chunk: &Chunk, program: &Program, offset: usize, out: &mut String) -> usize {
+    use OpCode::*;
+
+    let op = match OpCode::from_byte(chunk.code[offset]) {
+        Some(op) => op,
+        None => {
+            writeln!(out, "{:04} <invalid 0x{:02x}>", offset, chunk.code[offset]).unwrap();
+            return offset + 1;
+        }
+    };
+
+    let next = offset + 1 + op.operand_len();
+
+    write!(out, "{:04} {:<14}", offset, op.mnemonic()).unwrap();
+
+    match op {
+        Constant => {
+            let idx = chunk.read_u16(offset + 1);
+            match &chunk.constants[idx as usize] {
+                Value::String(s) => write!(out, "{:>5} {:?}", idx, s),
+                Value::Character(c) => write!(out, "{:>5} {:?}", idx, c),
+                v => write!(out, "{:>5} {}", idx, v),
+            }.unwrap();
+        },
+        GetGlobal | SetGlobal | DefineGlobal => {
+            let idx = chunk.read_u16(offset + 1);
+            let name = program.globals.get(idx as usize).map(String::as_str).unwrap_or("?");
+            write!(out, "{:>5} ({})", idx, name).unwrap();
+        },
+        Jump | JumpIfFalse | JumpIfNull | JumpIfNotNull | IterNext | Try => {
+            let jump = chunk.read_u16(offset + 1) as usize;
+            write!(out, "{:>5} -> {:04}", jump, next + jump).unwrap();
+        },
+        Loop => {
+            let jump = chunk.read_u16(offset + 1) as usize;
+            write!(out, "{:>5} -> {:04}", jump, next - jump).unwrap();
+        },
+        PopN | GetLocal | SetLocal | Array | Tuple | UnpackTuple | UnpackArray | Map | Set | Stash => {
+            write!(out, "{:>5}", chunk.read_u16(offset + 1)).unwrap();
+        },
+        Call | Bury | Range | Assert => {
+            write!(out, "{:>5}", chunk.code[offset + 1]).unwrap();
+        },
+        _ => (),
+    }
+
+    writeln!(out).unwrap();
+
+    next
+}
\ No newline at end of file
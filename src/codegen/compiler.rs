@@ -0,0 +1,1067 @@
+use super::super::lex::token::Token;
+use super::super::parse::ast::*;
+use super::super::parse::util::binop_symbol;
+use super::bytecode::{ Chunk, Function, OpCode, Program };
+use super::error::CompileError;
+use super::value::Value;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+type CompileResult = Result<(), CompileError>;
+
+/// Compiles an `AST` into bytecode for the `VM`. \
+/// Locals are resolved lexically to stack slots, globals to indices into the global table
+pub struct Compiler<'c> {
+    ast: &'c AST,
+    globals: Vec<String>,
+    global_slots: HashMap<String, u16>,
+    frames: Vec<FunctionState>,
+    program: Option<Program>,
+    call_main: bool,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+}
+
+struct LoopState {
+    /// Number of locals alive when the loop was entered
+    locals: usize,
+    /// Number of handlers active when the loop was entered
+    tries: usize,
+    /// Where `continue` jumps back to, or `None` if it jumps forward to a step expression
+    start: Option<usize>,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+struct FunctionState {
+    name: String,
+    arity: usize,
+    chunk: Chunk,
+    locals: Vec<Local>,
+    depth: usize,
+    loops: Vec<LoopState>,
+    /// Active handlers, with the `finally` that leaving one by `return`, `break` or `continue` runs
+    tries: Vec<Option<Block>>,
+}
+
+impl FunctionState {
+    fn new(name: String, arity: usize) -> Self {
+        Self {
+            name,
+            arity,
+            chunk: Chunk::new(),
+            locals: vec![],
+            depth: 0,
+            loops: vec![],
+            tries: vec![],
+        }
+    }
+
+    fn finish(self) -> Function {
+        Function {
+            name: self.name,
+            arity: self.arity,
+            chunk: self.chunk,
+        }
+    }
+}
+
+impl<'c> Compiler<'c> {
+    pub fn new(ast: &'c AST) -> Compiler<'c> {
+        Compiler {
+            ast,
+            globals: vec![],
+            global_slots: HashMap::new(),
+            frames: vec![],
+            program: None,
+            call_main: true,
+        }
+    }
+
+    /// Compiles the script to only run the top level, leaving `main` for the host to call
+    pub fn without_main(mut self) -> Self {
+        self.call_main = false;
+        self
+    }
+
+    pub fn compile(&mut self) -> CompileResult {
+        self.frames.push(FunctionState::new(String::from("<script>"), 0));
+
+        for (decl, span) in self.ast.program().iter().zip(self.ast.spans()) {
+            self.chunk().mark_line(span.line);
+            self.declaration(decl)?;
+        }
+
+        let main = self.ast.program().iter().position(|decl| {
+            matches!(decl, Declaration::Function { identifier, .. } if identifier.name() == "main")
+        });
+
+        if let Some(main) = main.filter(|_| self.call_main) {
+            // Traces show the call to `main` at its declaration
+            let line = self.ast.spans()[main].line;
+            self.chunk().mark_line(line);
+
+            self.emit_global(OpCode::GetGlobal, "main")?;
+            self.emit(OpCode::Call);
+            self.chunk().write_u8(0);
+        }
+        else {
+            self.emit(OpCode::Null);
+        }
+
+        self.emit(OpCode::Return);
+
+        let mut tests = vec![];
+
+        for test in self.ast.tests() {
+            let func = self.function(format!("test {:?}", test.name), &[], &test.body)?;
+            tests.push((test.name.clone(), Rc::new(func)));
+        }
+
+        let script = self.frames.pop().unwrap().finish();
+
+        self.program = Some(Program {
+            script: Rc::new(script),
+            globals: self.globals.clone(),
+            tests,
+        });
+
+        Ok(())
+    }
+
+    /// The compiled program, if `compile` has succeeded
+    pub fn program(&self) -> Option<&Program> {
+        self.program.as_ref()
+    }
+
+    /// Prints a disassembly of the compiled program to stderr
+    pub fn dump(&self) {
+        if let Some(program) = &self.program {
+            eprint!("{}", program.disassemble());
+        }
+    }
+
+    fn frame(&mut self) -> &mut FunctionState {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.frame().chunk
+    }
+
+    fn emit(&mut self, op: OpCode) {
+        self.chunk().write_op(op);
+    }
+
+    fn emit_u16(&mut self, op: OpCode, operand: usize) -> CompileResult {
+        let operand = u16::try_from(operand).map_err(|_| match op {
+            OpCode::Constant => CompileError::TooManyConstants,
+            OpCode::GetLocal | OpCode::SetLocal | OpCode::PopN | OpCode::Stash => CompileError::TooManyLocals,
+            _ => CompileError::TooManyArguments,
+        })?;
+
+        self.emit(op);
+        self.chunk().write_u16(operand);
+
+        Ok(())
+    }
+
+    fn emit_constant(&mut self, value: Value) -> CompileResult {
+        let idx = self.chunk().add_constant(value);
+        self.emit_u16(OpCode::Constant, idx)
+    }
+
+    fn emit_global(&mut self, op: OpCode, name: &str) -> CompileResult {
+        let slot = self.global_slot(name)?;
+        self.emit_u16(op, slot as usize)
+    }
+
+    /// Emits a jump with a placeholder offset and returns the offset of its operand
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit(op);
+        self.chunk().write_u16(u16::MAX);
+        self.chunk().len() - 2
+    }
+
+    /// Points a previously emitted jump at the current end of the chunk
+    fn patch_jump(&mut self, operand: usize) -> CompileResult {
+        let jump = self.chunk().len() - operand - 2;
+        let jump = u16::try_from(jump).map_err(|_| CompileError::JumpTooLarge)?;
+
+        self.chunk().patch_u16(operand, jump);
+
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, start: usize) -> CompileResult {
+        self.emit(OpCode::Loop);
+
+        let jump = self.chunk().len() + 2 - start;
+        let jump = u16::try_from(jump).map_err(|_| CompileError::JumpTooLarge)?;
+
+        self.chunk().write_u16(jump);
+
+        Ok(())
+    }
+
+    fn emit_pops(&mut self, count: usize) -> CompileResult {
+        match count {
+            0 => Ok(()),
+            1 => { self.emit(OpCode::Pop); Ok(()) },
+            n => self.emit_u16(OpCode::PopN, n),
+        }
+    }
+
+    fn global_slot(&mut self, name: &str) -> Result<u16, CompileError> {
+        if let Some(slot) = self.global_slots.get(name) {
+            return Ok(*slot)
+        }
+
+        let slot = u16::try_from(self.globals.len()).map_err(|_| CompileError::TooManyGlobals)?;
+
+        self.globals.push(name.to_string());
+        self.global_slots.insert(name.to_string(), slot);
+
+        Ok(slot)
+    }
+
+    fn begin_scope(&mut self) {
+        self.frame().depth += 1;
+    }
+
+    fn end_scope(&mut self) -> CompileResult {
+        let frame = self.frame();
+        frame.depth -= 1;
+
+        let depth = frame.depth;
+        let alive = frame.locals.iter().take_while(|l| l.depth <= depth).count();
+        let dead = frame.locals.len() - alive;
+
+        frame.locals.truncate(alive);
+
+        self.emit_pops(dead)
+    }
+
+    fn add_local(&mut self, name: String) -> CompileResult {
+        let frame = self.frame();
+
+        if frame.locals.len() > u16::MAX as usize {
+            return Err(CompileError::TooManyLocals)
+        }
+
+        let depth = frame.depth;
+        frame.locals.push(Local { name, depth });
+
+        Ok(())
+    }
+
+    /// Resolves a name to a local slot in the current function. \
+    /// Locals of enclosing functions can't be reached since there are no closures yet
+    fn resolve_local(&self, name: &str) -> Result<Option<usize>, CompileError> {
+        let (current, enclosing) = self.frames.split_last().unwrap();
+
+        if let Some(slot) = current.locals.iter().rposition(|l| l.name == name) {
+            return Ok(Some(slot))
+        }
+
+        if enclosing.iter().any(|f| f.locals.iter().any(|l| l.name == name)) {
+            return Err(CompileError::UnsupportedCapture(name.to_string()))
+        }
+
+        Ok(None)
+    }
+
+    fn get_variable(&mut self, name: &str) -> CompileResult {
+        match self.resolve_local(name)? {
+            Some(slot) => self.emit_u16(OpCode::GetLocal, slot),
+            None => self.emit_global(OpCode::GetGlobal, name),
+        }
+    }
+
+    fn set_variable(&mut self, name: &str) -> CompileResult {
+        match self.resolve_local(name)? {
+            Some(slot) => self.emit_u16(OpCode::SetLocal, slot),
+            None => self.emit_global(OpCode::SetGlobal, name),
+        }
+    }
+
+    /// Binds the value on top of the stack to a new variable in the current scope
+    fn define_variable(&mut self, name: String) -> CompileResult {
+        if self.frame().depth == 0 {
+            self.emit_global(OpCode::DefineGlobal, &name)
+        }
+        else {
+            self.add_local(name)
+        }
+    }
+
+    fn declaration(&mut self, decl: &Declaration) -> CompileResult {
+        match decl {
+            Declaration::Variable { identifier, value } => {
+                match value {
+                    Some(expr) => self.expression(expr)?,
+                    None => self.emit(OpCode::Null),
+                }
+
+                self.define_variable(identifier.name())
+            },
+            Declaration::Destructure { pattern, value } => {
+                self.expression(value)?;
+                self.destructure(pattern)
+            },
+            Declaration::Function { identifier, arguments, body } => {
+                let name = identifier.name();
+                let local = self.frame().depth > 0;
+
+                // Declare locals up front so a reference from inside the body is a capture error
+                // rather than silently resolving to a global of the same name
+                if local {
+                    self.add_local(name.clone())?;
+                }
+
+                let func = self.function(name.clone(), arguments.as_deref().unwrap_or_default(), body)?;
+
+                self.emit_constant(Value::Function(Rc::new(func)))?;
+
+                if local {
+                    return Ok(())
+                }
+
+                self.define_variable(name)
+            },
+        }
+    }
+
+    /// Binds the parts of the value on top of the stack to the names in `pattern`. \
+    /// Globals are defined from the top of the stack down. Locals stay in the slots the unpacked elements
+    /// land in, with those for nested patterns kept as hidden locals while they're destructured in turn
+    fn destructure(&mut self, pattern: &Pattern) -> CompileResult {
+        let (patterns, op) = match pattern {
+            Pattern::Identifier(identifier) => return self.define_variable(identifier.name()),
+            Pattern::Tuple(patterns) => (patterns, OpCode::UnpackTuple),
+            Pattern::Array(patterns) => (patterns, OpCode::UnpackArray),
+        };
+
+        self.emit_u16(op, patterns.len())?;
+
+        if self.frame().depth == 0 {
+            return patterns.iter().rev().try_for_each(|p| self.destructure(p))
+        }
+
+        let mut nested = vec![];
+
+        for pattern in patterns {
+            match pattern {
+                Pattern::Identifier(identifier) => self.add_local(identifier.name())?,
+                pattern => {
+                    self.add_local(String::from("@pattern"))?;
+                    nested.push((self.frame().locals.len() - 1, pattern));
+                },
+            }
+        }
+
+        for (slot, pattern) in nested {
+            self.emit_u16(OpCode::GetLocal, slot)?;
+            self.destructure(pattern)?;
+        }
+
+        Ok(())
+    }
+
+    fn function(&mut self, name: String, arguments: &[Identifier], body: &Block) -> Result<Function, CompileError> {
+        if arguments.len() > u8::MAX as usize {
+            return Err(CompileError::TooManyArguments)
+        }
+
+        self.frames.push(FunctionState::new(name, arguments.len()));
+        self.begin_scope();
+
+        for arg in arguments {
+            self.add_local(arg.name())?;
+        }
+
+        self.statements(body)?;
+
+        // Implicit `return null`, locals are discarded by the return itself
+        self.emit(OpCode::Null);
+        self.emit(OpCode::Return);
+
+        Ok(self.frames.pop().unwrap().finish())
+    }
+
+    fn block(&mut self, block: &Block) -> CompileResult {
+        self.begin_scope();
+        self.statements(block)?;
+        self.end_scope()
+    }
+
+    fn statements(&mut self, block: &Block) -> CompileResult {
+        for (stmt, span) in block.statements().iter().zip(block.spans()) {
+            self.chunk().mark_line(span.line);
+            self.statement(stmt)?;
+        }
+
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> CompileResult {
+        match stmt {
+            Statement::Expression(expr) => {
+                self.expression(expr)?;
+                self.emit(OpCode::Pop);
+                Ok(())
+            },
+            Statement::Declaration(decl) => self.declaration(decl),
+            Statement::Block(block) => self.block(block),
+            Statement::Else { body } => self.block(body),
+            Statement::Return(expr) => {
+                self.expression(expr)?;
+
+                // The value waits in a hidden local while `finally` blocks run
+                self.add_local(String::from("@return"))?;
+                self.leave_tries(0)?;
+                self.frame().locals.pop();
+
+                self.emit(OpCode::Return);
+                Ok(())
+            },
+            Statement::Throw(expr) => {
+                self.expression(expr)?;
+                self.emit(OpCode::Throw);
+                Ok(())
+            },
+            Statement::Try { body, catch, finally } => self.try_stmt(body, catch.as_ref(), finally.as_ref()),
+            Statement::Assert { condition, message } => self.assert_stmt(condition, message.as_ref()),
+            Statement::If { condition, body, else_stmt } => self.if_stmt(condition, body, else_stmt.as_deref()),
+            Statement::While { condition, body } => self.while_stmt(condition, body),
+            Statement::For { variable, condition, step, body } =>
+                self.for_stmt(variable.as_deref(), condition.as_ref(), step.as_ref(), body),
+            Statement::ForEach { variable, iterable, body } => self.for_each(variable, iterable, body),
+            Statement::Break => self.break_stmt(),
+            Statement::Continue => self.continue_stmt(),
+        }
+    }
+
+    fn if_stmt(&mut self, condition: &Expression, body: &Block, else_stmt: Option<&Statement>) -> CompileResult {
+        self.expression(condition)?;
+
+        let skip_body = self.emit_jump(OpCode::JumpIfFalse);
+
+        self.block(body)?;
+
+        match else_stmt {
+            Some(stmt) => {
+                let skip_else = self.emit_jump(OpCode::Jump);
+                self.patch_jump(skip_body)?;
+                self.statement(stmt)?;
+                self.patch_jump(skip_else)
+            },
+            None => self.patch_jump(skip_body),
+        }
+    }
+
+    fn while_stmt(&mut self, condition: &Expression, body: &Block) -> CompileResult {
+        let start = self.chunk().len();
+
+        self.expression(condition)?;
+
+        let exit = self.emit_jump(OpCode::JumpIfFalse);
+
+        self.begin_loop(Some(start));
+        self.block(body)?;
+        self.emit_loop(start)?;
+
+        self.patch_jump(exit)?;
+        self.end_loop()
+    }
+
+    fn for_stmt(&mut self, variable: Option<&Statement>, condition: Option<&Expression>,
+                step: Option<&Expression>, body: &Block) -> CompileResult {
+        self.begin_scope();
+
+        if let Some(stmt) = variable {
+            self.statement(stmt)?;
+        }
+
+        let start = self.chunk().len();
+
+        let exit = match condition {
+            Some(cond) => {
+                self.expression(cond)?;
+                Some(self.emit_jump(OpCode::JumpIfFalse))
+            },
+            None => None,
+        };
+
+        self.begin_loop(None);
+        self.block(body)?;
+
+        // `continue` lands on the step expression
+        let continues = std::mem::take(&mut self.frame().loops.last_mut().unwrap().continues);
+        for jump in continues {
+            self.patch_jump(jump)?;
+        }
+
+        if let Some(step) = step {
+            self.expression(step)?;
+            self.emit(OpCode::Pop);
+        }
+
+        self.emit_loop(start)?;
+
+        if let Some(exit) = exit {
+            self.patch_jump(exit)?;
+        }
+
+        self.end_loop()?;
+        self.end_scope()
+    }
+
+    /// The iterable and a cursor into it live in hidden locals for the whole loop, `IterNext` advances
+    /// the cursor and pushes the element bound to `variable`
+    fn for_each(&mut self, variable: &Identifier, iterable: &Expression, body: &Block) -> CompileResult {
+        self.begin_scope();
+
+        self.expression(iterable)?;
+        self.emit(OpCode::Snapshot);
+        self.add_local(String::from("@iterable"))?;
+        self.emit_constant(Value::Integer(0))?;
+        self.add_local(String::from("@cursor"))?;
+
+        let start = self.chunk().len();
+        let exit = self.emit_jump(OpCode::IterNext);
+
+        self.begin_loop(Some(start));
+
+        self.begin_scope();
+        self.add_local(variable.name())?;
+        self.block(body)?;
+        self.end_scope()?;
+
+        self.emit_loop(start)?;
+
+        self.patch_jump(exit)?;
+        self.end_loop()?;
+        self.end_scope()
+    }
+
+    /// The condition's value, or a binary operation's operands, stay on the stack until it's known
+    /// to hold, so a failure can show them
+    fn assert_stmt(&mut self, condition: &Expression, message: Option<&Expression>) -> CompileResult {
+        let binary = match condition {
+            Expression::Binary { lhs, operation, rhs } => {
+                self.expression(lhs)?;
+                self.expression(rhs)?;
+                self.emit(OpCode::Dup2);
+                self.emit(binary_op(operation)?);
+                Some(operation)
+            },
+            condition => {
+                self.expression(condition)?;
+                self.emit(OpCode::Dup);
+                None
+            },
+        };
+
+        let fail = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_pops(if binary.is_some() { 2 } else { 1 })?;
+        let end = self.emit_jump(OpCode::Jump);
+
+        self.patch_jump(fail)?;
+
+        if let Some(operation) = binary {
+            self.emit_constant(Value::String(binop_symbol(operation).into()))?;
+        }
+
+        match message {
+            Some(message) => self.expression(message)?,
+            None => self.emit(OpCode::Null),
+        }
+
+        self.emit(OpCode::Assert);
+        self.chunk().write_u8(binary.is_some() as u8);
+
+        self.patch_jump(end)
+    }
+
+    /// `try { body } catch (e) { catch } finally { finally }` is laid out as
+    /// ```text
+    ///     TRY catch; body; END_TRY; JUMP normal
+    /// catch:
+    ///     TRY rethrow; catch; END_TRY; JUMP normal
+    /// rethrow:
+    ///     finally; RETHROW
+    /// normal:
+    ///     finally
+    /// ```
+    /// where the handler around the catch and the rethrowing copy of `finally` are only there with a `finally`
+    fn try_stmt(&mut self, body: &Block, catch: Option<&(Identifier, Block)>, finally: Option<&Block>) -> CompileResult {
+        let mut normal = vec![];
+
+        let handler = self.emit_jump(OpCode::Try);
+        self.frame().tries.push(finally.cloned());
+        self.block(body)?;
+        self.frame().tries.pop();
+        self.emit(OpCode::EndTry);
+        normal.push(self.emit_jump(OpCode::Jump));
+
+        self.patch_jump(handler)?;
+
+        if let Some((identifier, block)) = catch {
+            let rethrow = match finally {
+                Some(finally) => {
+                    let jump = self.emit_jump(OpCode::Try);
+                    self.frame().tries.push(Some(finally.clone()));
+                    Some(jump)
+                },
+                None => None,
+            };
+
+            self.begin_scope();
+            self.add_local(identifier.name())?;
+            self.statements(block)?;
+            self.end_scope()?;
+
+            if let Some(jump) = rethrow {
+                self.frame().tries.pop();
+                self.emit(OpCode::EndTry);
+                normal.push(self.emit_jump(OpCode::Jump));
+                self.patch_jump(jump)?;
+            }
+            else {
+                normal.push(self.emit_jump(OpCode::Jump));
+            }
+        }
+
+        if let Some(finally) = finally {
+            self.add_local(String::from("@error"))?;
+            self.block(finally)?;
+            self.frame().locals.pop();
+            self.emit(OpCode::Rethrow);
+        }
+
+        for jump in normal {
+            self.patch_jump(jump)?;
+        }
+
+        match finally {
+            Some(finally) => self.block(finally),
+            None => Ok(()),
+        }
+    }
+
+    /// Leaves every handler entered since there were `level` of them, innermost first, running their
+    /// `finally` blocks on the way
+    fn leave_tries(&mut self, level: usize) -> CompileResult {
+        for i in (level..self.frame().tries.len()).rev() {
+            self.emit(OpCode::EndTry);
+
+            // The `finally` runs outside of its own handler, and of those inside it
+            let inner = self.frame().tries.split_off(i);
+
+            if let Some(finally) = &inner[0] {
+                self.block(finally)?;
+            }
+
+            self.frame().tries.extend(inner);
+        }
+
+        Ok(())
+    }
+
+    fn begin_loop(&mut self, start: Option<usize>) {
+        let locals = self.frame().locals.len();
+        let tries = self.frame().tries.len();
+
+        self.frame().loops.push(LoopState {
+            locals,
+            tries,
+            start,
+            breaks: vec![],
+            continues: vec![],
+        });
+    }
+
+    fn end_loop(&mut self) -> CompileResult {
+        let state = self.frame().loops.pop().unwrap();
+
+        for jump in state.breaks {
+            self.patch_jump(jump)?;
+        }
+
+        Ok(())
+    }
+
+    /// Leaves the handlers entered inside the innermost loop and pops every local declared inside it
+    /// before jumping out of it
+    fn unwind_loop_locals(&mut self) -> Result<&mut LoopState, CompileError> {
+        let tries = match self.frame().loops.last() {
+            Some(state) => state.tries,
+            None => return Err(CompileError::BreakOutsideLoop),
+        };
+
+        self.leave_tries(tries)?;
+
+        let frame = self.frame();
+        let locals = frame.locals.len() - frame.loops.last().unwrap().locals;
+
+        self.emit_pops(locals)?;
+
+        Ok(self.frame().loops.last_mut().unwrap())
+    }
+
+    fn break_stmt(&mut self) -> CompileResult {
+        self.unwind_loop_locals()?;
+
+        let jump = self.emit_jump(OpCode::Jump);
+        self.frame().loops.last_mut().unwrap().breaks.push(jump);
+
+        Ok(())
+    }
+
+    fn continue_stmt(&mut self) -> CompileResult {
+        let start = match self.unwind_loop_locals() {
+            Ok(state) => state.start,
+            Err(_) => return Err(CompileError::ContinueOutsideLoop),
+        };
+
+        match start {
+            Some(start) => self.emit_loop(start),
+            None => {
+                let jump = self.emit_jump(OpCode::Jump);
+                self.frame().loops.last_mut().unwrap().continues.push(jump);
+                Ok(())
+            },
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) -> CompileResult {
+        match expr {
+            Expression::Literal(lit) => self.literal(lit),
+            Expression::Value(identifier) => self.get_variable(&identifier.name()),
+            Expression::Member { target, property } => {
+                self.expression(target)?;
+                self.expression(property)?;
+                self.emit(OpCode::Index);
+                Ok(())
+            },
+            Expression::Array { elements, .. } => self.array(elements),
+            Expression::Tuple(elements) => {
+                for element in elements {
+                    self.expression(element)?;
+                }
+
+                self.emit_u16(OpCode::Tuple, elements.len())
+            },
+            Expression::Map(entries) => {
+                for (key, value) in entries {
+                    self.expression(key)?;
+                    self.expression(value)?;
+                }
+
+                self.emit_u16(OpCode::Map, entries.len())
+            },
+            Expression::Set(elements) if elements.iter().any(is_spread) => {
+                self.array(elements)?;
+                self.emit(OpCode::SetOf);
+                Ok(())
+            },
+            Expression::Set(elements) => {
+                for element in elements {
+                    self.expression(element)?;
+                }
+
+                self.emit_u16(OpCode::Set, elements.len())
+            },
+            Expression::Assignment { lhs, operation, rhs } => self.assignment(lhs, operation.as_ref(), rhs),
+            Expression::Postfix { operand, operation } => self.postfix(operand, operation),
+            Expression::Call { target, args } => {
+                self.expression(target)?;
+                self.call(args.as_deref().unwrap_or_default())
+            },
+            Expression::Range { start, end, inclusive } => {
+                self.expression(start)?;
+                self.expression(end)?;
+                self.emit(OpCode::Range);
+                self.chunk().write_u8(*inclusive as u8);
+                Ok(())
+            },
+            Expression::Spread(_) => Err(CompileError::UnsupportedOperator(Token::Spread.to_string())),
+            Expression::Comprehension { result, loops, .. } => self.comprehension(result, loops),
+            Expression::Append { target, value } => {
+                self.get_variable(&target.name())?;
+                self.expression(value)?;
+                self.emit(OpCode::Append);
+                Ok(())
+            },
+            Expression::Conditional { condition, then, otherwise } => {
+                self.expression(condition)?;
+                let skip_then = self.emit_jump(OpCode::JumpIfFalse);
+                self.expression(then)?;
+                let skip_otherwise = self.emit_jump(OpCode::Jump);
+                self.patch_jump(skip_then)?;
+                self.expression(otherwise)?;
+                self.patch_jump(skip_otherwise)
+            },
+            Expression::Coalesce { lhs, rhs } => {
+                self.expression(lhs)?;
+                let end = self.emit_jump(OpCode::JumpIfNotNull);
+                self.emit(OpCode::Pop);
+                self.expression(rhs)?;
+                self.patch_jump(end)
+            },
+            Expression::OptionalMember { target, property } => {
+                self.expression(target)?;
+                let end = self.emit_jump(OpCode::JumpIfNull);
+                self.expression(property)?;
+                self.emit(OpCode::Index);
+                self.patch_jump(end)
+            },
+            Expression::OptionalCall { target, args } => {
+                self.expression(target)?;
+                let end = self.emit_jump(OpCode::JumpIfNull);
+                self.call(args.as_deref().unwrap_or_default())?;
+                self.patch_jump(end)
+            },
+            Expression::Unary { prefix, operand } => {
+                self.expression(operand)?;
+
+                match prefix {
+                    Some(Token::Minus) => self.emit(OpCode::Negate),
+                    Some(Token::Not) => self.emit(OpCode::Not),
+                    Some(Token::BinaryNegate) => self.emit(OpCode::BitNot),
+                    Some(tok) => return Err(CompileError::UnsupportedOperator(tok.to_string())),
+                    None => (),
+                }
+
+                Ok(())
+            },
+            Expression::Binary { lhs, operation, rhs } => {
+                self.expression(lhs)?;
+                self.expression(rhs)?;
+                self.emit(binary_op(operation)?);
+                Ok(())
+            },
+            Expression::Logical { lhs, operation, rhs } => self.logical(lhs, operation, rhs),
+        }
+    }
+
+    /// `&&` and `||` jump past `rhs` once `lhs` decides the result, then push `true` or `false`
+    fn logical(&mut self, lhs: &Expression, operation: &Token, rhs: &Expression) -> CompileResult {
+        self.expression(lhs)?;
+        let lhs_false = self.emit_jump(OpCode::JumpIfFalse);
+
+        let lhs_true = match operation {
+            Token::LogicalAnd => None,
+            Token::LogicalOr => {
+                let lhs_true = self.emit_jump(OpCode::Jump);
+                self.patch_jump(lhs_false)?;
+                Some(lhs_true)
+            },
+            tok => return Err(CompileError::UnsupportedOperator(tok.to_string())),
+        };
+
+        self.expression(rhs)?;
+        let rhs_false = self.emit_jump(OpCode::JumpIfFalse);
+
+        if let Some(lhs_true) = lhs_true {
+            self.patch_jump(lhs_true)?;
+        }
+
+        self.emit(OpCode::True);
+        let end = self.emit_jump(OpCode::Jump);
+
+        if lhs_true.is_none() {
+            self.patch_jump(lhs_false)?;
+        }
+
+        self.patch_jump(rhs_false)?;
+        self.emit(OpCode::False);
+        self.patch_jump(end)
+    }
+
+    /// Plain elements go straight into `Array`, runs of them between spreads are appended with `Extend`
+    fn array(&mut self, elements: &[Expression]) -> CompileResult {
+        if !elements.iter().any(is_spread) {
+            for element in elements {
+                self.expression(element)?;
+            }
+
+            return self.emit_u16(OpCode::Array, elements.len())
+        }
+
+        self.emit_u16(OpCode::Array, 0)?;
+
+        for run in elements.chunk_by(|a, b| !is_spread(a) && !is_spread(b)) {
+            match run {
+                [Expression::Spread(iterable)] => self.expression(iterable)?,
+                run => {
+                    for element in run {
+                        self.expression(element)?;
+                    }
+
+                    self.emit_u16(OpCode::Array, run.len())?;
+                },
+            }
+
+            self.emit(OpCode::Extend);
+        }
+
+        Ok(())
+    }
+
+    /// The array being built stays on the stack as a hidden local while the loops run, then is left
+    /// there as the value. Locals need to sit right above the enclosing ones, so operands of the
+    /// surrounding expression are stashed while the loops run
+    fn comprehension(&mut self, result: &Identifier, loops: &Statement) -> CompileResult {
+        let locals = self.frame().locals.len();
+        self.emit_u16(OpCode::Stash, locals)?;
+
+        self.emit_u16(OpCode::Array, 0)?;
+        self.add_local(result.name())?;
+
+        self.statement(loops)?;
+
+        self.frame().locals.pop();
+        self.emit(OpCode::Unstash);
+        Ok(())
+    }
+
+    /// Calls the function already on the stack. \
+    /// With a spread argument the arguments are gathered into an array first and passed with `Apply`
+    fn call(&mut self, args: &[Expression]) -> CompileResult {
+        if args.iter().any(is_spread) {
+            self.array(args)?;
+            self.emit(OpCode::Apply);
+            return Ok(())
+        }
+
+        if args.len() > u8::MAX as usize {
+            return Err(CompileError::TooManyArguments)
+        }
+
+        for arg in args {
+            self.expression(arg)?;
+        }
+
+        self.emit(OpCode::Call);
+        self.chunk().write_u8(args.len() as u8);
+
+        Ok(())
+    }
+
+    /// Compound assignments to elements keep the target and index on the stack, so both are evaluated once
+    fn assignment(&mut self, lhs: &Expression, operation: Option<&Token>, rhs: &Expression) -> CompileResult {
+        match lhs.place().ok_or(CompileError::InvalidAssignmentTarget)? {
+            Place::Variable(identifier) => {
+                if let Some(operation) = operation {
+                    self.get_variable(&identifier.name())?;
+                    self.expression(rhs)?;
+                    self.emit(binary_op(operation)?);
+                }
+                else {
+                    self.expression(rhs)?;
+                }
+
+                self.set_variable(&identifier.name())
+            },
+            Place::Element { target, index } => {
+                self.expression(target)?;
+                self.expression(index)?;
+
+                if let Some(operation) = operation {
+                    self.emit(OpCode::Dup2);
+                    self.emit(OpCode::Index);
+                    self.expression(rhs)?;
+                    self.emit(binary_op(operation)?);
+                }
+                else {
+                    self.expression(rhs)?;
+                }
+
+                self.emit(OpCode::SetIndex);
+                Ok(())
+            },
+        }
+    }
+
+    /// Updates like `x += 1` but leaves the old value, buried under the target and index for elements
+    fn postfix(&mut self, operand: &Expression, operation: &Token) -> CompileResult {
+        let update = match operation {
+            Token::Increment => OpCode::Add,
+            Token::Decrement => OpCode::Subtract,
+            tok => return Err(CompileError::UnsupportedOperator(tok.to_string())),
+        };
+
+        match operand.place().ok_or(CompileError::InvalidAssignmentTarget)? {
+            Place::Variable(identifier) => {
+                self.get_variable(&identifier.name())?;
+                self.emit(OpCode::Dup);
+                self.emit_constant(Value::Integer(1))?;
+                self.emit(update);
+                self.set_variable(&identifier.name())?;
+            },
+            Place::Element { target, index } => {
+                self.expression(target)?;
+                self.expression(index)?;
+                self.emit(OpCode::Dup2);
+                self.emit(OpCode::Index);
+                self.emit(OpCode::Dup);
+                self.emit(OpCode::Bury);
+                self.chunk().write_u8(3);
+                self.emit_constant(Value::Integer(1))?;
+                self.emit(update);
+                self.emit(OpCode::SetIndex);
+            },
+        }
+
+        self.emit(OpCode::Pop);
+        Ok(())
+    }
+
+    fn literal(&mut self, lit: &Literal) -> CompileResult {
+        match lit {
+            Literal::Null => { self.emit(OpCode::Null); Ok(()) },
+            Literal::Boolean(true) => { self.emit(OpCode::True); Ok(()) },
+            Literal::Boolean(false) => { self.emit(OpCode::False); Ok(()) },
+            Literal::Integer(i) => self.emit_constant(Value::Integer(*i)),
+            Literal::Decimal(d) => self.emit_constant(Value::Decimal(*d)),
+            Literal::Character(c) => self.emit_constant(Value::Character(*c)),
+            Literal::String(s) => self.emit_constant(Value::String(s.as_str().into())),
+        }
+    }
+}
+
+fn is_spread(expr: &Expression) -> bool {
+    matches!(expr, Expression::Spread(_))
+}
+
+fn binary_op(tok: &Token) -> Result<OpCode, CompileError> {
+    use OpCode::*;
+    let op = match tok {
+        Token::Plus => Add,
+        Token::Minus => Subtract,
+        Token::Multiply => Multiply,
+        Token::Divide => Divide,
+        Token::Modulo => Modulo,
+        Token::Exponentiate => Power,
+        Token::BinaryAnd => BitAnd,
+        Token::BinaryOr => BitOr,
+        Token::Xor => BitXor,
+        Token::ShiftLeft => ShiftLeft,
+        Token::ShiftRight => ShiftRight,
+        Token::Equals => Equal,
+        Token::NotEquals => NotEqual,
+        Token::In => In,
+        Token::LessThan => Less,
+        Token::LessEquals => LessEqual,
+        Token::GreaterThan => Greater,
+        Token::GreaterEquals => GreaterEqual,
+        tok => return Err(CompileError::UnsupportedOperator(tok.to_string())),
+    };
+
+    Ok(op)
+}
\ No newline at end of file
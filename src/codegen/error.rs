@@ -0,0 +1,84 @@
+use super::value::Value;
+use std::error::Error;
+use std::fmt::{ Display, Result, Formatter };
+
+#[derive(Debug)]
+pub enum CompileError {
+    TooManyConstants,
+    TooManyLocals,
+    TooManyGlobals,
+    TooManyArguments,
+    JumpTooLarge,
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
+    InvalidAssignmentTarget,
+    UnsupportedCapture(String),
+    UnsupportedOperator(String),
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        use CompileError::*;
+        match self {
+            TooManyConstants => write!(f, "Too many constants in one function"),
+            TooManyLocals => write!(f, "Too many local variables in one function"),
+            TooManyGlobals => write!(f, "Too many global variables"),
+            TooManyArguments => write!(f, "Too many arguments in function call or declaration"),
+            JumpTooLarge => write!(f, "Too much code to jump over"),
+            BreakOutsideLoop => write!(f, "'break' used outside of a loop"),
+            ContinueOutsideLoop => write!(f, "'continue' used outside of a loop"),
+            InvalidAssignmentTarget => write!(f, "Invalid assignment target"),
+            UnsupportedCapture(name) => write!(f, "Cannot capture local variable '{}' from an enclosing function", name),
+            UnsupportedOperator(op) => write!(f, "Unsupported operator {}", op),
+        }
+    }
+}
+
+impl Error for CompileError {}
+
+#[derive(Debug)]
+pub enum RuntimeError {
+    /// A value raised by `throw`
+    Thrown(Value),
+    /// What failed and the values involved
+    AssertionFailed(String),
+    TypeError(String),
+    UndefinedVariable(String),
+    NotCallable(String),
+    ArityMismatch(String, usize, usize),
+    IndexOutOfBounds(i64, usize),
+    /// The key, quoted if it's a string
+    KeyNotFound(String),
+    /// What a pattern expected, and what it got instead
+    PatternMismatch(String, String),
+    DivisionByZero,
+    IntegerOverflow,
+    StackOverflow,
+    InvalidBytecode(u8),
+    /// Raised by a native function, with the function's name
+    Native(String, String),
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        use RuntimeError::*;
+        match self {
+            Thrown(value) => write!(f, "{}", value),
+            AssertionFailed(msg) => write!(f, "Assertion failed: {}", msg),
+            TypeError(msg) => write!(f, "Type error: {}", msg),
+            UndefinedVariable(name) => write!(f, "Undefined variable '{}'", name),
+            NotCallable(kind) => write!(f, "Value of type {} is not callable", kind),
+            ArityMismatch(name, e, g) => write!(f, "Function '{}' expected {} arguments but got {}", name, e, g),
+            IndexOutOfBounds(i, len) => write!(f, "Index {} out of bounds for length {}", i, len),
+            KeyNotFound(key) => write!(f, "Key {} not found in map", key),
+            PatternMismatch(e, g) => write!(f, "Cannot destructure {} as {}", g, e),
+            DivisionByZero => write!(f, "Division by zero"),
+            IntegerOverflow => write!(f, "Integer overflow"),
+            StackOverflow => write!(f, "Stack overflow"),
+            InvalidBytecode(b) => write!(f, "Invalid bytecode 0x{:02x}", b),
+            Native(name, msg) => write!(f, "Error in native function '{}': {}", name, msg),
+        }
+    }
+}
+
+impl Error for RuntimeError {}
\ No newline at end of file
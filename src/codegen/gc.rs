@@ -0,0 +1,323 @@
+use super::table::Table;
+use super::value::Value;
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt::{ Display, Result, Formatter };
+use std::rc::{ Rc, Weak };
+
+/// Objects tracked before the first collection in `GcMode::Normal`
+const INITIAL_THRESHOLD: usize = 1024;
+
+/// How eagerly the collector runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GcMode {
+    /// Collect once the heap has doubled since the last collection
+    #[default]
+    Normal,
+    /// Collect on every allocation, to shake out values the VM forgot to root
+    Stress,
+    /// Only collect when asked to with `VM::collect`
+    Manual,
+}
+
+/// Counters kept by a `Heap`, printed by `ult --gc-stats`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    pub allocations: usize,
+    /// Approximate size of everything allocated, in bytes
+    pub bytes: usize,
+    pub collections: usize,
+    /// Objects found dead by collections
+    pub freed: usize,
+    /// Arrays and maps among `freed` that were only reachable from each other, and so were freed by the collector
+    pub cycles: usize,
+    /// Objects alive after the last collection, or allocated since
+    pub live: usize,
+    pub peak: usize,
+}
+
+impl Display for GcStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f, "GC allocations: {} ({} bytes)", self.allocations, self.bytes)?;
+        writeln!(f, "GC collections: {}", self.collections)?;
+        writeln!(f, "GC freed:       {} ({} in cycles)", self.freed, self.cycles)?;
+        write!(f, "GC live:        {} (peak {})", self.live, self.peak)
+    }
+}
+
+enum Object {
+    String(Weak<str>),
+    Array(Weak<RefCell<Vec<Value>>>),
+    Map(Weak<RefCell<Table<Value>>>),
+}
+
+impl Object {
+    fn is_alive(&self) -> bool {
+        match self {
+            Object::String(s) => s.strong_count() > 0,
+            Object::Array(a) => a.strong_count() > 0,
+            Object::Map(m) => m.strong_count() > 0,
+        }
+    }
+}
+
+/// A live array or map, the objects that can hold each other
+enum Container {
+    Array(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<Table<Value>>>),
+}
+
+impl Container {
+    fn address(&self) -> usize {
+        match self {
+            Container::Array(a) => Rc::as_ptr(a) as usize,
+            Container::Map(m) => Rc::as_ptr(m) as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Container::Array(a) => Rc::strong_count(a),
+            Container::Map(m) => Rc::strong_count(m),
+        }
+    }
+
+    /// Calls `f` with every element, or every key and value
+    fn each(&self, mut f: impl FnMut(&Value)) {
+        match self {
+            Container::Array(a) => a.borrow().iter().for_each(f),
+            Container::Map(m) => m.borrow().iter().for_each(|(k, v)| { f(k); f(v); }),
+        }
+    }
+
+    /// Drops everything the container holds
+    fn clear(&self) {
+        match self {
+            Container::Array(a) => drop(std::mem::take(&mut *a.borrow_mut())),
+            Container::Map(m) => drop(std::mem::take(&mut *m.borrow_mut())),
+        }
+    }
+}
+
+/// The strings, arrays and maps allocated by a VM, and a tracing mark-and-sweep collector for them. \
+/// Values stay reference counted, so most objects are freed as soon as they're dropped and values handed
+/// to the host stay valid. What reference counting can't free is arrays and maps that contain each other,
+/// which the collector finds by marking everything reachable from the roots and emptying the containers it
+/// didn't reach. \
+/// Besides the stack and globals given to `collect`, a container referenced from outside the heap, like a value
+/// held by the host or by a running native function, is a root. It's detected by the container having more
+/// references than the tracked containers account for
+pub struct Heap {
+    /// Keyed by address. Holding the `Weak` keeps the address from being reused while it's tracked
+    objects: HashMap<usize, Object>,
+    mode: GcMode,
+    /// Collect once this many objects are tracked
+    threshold: usize,
+    stats: GcStats,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new(GcMode::Normal)
+    }
+}
+
+impl Heap {
+    pub fn new(mode: GcMode) -> Self {
+        Heap { objects: HashMap::new(), mode, threshold: INITIAL_THRESHOLD, stats: GcStats::default() }
+    }
+
+    pub fn mode(&self) -> GcMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: GcMode) {
+        self.mode = mode;
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.stats
+    }
+
+    /// Starts tracking the objects in `value` the heap doesn't know yet, and those inside them. \
+    /// Returns whether there were any
+    pub fn track(&mut self, value: &Value) -> bool {
+        let before = self.objects.len();
+
+        match value {
+            Value::String(s) => {
+                let address = Rc::as_ptr(s) as *const u8 as usize;
+
+                if let Entry::Vacant(entry) = self.objects.entry(address) {
+                    entry.insert(Object::String(Rc::downgrade(s)));
+                    self.allocated(s.len());
+                }
+            },
+            Value::Array(elements) => {
+                let address = Rc::as_ptr(elements) as usize;
+
+                if let Entry::Vacant(entry) = self.objects.entry(address) {
+                    entry.insert(Object::Array(Rc::downgrade(elements)));
+
+                    let elements = elements.borrow();
+                    self.allocated(elements.len() * std::mem::size_of::<Value>());
+
+                    for element in elements.iter() {
+                        self.track(element);
+                    }
+                }
+            },
+            Value::Map(map) => {
+                let address = Rc::as_ptr(map) as usize;
+
+                if let Entry::Vacant(entry) = self.objects.entry(address) {
+                    entry.insert(Object::Map(Rc::downgrade(map)));
+
+                    let map = map.borrow();
+                    self.allocated(map.len() * 2 * std::mem::size_of::<Value>());
+
+                    for (key, value) in map.iter() {
+                        self.track(key);
+                        self.track(value);
+                    }
+                }
+            },
+            // Tuples and sets can't be part of a cycle on their own, only the containers in them need tracking
+            Value::Tuple(items) => {
+                for item in items.iter() {
+                    self.track(item);
+                }
+            },
+            Value::Set(set) => {
+                for element in set.keys() {
+                    self.track(element);
+                }
+            },
+            _ => (),
+        }
+
+        self.objects.len() > before
+    }
+
+    fn allocated(&mut self, bytes: usize) {
+        self.stats.allocations += 1;
+        self.stats.bytes += bytes;
+        self.stats.live = self.objects.len();
+        self.stats.peak = self.stats.peak.max(self.stats.live);
+    }
+
+    /// Whether enough was allocated since the last collection to run another
+    pub fn due(&self) -> bool {
+        match self.mode {
+            GcMode::Normal => self.objects.len() >= self.threshold,
+            GcMode::Stress => true,
+            GcMode::Manual => false,
+        }
+    }
+
+    /// Marks everything reachable from `roots` and from outside the heap, then frees the rest
+    pub fn collect<'v>(&mut self, roots: impl IntoIterator<Item = &'v Value>) {
+        self.stats.collections += 1;
+
+        // Objects already freed by reference counting only need forgetting
+        self.forget_freed();
+
+        let containers: Vec<_> = self.objects.values()
+            .filter_map(|object| match object {
+                Object::Array(a) => a.upgrade().map(Container::Array),
+                Object::Map(m) => m.upgrade().map(Container::Map),
+                Object::String(_) => None,
+            })
+            .collect();
+
+        let index: HashMap<usize, usize> = containers.iter().enumerate()
+            .map(|(i, c)| (c.address(), i))
+            .collect();
+
+        let mut marked = vec![false; containers.len()];
+        let mut pending = vec![];
+
+        for root in roots {
+            mark(root, &index, &mut marked, &mut pending);
+        }
+
+        // References from tracked containers, anything past those (and `containers` itself) comes from outside
+        let mut inner = vec![0; containers.len()];
+        for container in &containers {
+            container.each(|element| count_inner(element, &index, &mut inner));
+        }
+
+        for (i, container) in containers.iter().enumerate() {
+            if !marked[i] && container.strong_count() - 1 > inner[i] {
+                marked[i] = true;
+                pending.push(i);
+            }
+        }
+
+        while let Some(i) = pending.pop() {
+            containers[i].each(|element| mark(element, &index, &mut marked, &mut pending));
+        }
+
+        // Sweep by emptying the unreachable containers, which drops them along with everything only they held
+        for (container, _) in containers.iter().zip(&marked).filter(|(_, marked)| !**marked) {
+            container.clear();
+            self.stats.cycles += 1;
+        }
+
+        drop(containers);
+
+        self.forget_freed();
+
+        self.stats.live = self.objects.len();
+        self.threshold = (self.objects.len() * 2).max(INITIAL_THRESHOLD);
+    }
+
+    fn forget_freed(&mut self) {
+        let before = self.objects.len();
+
+        self.objects.retain(|_, object| object.is_alive());
+
+        self.stats.freed += before - self.objects.len();
+    }
+}
+
+/// The address of the array or map `value` is, if it's one
+fn container(value: &Value) -> Option<usize> {
+    match value {
+        Value::Array(a) => Some(Rc::as_ptr(a) as usize),
+        Value::Map(m) => Some(Rc::as_ptr(m) as usize),
+        _ => None,
+    }
+}
+
+fn mark(value: &Value, index: &HashMap<usize, usize>, marked: &mut [bool], pending: &mut Vec<usize>) {
+    match value {
+        Value::Tuple(items) => items.iter().for_each(|item| mark(item, index, marked, pending)),
+        Value::Set(set) => set.keys().for_each(|element| mark(element, index, marked, pending)),
+        value => {
+            if let Some(&i) = container(value).and_then(|address| index.get(&address)) {
+                if !marked[i] {
+                    marked[i] = true;
+                    pending.push(i);
+                }
+            }
+        },
+    }
+}
+
+/// Counts the tracked containers `value` references. \
+/// A tuple or set only counts when nothing else holds it, otherwise the containers in it are conservatively roots
+fn count_inner(value: &Value, index: &HashMap<usize, usize>, inner: &mut [usize]) {
+    match value {
+        Value::Tuple(items) if Rc::strong_count(items) == 1 =>
+            items.iter().for_each(|item| count_inner(item, index, inner)),
+        Value::Set(set) if Rc::strong_count(set) == 1 =>
+            set.keys().for_each(|element| count_inner(element, index, inner)),
+        value => {
+            if let Some(&i) = container(value).and_then(|address| index.get(&address)) {
+                inner[i] += 1;
+            }
+        },
+    }
+}
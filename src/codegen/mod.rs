@@ -0,0 +1,9 @@
+pub mod bytecode;
+pub mod compiler;
+pub mod gc;
+pub mod table;
+pub mod value;
+pub mod vm;
+mod error;
+
+pub use error::{ CompileError, RuntimeError };
\ No newline at end of file
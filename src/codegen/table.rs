@@ -0,0 +1,177 @@
+use super::value::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{ Hash, Hasher };
+use std::rc::Rc;
+
+/// A hash table keyed by values that remembers insertion order, a map as `Table<Value>` and a set as `Table<()>`. \
+/// Keys hash by content and match like `==` does, so `1` and `1.0` are the same key, except that NaN
+/// matches itself so it can be found again. The VM only inserts keys that are `Value::hashable`, which can't change. \
+/// Entries live in a vector in the order they were inserted, `heads` finds the first entry with a given
+/// hash and the entries sharing it are chained through `next`
+#[derive(Debug, Clone)]
+pub struct Table<V> {
+    entries: Vec<Entry<V>>,
+    heads: HashMap<u64, usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry<V> {
+    key: Value,
+    value: V,
+    /// The entry inserted before this one with the same hash
+    next: Option<usize>,
+}
+
+impl<V> Default for Table<V> {
+    fn default() -> Self {
+        Table { entries: vec![], heads: HashMap::new() }
+    }
+}
+
+impl<V> Table<V> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Table { entries: Vec::with_capacity(capacity), heads: HashMap::with_capacity(capacity) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &Value) -> Option<&V> {
+        self.find(key, hash(key)).map(|i| &self.entries[i].value)
+    }
+
+    pub fn contains(&self, key: &Value) -> bool {
+        self.find(key, hash(key)).is_some()
+    }
+
+    /// Sets the value of `key`, a key that is already there keeps its place
+    pub fn insert(&mut self, key: Value, value: V) {
+        let hash = hash(&key);
+
+        match self.find(&key, hash) {
+            Some(i) => self.entries[i].value = value,
+            None => {
+                let next = self.heads.insert(hash, self.entries.len());
+                self.entries.push(Entry { key, value, next });
+            },
+        }
+    }
+
+    /// The `i`-th entry in insertion order
+    pub fn entry(&self, i: usize) -> Option<(&Value, &V)> {
+        self.entries.get(i).map(|e| (&e.key, &e.value))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Value, &V)> {
+        self.entries.iter().map(|e| (&e.key, &e.value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|e| &e.key)
+    }
+
+    fn find(&self, key: &Value, hash: u64) -> Option<usize> {
+        let mut next = self.heads.get(&hash).copied();
+
+        while let Some(i) = next {
+            if same(&self.entries[i].key, key) {
+                return Some(i)
+            }
+            next = self.entries[i].next;
+        }
+
+        None
+    }
+}
+
+impl<V: PartialEq> PartialEq for Table<V> {
+    /// The same keys with equal values, in any order
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<V> FromIterator<(Value, V)> for Table<V> {
+    fn from_iter<I: IntoIterator<Item = (Value, V)>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut table = Table::with_capacity(iter.size_hint().0);
+
+        for (key, value) in iter {
+            table.insert(key, value);
+        }
+
+        table
+    }
+}
+
+/// Collections nested deeper than this only hash their type
+const MAX_DEPTH: usize = 4;
+
+/// Hashes a value by its content, agreeing with `==`. \
+/// Integers hash as the decimal they equal, and maps and sets combine their entries' hashes in any order
+pub fn hash(value: &Value) -> u64 {
+    hash_nested(value, 0)
+}
+
+fn hash_nested(value: &Value, depth: usize) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    write(value, depth, &mut hasher);
+    hasher.finish()
+}
+
+fn write(value: &Value, depth: usize, state: &mut DefaultHasher) {
+    let nested = |v: &Value| hash_nested(v, depth + 1);
+
+    match value {
+        Value::Array(_) | Value::Tuple(_) | Value::Map(_) | Value::Set(_) if depth == MAX_DEPTH =>
+            value.type_name().hash(state),
+        Value::Null => 0u8.hash(state),
+        Value::Integer(i) => number(*i as f64, state),
+        Value::Decimal(d) => number(*d, state),
+        Value::Boolean(b) => (2u8, b).hash(state),
+        Value::Character(c) => (3u8, c).hash(state),
+        Value::String(s) => (4u8, s).hash(state),
+        Value::Array(arr) => {
+            let arr = arr.borrow();
+            (5u8, arr.len()).hash(state);
+            arr.iter().for_each(|v| nested(v).hash(state));
+        },
+        Value::Tuple(items) => {
+            (6u8, items.len()).hash(state);
+            items.iter().for_each(|v| nested(v).hash(state));
+        },
+        Value::Range(start, end) => (7u8, start, end).hash(state),
+        Value::Map(map) => {
+            let map = map.borrow();
+            (8u8, map.len(), unordered(map.iter().map(|(k, v)| nested(k) ^ nested(v).rotate_left(1)))).hash(state)
+        },
+        Value::Set(set) => (9u8, set.len(), unordered(set.keys().map(nested))).hash(state),
+        Value::Function(func) => (10u8, Rc::as_ptr(func) as usize).hash(state),
+        Value::Native(func) => (11u8, Rc::as_ptr(func) as usize).hash(state),
+    }
+}
+
+/// `-0.0` is the same key as `0.0`, and every NaN the same as any other
+fn number(d: f64, state: &mut DefaultHasher) {
+    let bits = match d {
+        0.0 => 0,
+        d if d.is_nan() => f64::NAN.to_bits(),
+        d => d.to_bits(),
+    };
+
+    (1u8, bits).hash(state)
+}
+
+fn unordered(hashes: impl Iterator<Item = u64>) -> u64 {
+    hashes.fold(0, u64::wrapping_add)
+}
+
+fn same(a: &Value, b: &Value) -> bool {
+    a == b || matches!((a, b), (Value::Decimal(x), Value::Decimal(y)) if x.is_nan() && y.is_nan())
+}
\ No newline at end of file
@@ -0,0 +1,536 @@
+use super::bytecode::Function;
+use super::error::RuntimeError;
+use super::table::Table;
+use super::vm::VM;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fmt::{ Display, Result, Formatter };
+use std::rc::Rc;
+
+type ValueResult = std::result::Result<Value, RuntimeError>;
+
+/// A runtime value on the VM stack
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Decimal(f64),
+    Boolean(bool),
+    Character(char),
+    String(Rc<str>),
+    Array(Rc<RefCell<Vec<Value>>>),
+    /// Immutable, so it's shared rather than tracked by the heap, but arrays inside it are
+    Tuple(Rc<[Value]>),
+    /// Entries in the order their keys were first inserted
+    Map(Rc<RefCell<Table<Value>>>),
+    /// Immutable like a tuple, elements in the order they were first inserted
+    Set(Rc<Table<()>>),
+    /// `start..end`, with the end excluded. `start..=end` is stored as `start..end + 1`
+    Range(i64, i64),
+    Function(Rc<Function>),
+    Native(Rc<Native>),
+}
+
+type NativeBody = dyn Fn(&mut VM, Vec<Value>) -> ValueResult;
+
+/// A function implemented by the host. \
+/// It gets the VM running it, to call back into function values it was passed
+pub struct Native {
+    pub name: String,
+    /// Number of arguments, `None` takes any number
+    pub arity: Option<usize>,
+    body: Box<NativeBody>,
+}
+
+impl Native {
+    pub fn new(name: String, arity: Option<usize>, body: impl Fn(&mut VM, Vec<Value>) -> ValueResult + 'static) -> Self {
+        Self { name, arity, body: Box::new(body) }
+    }
+
+    pub fn call(&self, vm: &mut VM, args: Vec<Value>) -> ValueResult {
+        (self.body)(vm, args)
+    }
+}
+
+impl std::fmt::Debug for Native {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Native({})", self.name)
+    }
+}
+
+impl Value {
+    /// Name of the value's type, used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "Null",
+            Value::Integer(_) => "Integer",
+            Value::Decimal(_) => "Decimal",
+            Value::Boolean(_) => "Boolean",
+            Value::Character(_) => "Character",
+            Value::String(_) => "String",
+            Value::Array(_) => "Array",
+            Value::Tuple(_) => "Tuple",
+            Value::Map(_) => "Map",
+            Value::Set(_) => "Set",
+            Value::Range(..) => "Range",
+            Value::Function(_) | Value::Native(_) => "Function",
+        }
+    }
+
+    /// Checks that the value can be a map key or a set element. Arrays and maps could change after they
+    /// were inserted and then not be found again, so they're unhashable, and so are tuples holding them
+    pub fn hashable(&self) -> std::result::Result<(), RuntimeError> {
+        match self {
+            Value::Array(_) | Value::Map(_) => Err(RuntimeError::TypeError(format!("unhashable {}", self.type_name()))),
+            Value::Tuple(items) => items.iter().try_for_each(Value::hashable),
+            _ => Ok(()),
+        }
+    }
+
+    /// `null`, `false`, `0` and `0.0` are falsy, everything else is truthy
+    pub fn truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Boolean(b) => *b,
+            Value::Integer(i) => *i != 0,
+            Value::Decimal(d) => *d != 0.0,
+            _ => true,
+        }
+    }
+
+    pub fn add(&self, rhs: &Value) -> ValueResult {
+        use Value::*;
+        match (self, rhs) {
+            (Integer(a), Integer(b)) => a.checked_add(*b).map(Integer).ok_or(RuntimeError::IntegerOverflow),
+            (String(a), b) => Ok(String(format!("{}{}", a, b).into())),
+            (a, String(b)) => Ok(String(format!("{}{}", a, b).into())),
+            _ => self.decimal_op(rhs, "+", |a, b| a + b),
+        }
+    }
+
+    pub fn subtract(&self, rhs: &Value) -> ValueResult {
+        match (self, rhs) {
+            (Value::Integer(a), Value::Integer(b)) =>
+                a.checked_sub(*b).map(Value::Integer).ok_or(RuntimeError::IntegerOverflow),
+            _ => self.decimal_op(rhs, "-", |a, b| a - b),
+        }
+    }
+
+    pub fn multiply(&self, rhs: &Value) -> ValueResult {
+        match (self, rhs) {
+            (Value::Integer(a), Value::Integer(b)) =>
+                a.checked_mul(*b).map(Value::Integer).ok_or(RuntimeError::IntegerOverflow),
+            _ => self.decimal_op(rhs, "*", |a, b| a * b),
+        }
+    }
+
+    pub fn divide(&self, rhs: &Value) -> ValueResult {
+        match (self, rhs) {
+            (Value::Integer(_), Value::Integer(0)) => Err(RuntimeError::DivisionByZero),
+            (Value::Integer(a), Value::Integer(b)) =>
+                a.checked_div(*b).map(Value::Integer).ok_or(RuntimeError::IntegerOverflow),
+            _ => self.decimal_op(rhs, "/", |a, b| a / b),
+        }
+    }
+
+    pub fn modulo(&self, rhs: &Value) -> ValueResult {
+        match (self, rhs) {
+            (Value::Integer(_), Value::Integer(0)) => Err(RuntimeError::DivisionByZero),
+            (Value::Integer(a), Value::Integer(b)) =>
+                a.checked_rem(*b).map(Value::Integer).ok_or(RuntimeError::IntegerOverflow),
+            _ => self.decimal_op(rhs, "%", |a, b| a % b),
+        }
+    }
+
+    pub fn power(&self, rhs: &Value) -> ValueResult {
+        match (self, rhs) {
+            (Value::Integer(a), Value::Integer(b)) if *b >= 0 => {
+                let exp = u32::try_from(*b).map_err(|_| RuntimeError::IntegerOverflow)?;
+                a.checked_pow(exp).map(Value::Integer).ok_or(RuntimeError::IntegerOverflow)
+            },
+            _ => self.decimal_op(rhs, "**", f64::powf),
+        }
+    }
+
+    pub fn negate(&self) -> ValueResult {
+        match self {
+            Value::Integer(i) => i.checked_neg().map(Value::Integer).ok_or(RuntimeError::IntegerOverflow),
+            Value::Decimal(d) => Ok(Value::Decimal(-d)),
+            v => Err(RuntimeError::TypeError(format!("cannot negate {}", v.type_name()))),
+        }
+    }
+
+    pub fn not(&self) -> Value {
+        Value::Boolean(!self.truthy())
+    }
+
+    pub fn bit_not(&self) -> ValueResult {
+        match self {
+            Value::Integer(i) => Ok(Value::Integer(!i)),
+            v => Err(RuntimeError::TypeError(format!("cannot apply '~' to {}", v.type_name()))),
+        }
+    }
+
+    pub fn bit_and(&self, rhs: &Value) -> ValueResult {
+        self.integer_op(rhs, "&", |a, b| Some(a & b))
+    }
+
+    pub fn bit_or(&self, rhs: &Value) -> ValueResult {
+        self.integer_op(rhs, "|", |a, b| Some(a | b))
+    }
+
+    pub fn bit_xor(&self, rhs: &Value) -> ValueResult {
+        self.integer_op(rhs, "^", |a, b| Some(a ^ b))
+    }
+
+    pub fn shift_left(&self, rhs: &Value) -> ValueResult {
+        self.integer_op(rhs, "<<", |a, b| u32::try_from(b).ok().and_then(|b| a.checked_shl(b)))
+    }
+
+    pub fn shift_right(&self, rhs: &Value) -> ValueResult {
+        self.integer_op(rhs, ">>", |a, b| u32::try_from(b).ok().and_then(|b| a.checked_shr(b)))
+    }
+
+    pub fn less(&self, rhs: &Value) -> ValueResult {
+        self.compare(rhs, "<").map(|o| Value::Boolean(o.is_some_and(|o| o.is_lt())))
+    }
+
+    pub fn less_equal(&self, rhs: &Value) -> ValueResult {
+        self.compare(rhs, "<=").map(|o| Value::Boolean(o.is_some_and(|o| o.is_le())))
+    }
+
+    pub fn greater(&self, rhs: &Value) -> ValueResult {
+        self.compare(rhs, ">").map(|o| Value::Boolean(o.is_some_and(|o| o.is_gt())))
+    }
+
+    pub fn greater_equal(&self, rhs: &Value) -> ValueResult {
+        self.compare(rhs, ">=").map(|o| Value::Boolean(o.is_some_and(|o| o.is_ge())))
+    }
+
+    /// Indexes into an array, tuple or string, or looks up a key in a map
+    pub fn index(&self, idx: &Value) -> ValueResult {
+        if let Value::Map(map) = self {
+            idx.hashable()?;
+            return map.borrow().get(idx).cloned().ok_or_else(|| RuntimeError::KeyNotFound(Quoted(idx).to_string()))
+        }
+
+        let i = match idx {
+            Value::Integer(i) => *i,
+            v => return Err(RuntimeError::TypeError(format!("cannot index with {}", v.type_name()))),
+        };
+
+        match self {
+            Value::Array(arr) => {
+                let arr = arr.borrow();
+                bounded(i, arr.len()).map(|i| arr[i].clone())
+            },
+            Value::Tuple(items) => bounded(i, items.len()).map(|i| items[i].clone()),
+            Value::String(s) => {
+                let len = s.chars().count();
+                bounded(i, len).map(|i| Value::Character(s.chars().nth(i).unwrap()))
+            },
+            v => Err(RuntimeError::TypeError(format!("cannot index into {}", v.type_name()))),
+        }
+    }
+
+    /// Replaces an element of an array, or sets the value of a key in a map. \
+    /// Strings, tuples and sets can't be changed in place
+    pub fn set_index(&self, idx: &Value, value: Value) -> std::result::Result<(), RuntimeError> {
+        if let Value::Map(map) = self {
+            idx.hashable()?;
+            map.borrow_mut().insert(idx.clone(), value);
+            return Ok(())
+        }
+
+        let i = match idx {
+            Value::Integer(i) => *i,
+            v => return Err(RuntimeError::TypeError(format!("cannot index with {}", v.type_name()))),
+        };
+
+        match self {
+            Value::Array(arr) => {
+                let mut arr = arr.borrow_mut();
+                let i = bounded(i, arr.len())?;
+                arr[i] = value;
+                Ok(())
+            },
+            v => Err(RuntimeError::TypeError(format!("cannot assign into {}", v.type_name()))),
+        }
+    }
+
+    /// The range `self..end`, or `self..=end` when `inclusive`
+    pub fn range(&self, end: &Value, inclusive: bool) -> ValueResult {
+        match (self, end) {
+            (Value::Integer(a), Value::Integer(b)) if inclusive =>
+                b.checked_add(1).map(|b| Value::Range(*a, b)).ok_or(RuntimeError::IntegerOverflow),
+            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Range(*a, *b)),
+            _ => Err(self.mismatch(end, if inclusive { "..=" } else { ".." })),
+        }
+    }
+
+    /// `item in self`: whether a map has the key or a set the element, another collection has an element
+    /// equal to it, a string contains it as a substring or character, or a range the integer
+    pub fn contains(&self, item: &Value) -> ValueResult {
+        let found = match (self, item) {
+            (Value::Map(map), key) => { key.hashable()?; map.borrow().contains(key) },
+            (Value::Set(set), element) => { element.hashable()?; set.contains(element) },
+            (Value::Array(arr), element) => arr.borrow().contains(element),
+            (Value::Tuple(items), element) => items.contains(element),
+            (Value::String(s), Value::String(sub)) => s.contains(&**sub),
+            (Value::String(s), Value::Character(c)) => s.contains(*c),
+            (Value::Range(start, end), Value::Integer(i)) => start <= i && i < end,
+            (Value::Range(..), Value::Decimal(_)) => false,
+            _ => return Err(item.mismatch(self, "in")),
+        };
+
+        Ok(Value::Boolean(found))
+    }
+
+    /// The elements of a tuple, or of an array when `!tuple`, which has to have exactly `len` of them. \
+    /// Destructuring matches values against patterns with this
+    pub fn unpack(&self, tuple: bool, len: usize) -> std::result::Result<Vec<Value>, RuntimeError> {
+        let elements = match (self, tuple) {
+            (Value::Tuple(items), true) => items.to_vec(),
+            (Value::Array(arr), false) => arr.borrow().clone(),
+            (v, _) => return Err(RuntimeError::PatternMismatch(shape(tuple, len), v.type_name().to_string())),
+        };
+
+        match elements.len() == len {
+            true => Ok(elements),
+            false => {
+                let got = format!("{} of {} elements", self.type_name(), elements.len());
+                Err(RuntimeError::PatternMismatch(shape(tuple, len), got))
+            },
+        }
+    }
+
+    /// The iteration protocol: the element at `cursor` and the cursor of the one after it, `None` once done. \
+    /// Iteration starts at cursor 0. Strings count bytes and yield characters, maps yield `(key, value)` tuples
+    /// and everything else counts elements. \
+    /// Arrays and maps are read live, loops walk a `snapshot` of them instead
+    pub fn next(&self, cursor: i64) -> std::result::Result<Option<(Value, i64)>, RuntimeError> {
+        let element = match self {
+            Value::Array(arr) => usize::try_from(cursor).ok()
+                .and_then(|i| arr.borrow().get(i).cloned())
+                .map(|v| (v, cursor + 1)),
+            Value::Tuple(items) => usize::try_from(cursor).ok()
+                .and_then(|i| items.get(i).cloned())
+                .map(|v| (v, cursor + 1)),
+            Value::Map(map) => usize::try_from(cursor).ok()
+                .and_then(|i| map.borrow().entry(i).map(|(k, v)| Value::Tuple(Rc::new([k.clone(), v.clone()]))))
+                .map(|v| (v, cursor + 1)),
+            Value::Set(set) => usize::try_from(cursor).ok()
+                .and_then(|i| set.entry(i).map(|(k, _)| k.clone()))
+                .map(|v| (v, cursor + 1)),
+            Value::Range(start, end) => start.checked_add(cursor)
+                .filter(|i| i < end)
+                .map(|i| (Value::Integer(i), cursor + 1)),
+            Value::String(s) => usize::try_from(cursor).ok()
+                .and_then(|i| s.get(i..)?.chars().next())
+                .map(|c| (Value::Character(c), cursor + c.len_utf8() as i64)),
+            v => return Err(RuntimeError::TypeError(format!("cannot iterate over {}", v.type_name()))),
+        };
+
+        Ok(element)
+    }
+
+    /// What a for-each loop walks: arrays and maps become tuples of their elements and entries as they are
+    /// when the loop starts, so changing them in the loop neither adds iterations nor skips any
+    pub fn snapshot(&self) -> Value {
+        match self {
+            Value::Array(arr) => Value::Tuple(arr.borrow().as_slice().into()),
+            Value::Map(map) => Value::Tuple(map.borrow().iter()
+                .map(|(k, v)| Value::Tuple(Rc::new([k.clone(), v.clone()])))
+                .collect()),
+            v => v.clone(),
+        }
+    }
+
+    /// Orders two values, `None` if either is NaN
+    fn compare(&self, rhs: &Value, op: &str) -> std::result::Result<Option<Ordering>, RuntimeError> {
+        use Value::*;
+        match (self, rhs) {
+            (Integer(a), Integer(b)) => Ok(Some(a.cmp(b))),
+            (String(a), String(b)) => Ok(Some(a.cmp(b))),
+            (Character(a), Character(b)) => Ok(Some(a.cmp(b))),
+            (a, b) => match (a.as_decimal(), b.as_decimal()) {
+                (Some(a), Some(b)) => Ok(a.partial_cmp(&b)),
+                _ => Err(self.mismatch(rhs, op)),
+            }
+        }
+    }
+
+    fn as_decimal(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Decimal(d) => Some(*d),
+            _ => None,
+        }
+    }
+
+    fn decimal_op(&self, rhs: &Value, op: &str, f: impl Fn(f64, f64) -> f64) -> ValueResult {
+        match (self.as_decimal(), rhs.as_decimal()) {
+            (Some(a), Some(b)) => Ok(Value::Decimal(f(a, b))),
+            _ => Err(self.mismatch(rhs, op)),
+        }
+    }
+
+    fn integer_op(&self, rhs: &Value, op: &str, f: impl Fn(i64, i64) -> Option<i64>) -> ValueResult {
+        match (self, rhs) {
+            (Value::Integer(a), Value::Integer(b)) => f(*a, *b).map(Value::Integer).ok_or(RuntimeError::IntegerOverflow),
+            _ => Err(self.mismatch(rhs, op)),
+        }
+    }
+
+    fn mismatch(&self, rhs: &Value, op: &str) -> RuntimeError {
+        RuntimeError::TypeError(format!("cannot apply '{}' to {} and {}", op, self.type_name(), rhs.type_name()))
+    }
+}
+
+/// How `unpack` describes what it expected, like `a tuple of 2 elements`
+fn shape(tuple: bool, len: usize) -> String {
+    match tuple {
+        true => format!("a tuple of {} elements", len),
+        false => format!("an array of {} elements", len),
+    }
+}
+
+fn bounded(i: i64, len: usize) -> std::result::Result<usize, RuntimeError> {
+    match usize::try_from(i) {
+        Ok(u) if u < len => Ok(u),
+        _ => Err(RuntimeError::IndexOutOfBounds(i, len)),
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        equal(self, other, &mut vec![])
+    }
+}
+
+/// `==` for values that may hold themselves. A pair of collections already being compared further up
+/// counts as equal, so two cycles are compared until they repeat
+fn equal(a: &Value, b: &Value, comparing: &mut Vec<(usize, usize)>) -> bool {
+    use Value::*;
+    match (a, b) {
+        (Null, Null) => true,
+        (Integer(a), Integer(b)) => a == b,
+        (Boolean(a), Boolean(b)) => a == b,
+        (Character(a), Character(b)) => a == b,
+        (String(a), String(b)) => a == b,
+        (Array(x), Array(y)) => Rc::ptr_eq(x, y) || nested(Rc::as_ptr(x) as usize, Rc::as_ptr(y) as usize, comparing, |comparing| {
+            let (x, y) = (x.borrow(), y.borrow());
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|(v, w)| equal(v, w, comparing))
+        }),
+        (Tuple(x), Tuple(y)) => x.len() == y.len() && x.iter().zip(y.iter()).all(|(v, w)| equal(v, w, comparing)),
+        (Map(x), Map(y)) => Rc::ptr_eq(x, y) || nested(Rc::as_ptr(x) as usize, Rc::as_ptr(y) as usize, comparing, |comparing| {
+            let (x, y) = (x.borrow(), y.borrow());
+            x.len() == y.len() && x.iter().all(|(k, v)| y.get(k).is_some_and(|w| equal(v, w, comparing)))
+        }),
+        (Set(a), Set(b)) => a == b,
+        (Range(a, b), Range(c, d)) => a == c && b == d,
+        (Function(a), Function(b)) => Rc::ptr_eq(a, b),
+        (Native(a), Native(b)) => Rc::ptr_eq(a, b),
+        (a, b) => match (a.as_decimal(), b.as_decimal()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+fn nested(x: usize, y: usize, comparing: &mut Vec<(usize, usize)>, f: impl FnOnce(&mut Vec<(usize, usize)>) -> bool) -> bool {
+    if comparing.contains(&(x, y)) {
+        return true
+    }
+
+    comparing.push((x, y));
+    let equal = f(comparing);
+    comparing.pop();
+    equal
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write_value(f, self, false, &mut vec![])
+    }
+}
+
+/// Writes `value`, with strings and characters quoted if it's inside a collection. \
+/// `printing` holds the arrays and maps being written further up, which print as `[...]` and `{...}`
+/// when they hold themselves
+fn write_value(f: &mut Formatter<'_>, value: &Value, quoted: bool, printing: &mut Vec<usize>) -> Result {
+    match value {
+        Value::Null => write!(f, "null"),
+        Value::Integer(i) => write!(f, "{}", i),
+        Value::Decimal(d) => write!(f, "{:?}", d),
+        Value::Boolean(b) => write!(f, "{}", b),
+        Value::Character(c) if quoted => write!(f, "{:?}", c),
+        Value::Character(c) => write!(f, "{}", c),
+        Value::String(s) if quoted => write!(f, "{:?}", s),
+        Value::String(s) => write!(f, "{}", s),
+        Value::Array(arr) if printing.contains(&(Rc::as_ptr(arr) as usize)) => write!(f, "[...]"),
+        Value::Array(arr) => {
+            printing.push(Rc::as_ptr(arr) as usize);
+            write!(f, "[")?;
+            elements(f, &arr.borrow(), printing)?;
+            printing.pop();
+            write!(f, "]")
+        },
+        // A trailing comma tells a one element tuple apart from a parenthesized value
+        Value::Tuple(items) if items.len() == 1 => {
+            write!(f, "(")?;
+            elements(f, items, printing)?;
+            write!(f, ",)")
+        },
+        Value::Tuple(items) => {
+            write!(f, "(")?;
+            elements(f, items, printing)?;
+            write!(f, ")")
+        },
+        Value::Map(map) if printing.contains(&(Rc::as_ptr(map) as usize)) => write!(f, "{{...}}"),
+        Value::Map(map) => {
+            printing.push(Rc::as_ptr(map) as usize);
+            write!(f, "{{")?;
+            for (i, (key, value)) in map.borrow().iter().enumerate() {
+                if i > 0 { write!(f, ", ")?; }
+                write_value(f, key, true, printing)?;
+                write!(f, ": ")?;
+                write_value(f, value, true, printing)?;
+            }
+            printing.pop();
+            write!(f, "}}")
+        },
+        // Like the literal, `{,}` is the empty set and `{}` the empty map
+        Value::Set(set) if set.is_empty() => write!(f, "{{,}}"),
+        Value::Set(set) => {
+            write!(f, "{{")?;
+            for (i, element) in set.keys().enumerate() {
+                if i > 0 { write!(f, ", ")?; }
+                write_value(f, element, true, printing)?;
+            }
+            write!(f, "}}")
+        },
+        Value::Range(start, end) => write!(f, "{}..{}", start, end),
+        Value::Function(func) => write!(f, "<func {}>", func.name),
+        Value::Native(func) => write!(f, "<native func {}>", func.name),
+    }
+}
+
+/// Comma separated, with strings and characters quoted
+fn elements(f: &mut Formatter<'_>, values: &[Value], printing: &mut Vec<usize>) -> Result {
+    for (i, v) in values.iter().enumerate() {
+        if i > 0 { write!(f, ", ")?; }
+        write_value(f, v, true, printing)?;
+    }
+
+    Ok(())
+}
+
+/// A value inside a collection, where strings and characters are quoted
+pub(crate) struct Quoted<'v>(pub(crate) &'v Value);
+
+impl Display for Quoted<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write_value(f, self.0, true, &mut vec![])
+    }
+}
\ No newline at end of file
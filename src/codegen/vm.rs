@@ -0,0 +1,576 @@
+use super::bytecode::{ Function, OpCode, Program };
+use super::error::RuntimeError;
+use super::gc::{ GcMode, GcStats, Heap };
+use super::value::{ Native, Quoted, Value };
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+type VMResult<T> = Result<T, RuntimeError>;
+
+const MAX_FRAMES: usize = 1024;
+
+struct CallFrame {
+    function: Rc<Function>,
+    ip: usize,
+    /// Stack index of the frame's first local, the callee sits just below it
+    base: usize,
+}
+
+/// An active `try`, where to resume when something is thrown inside it
+struct Handler {
+    /// Frame count when the `try` was entered, the frame that catches is the last of them
+    frames: usize,
+    stack: usize,
+    stash: usize,
+    target: usize,
+}
+
+/// A frame of an error's trace, innermost first
+#[derive(Debug, Clone)]
+pub struct TraceEntry {
+    pub function: String,
+    pub line: Option<i32>,
+}
+
+/// A stack based virtual machine that executes a compiled `Program`
+pub struct VM {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: Vec<Option<Value>>,
+    /// Names of `globals`, from the program being run
+    names: Vec<String>,
+    /// Host functions, bound to globals of the same name when a program starts
+    natives: HashMap<String, Value>,
+    heap: Heap,
+    handlers: Vec<Handler>,
+    /// Operands set aside by `Stash` while a comprehension's loops run
+    stash: Vec<Vec<Value>>,
+    /// The frames the last error was thrown from
+    trace: Vec<TraceEntry>,
+    /// Set while an error keeps the trace it already has, when rethrown or passed up through a native
+    unwinding: bool,
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VM {
+    pub fn new() -> VM {
+        VM {
+            stack: Vec::with_capacity(256),
+            frames: Vec::with_capacity(64),
+            globals: vec![],
+            names: vec![],
+            natives: HashMap::new(),
+            heap: Heap::default(),
+            handlers: vec![],
+            stash: vec![],
+            trace: vec![],
+            unwinding: false,
+        }
+    }
+
+    pub fn with_gc(mut self, mode: GcMode) -> Self {
+        self.heap.set_mode(mode);
+        self
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
+
+    /// Runs the garbage collector now, whatever its mode
+    pub fn collect(&mut self) {
+        let globals = self.globals.iter().flatten();
+        self.heap.collect(self.stack.iter().chain(self.stash.iter().flatten()).chain(globals));
+    }
+
+    /// Makes a host function available to programs run afterwards. \
+    /// Programs can still declare a global of the same name, which replaces it
+    pub fn register(&mut self, native: Native) {
+        self.natives.insert(native.name.clone(), Value::Native(Rc::new(native)));
+    }
+
+    /// Runs a program to completion and returns the value returned by its `main`
+    pub fn run(&mut self, program: &Program) -> VMResult<Value> {
+        self.stack.clear();
+        self.frames.clear();
+        self.handlers.clear();
+        self.stash.clear();
+        self.trace.clear();
+        self.unwinding = false;
+        self.globals = program.globals.iter().map(|name| self.natives.get(name).cloned()).collect();
+        self.names = program.globals.clone();
+
+        self.stack.push(Value::Function(program.script.clone()));
+        self.frames.push(CallFrame {
+            function: program.script.clone(),
+            ip: 0,
+            base: 1,
+        });
+
+        self.execute(0)
+    }
+
+    /// The value of a global after `run`, `None` if the program has no such global or never defined it
+    pub fn global(&self, name: &str) -> Option<&Value> {
+        let idx = self.names.iter().position(|g| g == name)?;
+        self.globals.get(idx)?.as_ref()
+    }
+
+    /// Where the last uncaught error was thrown from, innermost frame first
+    pub fn trace(&self) -> &[TraceEntry] {
+        &self.trace
+    }
+
+    /// Calls a function value with `args` and runs it to completion. \
+    /// Works both after `run`, with the globals it left behind, and from native functions during a run
+    pub fn call_value(&mut self, function: Value, args: Vec<Value>) -> VMResult<Value> {
+        let (depth, height, handlers, stash) = (self.frames.len(), self.stack.len(), self.handlers.len(), self.stash.len());
+        let argc = args.len();
+
+        self.unwinding = false;
+
+        for arg in &args {
+            self.heap.track(arg);
+        }
+
+        self.stack.push(function);
+        self.stack.extend(args);
+
+        // A native call has already left its result
+        let result = self.call(argc).and_then(|()| match self.frames.len() == depth {
+            true => Ok(self.pop()),
+            false => self.execute(depth),
+        });
+
+        // An error leaves the frames it unwound through behind
+        if result.is_err() {
+            self.frames.truncate(depth);
+            self.stack.truncate(height);
+            self.handlers.truncate(handlers);
+            self.stash.truncate(stash);
+        }
+
+        result
+    }
+
+    /// Executes until the frame count drops back to `depth`, returning the value of that last return. \
+    /// Errors unwind to the innermost handler entered since, if there is one
+    fn execute(&mut self, depth: usize) -> VMResult<Value> {
+        loop {
+            match self.dispatch(depth) {
+                Ok(value) => return Ok(value),
+                Err(error) => self.unwind(error, depth)?,
+            }
+        }
+    }
+
+    /// Resumes at the innermost handler with the error on the stack, or gives the error back if no
+    /// handler was entered at or above `depth`
+    fn unwind(&mut self, error: RuntimeError, depth: usize) -> VMResult<()> {
+        if !std::mem::take(&mut self.unwinding) {
+            self.trace = self.frames.iter().rev().map(|frame| TraceEntry {
+                function: frame.function.name.clone(),
+                line: frame.function.chunk.line_at(frame.ip.saturating_sub(1)),
+            }).collect();
+        }
+
+        let handler = match self.handlers.last() {
+            Some(handler) if handler.frames > depth => self.handlers.pop().unwrap(),
+            _ => {
+                self.unwinding = true;
+                return Err(error)
+            },
+        };
+
+        let value = match error {
+            RuntimeError::Thrown(value) => value,
+            error => Value::String(error.to_string().into()),
+        };
+
+        self.frames.truncate(handler.frames);
+        self.stack.truncate(handler.stack);
+        self.stash.truncate(handler.stash);
+        self.stack.push(value);
+        self.allocated();
+        self.frame().ip = handler.target;
+
+        Ok(())
+    }
+
+    fn dispatch(&mut self, depth: usize) -> VMResult<Value> {
+        use OpCode::*;
+
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let code = &frame.function.chunk.code;
+
+            let byte = code[frame.ip];
+            let op = OpCode::from_byte(byte).ok_or(RuntimeError::InvalidBytecode(byte))?;
+            frame.ip += 1;
+
+            match op {
+                Constant => {
+                    let idx = self.read_u16() as usize;
+                    let value = self.frame().function.chunk.constants[idx].clone();
+                    self.stack.push(value);
+                },
+                Null => self.stack.push(Value::Null),
+                True => self.stack.push(Value::Boolean(true)),
+                False => self.stack.push(Value::Boolean(false)),
+                Pop => { self.pop(); },
+                PopN => {
+                    let n = self.read_u16() as usize;
+                    self.stack.truncate(self.stack.len() - n);
+                },
+                Dup => self.stack.push(self.peek().clone()),
+                Dup2 => {
+                    let len = self.stack.len();
+                    self.stack.extend_from_within(len - 2..);
+                },
+                Bury => {
+                    let depth = self.read_u8() as usize;
+                    let value = self.pop();
+                    self.stack.insert(self.stack.len() - depth, value);
+                },
+                GetLocal => {
+                    let slot = self.frame().base + self.read_u16() as usize;
+                    self.stack.push(self.stack[slot].clone());
+                },
+                SetLocal => {
+                    let slot = self.frame().base + self.read_u16() as usize;
+                    self.stack[slot] = self.peek().clone();
+                },
+                GetGlobal => {
+                    let idx = self.read_u16() as usize;
+                    match &self.globals[idx] {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(RuntimeError::UndefinedVariable(self.names[idx].clone())),
+                    }
+                },
+                SetGlobal => {
+                    let idx = self.read_u16() as usize;
+                    if self.globals[idx].is_none() {
+                        return Err(RuntimeError::UndefinedVariable(self.names[idx].clone()))
+                    }
+                    self.globals[idx] = Some(self.peek().clone());
+                },
+                DefineGlobal => {
+                    let idx = self.read_u16() as usize;
+                    self.globals[idx] = Some(self.pop());
+                },
+                Add => {
+                    self.binary(Value::add)?;
+                    self.allocated();
+                },
+                Subtract => self.binary(Value::subtract)?,
+                Multiply => self.binary(Value::multiply)?,
+                Divide => self.binary(Value::divide)?,
+                Modulo => self.binary(Value::modulo)?,
+                Power => self.binary(Value::power)?,
+                BitAnd => self.binary(Value::bit_and)?,
+                BitOr => self.binary(Value::bit_or)?,
+                BitXor => self.binary(Value::bit_xor)?,
+                ShiftLeft => self.binary(Value::shift_left)?,
+                ShiftRight => self.binary(Value::shift_right)?,
+                Less => self.binary(Value::less)?,
+                LessEqual => self.binary(Value::less_equal)?,
+                Greater => self.binary(Value::greater)?,
+                GreaterEqual => self.binary(Value::greater_equal)?,
+                Equal => self.binary(|a, b| Ok(Value::Boolean(a == b)))?,
+                NotEqual => self.binary(|a, b| Ok(Value::Boolean(a != b)))?,
+                Negate => {
+                    let value = self.pop().negate()?;
+                    self.stack.push(value);
+                },
+                Not => {
+                    let value = self.pop().not();
+                    self.stack.push(value);
+                },
+                BitNot => {
+                    let value = self.pop().bit_not()?;
+                    self.stack.push(value);
+                },
+                Jump => {
+                    let offset = self.read_u16() as usize;
+                    self.frame().ip += offset;
+                },
+                JumpIfFalse => {
+                    let offset = self.read_u16() as usize;
+                    if !self.pop().truthy() {
+                        self.frame().ip += offset;
+                    }
+                },
+                JumpIfNull | JumpIfNotNull => {
+                    let offset = self.read_u16() as usize;
+                    let null = matches!(self.peek(), Value::Null);
+                    if null == (op == JumpIfNull) {
+                        self.frame().ip += offset;
+                    }
+                },
+                Loop => {
+                    let offset = self.read_u16() as usize;
+                    self.frame().ip -= offset;
+                },
+                Call => {
+                    let argc = self.read_u8() as usize;
+                    self.call(argc)?;
+                },
+                Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().unwrap();
+
+                    self.stack.truncate(frame.base - 1);
+
+                    if self.frames.len() == depth {
+                        return Ok(result)
+                    }
+
+                    self.stack.push(result);
+                },
+                Array => {
+                    let count = self.read_u16() as usize;
+                    let elements = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(Value::Array(Rc::new(RefCell::new(elements))));
+                    self.allocated();
+                },
+                Tuple => {
+                    let count = self.read_u16() as usize;
+                    let items = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(Value::Tuple(items.into()));
+                },
+                Map => {
+                    let count = self.read_u16() as usize;
+                    let entries = self.stack.split_off(self.stack.len() - 2 * count);
+                    entries.iter().step_by(2).try_for_each(Value::hashable)?;
+                    let mut entries = entries.into_iter();
+                    let map = std::iter::from_fn(|| Some((entries.next()?, entries.next()?))).collect();
+
+                    self.stack.push(Value::Map(Rc::new(RefCell::new(map))));
+                    self.allocated();
+                },
+                Set => {
+                    let count = self.read_u16() as usize;
+                    let elements = self.stack.split_off(self.stack.len() - count);
+                    elements.iter().try_for_each(Value::hashable)?;
+                    self.stack.push(Value::Set(Rc::new(elements.into_iter().map(|e| (e, ())).collect())));
+                },
+                SetOf => {
+                    let iterable = self.pop();
+                    let elements = elements(&iterable)?;
+                    elements.iter().try_for_each(Value::hashable)?;
+                    self.stack.push(Value::Set(Rc::new(elements.into_iter().map(|e| (e, ())).collect())));
+                },
+                In => self.binary(|item, collection| collection.contains(item))?,
+                UnpackTuple | UnpackArray => {
+                    let count = self.read_u16() as usize;
+                    let elements = self.pop().unpack(op == UnpackTuple, count)?;
+                    self.stack.extend(elements);
+                },
+                Index => self.binary(Value::index)?,
+                SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let target = self.pop();
+
+                    target.set_index(&index, value.clone())?;
+                    self.stack.push(value);
+                },
+                Range => {
+                    let inclusive = self.read_u8() != 0;
+                    self.binary(|a, b| a.range(b, inclusive))?;
+                },
+                IterNext => {
+                    let offset = self.read_u16() as usize;
+                    let len = self.stack.len();
+
+                    let cursor = match &self.stack[len - 1] {
+                        Value::Integer(c) => *c,
+                        v => return Err(RuntimeError::TypeError(format!("cannot iterate from {}", v.type_name()))),
+                    };
+
+                    match self.stack[len - 2].next(cursor)? {
+                        Some((element, cursor)) => {
+                            self.stack[len - 1] = Value::Integer(cursor);
+                            self.stack.push(element);
+                        },
+                        None => self.frame().ip += offset,
+                    }
+                },
+                Snapshot => {
+                    let iterable = self.pop();
+                    self.stack.push(iterable.snapshot());
+                },
+                Extend => {
+                    let iterable = self.pop();
+                    let elements = elements(&iterable)?;
+
+                    match self.peek() {
+                        Value::Array(arr) => arr.borrow_mut().extend(elements),
+                        v => return Err(RuntimeError::TypeError(format!("cannot extend {}", v.type_name()))),
+                    }
+                },
+                Append => {
+                    let value = self.pop();
+
+                    match self.peek() {
+                        Value::Array(arr) => arr.borrow_mut().push(value),
+                        v => return Err(RuntimeError::TypeError(format!("cannot append to {}", v.type_name()))),
+                    }
+                },
+                Try => {
+                    let offset = self.read_u16() as usize;
+                    let target = self.frame().ip + offset;
+
+                    self.handlers.push(Handler { frames: self.frames.len(), stack: self.stack.len(), stash: self.stash.len(), target });
+                },
+                EndTry => { self.handlers.pop(); },
+                Stash => {
+                    let locals = self.frame().base + self.read_u16() as usize;
+                    let operands = self.stack.split_off(locals);
+                    self.stash.push(operands);
+                },
+                Unstash => {
+                    let value = self.pop();
+                    let operands = self.stash.pop().unwrap();
+                    self.stack.extend(operands);
+                    self.stack.push(value);
+                },
+                Throw => return Err(RuntimeError::Thrown(self.pop())),
+                Rethrow => {
+                    self.unwinding = true;
+                    return Err(RuntimeError::Thrown(self.pop()))
+                },
+                Assert => {
+                    let binary = self.read_u8() != 0;
+                    let message = self.pop();
+
+                    let detail = match binary {
+                        true => {
+                            let op = self.pop();
+                            let rhs = self.pop();
+                            let lhs = self.pop();
+                            format!("{} {} {}", Quoted(&lhs), op, Quoted(&rhs))
+                        },
+                        false => format!("got {}", Quoted(&self.pop())),
+                    };
+
+                    return Err(RuntimeError::AssertionFailed(match message {
+                        Value::Null => detail,
+                        message => format!("{} ({})", message, detail),
+                    }))
+                },
+                Apply => {
+                    let args = self.pop();
+                    let args = elements(&args)?;
+                    let argc = args.len();
+
+                    self.stack.extend(args);
+                    self.call(argc)?;
+                },
+            }
+        }
+    }
+
+    fn call(&mut self, argc: usize) -> VMResult<()> {
+        let base = self.stack.len() - argc;
+
+        let function = match &self.stack[base - 1] {
+            Value::Function(f) => f.clone(),
+            Value::Native(native) => {
+                let native = native.clone();
+
+                if native.arity.is_some_and(|arity| arity != argc) {
+                    return Err(RuntimeError::ArityMismatch(native.name.clone(), native.arity.unwrap(), argc))
+                }
+
+                let args = self.stack.split_off(base);
+                self.stack.pop();
+
+                let result = native.call(self, args)?;
+                self.stack.push(result);
+                self.allocated();
+
+                return Ok(())
+            },
+            v => return Err(RuntimeError::NotCallable(v.type_name().to_string())),
+        };
+
+        if function.arity != argc {
+            return Err(RuntimeError::ArityMismatch(function.name.clone(), function.arity, argc))
+        }
+
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(RuntimeError::StackOverflow)
+        }
+
+        self.frames.push(CallFrame {
+            function,
+            ip: 0,
+            base,
+        });
+
+        Ok(())
+    }
+
+    /// Tracks whatever the last instruction left on top of the stack, collecting garbage if that was
+    /// a new object and a collection is due
+    fn allocated(&mut self) {
+        if self.heap.track(self.stack.last().unwrap()) && self.heap.due() {
+            self.collect();
+        }
+    }
+
+    fn binary(&mut self, op: impl Fn(&Value, &Value) -> VMResult<Value>) -> VMResult<()> {
+        let rhs = self.pop();
+        let lhs = self.pop();
+
+        self.stack.push(op(&lhs, &rhs)?);
+
+        Ok(())
+    }
+
+    fn frame(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn read_u8(&mut self) -> u8 {
+        let frame = self.frame();
+        let byte = frame.function.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let frame = self.frame();
+        let value = frame.function.chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        value
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("VM stack underflow")
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack.last().expect("VM stack underflow")
+    }
+}
+
+/// Every element of an iterable, in order
+fn elements(iterable: &Value) -> VMResult<Vec<Value>> {
+    let mut elements = vec![];
+    let mut cursor = 0;
+
+    while let Some((element, next)) = iterable.next(cursor)? {
+        elements.push(element);
+        cursor = next;
+    }
+
+    Ok(elements)
+}
\ No newline at end of file
@@ -0,0 +1,228 @@
+use super::super::codegen::value::Value;
+use super::super::codegen::RuntimeError;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::rc::Rc;
+
+/// Conversion from an Ult value, for native function arguments and the results of `Engine::call`
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self, RuntimeError>;
+}
+
+/// Conversion to an Ult value, for native function results and the arguments of `Engine::call`. \
+/// A `Result` converts its `Ok` value and turns an `Err` into a runtime error that stops the script
+pub trait IntoValue {
+    fn into_value(self) -> Result<Value, RuntimeError>;
+}
+
+fn expected(what: &str, value: &Value) -> RuntimeError {
+    RuntimeError::TypeError(format!("expected {}, got {}", what, value.type_name()))
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        Ok(value)
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Result<Value, RuntimeError> {
+        Ok(self)
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Integer(i) => Ok(i),
+            v => Err(expected("Integer", &v)),
+        }
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Result<Value, RuntimeError> {
+        Ok(Value::Integer(self))
+    }
+}
+
+impl FromValue for i32 {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        i32::try_from(i64::from_value(value)?).map_err(|_| RuntimeError::IntegerOverflow)
+    }
+}
+
+impl IntoValue for i32 {
+    fn into_value(self) -> Result<Value, RuntimeError> {
+        Ok(Value::Integer(self.into()))
+    }
+}
+
+impl FromValue for usize {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        usize::try_from(i64::from_value(value)?).map_err(|_| RuntimeError::IntegerOverflow)
+    }
+}
+
+impl IntoValue for usize {
+    fn into_value(self) -> Result<Value, RuntimeError> {
+        i64::try_from(self).map(Value::Integer).map_err(|_| RuntimeError::IntegerOverflow)
+    }
+}
+
+/// Integers are widened, like they are by arithmetic on mixed operands
+impl FromValue for f64 {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Decimal(d) => Ok(d),
+            Value::Integer(i) => Ok(i as f64),
+            v => Err(expected("Decimal", &v)),
+        }
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Result<Value, RuntimeError> {
+        Ok(Value::Decimal(self))
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Boolean(b) => Ok(b),
+            v => Err(expected("Boolean", &v)),
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Result<Value, RuntimeError> {
+        Ok(Value::Boolean(self))
+    }
+}
+
+impl FromValue for char {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Character(c) => Ok(c),
+            v => Err(expected("Character", &v)),
+        }
+    }
+}
+
+impl IntoValue for char {
+    fn into_value(self) -> Result<Value, RuntimeError> {
+        Ok(Value::Character(self))
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::String(s) => Ok(s.to_string()),
+            v => Err(expected("String", &v)),
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Result<Value, RuntimeError> {
+        Ok(Value::String(self.into()))
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Result<Value, RuntimeError> {
+        Ok(Value::String(self.into()))
+    }
+}
+
+/// `()` is `null`, so native functions can return nothing
+impl FromValue for () {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Null => Ok(()),
+            v => Err(expected("Null", &v)),
+        }
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Result<Value, RuntimeError> {
+        Ok(Value::Null)
+    }
+}
+
+/// `None` is `null`
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Null => Ok(None),
+            v => T::from_value(v).map(Some),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Result<Value, RuntimeError> {
+        self.map_or(Ok(Value::Null), T::into_value)
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Array(elements) => elements.borrow().iter().cloned().map(T::from_value).collect(),
+            v => Err(expected("Array", &v)),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Result<Value, RuntimeError> {
+        let elements = self.into_iter().map(T::into_value).collect::<Result<_, _>>()?;
+        Ok(Value::Array(Rc::new(RefCell::new(elements))))
+    }
+}
+
+/// The error's message becomes the message of the runtime error, which names the native function
+impl<T: IntoValue, E: Display> IntoValue for Result<T, E> {
+    fn into_value(self) -> Result<Value, RuntimeError> {
+        match self {
+            Ok(value) => value.into_value(),
+            Err(e) => Err(RuntimeError::Native(String::new(), e.to_string())),
+        }
+    }
+}
+
+/// Argument lists for `Engine::call`: tuples of up to six `IntoValue`s, or a `Vec<Value>`
+pub trait IntoArgs {
+    fn into_args(self) -> Result<Vec<Value>, RuntimeError>;
+}
+
+impl IntoArgs for Vec<Value> {
+    fn into_args(self) -> Result<Vec<Value>, RuntimeError> {
+        Ok(self)
+    }
+}
+
+macro_rules! tuple_args {
+    ($($arg:ident),*) => {
+        impl<$($arg: IntoValue),*> IntoArgs for ($($arg,)*) {
+            #[allow(non_snake_case)]
+            fn into_args(self) -> Result<Vec<Value>, RuntimeError> {
+                let ($($arg,)*) = self;
+                Ok(vec![$($arg.into_value()?),*])
+            }
+        }
+    };
+}
+
+tuple_args!();
+tuple_args!(A);
+tuple_args!(A, B);
+tuple_args!(A, B, C);
+tuple_args!(A, B, C, D);
+tuple_args!(A, B, C, D, E);
+tuple_args!(A, B, C, D, E, F);
@@ -0,0 +1,97 @@
+pub mod convert;
+pub mod native;
+
+pub use convert::{ FromValue, IntoArgs, IntoValue };
+pub use native::NativeFn;
+
+use super::codegen::bytecode::Program;
+use super::codegen::compiler::Compiler;
+use super::codegen::vm::VM;
+use super::error::Error;
+use super::parse::ast::Declaration;
+use super::stdlib;
+
+/// Runs Ult as a scripting language inside a Rust program. \
+/// `load` runs a script's top level, after which its functions can be called from Rust. Host closures
+/// registered with `register` can be called from the script like any other function
+///
+/// ```
+/// let mut engine = ult::Engine::new();
+/// engine.register("scale", |x: i64| x * 10);
+/// engine.load("let offset := 2\nfunc shifted(x) { return scale(x) + offset }")?;
+///
+/// let result: i64 = engine.call("shifted", (4,))?;
+/// assert_eq!(result, 42);
+/// # Ok::<(), ult::Error>(())
+/// ```
+pub struct Engine {
+    vm: VM,
+    program: Option<Program>,
+    /// Top level functions of the loaded script
+    functions: Vec<String>,
+}
+
+impl Engine {
+    /// An engine with the standard library installed
+    pub fn new() -> Self {
+        let mut vm = VM::new();
+        stdlib::install(&mut vm);
+
+        Self { vm, program: None, functions: vec![] }
+    }
+
+    /// Registers `function` under `name` for scripts loaded afterwards.
+    /// A script declaring a global of the same name replaces it
+    pub fn register<Args>(&mut self, name: &str, function: impl NativeFn<Args>) -> &mut Self {
+        self.vm.register(function.into_native(name));
+        self
+    }
+
+    /// Compiles `source` and runs its top level, replacing any previously loaded script. \
+    /// Unlike `ult <file>` this doesn't call `main`
+    pub fn load(&mut self, source: &str) -> Result<(), Error> {
+        let ast = super::parse(&super::lex(source)?)?;
+
+        let mut compiler = Compiler::new(&ast).without_main();
+        compiler.compile()?;
+
+        let program = compiler.program().unwrap().clone();
+
+        self.functions = ast.program().iter()
+            .filter_map(|decl| match decl {
+                Declaration::Function { identifier, .. } => Some(identifier.name()),
+                Declaration::Variable { .. } | Declaration::Destructure { .. } => None,
+            })
+            .collect();
+        self.program = None;
+
+        self.vm.run(&program)?;
+        self.program = Some(program);
+
+        Ok(())
+    }
+
+    /// Whether the loaded script declares a top level function called `name`
+    pub fn has_function(&self, name: &str) -> bool {
+        self.functions.iter().any(|f| f == name)
+    }
+
+    /// Calls a top level function of the loaded script, converting the arguments and result
+    pub fn call<R: FromValue>(&mut self, name: &str, args: impl IntoArgs) -> Result<R, Error> {
+        let function = match &self.program {
+            Some(_) if self.has_function(name) => self.vm.global(name).cloned(),
+            _ => None,
+        };
+
+        let function = function.ok_or_else(|| Error::UndefinedFunction(name.to_string()))?;
+        let result = self.vm.call_value(function, args.into_args()?)?;
+
+        Ok(R::from_value(result)?)
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,59 @@
+use super::super::codegen::value::{ Native, Value };
+use super::super::codegen::vm::VM;
+use super::super::codegen::RuntimeError;
+use super::convert::{ FromValue, IntoValue };
+
+/// Rust closures usable as native Ult functions: `Fn`s of up to six `FromValue` arguments
+/// returning an `IntoValue`. `Args` only tells the implementations apart
+pub trait NativeFn<Args>: 'static {
+    fn into_native(self, name: &str) -> Native;
+}
+
+/// Gives errors raised while running a native function its name and argument position
+fn argument_error(name: &str, position: usize, e: RuntimeError) -> RuntimeError {
+    match e {
+        RuntimeError::TypeError(msg) => RuntimeError::TypeError(format!("argument {} of '{}': {}", position, name, msg)),
+        e => e,
+    }
+}
+
+fn result_error(name: &str, e: RuntimeError) -> RuntimeError {
+    match e {
+        RuntimeError::Native(_, msg) => RuntimeError::Native(name.to_string(), msg),
+        e => e,
+    }
+}
+
+macro_rules! native_fn {
+    ($arity:expr $(, $arg:ident)*) => {
+        impl<Func, Ret, $($arg),*> NativeFn<($($arg,)*)> for Func
+        where
+            Func: Fn($($arg),*) -> Ret + 'static,
+            Ret: IntoValue,
+            $($arg: FromValue,)*
+        {
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn into_native(self, name: &str) -> Native {
+                let owned = name.to_string();
+
+                Native::new(owned.clone(), Some($arity), move |_: &mut VM, args: Vec<Value>| {
+                    let mut args = args.into_iter().enumerate();
+                    $(
+                        let (i, value) = args.next().unwrap();
+                        let $arg = $arg::from_value(value).map_err(|e| argument_error(&owned, i + 1, e))?;
+                    )*
+
+                    self($($arg),*).into_value().map_err(|e| result_error(&owned, e))
+                })
+            }
+        }
+    };
+}
+
+native_fn!(0);
+native_fn!(1, A);
+native_fn!(2, A, B);
+native_fn!(3, A, B, C);
+native_fn!(4, A, B, C, D);
+native_fn!(5, A, B, C, D, E);
+native_fn!(6, A, B, C, D, E, F);
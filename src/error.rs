@@ -0,0 +1,64 @@
+use super::codegen::{ CompileError, RuntimeError };
+use super::lex::TokenError;
+use super::parse::ParseError;
+use std::fmt::{ Display, Result, Formatter };
+
+/// Any error from the stages behind the crate root functions
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    Lex(TokenError),
+    Parse(ParseError),
+    Compile(CompileError),
+    Runtime(RuntimeError),
+    /// `Engine::call` of a name that isn't a top level function of the loaded script
+    UndefinedFunction(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Error::Lex(e) => write!(f, "{}", e),
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::Compile(e) => write!(f, "{}", e),
+            Error::Runtime(e) => write!(f, "{}", e),
+            Error::UndefinedFunction(name) => write!(f, "No function '{}' in the loaded script", name),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Lex(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Compile(e) => Some(e),
+            Error::Runtime(e) => Some(e),
+            Error::UndefinedFunction(_) => None,
+        }
+    }
+}
+
+impl From<TokenError> for Error {
+    fn from(e: TokenError) -> Self {
+        Error::Lex(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<CompileError> for Error {
+    fn from(e: CompileError) -> Self {
+        Error::Compile(e)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(e: RuntimeError) -> Self {
+        Error::Runtime(e)
+    }
+}
@@ -0,0 +1,72 @@
+use super::{ BlockId, Value };
+use std::error::Error;
+use std::fmt::{ Display, Result, Formatter };
+
+#[derive(Debug)]
+pub enum LowerError {
+    UndefinedVariable(String),
+    ArityMismatch(String, usize, usize),
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
+    InvalidAssignmentTarget,
+    UnsupportedCapture(String),
+    UnsupportedOperator(String),
+    UnsupportedStatement(String),
+}
+
+impl Display for LowerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        use LowerError::*;
+        match self {
+            UndefinedVariable(name) => write!(f, "Undefined variable '{}'", name),
+            ArityMismatch(name, e, g) => write!(f, "Function '{}' expected {} arguments but got {}", name, e, g),
+            BreakOutsideLoop => write!(f, "'break' used outside of a loop"),
+            ContinueOutsideLoop => write!(f, "'continue' used outside of a loop"),
+            InvalidAssignmentTarget => write!(f, "Invalid assignment target"),
+            UnsupportedCapture(name) => write!(f, "Cannot capture local variable '{}' from an enclosing function", name),
+            UnsupportedOperator(op) => write!(f, "Unsupported operator {}", op),
+            UnsupportedStatement(what) => write!(f, "Unsupported statement: {}", what),
+        }
+    }
+}
+
+impl Error for LowerError {}
+
+/// A broken invariant, reported with the function it was found in
+#[derive(Debug)]
+pub enum VerifyError {
+    UndefinedValue(String, Value),
+    UnplacedValue(String, Value),
+    MultipleDefinitions(String, Value),
+    InvalidBlock(String, BlockId),
+    MisplacedPhi(String, Value),
+    PhiPredecessors(String, Value),
+    MisplacedParam(String, Value),
+    EntryHasPredecessors(String),
+    NotDominated(String, Value),
+    UndefinedFunction(String, String),
+    ArityMismatch(String, String),
+    UndefinedGlobal(String, String),
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        use VerifyError::*;
+        match self {
+            UndefinedValue(func, v) => write!(f, "In @{}: use of undefined value {}", func, v),
+            UnplacedValue(func, v) => write!(f, "In @{}: value {} is not placed in any block", func, v),
+            MultipleDefinitions(func, v) => write!(f, "In @{}: value {} is defined more than once", func, v),
+            InvalidBlock(func, b) => write!(f, "In @{}: branch to missing block {}", func, b),
+            MisplacedPhi(func, v) => write!(f, "In @{}: phi {} is not at the start of its block", func, v),
+            PhiPredecessors(func, v) => write!(f, "In @{}: phi {} does not match the block's predecessors", func, v),
+            MisplacedParam(func, v) => write!(f, "In @{}: parameter {} is outside the entry block or out of range", func, v),
+            EntryHasPredecessors(func) => write!(f, "In @{}: the entry block has predecessors", func),
+            NotDominated(func, v) => write!(f, "In @{}: value {} does not dominate all of its uses", func, v),
+            UndefinedFunction(func, name) => write!(f, "In @{}: call to undefined function @{}", func, name),
+            ArityMismatch(func, name) => write!(f, "In @{}: wrong number of arguments to @{}", func, name),
+            UndefinedGlobal(func, name) => write!(f, "In @{}: use of undeclared global @{}", func, name),
+        }
+    }
+}
+
+impl Error for VerifyError {}
\ No newline at end of file
@@ -0,0 +1,935 @@
+use super::super::lex::token::Token;
+use super::super::parse::ast::{ self, AST, Declaration, Expression, Identifier, Literal, Pattern, Statement };
+use super::super::stdlib::{ self, Builtin };
+use super::*;
+use std::collections::{ HashMap, HashSet };
+
+type LowerResult<T> = std::result::Result<T, LowerError>;
+
+/// Lowers an `AST` to an SSA `Module`. \
+/// SSA form is built directly while walking the tree (Braun et al., "Simple and Efficient
+/// Construction of Static Single Assignment Form"): every block remembers the current value of
+/// each local, reads in blocks whose predecessors are still unknown leave an operandless phi behind
+/// that is filled in once the block is sealed, and redundant phis are removed at the end. \
+/// Globals start out as `null` and are set by `__init`, nested functions are hoisted as `outer.inner`
+pub fn lower(ast: &AST) -> LowerResult<Module> {
+    let mut lowerer = Lowerer::new();
+
+    lowerer.program(ast)?;
+
+    Ok(Module {
+        globals: lowerer.globals,
+        functions: lowerer.functions,
+    })
+}
+
+/// A local variable, one per declaration so shadowed names stay apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct VarId(usize);
+
+#[derive(Debug, Clone)]
+enum Binding {
+    Local(VarId),
+    Global(String),
+    Function(String, usize),
+}
+
+/// Where an assignment stores its value
+enum Place {
+    Variable(Binding),
+    Element(Value, Value),
+}
+
+struct Builder {
+    func: Function,
+    /// Block being filled, code after a terminator goes to a fresh unreachable block
+    current: BlockId,
+    preds: Vec<Vec<BlockId>>,
+    sealed: Vec<bool>,
+    defs: HashMap<(VarId, BlockId), Value>,
+    /// Phis waiting for their block to be sealed
+    incomplete: Vec<Vec<(VarId, Value)>>,
+    scopes: Vec<HashMap<String, Binding>>,
+    /// `(continue, break)` targets of enclosing loops
+    loops: Vec<(BlockId, BlockId)>,
+}
+
+impl Builder {
+    fn new(name: String, params: usize) -> Self {
+        let mut builder = Self {
+            func: Function { name, params, blocks: vec![], values: vec![] },
+            current: Function::ENTRY,
+            preds: vec![],
+            sealed: vec![],
+            defs: HashMap::new(),
+            incomplete: vec![],
+            scopes: vec![HashMap::new()],
+            loops: vec![],
+        };
+
+        let entry = builder.new_block();
+        builder.seal(entry);
+        builder
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        let id = BlockId(self.func.blocks.len() as u32);
+
+        self.func.blocks.push(Block { insts: vec![], terminator: Terminator::Unreachable });
+        self.preds.push(vec![]);
+        self.sealed.push(false);
+        self.incomplete.push(vec![]);
+
+        id
+    }
+
+    fn switch_to(&mut self, block: BlockId) {
+        self.current = block;
+    }
+
+    fn create(&mut self, inst: Inst) -> Value {
+        let value = Value(self.func.values.len() as u32);
+        let ty = match &inst {
+            Inst::Const(c) => c.ty(),
+            _ => Type::Any,
+        };
+
+        self.func.values.push(InstData { inst, ty });
+        value
+    }
+
+    fn emit(&mut self, inst: Inst) -> Value {
+        let value = self.create(inst);
+        self.func.blocks[self.current.0 as usize].insts.push(value);
+        value
+    }
+
+    /// Places a new instruction after the phis of `block`
+    fn emit_at_start(&mut self, block: BlockId, inst: Inst) -> Value {
+        let value = self.create(inst);
+        let at = self.func.blocks[block.0 as usize].insts.iter()
+            .position(|v| !matches!(self.func.inst(*v), Inst::Phi(_)))
+            .unwrap_or(self.func.blocks[block.0 as usize].insts.len());
+
+        self.func.blocks[block.0 as usize].insts.insert(at, value);
+        value
+    }
+
+    fn constant(&mut self, c: Constant) -> Value {
+        self.emit(Inst::Const(c))
+    }
+
+    /// Ends the current block, code that follows goes to a fresh unreachable block
+    fn terminate(&mut self, terminator: Terminator) {
+        let block = self.current;
+
+        for succ in terminator.successors() {
+            self.preds[succ.0 as usize].push(block);
+        }
+
+        self.func.blocks[block.0 as usize].terminator = terminator;
+
+        let dead = self.new_block();
+        self.seal(dead);
+        self.switch_to(dead);
+    }
+
+    fn jump(&mut self, target: BlockId) {
+        self.terminate(Terminator::Jump(target));
+    }
+
+    /// Jumps from the current block to `block` and continues there
+    fn enter(&mut self, block: BlockId) {
+        self.jump(block);
+        self.switch_to(block);
+    }
+
+    fn write_variable(&mut self, var: VarId, block: BlockId, value: Value) {
+        self.defs.insert((var, block), value);
+    }
+
+    fn read_variable(&mut self, var: VarId, block: BlockId) -> Value {
+        match self.defs.get(&(var, block)) {
+            Some(value) => *value,
+            None => self.read_variable_recursive(var, block),
+        }
+    }
+
+    fn read_variable_recursive(&mut self, var: VarId, block: BlockId) -> Value {
+        let i = block.0 as usize;
+
+        let value = if !self.sealed[i] {
+            let phi = self.emit_at_start(block, Inst::Phi(vec![]));
+            self.incomplete[i].push((var, phi));
+            phi
+        }
+        else if self.preds[i].is_empty() {
+            // Only unreachable blocks get here, the variable was declared on the way in
+            self.emit_at_start(block, Inst::Const(Constant::Null))
+        }
+        else if self.preds[i].len() == 1 {
+            self.read_variable(var, self.preds[i][0])
+        }
+        else {
+            // Defining the phi first breaks cycles through loops
+            let phi = self.emit_at_start(block, Inst::Phi(vec![]));
+            self.write_variable(var, block, phi);
+            self.add_phi_operands(var, block, phi);
+            phi
+        };
+
+        self.write_variable(var, block, value);
+        value
+    }
+
+    fn add_phi_operands(&mut self, var: VarId, block: BlockId, phi: Value) {
+        let mut incoming = vec![];
+
+        for pred in self.preds[block.0 as usize].clone() {
+            incoming.push((pred, self.read_variable(var, pred)));
+        }
+
+        self.func.values[phi.0 as usize].inst = Inst::Phi(incoming);
+    }
+
+    /// Marks that every predecessor of `block` is known
+    fn seal(&mut self, block: BlockId) {
+        let i = block.0 as usize;
+
+        for (var, phi) in std::mem::take(&mut self.incomplete[i]) {
+            self.add_phi_operands(var, block, phi);
+        }
+
+        self.sealed[i] = true;
+    }
+
+    fn lookup_local(&self, name: &str) -> Option<Binding> {
+        self.scopes.iter().rev().find_map(|s| s.get(name)).cloned()
+    }
+
+    /// Removes unreachable blocks and phis that merge a single value, then numbers values in order
+    fn finish(mut self) -> Function {
+        // Falling off the end returns null like the VM, this is pruned again after a `return`
+        let null = self.constant(Constant::Null);
+        self.terminate(Terminator::Return(null));
+
+        let mut func = self.func;
+
+        func.remove_unreachable();
+        func.remove_trivial_phis();
+        func.compact();
+        func.infer_types();
+
+        func
+    }
+}
+
+struct Lowerer {
+    globals: Vec<String>,
+    functions: Vec<Function>,
+    /// Top level names, visible from every function
+    top: HashMap<String, Binding>,
+    /// Functions being lowered, innermost last
+    builders: Vec<Builder>,
+    vars: usize,
+}
+
+impl Lowerer {
+    fn new() -> Self {
+        Self {
+            globals: vec![],
+            functions: vec![],
+            top: HashMap::new(),
+            builders: vec![],
+            vars: 0,
+        }
+    }
+
+    fn program(&mut self, ast: &AST) -> LowerResult<()> {
+        let mut symbols = HashSet::from([String::from(Module::INIT)]);
+        let mut order = vec![];
+
+        // Register every top level name first so functions can refer to later declarations
+        for decl in ast.program() {
+            match decl {
+                Declaration::Variable { identifier, .. } => {
+                    let name = identifier.name();
+                    if !self.globals.contains(&name) {
+                        self.globals.push(name.clone());
+                    }
+                    self.top.insert(name, Binding::Global(identifier.name()));
+                    order.push(None);
+                },
+                Declaration::Destructure { pattern, .. } => {
+                    for identifier in pattern.identifiers() {
+                        let name = identifier.name();
+                        if !self.globals.contains(&name) {
+                            self.globals.push(name.clone());
+                        }
+                        self.top.insert(name, Binding::Global(identifier.name()));
+                    }
+                    order.push(None);
+                },
+                Declaration::Function { identifier, arguments, .. } => {
+                    let symbol = unique(&mut symbols, identifier.name());
+                    let arity = arguments.as_ref().map_or(0, Vec::len);
+                    self.top.insert(identifier.name(), Binding::Function(symbol.clone(), arity));
+                    order.push(Some(symbol));
+                },
+            }
+        }
+
+        self.builders.push(Builder::new(String::from(Module::INIT), 0));
+
+        for (decl, symbol) in ast.program().iter().zip(order) {
+            match (decl, symbol) {
+                (Declaration::Variable { identifier, value: Some(value) }, _) => {
+                    let value = self.expression(value)?;
+                    self.builder().emit(Inst::SetGlobal(identifier.name(), value));
+                },
+                (Declaration::Destructure { pattern, value }, _) => {
+                    let value = self.expression(value)?;
+                    self.destructure(pattern, value, true);
+                },
+                (Declaration::Function { arguments, body, .. }, Some(symbol)) =>
+                    self.function(symbol, arguments.as_deref().unwrap_or_default(), body)?,
+                _ => (),
+            }
+        }
+
+        let init = self.builders.pop().unwrap().finish();
+        self.functions.insert(0, init);
+
+        Ok(())
+    }
+
+    fn function(&mut self, symbol: String, arguments: &[Identifier], body: &ast::Block) -> LowerResult<()> {
+        self.builders.push(Builder::new(symbol, arguments.len()));
+
+        for (i, arg) in arguments.iter().enumerate() {
+            let param = self.builder().emit(Inst::Param(i));
+            self.declare(arg.name(), param);
+        }
+
+        for stmt in body.statements() {
+            self.statement(stmt)?;
+        }
+
+        let func = self.builders.pop().unwrap().finish();
+        self.functions.push(func);
+
+        Ok(())
+    }
+
+    fn builder(&mut self) -> &mut Builder {
+        self.builders.last_mut().unwrap()
+    }
+
+    fn declare(&mut self, name: String, value: Value) -> VarId {
+        let var = VarId(self.vars);
+        self.vars += 1;
+
+        let builder = self.builder();
+        let block = builder.current;
+        builder.scopes.last_mut().unwrap().insert(name, Binding::Local(var));
+        builder.write_variable(var, block, value);
+
+        var
+    }
+
+    /// Checks the shape of `value` and binds its elements to the pattern's names, as globals or locals
+    fn destructure(&mut self, pattern: &Pattern, value: Value, global: bool) {
+        let (patterns, ty) = match pattern {
+            Pattern::Identifier(identifier) if global => {
+                self.builder().emit(Inst::SetGlobal(identifier.name(), value));
+                return
+            },
+            Pattern::Identifier(identifier) => {
+                self.declare(identifier.name(), value);
+                return
+            },
+            Pattern::Tuple(patterns) => (patterns, Type::Tuple),
+            Pattern::Array(patterns) => (patterns, Type::Array),
+        };
+
+        let value = self.builder().emit(Inst::Unpack(value, ty, patterns.len()));
+
+        for (i, pattern) in patterns.iter().enumerate() {
+            let index = self.builder().constant(Constant::Int(i as i64));
+            let element = self.builder().emit(Inst::Index(value, index));
+            self.destructure(pattern, element, global);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> LowerResult<Binding> {
+        let (current, enclosing) = self.builders.split_last().unwrap();
+
+        if let Some(binding) = current.lookup_local(name) {
+            return Ok(binding)
+        }
+
+        // Enclosing functions' nested functions are reachable, their locals are not
+        for builder in enclosing.iter().rev() {
+            match builder.lookup_local(name) {
+                Some(Binding::Local(_)) => return Err(LowerError::UnsupportedCapture(name.to_string())),
+                Some(binding) => return Ok(binding),
+                None => (),
+            }
+        }
+
+        self.top.get(name).cloned().ok_or_else(|| LowerError::UndefinedVariable(name.to_string()))
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> LowerResult<()> {
+        match stmt {
+            Statement::Expression(expr) => { self.expression(expr)?; },
+            Statement::Declaration(Declaration::Variable { identifier, value }) => {
+                let value = match value {
+                    Some(expr) => self.expression(expr)?,
+                    None => self.builder().constant(Constant::Null),
+                };
+                self.declare(identifier.name(), value);
+            },
+            Statement::Declaration(Declaration::Destructure { pattern, value }) => {
+                let value = self.expression(value)?;
+                self.destructure(pattern, value, false);
+            },
+            Statement::ForEach { variable, iterable, body } => self.for_each(variable, iterable, body)?,
+            Statement::Throw(_) | Statement::Try { .. } => return Err(LowerError::UnsupportedStatement(String::from("exceptions"))),
+            Statement::Assert { .. } => return Err(LowerError::UnsupportedStatement(String::from("assertions"))),
+            Statement::Declaration(Declaration::Function { identifier, arguments, body }) => {
+                let arguments = arguments.as_deref().unwrap_or_default();
+                let symbol = format!("{}.{}", self.builder().func.name, identifier.name());
+                let symbol = unique(&mut self.symbols(), symbol);
+
+                self.builder().scopes.last_mut().unwrap()
+                    .insert(identifier.name(), Binding::Function(symbol.clone(), arguments.len()));
+                self.function(symbol, arguments, body)?;
+            },
+            Statement::Block(block) | Statement::Else { body: block } => self.block(block)?,
+            Statement::Return(expr) => {
+                let value = self.expression(expr)?;
+                self.builder().terminate(Terminator::Return(value));
+            },
+            Statement::If { condition, body, else_stmt } => {
+                let cond = self.expression(condition)?;
+
+                let builder = self.builder();
+                let then = builder.new_block();
+                let otherwise = else_stmt.as_ref().map(|_| builder.new_block());
+                let end = builder.new_block();
+                let otherwise = otherwise.unwrap_or(end);
+
+                builder.terminate(Terminator::Branch(cond, then, otherwise));
+                builder.seal(then);
+                builder.switch_to(then);
+                self.block(body)?;
+                self.builder().jump(end);
+
+                if let Some(stmt) = else_stmt {
+                    self.builder().seal(otherwise);
+                    self.builder().switch_to(otherwise);
+                    self.statement(stmt)?;
+                    self.builder().jump(end);
+                }
+
+                self.builder().seal(end);
+                self.builder().switch_to(end);
+            },
+            Statement::While { condition, body } => {
+                let builder = self.builder();
+                let header = builder.new_block();
+                let then = builder.new_block();
+                let end = builder.new_block();
+
+                builder.enter(header);
+                let cond = self.expression(condition)?;
+
+                let builder = self.builder();
+                builder.terminate(Terminator::Branch(cond, then, end));
+                builder.seal(then);
+                builder.switch_to(then);
+
+                self.loop_body(body, header, end)?;
+
+                let builder = self.builder();
+                builder.jump(header);
+                builder.seal(header);
+                builder.seal(end);
+                builder.switch_to(end);
+            },
+            Statement::For { variable, condition, step, body } => {
+                self.builder().scopes.push(HashMap::new());
+
+                if let Some(stmt) = variable {
+                    self.statement(stmt)?;
+                }
+
+                let builder = self.builder();
+                let header = builder.new_block();
+                let then = builder.new_block();
+                let next = builder.new_block();
+                let end = builder.new_block();
+
+                builder.enter(header);
+
+                match condition {
+                    Some(cond) => {
+                        let cond = self.expression(cond)?;
+                        self.builder().terminate(Terminator::Branch(cond, then, end));
+                    },
+                    None => self.builder().jump(then),
+                }
+
+                self.builder().seal(then);
+                self.builder().switch_to(then);
+                self.loop_body(body, next, end)?;
+
+                self.builder().enter(next);
+                self.builder().seal(next);
+
+                if let Some(step) = step {
+                    self.expression(step)?;
+                }
+
+                let builder = self.builder();
+                builder.jump(header);
+                builder.seal(header);
+                builder.seal(end);
+                builder.switch_to(end);
+                builder.scopes.pop();
+            },
+            Statement::Break => {
+                let (_, end) = self.builder().loops.last().cloned().ok_or(LowerError::BreakOutsideLoop)?;
+                self.builder().jump(end);
+            },
+            Statement::Continue => {
+                let (next, _) = self.builder().loops.last().cloned().ok_or(LowerError::ContinueOutsideLoop)?;
+                self.builder().jump(next);
+            },
+        }
+
+        Ok(())
+    }
+
+    /// A range written in place counts from its start to its end, anything else is indexed up to
+    /// its `len`. Like the VM, which walks a snapshot, the `len` is read once so growing an array
+    /// in the loop doesn't add iterations
+    fn for_each(&mut self, variable: &Identifier, iterable: &Expression, body: &ast::Block) -> LowerResult<()> {
+        self.builder().scopes.push(HashMap::new());
+
+        let (source, start, (op, bound)) = match iterable {
+            Expression::Range { start, end, inclusive } => {
+                let start = self.expression(start)?;
+                let end = self.expression(end)?;
+                (None, start, (if *inclusive { BinOp::Le } else { BinOp::Lt }, end))
+            },
+            iterable => {
+                let source = self.expression(iterable)?;
+                let len = self.builder().emit(Inst::Call(Callee::Builtin(String::from("len")), vec![source]));
+                (Some(source), self.builder().constant(Constant::Int(0)), (BinOp::Lt, len))
+            },
+        };
+
+        let counter = self.declare(String::from("@index"), start);
+
+        let builder = self.builder();
+        let header = builder.new_block();
+        let then = builder.new_block();
+        let next = builder.new_block();
+        let end = builder.new_block();
+
+        builder.enter(header);
+        let index = builder.read_variable(counter, header);
+
+        let cond = builder.emit(Inst::Binary(op, index, bound));
+
+        builder.terminate(Terminator::Branch(cond, then, end));
+        builder.seal(then);
+        builder.switch_to(then);
+
+        let element = match source {
+            Some(source) => builder.emit(Inst::Index(source, index)),
+            None => index,
+        };
+
+        builder.scopes.push(HashMap::new());
+        self.declare(variable.name(), element);
+        self.loop_body(body, next, end)?;
+        self.builder().scopes.pop();
+
+        let builder = self.builder();
+        builder.enter(next);
+        builder.seal(next);
+
+        let index = builder.read_variable(counter, next);
+        let one = builder.constant(Constant::Int(1));
+        let index = builder.emit(Inst::Binary(BinOp::Add, index, one));
+        builder.write_variable(counter, next, index);
+
+        builder.jump(header);
+        builder.seal(header);
+        builder.seal(end);
+        builder.switch_to(end);
+        builder.scopes.pop();
+
+        Ok(())
+    }
+
+    /// Names of every function lowered or being lowered, for hoisting nested ones uniquely
+    fn symbols(&self) -> HashSet<String> {
+        let mut symbols = self.functions.iter().map(|f| f.name.clone()).collect::<HashSet<_>>();
+        symbols.extend(self.builders.iter().map(|b| b.func.name.clone()));
+        symbols.extend(self.top.values().filter_map(|b| match b {
+            Binding::Function(symbol, _) => Some(symbol.clone()),
+            _ => None,
+        }));
+        symbols
+    }
+
+    fn block(&mut self, block: &ast::Block) -> LowerResult<()> {
+        self.builder().scopes.push(HashMap::new());
+
+        for stmt in block.statements() {
+            self.statement(stmt)?;
+        }
+
+        self.builder().scopes.pop();
+        Ok(())
+    }
+
+    fn loop_body(&mut self, body: &ast::Block, next: BlockId, end: BlockId) -> LowerResult<()> {
+        self.builder().loops.push((next, end));
+        self.block(body)?;
+        self.builder().loops.pop();
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &Expression) -> LowerResult<Value> {
+        match expr {
+            Expression::Literal(lit) => {
+                let c = match lit {
+                    Literal::String(s) => Constant::Str(s.clone()),
+                    Literal::Integer(i) => Constant::Int(*i),
+                    Literal::Decimal(d) => Constant::Dec(*d),
+                    Literal::Character(c) => Constant::Char(*c),
+                    Literal::Boolean(b) => Constant::Bool(*b),
+                    Literal::Null => Constant::Null,
+                };
+                Ok(self.builder().constant(c))
+            },
+            Expression::Value(identifier) => match self.lookup(&identifier.name())? {
+                Binding::Local(var) => {
+                    let builder = self.builder();
+                    let block = builder.current;
+                    Ok(builder.read_variable(var, block))
+                },
+                Binding::Global(name) => Ok(self.builder().emit(Inst::GetGlobal(name))),
+                Binding::Function(symbol, _) => Ok(self.builder().constant(Constant::Func(symbol))),
+            },
+            Expression::Member { target, property } => {
+                let target = self.expression(target)?;
+                let index = self.expression(property)?;
+                Ok(self.builder().emit(Inst::Index(target, index)))
+            },
+            Expression::Array { elements, .. } => {
+                let elements = elements.iter().map(|e| self.expression(e)).collect::<LowerResult<Vec<_>>>()?;
+                Ok(self.builder().emit(Inst::Array(elements)))
+            },
+            Expression::Tuple(elements) => {
+                let elements = elements.iter().map(|e| self.expression(e)).collect::<LowerResult<Vec<_>>>()?;
+                Ok(self.builder().emit(Inst::Tuple(elements)))
+            },
+            Expression::Map(entries) => {
+                let entries = entries.iter()
+                    .map(|(k, v)| Ok((self.expression(k)?, self.expression(v)?)))
+                    .collect::<LowerResult<Vec<_>>>()?;
+                Ok(self.builder().emit(Inst::Map(entries)))
+            },
+            Expression::Set(elements) => {
+                let elements = elements.iter().map(|e| self.expression(e)).collect::<LowerResult<Vec<_>>>()?;
+                Ok(self.builder().emit(Inst::Set(elements)))
+            },
+            Expression::Assignment { lhs, operation, rhs } => {
+                let place = self.place(lhs)?;
+
+                let value = match operation {
+                    Some(operation) => {
+                        let old = self.load(&place)?;
+                        let rhs = self.expression(rhs)?;
+                        let op = binary_op(operation)?;
+                        self.builder().emit(Inst::Binary(op, old, rhs))
+                    },
+                    None => self.expression(rhs)?,
+                };
+
+                self.store(place, value)?;
+                Ok(value)
+            },
+            Expression::Postfix { operand, operation } => {
+                let place = self.place(operand)?;
+                let op = if *operation == Token::Increment { BinOp::Add } else { BinOp::Sub };
+
+                let old = self.load(&place)?;
+                let one = self.builder().constant(Constant::Int(1));
+                let value = self.builder().emit(Inst::Binary(op, old, one));
+
+                self.store(place, value)?;
+                Ok(old)
+            },
+            Expression::Call { target, args } => self.call(target, args.as_deref().unwrap_or_default()),
+            Expression::Conditional { condition, then, otherwise } => {
+                let cond = self.expression(condition)?;
+                self.merge(cond, |l| l.expression(then), |l| l.expression(otherwise))
+            },
+            Expression::Coalesce { lhs, rhs } => {
+                let lhs = self.expression(lhs)?;
+                let null = self.is_null(lhs);
+                self.merge(null, |l| l.expression(rhs), |_| Ok(lhs))
+            },
+            Expression::OptionalMember { target, property } => {
+                let target = self.expression(target)?;
+                let null = self.is_null(target);
+
+                self.merge(null, |_| Ok(target), |l| {
+                    let index = l.expression(property)?;
+                    Ok(l.builder().emit(Inst::Index(target, index)))
+                })
+            },
+            Expression::OptionalCall { target, args } => {
+                let args = args.as_deref().unwrap_or_default();
+
+                match self.callee(target, args.len())? {
+                    Callee::Indirect(target) => {
+                        let null = self.is_null(target);
+                        self.merge(null, |_| Ok(target), |l| l.call_with(Callee::Indirect(target), args))
+                    },
+                    // Functions are never null
+                    callee => self.call_with(callee, args),
+                }
+            },
+            Expression::Logical { lhs, operation, rhs } => {
+                let lhs = self.expression(lhs)?;
+
+                match operation {
+                    Token::LogicalAnd => self.merge(lhs, |l| l.truth(rhs), |l| Ok(l.builder().constant(Constant::Bool(false)))),
+                    Token::LogicalOr => self.merge(lhs, |l| Ok(l.builder().constant(Constant::Bool(true))), |l| l.truth(rhs)),
+                    tok => Err(LowerError::UnsupportedOperator(tok.to_string())),
+                }
+            },
+            Expression::Unary { prefix, operand } => {
+                let value = self.expression(operand)?;
+
+                let op = match prefix {
+                    None => return Ok(value),
+                    Some(Token::Minus) => UnOp::Neg,
+                    Some(Token::Not) => UnOp::Not,
+                    Some(Token::BinaryNegate) => UnOp::BitNot,
+                    Some(tok) => return Err(LowerError::UnsupportedOperator(tok.to_string())),
+                };
+
+                Ok(self.builder().emit(Inst::Unary(op, value)))
+            },
+            Expression::Binary { lhs, operation, rhs } => {
+                let lhs = self.expression(lhs)?;
+                let rhs = self.expression(rhs)?;
+                let op = binary_op(operation)?;
+                Ok(self.builder().emit(Inst::Binary(op, lhs, rhs)))
+            },
+            // Only `for` loops over ranges written in place, which count without making one
+            Expression::Range { inclusive, .. } => {
+                let tok = if *inclusive { Token::RangeInclusive } else { Token::Range };
+                Err(LowerError::UnsupportedOperator(tok.to_string()))
+            },
+            Expression::Spread(_) => Err(LowerError::UnsupportedOperator(Token::Spread.to_string())),
+            // The loops append to the array through the variable, which is only bound while they run
+            Expression::Comprehension { result, loops, .. } => {
+                let array = self.builder().emit(Inst::Array(vec![]));
+
+                self.builder().scopes.push(HashMap::new());
+                self.declare(result.name(), array);
+                self.statement(loops)?;
+                self.builder().scopes.pop();
+
+                Ok(array)
+            },
+            Expression::Append { target, value } => {
+                let target = self.expression(&Expression::Value(target.clone()))?;
+                let value = self.expression(value)?;
+                Ok(self.builder().emit(Inst::Call(Callee::Builtin(String::from("push")), vec![target, value])))
+            },
+        }
+    }
+
+    /// Evaluates what an assignment stores into, an element's target and index only once
+    fn place(&mut self, lhs: &Expression) -> LowerResult<Place> {
+        match lhs.place().ok_or(LowerError::InvalidAssignmentTarget)? {
+            ast::Place::Variable(identifier) => match self.lookup(&identifier.name())? {
+                Binding::Function(..) => Err(LowerError::InvalidAssignmentTarget),
+                binding => Ok(Place::Variable(binding)),
+            },
+            ast::Place::Element { target, index } => {
+                let target = self.expression(target)?;
+                let index = self.expression(index)?;
+                Ok(Place::Element(target, index))
+            },
+        }
+    }
+
+    fn load(&mut self, place: &Place) -> LowerResult<Value> {
+        let builder = self.builder();
+
+        match place {
+            Place::Variable(Binding::Local(var)) => {
+                let block = builder.current;
+                Ok(builder.read_variable(*var, block))
+            },
+            Place::Variable(Binding::Global(name)) => Ok(builder.emit(Inst::GetGlobal(name.clone()))),
+            Place::Element(target, index) => Ok(builder.emit(Inst::Index(*target, *index))),
+            Place::Variable(Binding::Function(..)) => Err(LowerError::InvalidAssignmentTarget),
+        }
+    }
+
+    fn store(&mut self, place: Place, value: Value) -> LowerResult<()> {
+        let builder = self.builder();
+
+        match place {
+            Place::Variable(Binding::Local(var)) => {
+                let block = builder.current;
+                builder.write_variable(var, block, value);
+            },
+            Place::Variable(Binding::Global(name)) => { builder.emit(Inst::SetGlobal(name, value)); },
+            Place::Element(target, index) => { builder.emit(Inst::SetIndex(target, index, value)); },
+            Place::Variable(Binding::Function(..)) => return Err(LowerError::InvalidAssignmentTarget),
+        }
+
+        Ok(())
+    }
+
+    fn call(&mut self, target: &Expression, args: &[Expression]) -> LowerResult<Value> {
+        let callee = self.callee(target, args.len())?;
+        self.call_with(callee, args)
+    }
+
+    /// What calling `target` with `argc` arguments calls, evaluating `target` if it's not a known function
+    fn callee(&mut self, target: &Expression, argc: usize) -> LowerResult<Callee> {
+        let callee = match target {
+            Expression::Value(identifier) => match self.lookup(&identifier.name()) {
+                Ok(Binding::Function(_, arity)) if arity != argc =>
+                    return Err(LowerError::ArityMismatch(identifier.name(), arity, argc)),
+                Ok(Binding::Function(symbol, _)) => Callee::Direct(symbol),
+                Err(LowerError::UndefinedVariable(name)) => match stdlib::lookup(&name) {
+                    Some(Builtin { arity: Some(arity), .. }) if *arity != argc =>
+                        return Err(LowerError::ArityMismatch(name, *arity, argc)),
+                    Some(_) => Callee::Builtin(name),
+                    None => return Err(LowerError::UndefinedVariable(name)),
+                },
+                Err(e) => return Err(e),
+                Ok(_) => Callee::Indirect(self.expression(target)?),
+            },
+            target => Callee::Indirect(self.expression(target)?),
+        };
+
+        Ok(callee)
+    }
+
+    fn call_with(&mut self, callee: Callee, args: &[Expression]) -> LowerResult<Value> {
+        let args = args.iter().map(|a| self.expression(a)).collect::<LowerResult<Vec<_>>>()?;
+
+        Ok(self.builder().emit(Inst::Call(callee, args)))
+    }
+
+    /// `expr` as a boolean, the way conditions see it
+    fn truth(&mut self, expr: &Expression) -> LowerResult<Value> {
+        let value = self.expression(expr)?;
+        let not = self.builder().emit(Inst::Unary(UnOp::Not, value));
+        Ok(self.builder().emit(Inst::Unary(UnOp::Not, not)))
+    }
+
+    fn is_null(&mut self, value: Value) -> Value {
+        let null = self.builder().constant(Constant::Null);
+        self.builder().emit(Inst::Binary(BinOp::Eq, value, null))
+    }
+
+    /// Branches on `cond` and joins the values of both sides, through a fresh variable so the join gets its phi
+    fn merge(
+        &mut self,
+        cond: Value,
+        then: impl FnOnce(&mut Self) -> LowerResult<Value>,
+        otherwise: impl FnOnce(&mut Self) -> LowerResult<Value>,
+    ) -> LowerResult<Value> {
+        let var = VarId(self.vars);
+        self.vars += 1;
+
+        let builder = self.builder();
+        let then_block = builder.new_block();
+        let otherwise_block = builder.new_block();
+        let end = builder.new_block();
+
+        builder.terminate(Terminator::Branch(cond, then_block, otherwise_block));
+
+        self.merge_side(then_block, var, end, then)?;
+        self.merge_side(otherwise_block, var, end, otherwise)?;
+
+        let builder = self.builder();
+        builder.seal(end);
+        builder.switch_to(end);
+        Ok(builder.read_variable(var, end))
+    }
+
+    fn merge_side(
+        &mut self,
+        block: BlockId,
+        var: VarId,
+        end: BlockId,
+        side: impl FnOnce(&mut Self) -> LowerResult<Value>,
+    ) -> LowerResult<()> {
+        self.builder().seal(block);
+        self.builder().switch_to(block);
+
+        let value = side(self)?;
+
+        let builder = self.builder();
+        let current = builder.current;
+        builder.write_variable(var, current, value);
+        builder.jump(end);
+        Ok(())
+    }
+}
+
+fn unique(symbols: &mut HashSet<String>, base: String) -> String {
+    let mut symbol = base.clone();
+    let mut n = 0;
+
+    while !symbols.insert(symbol.clone()) {
+        n += 1;
+        symbol = format!("{}.{}", base, n);
+    }
+
+    symbol
+}
+
+fn binary_op(tok: &Token) -> LowerResult<BinOp> {
+    use BinOp::*;
+    Ok(match tok {
+        Token::Plus => Add,
+        Token::Minus => Sub,
+        Token::Multiply => Mul,
+        Token::Divide => Div,
+        Token::Modulo => Mod,
+        Token::Exponentiate => Pow,
+        Token::BinaryAnd => BitAnd,
+        Token::BinaryOr => BitOr,
+        Token::Xor => BitXor,
+        Token::ShiftLeft => Shl,
+        Token::ShiftRight => Shr,
+        Token::Equals => Eq,
+        Token::NotEquals => Ne,
+        Token::LessThan => Lt,
+        Token::LessEquals => Le,
+        Token::GreaterThan => Gt,
+        Token::GreaterEquals => Ge,
+        Token::In => In,
+        tok => return Err(LowerError::UnsupportedOperator(tok.to_string())),
+    })
+}
\ No newline at end of file
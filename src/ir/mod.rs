@@ -0,0 +1,755 @@
+pub mod lower;
+pub mod opt;
+pub mod verify;
+mod error;
+
+pub use error::{ LowerError, VerifyError };
+
+use std::collections::HashSet;
+use std::fmt::{ Display, Formatter, Result };
+
+/// An SSA value, defined by exactly one instruction of its function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Value(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub u32);
+
+/// Static type of a value, `Any` when it's only known at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Null,
+    Int,
+    Dec,
+    Bool,
+    Char,
+    Str,
+    Array,
+    Tuple,
+    Map,
+    Set,
+    Func,
+    Any,
+}
+
+impl Type {
+    pub fn join(self, other: Type) -> Type {
+        if self == other { self } else { Type::Any }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Null,
+    Int(i64),
+    Dec(f64),
+    Bool(bool),
+    Char(char),
+    Str(String),
+    /// A function by its module-wide name
+    Func(String),
+}
+
+impl Constant {
+    pub fn ty(&self) -> Type {
+        match self {
+            Constant::Null => Type::Null,
+            Constant::Int(_) => Type::Int,
+            Constant::Dec(_) => Type::Dec,
+            Constant::Bool(_) => Type::Bool,
+            Constant::Char(_) => Type::Char,
+            Constant::Str(_) => Type::Str,
+            Constant::Func(_) => Type::Func,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Membership of the left value in the right collection
+    In,
+}
+
+impl BinOp {
+    pub fn name(&self) -> &'static str {
+        use BinOp::*;
+        match self {
+            Add => "add", Sub => "sub", Mul => "mul", Div => "div", Mod => "mod", Pow => "pow",
+            BitAnd => "bit_and", BitOr => "bit_or", BitXor => "bit_xor", Shl => "shl", Shr => "shr",
+            Eq => "eq", Ne => "ne", Lt => "lt", Le => "le", Gt => "gt", Ge => "ge", In => "in",
+        }
+    }
+
+    /// Result type given the operand types, following the VM's arithmetic rules
+    pub fn result(&self, lhs: Type, rhs: Type) -> Type {
+        use BinOp::*;
+        match self {
+            Eq | Ne | Lt | Le | Gt | Ge | In => Type::Bool,
+            BitAnd | BitOr | BitXor | Shl | Shr => Type::Int,
+            Add if lhs == Type::Str || rhs == Type::Str => Type::Str,
+            _ => match (lhs, rhs) {
+                (Type::Int, Type::Int) => Type::Int,
+                (Type::Int | Type::Dec, Type::Int | Type::Dec) => Type::Dec,
+                _ => Type::Any,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,
+    Not,
+    BitNot,
+}
+
+impl UnOp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnOp::Neg => "neg",
+            UnOp::Not => "not",
+            UnOp::BitNot => "bit_not",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Callee {
+    /// A function of the module, by name
+    Direct(String),
+    /// A standard library function
+    Builtin(String),
+    Indirect(Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inst {
+    Const(Constant),
+    /// The n-th parameter, only in the entry block
+    Param(usize),
+    /// Another value under a new name, left behind by simplifications for copy propagation
+    Copy(Value),
+    Unary(UnOp, Value),
+    Binary(BinOp, Value, Value),
+    Call(Callee, Vec<Value>),
+    GetGlobal(String),
+    SetGlobal(String, Value),
+    Array(Vec<Value>),
+    Tuple(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Set(Vec<Value>),
+    /// The value itself, after checking it's a tuple or an array with as many elements
+    Unpack(Value, Type, usize),
+    Index(Value, Value),
+    /// Stores the last value into an array element
+    SetIndex(Value, Value, Value),
+    /// One incoming value per predecessor, only at the start of a block
+    Phi(Vec<(BlockId, Value)>),
+}
+
+impl Inst {
+    /// Values read by the instruction
+    pub fn operands(&self) -> Vec<Value> {
+        match self {
+            Inst::Const(_) | Inst::Param(_) | Inst::GetGlobal(_) => vec![],
+            Inst::Copy(v) | Inst::Unary(_, v) | Inst::SetGlobal(_, v) | Inst::Unpack(v, ..) => vec![*v],
+            Inst::Binary(_, a, b) | Inst::Index(a, b) => vec![*a, *b],
+            Inst::SetIndex(a, i, v) => vec![*a, *i, *v],
+            Inst::Call(callee, args) => {
+                let mut ops = args.clone();
+                if let Callee::Indirect(v) = callee {
+                    ops.insert(0, *v);
+                }
+                ops
+            },
+            Inst::Array(elements) | Inst::Tuple(elements) | Inst::Set(elements) => elements.clone(),
+            Inst::Map(entries) => entries.iter().flat_map(|(k, v)| [*k, *v]).collect(),
+            Inst::Phi(incoming) => incoming.iter().map(|(_, v)| *v).collect(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Inst::Const(_) | Inst::Param(_) | Inst::GetGlobal(_) => vec![],
+            Inst::Copy(v) | Inst::Unary(_, v) | Inst::SetGlobal(_, v) | Inst::Unpack(v, ..) => vec![v],
+            Inst::Binary(_, a, b) | Inst::Index(a, b) => vec![a, b],
+            Inst::SetIndex(a, i, v) => vec![a, i, v],
+            Inst::Call(callee, args) => {
+                let mut ops = vec![];
+                if let Callee::Indirect(v) = callee {
+                    ops.push(v);
+                }
+                ops.extend(args.iter_mut());
+                ops
+            },
+            Inst::Array(elements) | Inst::Tuple(elements) | Inst::Set(elements) => elements.iter_mut().collect(),
+            Inst::Map(entries) => entries.iter_mut().flat_map(|(k, v)| [k, v]).collect(),
+            Inst::Phi(incoming) => incoming.iter_mut().map(|(_, v)| v).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Jump(BlockId),
+    Branch(Value, BlockId, BlockId),
+    Return(Value),
+    Unreachable,
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(b) => vec![*b],
+            Terminator::Branch(_, t, e) => vec![*t, *e],
+            Terminator::Return(_) | Terminator::Unreachable => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InstData {
+    pub inst: Inst,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub insts: Vec<Value>,
+    pub terminator: Terminator,
+}
+
+/// A function as a control flow graph. Instructions live in `values` and are placed
+/// in blocks by id, block 0 is the entry
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub params: usize,
+    pub blocks: Vec<Block>,
+    pub values: Vec<InstData>,
+}
+
+impl Function {
+    pub const ENTRY: BlockId = BlockId(0);
+
+    pub fn inst(&self, value: Value) -> &Inst {
+        &self.values[value.0 as usize].inst
+    }
+
+    pub fn ty(&self, value: Value) -> Type {
+        self.values[value.0 as usize].ty
+    }
+
+    /// Whether `value` can neither fail at runtime nor affect anything but its result, so it may be
+    /// removed when unused or computed earlier than written
+    pub fn is_pure(&self, value: Value) -> bool {
+        use Type::*;
+        let numeric = |t: Type| matches!(t, Int | Dec);
+
+        match self.inst(value) {
+            Inst::Const(_) | Inst::Param(_) | Inst::Copy(_) | Inst::Array(_) | Inst::Tuple(_) | Inst::Phi(_) => true,
+            // Every value can be hashed, so building a collection can't fail
+            Inst::Map(_) | Inst::Set(_) => true,
+            // Reading a global before it's defined is a runtime error
+            Inst::GetGlobal(_) => false,
+            Inst::Call(..) | Inst::SetGlobal(..) | Inst::Unpack(..) | Inst::Index(..) | Inst::SetIndex(..) => false,
+            Inst::Unary(op, v) => match op {
+                UnOp::Not => true,
+                UnOp::Neg => self.ty(*v) == Dec,
+                UnOp::BitNot => self.ty(*v) == Int,
+            },
+            Inst::Binary(op, a, b) => {
+                let (a, b) = (self.ty(*a), self.ty(*b));
+                match op {
+                    BinOp::Eq | BinOp::Ne => true,
+                    BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge =>
+                        (numeric(a) && numeric(b)) || (a == b && matches!(a, Str | Char)),
+                    BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor => a == Int && b == Int,
+                    BinOp::Add if a == Str || b == Str => true,
+                    // Integer arithmetic can overflow or divide by zero, decimal arithmetic can't fail
+                    BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod | BinOp::Pow =>
+                        numeric(a) && numeric(b) && (a == Dec || b == Dec),
+                    BinOp::Shl | BinOp::Shr => false,
+                    BinOp::In => matches!(b, Map | Set),
+                }
+            },
+        }
+    }
+
+    pub fn block(&self, block: BlockId) -> &Block {
+        &self.blocks[block.0 as usize]
+    }
+
+    pub fn block_ids(&self) -> impl Iterator<Item = BlockId> {
+        (0..self.blocks.len() as u32).map(BlockId)
+    }
+
+    /// Predecessors of every block, indexed by block id
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![vec![]; self.blocks.len()];
+
+        for (id, block) in self.block_ids().zip(&self.blocks) {
+            for succ in block.terminator.successors() {
+                if !preds[succ.0 as usize].contains(&id) {
+                    preds[succ.0 as usize].push(id);
+                }
+            }
+        }
+
+        preds
+    }
+
+    /// Blocks reachable from the entry in reverse postorder
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut order = vec![];
+        let mut stack = vec![(Self::ENTRY, 0)];
+        visited[0] = true;
+
+        while let Some((block, next)) = stack.pop() {
+            let succs = self.block(block).terminator.successors();
+
+            match succs.get(next) {
+                Some(succ) => {
+                    stack.push((block, next + 1));
+                    if !visited[succ.0 as usize] {
+                        visited[succ.0 as usize] = true;
+                        stack.push((*succ, 0));
+                    }
+                },
+                None => order.push(block),
+            }
+        }
+
+        order.reverse();
+        order
+    }
+
+    /// Immediate dominator of each reachable block, `None` for the entry and unreachable blocks
+    pub fn dominators(&self) -> Vec<Option<BlockId>> {
+        let rpo = self.reverse_postorder();
+        let preds = self.predecessors();
+
+        let mut index = vec![usize::MAX; self.blocks.len()];
+        for (i, b) in rpo.iter().enumerate() {
+            index[b.0 as usize] = i;
+        }
+
+        let mut idom: Vec<Option<BlockId>> = vec![None; self.blocks.len()];
+        idom[0] = Some(Self::ENTRY);
+
+        let intersect = |idom: &[Option<BlockId>], mut a: BlockId, mut b: BlockId| {
+            while a != b {
+                while index[a.0 as usize] > index[b.0 as usize] {
+                    a = idom[a.0 as usize].unwrap();
+                }
+                while index[b.0 as usize] > index[a.0 as usize] {
+                    b = idom[b.0 as usize].unwrap();
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for &block in rpo.iter().skip(1) {
+                let mut new = None;
+
+                for &pred in &preds[block.0 as usize] {
+                    if idom[pred.0 as usize].is_none() {
+                        continue
+                    }
+                    new = Some(match new {
+                        None => pred,
+                        Some(cur) => intersect(&idom, pred, cur),
+                    });
+                }
+
+                if new.is_some() && idom[block.0 as usize] != new {
+                    idom[block.0 as usize] = new;
+                    changed = true;
+                }
+            }
+        }
+
+        idom[0] = None;
+        idom
+    }
+
+    /// Replaces every use of `from` with `to`
+    pub fn replace_uses(&mut self, from: Value, to: Value) {
+        for data in &mut self.values {
+            for op in data.inst.operands_mut() {
+                if *op == from {
+                    *op = to;
+                }
+            }
+        }
+
+        for block in &mut self.blocks {
+            match &mut block.terminator {
+                Terminator::Branch(v, ..) | Terminator::Return(v) if *v == from => *v = to,
+                _ => (),
+            }
+        }
+    }
+
+    /// Forgets the incoming values from `pred` in the phis of `block`, after removing the edge between them
+    pub fn remove_incoming(&mut self, block: BlockId, pred: BlockId) {
+        for value in &self.blocks[block.0 as usize].insts {
+            if let Inst::Phi(incoming) = &mut self.values[value.0 as usize].inst {
+                incoming.retain(|(b, _)| *b != pred);
+            }
+        }
+    }
+
+    /// Drops blocks that can't be reached from the entry, renumbering the rest
+    pub fn remove_unreachable(&mut self) {
+        let reachable = self.reverse_postorder();
+
+        if reachable.len() == self.blocks.len() {
+            return
+        }
+
+        let mut keep = reachable.clone();
+        keep.sort();
+
+        let mut renumber = vec![None; self.blocks.len()];
+        for (new, old) in keep.iter().enumerate() {
+            renumber[old.0 as usize] = Some(BlockId(new as u32));
+        }
+
+        let map = |b: &mut BlockId| *b = renumber[b.0 as usize].unwrap();
+
+        let blocks = std::mem::take(&mut self.blocks);
+        for (old, mut block) in blocks.into_iter().enumerate() {
+            if renumber[old].is_none() {
+                continue
+            }
+
+            match &mut block.terminator {
+                Terminator::Jump(b) => map(b),
+                Terminator::Branch(_, t, e) => { map(t); map(e); },
+                _ => (),
+            }
+
+            for value in &block.insts {
+                if let Inst::Phi(incoming) = &mut self.values[value.0 as usize].inst {
+                    incoming.retain(|(b, _)| renumber[b.0 as usize].is_some());
+                    incoming.iter_mut().for_each(|(b, _)| map(b));
+                }
+            }
+
+            self.blocks.push(block);
+        }
+    }
+
+    /// Replaces phis whose operands are all the same value (or the phi itself) with that value
+    pub fn remove_trivial_phis(&mut self) -> bool {
+        let mut any = false;
+
+        loop {
+            let mut changed = false;
+
+            for b in 0..self.blocks.len() {
+                let mut i = 0;
+
+                while i < self.blocks[b].insts.len() {
+                    let phi = self.blocks[b].insts[i];
+
+                    let same = match self.inst(phi) {
+                        Inst::Phi(incoming) => {
+                            let values = incoming.iter()
+                                .map(|(_, v)| *v)
+                                .filter(|v| *v != phi)
+                                .collect::<HashSet<_>>();
+
+                            if values.len() == 1 { values.into_iter().next() } else { None }
+                        },
+                        _ => None,
+                    };
+
+                    match same {
+                        Some(value) => {
+                            self.blocks[b].insts.remove(i);
+                            self.replace_uses(phi, value);
+                            changed = true;
+                        },
+                        None => i += 1,
+                    }
+                }
+            }
+
+            if !changed {
+                return any
+            }
+            any = true;
+        }
+    }
+
+    /// Drops values that aren't placed in a block and renumbers the rest in block order
+    pub fn compact(&mut self) {
+        let mut renumber = vec![None; self.values.len()];
+        let mut values = vec![];
+
+        for block in &mut self.blocks {
+            for value in &mut block.insts {
+                renumber[value.0 as usize] = Some(Value(values.len() as u32));
+                values.push(self.values[value.0 as usize].clone());
+                *value = Value(values.len() as u32 - 1);
+            }
+        }
+
+        // Operands of placed values are placed too, in a well formed function
+        let map = |v: &mut Value| *v = renumber[v.0 as usize].unwrap_or(*v);
+
+        for data in &mut values {
+            data.inst.operands_mut().into_iter().for_each(map);
+        }
+
+        for block in &mut self.blocks {
+            match &mut block.terminator {
+                Terminator::Branch(v, ..) | Terminator::Return(v) => map(v),
+                _ => (),
+            }
+        }
+
+        self.values = values;
+    }
+
+    /// Recomputes value types, propagating through phis and copies until they settle
+    pub fn infer_types(&mut self) {
+        let mut known: Vec<Option<Type>> = vec![None; self.values.len()];
+
+        loop {
+            let mut changed = false;
+
+            for block in &self.blocks {
+                for value in &block.insts {
+                    let i = value.0 as usize;
+                    let ty = |v: &Value| known[v.0 as usize];
+
+                    let new = match &self.values[i].inst {
+                        Inst::Const(c) => Some(c.ty()),
+                        Inst::Copy(v) => ty(v),
+                        Inst::Unary(UnOp::Not, _) => Some(Type::Bool),
+                        Inst::Unary(UnOp::BitNot, _) => Some(Type::Int),
+                        Inst::Unary(UnOp::Neg, v) => ty(v).map(|t| match t {
+                            Type::Int | Type::Dec => t,
+                            _ => Type::Any,
+                        }),
+                        Inst::Binary(op, a, b) => match (ty(a), ty(b)) {
+                            (Some(a), Some(b)) => Some(op.result(a, b)),
+                            _ => None,
+                        },
+                        Inst::SetGlobal(..) | Inst::SetIndex(..) => Some(Type::Null),
+                        Inst::Array(_) => Some(Type::Array),
+                        Inst::Tuple(_) => Some(Type::Tuple),
+                        Inst::Map(_) => Some(Type::Map),
+                        Inst::Set(_) => Some(Type::Set),
+                        Inst::Unpack(_, ty, _) => Some(*ty),
+                        Inst::Param(_) | Inst::Call(..) | Inst::GetGlobal(_) | Inst::Index(..) => Some(Type::Any),
+                        Inst::Phi(incoming) => incoming.iter()
+                            .filter_map(|(_, v)| ty(v))
+                            .reduce(Type::join),
+                    };
+
+                    if new.is_some() && known[i] != new {
+                        known[i] = new;
+                        changed = true;
+                    }
+                }
+            }
+
+            if !changed {
+                break
+            }
+        }
+
+        for (data, ty) in self.values.iter_mut().zip(known) {
+            data.ty = ty.unwrap_or(Type::Any);
+        }
+    }
+}
+
+/// Whether block `a` dominates block `b`, given the immediate dominators from `Function::dominators`
+pub fn dominates(idom: &[Option<BlockId>], a: BlockId, mut b: BlockId) -> bool {
+    loop {
+        if a == b {
+            return true
+        }
+        match idom[b.0 as usize] {
+            Some(up) => b = up,
+            None => return false,
+        }
+    }
+}
+
+/// A lowered program. `__init` runs global initializers before `main` is called
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub globals: Vec<String>,
+    pub functions: Vec<Function>,
+}
+
+impl Module {
+    pub const INIT: &'static str = "__init";
+
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl Display for BlockId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let name = match self {
+            Type::Null => "null",
+            Type::Int => "int",
+            Type::Dec => "dec",
+            Type::Bool => "bool",
+            Type::Char => "char",
+            Type::Str => "str",
+            Type::Array => "array",
+            Type::Tuple => "tuple",
+            Type::Map => "map",
+            Type::Set => "set",
+            Type::Func => "func",
+            Type::Any => "any",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Constant::Null => write!(f, "null"),
+            Constant::Int(i) => write!(f, "{}", i),
+            Constant::Dec(d) => write!(f, "{:?}", d),
+            Constant::Bool(b) => write!(f, "{}", b),
+            Constant::Char(c) => write!(f, "{:?}", c),
+            Constant::Str(s) => write!(f, "{:?}", s),
+            Constant::Func(name) => write!(f, "@{}", name),
+        }
+    }
+}
+
+fn list(values: &[Value]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl Display for Inst {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Inst::Const(c) => write!(f, "const {}", c),
+            Inst::Param(i) => write!(f, "param {}", i),
+            Inst::Copy(v) => write!(f, "copy {}", v),
+            Inst::Unary(op, v) => write!(f, "{} {}", op.name(), v),
+            Inst::Binary(op, a, b) => write!(f, "{} {}, {}", op.name(), a, b),
+            Inst::Call(Callee::Direct(name), args) => write!(f, "call @{}({})", name, list(args)),
+            Inst::Call(Callee::Builtin(name), args) => write!(f, "call builtin {}({})", name, list(args)),
+            Inst::Call(Callee::Indirect(target), args) => write!(f, "call {}({})", target, list(args)),
+            Inst::GetGlobal(name) => write!(f, "get_global @{}", name),
+            Inst::SetGlobal(name, v) => write!(f, "set_global @{}, {}", name, v),
+            Inst::Array(elements) => write!(f, "array [{}]", list(elements)),
+            Inst::Tuple(elements) => write!(f, "tuple ({})", list(elements)),
+            Inst::Map(entries) => {
+                let entries = entries.iter().map(|(k, v)| format!("{}: {}", k, v)).collect::<Vec<_>>();
+                write!(f, "map {{{}}}", entries.join(", "))
+            },
+            Inst::Set(elements) => write!(f, "set {{{}}}", list(elements)),
+            Inst::Unpack(v, ty, len) => write!(f, "unpack {}, {} {}", v, ty, len),
+            Inst::Index(a, i) => write!(f, "index {}, {}", a, i),
+            Inst::SetIndex(a, i, v) => write!(f, "set_index {}, {}, {}", a, i, v),
+            Inst::Phi(incoming) => {
+                let incoming = incoming.iter().map(|(b, v)| format!("[{}: {}]", b, v)).collect::<Vec<_>>();
+                write!(f, "phi {}", incoming.join(", "))
+            },
+        }
+    }
+}
+
+impl Display for Terminator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Terminator::Jump(b) => write!(f, "jump {}", b),
+            Terminator::Branch(v, t, e) => write!(f, "branch {}, {}, {}", v, t, e),
+            Terminator::Return(v) => write!(f, "ret {}", v),
+            Terminator::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f, "fn @{}/{} {{", self.name, self.params)?;
+
+        let preds = self.predecessors();
+
+        for (id, block) in self.block_ids().zip(&self.blocks) {
+            write!(f, "{}:", id)?;
+            if !preds[id.0 as usize].is_empty() {
+                let preds = preds[id.0 as usize].iter().map(|b| b.to_string()).collect::<Vec<_>>();
+                write!(f, "    ; preds: {}", preds.join(", "))?;
+            }
+            writeln!(f)?;
+
+            for value in &block.insts {
+                let data = &self.values[value.0 as usize];
+                writeln!(f, "    {}: {} = {}", value, data.ty, data.inst)?;
+            }
+
+            writeln!(f, "    {}", block.terminator)?;
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+impl Display for Module {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        for global in &self.globals {
+            writeln!(f, "global @{}", global)?;
+        }
+
+        for (i, func) in self.functions.iter().enumerate() {
+            if i > 0 || !self.globals.is_empty() {
+                writeln!(f)?;
+            }
+            write!(f, "{}", func)?;
+        }
+
+        Ok(())
+    }
+}
\ No newline at end of file
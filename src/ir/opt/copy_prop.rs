@@ -0,0 +1,39 @@
+use super::super::*;
+use super::Pass;
+
+/// Replaces uses of copies, and of phis merging a single value, with the original value
+pub struct CopyPropagation;
+
+impl Pass for CopyPropagation {
+    fn name(&self) -> &'static str {
+        "copy-prop"
+    }
+
+    fn run(&self, module: &mut Module) -> bool {
+        let mut changed = false;
+
+        for func in &mut module.functions {
+            for b in 0..func.blocks.len() {
+                let mut i = 0;
+
+                while i < func.blocks[b].insts.len() {
+                    let value = func.blocks[b].insts[i];
+
+                    match *func.inst(value) {
+                        Inst::Copy(source) => {
+                            func.blocks[b].insts.remove(i);
+                            func.replace_uses(value, source);
+                            changed = true;
+                        },
+                        _ => i += 1,
+                    }
+                }
+            }
+
+            changed |= func.remove_trivial_phis();
+            func.infer_types();
+        }
+
+        changed
+    }
+}
\ No newline at end of file
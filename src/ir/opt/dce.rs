@@ -0,0 +1,106 @@
+use super::super::*;
+use super::Pass;
+
+/// Removes unreachable blocks and pure instructions whose results are never used,
+/// then merges blocks into their only predecessor when it jumps straight to them
+pub struct DeadCodeElimination;
+
+impl Pass for DeadCodeElimination {
+    fn name(&self) -> &'static str {
+        "dce"
+    }
+
+    fn run(&self, module: &mut Module) -> bool {
+        let mut changed = false;
+
+        for func in &mut module.functions {
+            let blocks = func.blocks.len();
+            func.remove_unreachable();
+
+            changed |= func.blocks.len() != blocks;
+            changed |= remove_dead_values(func);
+            changed |= merge_blocks(func);
+        }
+
+        changed
+    }
+}
+
+fn remove_dead_values(func: &mut Function) -> bool {
+    let mut live = vec![false; func.values.len()];
+    let mut worklist = vec![];
+
+    for block in &func.blocks {
+        for value in &block.insts {
+            if !func.is_pure(*value) {
+                worklist.push(*value);
+            }
+        }
+
+        if let Terminator::Branch(v, ..) | Terminator::Return(v) = block.terminator {
+            worklist.push(v);
+        }
+    }
+
+    while let Some(value) = worklist.pop() {
+        if live[value.0 as usize] {
+            continue
+        }
+
+        live[value.0 as usize] = true;
+        worklist.extend(func.inst(value).operands());
+    }
+
+    let mut changed = false;
+
+    for block in &mut func.blocks {
+        let len = block.insts.len();
+        block.insts.retain(|v| live[v.0 as usize]);
+        changed |= block.insts.len() != len;
+    }
+
+    changed
+}
+
+fn merge_blocks(func: &mut Function) -> bool {
+    let mut changed = false;
+
+    loop {
+        let preds = func.predecessors();
+
+        let pair = func.block_ids().find_map(|a| match func.block(a).terminator {
+            Terminator::Jump(b) if b != a && b != Function::ENTRY && preds[b.0 as usize] == [a] => Some((a, b)),
+            _ => None,
+        });
+
+        let Some((a, b)) = pair else { break };
+
+        let insts = std::mem::take(&mut func.blocks[b.0 as usize].insts);
+        let terminator = std::mem::replace(&mut func.blocks[b.0 as usize].terminator, Terminator::Unreachable);
+
+        for value in insts {
+            match func.inst(value) {
+                // With a single predecessor a phi has a single incoming value
+                Inst::Phi(incoming) => {
+                    let (_, v) = incoming[0];
+                    func.replace_uses(value, v);
+                },
+                _ => func.blocks[a.0 as usize].insts.push(value),
+            }
+        }
+
+        for succ in terminator.successors() {
+            for value in func.blocks[succ.0 as usize].insts.clone() {
+                if let Inst::Phi(incoming) = &mut func.values[value.0 as usize].inst {
+                    incoming.iter_mut().filter(|(p, _)| *p == b).for_each(|(p, _)| *p = a);
+                }
+            }
+        }
+
+        func.blocks[a.0 as usize].terminator = terminator;
+        func.remove_unreachable();
+        changed = true;
+    }
+
+    changed
+}
\ No newline at end of file
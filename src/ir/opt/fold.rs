@@ -0,0 +1,177 @@
+use super::super::super::codegen::value::Value as Runtime;
+use super::super::*;
+use super::Pass;
+
+/// Evaluates operations on constants at compile time with the VM's own arithmetic, so folding
+/// never changes a result or hides a runtime error. \
+/// Also drops the arithmetic identities `x + 0`, `x - 0`, `x * 1` and `x / 1` on integers, and
+/// turns branches on a constant into jumps
+pub struct ConstantFolding;
+
+impl Pass for ConstantFolding {
+    fn name(&self) -> &'static str {
+        "fold"
+    }
+
+    fn run(&self, module: &mut Module) -> bool {
+        let mut changed = false;
+
+        for func in &mut module.functions {
+            changed |= fold(func);
+        }
+
+        changed
+    }
+}
+
+fn fold(func: &mut Function) -> bool {
+    let mut changed = false;
+
+    // Types sharpen as operands fold, which may expose more identities
+    loop {
+        func.infer_types();
+
+        let mut round = false;
+
+        for b in 0..func.blocks.len() {
+            for i in 0..func.blocks[b].insts.len() {
+                let value = func.blocks[b].insts[i];
+
+                if let Some(inst) = simplify(func, value) {
+                    func.values[value.0 as usize].inst = inst;
+                    round = true;
+                }
+            }
+        }
+
+        if !round {
+            break
+        }
+        changed = true;
+    }
+
+    for b in func.block_ids().collect::<Vec<_>>() {
+        if let Terminator::Branch(cond, then, otherwise) = func.block(b).terminator.clone() {
+            let taken = match func.inst(cond) {
+                Inst::Const(c) => if truthy(c) { then } else { otherwise },
+                _ => continue,
+            };
+
+            let skipped = if taken == then { otherwise } else { then };
+            if skipped != taken {
+                func.remove_incoming(skipped, b);
+            }
+
+            func.blocks[b.0 as usize].terminator = Terminator::Jump(taken);
+            changed = true;
+        }
+    }
+
+    if changed {
+        func.remove_unreachable();
+        func.remove_trivial_phis();
+        func.infer_types();
+    }
+
+    changed
+}
+
+/// The replacement for `value` if it can be simplified
+fn simplify(func: &Function, value: Value) -> Option<Inst> {
+    let constant = |v: &Value| match func.inst(*v) {
+        Inst::Const(c) => runtime(c),
+        _ => None,
+    };
+
+    let result = match func.inst(value) {
+        Inst::Unary(op, v) => {
+            let v = constant(v)?;
+            match op {
+                UnOp::Neg => v.negate().ok(),
+                UnOp::Not => Some(v.not()),
+                UnOp::BitNot => v.bit_not().ok(),
+            }
+        },
+        Inst::Binary(op, a, b) => match (constant(a), constant(b)) {
+            (Some(a), Some(b)) => binary(*op, &a, &b),
+            _ => return identity(func, *op, *a, *b),
+        },
+        _ => return None,
+    };
+
+    result.and_then(|v| constant_of(&v)).map(Inst::Const)
+}
+
+/// `x op c` that leaves an integer `x` as it is
+fn identity(func: &Function, op: BinOp, a: Value, b: Value) -> Option<Inst> {
+    let is = |v: Value, n: i64| matches!(func.inst(v), Inst::Const(Constant::Int(i)) if *i == n);
+
+    if func.ty(a) != Type::Int {
+        return None
+    }
+
+    match op {
+        BinOp::Add | BinOp::Sub if is(b, 0) => Some(Inst::Copy(a)),
+        BinOp::Mul | BinOp::Div if is(b, 1) => Some(Inst::Copy(a)),
+        BinOp::Add if is(a, 0) && func.ty(b) == Type::Int => Some(Inst::Copy(b)),
+        _ => None,
+    }
+}
+
+/// `None` when evaluating fails, leaving the error to runtime
+fn binary(op: BinOp, a: &Runtime, b: &Runtime) -> Option<Runtime> {
+    use BinOp::*;
+    let result = match op {
+        Add => a.add(b),
+        Sub => a.subtract(b),
+        Mul => a.multiply(b),
+        Div => a.divide(b),
+        Mod => a.modulo(b),
+        Pow => a.power(b),
+        BitAnd => a.bit_and(b),
+        BitOr => a.bit_or(b),
+        BitXor => a.bit_xor(b),
+        Shl => a.shift_left(b),
+        Shr => a.shift_right(b),
+        Eq => Ok(Runtime::Boolean(a == b)),
+        Ne => Ok(Runtime::Boolean(a != b)),
+        Lt => a.less(b),
+        Le => a.less_equal(b),
+        Gt => a.greater(b),
+        Ge => a.greater_equal(b),
+        In => b.contains(a),
+    };
+
+    result.ok()
+}
+
+fn truthy(c: &Constant) -> bool {
+    match c {
+        Constant::Func(_) => true,
+        c => runtime(c).unwrap().truthy(),
+    }
+}
+
+fn runtime(c: &Constant) -> Option<Runtime> {
+    Some(match c {
+        Constant::Null => Runtime::Null,
+        Constant::Int(i) => Runtime::Integer(*i),
+        Constant::Dec(d) => Runtime::Decimal(*d),
+        Constant::Bool(b) => Runtime::Boolean(*b),
+        Constant::Char(c) => Runtime::Character(*c),
+        Constant::Str(s) => Runtime::String(s.as_str().into()),
+        Constant::Func(_) => return None,
+    })
+}
+
+fn constant_of(v: &Runtime) -> Option<Constant> {
+    Some(match v {
+        Runtime::Null => Constant::Null,
+        Runtime::Integer(i) => Constant::Int(*i),
+        Runtime::Decimal(d) => Constant::Dec(*d),
+        Runtime::Boolean(b) => Constant::Bool(*b),
+        Runtime::Character(c) => Constant::Char(*c),
+        Runtime::String(s) => Constant::Str(s.to_string()),
+        Runtime::Array(_) | Runtime::Tuple(_) | Runtime::Map(_) | Runtime::Set(_) | Runtime::Range(..) | Runtime::Function(_) | Runtime::Native(_) => return None,
+    })
+}
\ No newline at end of file
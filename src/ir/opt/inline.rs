@@ -0,0 +1,146 @@
+use super::super::*;
+use super::Pass;
+use std::collections::HashSet;
+
+/// Replaces direct calls to functions of at most `threshold` instructions with a copy of their body. \
+/// Functions that call themselves are never inlined, and code inlined in one run isn't inlined
+/// into again until the next, so mutual recursion can't grow a function without bound
+pub struct Inlining {
+    pub threshold: usize,
+}
+
+impl Pass for Inlining {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn run(&self, module: &mut Module) -> bool {
+        let mut changed = false;
+
+        for caller in 0..module.functions.len() {
+            let name = module.functions[caller].name.clone();
+            let sites = module.functions[caller].blocks.iter()
+                .flat_map(|b| b.insts.iter().copied())
+                .collect::<HashSet<_>>();
+
+            while let Some((block, pos, callee)) = self.find_site(module, caller, &sites, &name) {
+                inline_call(&mut module.functions[caller], block, pos, &callee);
+                changed = true;
+            }
+
+            if changed {
+                module.functions[caller].infer_types();
+            }
+        }
+
+        changed
+    }
+}
+
+impl Inlining {
+    /// The first call among `sites` to a function worth inlining
+    fn find_site(&self, module: &Module, caller: usize, sites: &HashSet<Value>, name: &str) -> Option<(usize, usize, Function)> {
+        let func = &module.functions[caller];
+
+        for (b, block) in func.blocks.iter().enumerate() {
+            for (pos, value) in block.insts.iter().enumerate() {
+                let callee = match func.inst(*value) {
+                    Inst::Call(Callee::Direct(callee), _) if sites.contains(value) && callee != name => callee,
+                    _ => continue,
+                };
+
+                match module.function(callee) {
+                    Some(callee) if self.inlinable(callee) => return Some((b, pos, callee.clone())),
+                    _ => (),
+                }
+            }
+        }
+
+        None
+    }
+
+    fn inlinable(&self, func: &Function) -> bool {
+        let size = func.blocks.iter().map(|b| b.insts.len() + 1).sum::<usize>();
+
+        let recursive = func.blocks.iter()
+            .flat_map(|b| &b.insts)
+            .any(|v| matches!(func.inst(*v), Inst::Call(Callee::Direct(name), _) if *name == func.name));
+
+        size <= self.threshold && !recursive
+    }
+}
+
+/// Splits the block at the call, jumps into a renumbered copy of the callee's blocks
+/// and merges its returns back into the call's value at the start of the continuation
+fn inline_call(caller: &mut Function, block: usize, pos: usize, callee: &Function) {
+    let call = caller.blocks[block].insts[pos];
+    let args = match caller.inst(call) {
+        Inst::Call(_, args) => args.clone(),
+        _ => unreachable!(),
+    };
+
+    let rest = caller.blocks[block].insts.split_off(pos + 1);
+    caller.blocks[block].insts.pop();
+
+    let cont = BlockId(caller.blocks.len() as u32);
+    let terminator = std::mem::replace(&mut caller.blocks[block].terminator, Terminator::Unreachable);
+
+    for succ in terminator.successors() {
+        for value in caller.blocks[succ.0 as usize].insts.clone() {
+            if let Inst::Phi(incoming) = &mut caller.values[value.0 as usize].inst {
+                incoming.iter_mut().filter(|(p, _)| p.0 as usize == block).for_each(|(p, _)| *p = cont);
+            }
+        }
+    }
+
+    caller.blocks.push(Block { insts: rest, terminator });
+
+    let block_offset = caller.blocks.len() as u32;
+    let value_offset = caller.values.len() as u32;
+    let map_block = |b: BlockId| BlockId(b.0 + block_offset);
+    let map_value = |v: Value| Value(v.0 + value_offset);
+
+    for data in &callee.values {
+        let mut inst = match &data.inst {
+            Inst::Param(i) => Inst::Copy(args[*i]),
+            inst => {
+                let mut inst = inst.clone();
+                inst.operands_mut().into_iter().for_each(|v| *v = map_value(*v));
+                inst
+            },
+        };
+
+        if let Inst::Phi(incoming) = &mut inst {
+            incoming.iter_mut().for_each(|(b, _)| *b = map_block(*b));
+        }
+
+        caller.values.push(InstData { inst, ty: data.ty });
+    }
+
+    let mut returns = vec![];
+
+    for (id, b) in callee.block_ids().zip(&callee.blocks) {
+        let terminator = match &b.terminator {
+            Terminator::Jump(target) => Terminator::Jump(map_block(*target)),
+            Terminator::Branch(v, t, e) => Terminator::Branch(map_value(*v), map_block(*t), map_block(*e)),
+            Terminator::Return(v) => {
+                returns.push((map_block(id), map_value(*v)));
+                Terminator::Jump(cont)
+            },
+            Terminator::Unreachable => Terminator::Unreachable,
+        };
+
+        let insts = b.insts.iter().map(|v| map_value(*v)).collect();
+        caller.blocks.push(Block { insts, terminator });
+    }
+
+    caller.blocks[block].terminator = Terminator::Jump(map_block(Function::ENTRY));
+
+    caller.values[call.0 as usize].inst = match returns.len() {
+        0 => Inst::Const(Constant::Null),
+        1 => Inst::Copy(returns[0].1),
+        _ => Inst::Phi(returns),
+    };
+
+    caller.blocks[cont.0 as usize].insts.insert(0, call);
+}
\ No newline at end of file
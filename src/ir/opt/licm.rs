@@ -0,0 +1,123 @@
+use super::super::*;
+use super::Pass;
+
+/// Moves pure computations whose operands are all defined outside a loop into the loop's preheader. \
+/// Only instructions that can't fail are hoisted, as the loop body might never have run them. Arrays
+/// and maps are left alone since each evaluation creates a new one, and globals are only read early from loops
+/// without calls or stores, when the header reads them and so would have failed on entry anyway
+pub struct LoopInvariantCodeMotion;
+
+impl Pass for LoopInvariantCodeMotion {
+    fn name(&self) -> &'static str {
+        "licm"
+    }
+
+    fn run(&self, module: &mut Module) -> bool {
+        let mut changed = false;
+
+        for func in &mut module.functions {
+            // Hoisting out of an inner loop can make the code invariant in the outer loop
+            while hoist(func) {
+                changed = true;
+            }
+        }
+
+        changed
+    }
+}
+
+/// A natural loop: its header and every block that can reach a back edge without passing the header
+struct Loop {
+    header: BlockId,
+    body: Vec<bool>,
+}
+
+fn loops(func: &Function, idom: &[Option<BlockId>], preds: &[Vec<BlockId>]) -> Vec<Loop> {
+    let mut loops: Vec<Loop> = vec![];
+
+    for latch in func.block_ids() {
+        for header in func.block(latch).terminator.successors() {
+            if !dominates(idom, header, latch) {
+                continue
+            }
+
+            let index = match loops.iter().position(|l| l.header == header) {
+                Some(i) => i,
+                None => {
+                    let mut body = vec![false; func.blocks.len()];
+                    body[header.0 as usize] = true;
+                    loops.push(Loop { header, body });
+                    loops.len() - 1
+                },
+            };
+
+            let body = &mut loops[index].body;
+            let mut worklist = vec![latch];
+
+            while let Some(b) = worklist.pop() {
+                if !body[b.0 as usize] {
+                    body[b.0 as usize] = true;
+                    worklist.extend(&preds[b.0 as usize]);
+                }
+            }
+        }
+    }
+
+    loops
+}
+
+fn hoist(func: &mut Function) -> bool {
+    let idom = func.dominators();
+    let preds = func.predecessors();
+    let order = func.reverse_postorder();
+
+    let mut placement = vec![None; func.values.len()];
+    for (id, block) in func.block_ids().zip(&func.blocks) {
+        for value in &block.insts {
+            placement[value.0 as usize] = Some(id);
+        }
+    }
+
+    let mut changed = false;
+
+    for l in loops(func, &idom, &preds) {
+        let outside = preds[l.header.0 as usize].iter().filter(|p| !l.body[p.0 as usize]).collect::<Vec<_>>();
+
+        // Lowering always enters a loop from a single block, which then serves as the preheader
+        let preheader = match outside[..] {
+            [p] if func.block(*p).terminator == Terminator::Jump(l.header) => *p,
+            _ => continue,
+        };
+
+        let inside = |b: Option<BlockId>| b.is_some_and(|b| l.body[b.0 as usize]);
+
+        let stores = order.iter().filter(|b| l.body[b.0 as usize]).any(|b| func.block(*b).insts.iter()
+            .any(|v| matches!(func.inst(*v), Inst::Call(..) | Inst::SetGlobal(..))));
+
+        for &b in order.iter().filter(|b| l.body[b.0 as usize]) {
+            let mut i = 0;
+
+            while i < func.blocks[b.0 as usize].insts.len() {
+                let value = func.blocks[b.0 as usize].insts[i];
+
+                let invariant = match func.inst(value) {
+                    Inst::Phi(_) | Inst::Array(_) | Inst::Map(_) => false,
+                    Inst::GetGlobal(_) => !stores && b == l.header,
+                    inst => func.is_pure(value) && inst.operands().iter().all(|v| !inside(placement[v.0 as usize])),
+                };
+
+                if invariant {
+                    func.blocks[b.0 as usize].insts.remove(i);
+                    func.blocks[preheader.0 as usize].insts.push(value);
+                    placement[value.0 as usize] = Some(preheader);
+                    changed = true;
+                }
+                else {
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    changed
+}
\ No newline at end of file
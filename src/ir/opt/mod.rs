@@ -0,0 +1,125 @@
+pub mod fold;
+pub mod dce;
+pub mod copy_prop;
+pub mod inline;
+pub mod licm;
+
+use super::{ Module, VerifyError };
+use super::verify::verify;
+use std::io::Write;
+
+/// A transformation over a whole module
+pub trait Pass {
+    fn name(&self) -> &'static str;
+
+    /// Returns whether anything changed
+    fn run(&self, module: &mut Module) -> bool;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    O0,
+    O1,
+    O2,
+    O3,
+}
+
+impl OptLevel {
+    /// Parses the `-O0` to `-O3` flags
+    pub fn from_flag(flag: &str) -> Option<OptLevel> {
+        match flag {
+            "-O0" => Some(OptLevel::O0),
+            "-O1" => Some(OptLevel::O1),
+            "-O2" => Some(OptLevel::O2),
+            "-O3" => Some(OptLevel::O3),
+            _ => None,
+        }
+    }
+}
+
+/// Runs passes in order, verifying the module after each one. \
+/// With `dump` set the module is printed before and after every pass
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    /// How often the whole pipeline may run while it keeps changing the module
+    rounds: usize,
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self {
+            passes: vec![],
+            rounds: 1,
+        }
+    }
+
+    /// The pipeline for an optimization level: \
+    /// `-O1` folds constants and cleans up, `-O2` also inlines small functions and hoists
+    /// loop invariant code, `-O3` inlines larger functions and repeats until nothing changes
+    pub fn for_level(level: OptLevel) -> Self {
+        let mut pm = Self::new();
+
+        if level >= OptLevel::O1 {
+            pm.add(fold::ConstantFolding).add(copy_prop::CopyPropagation).add(dce::DeadCodeElimination);
+        }
+
+        if level >= OptLevel::O2 {
+            let threshold = if level == OptLevel::O3 { 60 } else { 20 };
+
+            pm.add(inline::Inlining { threshold })
+                .add(fold::ConstantFolding)
+                .add(copy_prop::CopyPropagation)
+                .add(licm::LoopInvariantCodeMotion)
+                .add(dce::DeadCodeElimination);
+        }
+
+        if level == OptLevel::O3 {
+            pm.rounds = 4;
+        }
+
+        pm
+    }
+
+    pub fn add(&mut self, pass: impl Pass + 'static) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    pub fn run(&self, module: &mut Module, mut dump: Option<&mut dyn Write>) -> Result<(), VerifyError> {
+        for _ in 0..self.rounds {
+            let mut changed = false;
+
+            for pass in &self.passes {
+                if let Some(out) = dump.as_mut() {
+                    let _ = write!(out, "; IR before {}\n{}\n", pass.name(), module);
+                }
+
+                let result = pass.run(module);
+                changed |= result;
+
+                for func in &mut module.functions {
+                    func.compact();
+                }
+
+                verify(module)?;
+
+                if let Some(out) = dump.as_mut() {
+                    let status = if result { "changed" } else { "unchanged" };
+                    let _ = write!(out, "; IR after {} ({})\n{}\n", pass.name(), status, module);
+                }
+            }
+
+            if !changed {
+                break
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for PassManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
\ No newline at end of file
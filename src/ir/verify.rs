@@ -0,0 +1,123 @@
+use super::*;
+
+type VerifyResult = std::result::Result<(), VerifyError>;
+
+/// Checks that every function of `module` is well formed SSA: \
+/// blocks end in a terminator with valid targets, every value is defined once and dominates its uses,
+/// phis lead their block with one operand per predecessor, and calls and globals refer to declared names
+pub fn verify(module: &Module) -> VerifyResult {
+    for func in &module.functions {
+        verify_function(module, func)?;
+    }
+
+    Ok(())
+}
+
+pub fn verify_function(module: &Module, func: &Function) -> VerifyResult {
+    let name = || func.name.clone();
+
+    for block in &func.blocks {
+        for succ in block.terminator.successors() {
+            if succ.0 as usize >= func.blocks.len() {
+                return Err(VerifyError::InvalidBlock(name(), succ))
+            }
+        }
+    }
+
+    // Where each value is placed, as `(block, position)`
+    let mut placement = vec![None; func.values.len()];
+
+    for (id, block) in func.block_ids().zip(&func.blocks) {
+        for (pos, value) in block.insts.iter().enumerate() {
+            match placement.get_mut(value.0 as usize) {
+                None => return Err(VerifyError::UndefinedValue(name(), *value)),
+                Some(Some(_)) => return Err(VerifyError::MultipleDefinitions(name(), *value)),
+                Some(slot) => *slot = Some((id, pos)),
+            }
+        }
+    }
+
+    let preds = func.predecessors();
+
+    if !preds[0].is_empty() {
+        return Err(VerifyError::EntryHasPredecessors(name()))
+    }
+
+    let idom = func.dominators();
+    let reachable = |b: BlockId| b == Function::ENTRY || idom[b.0 as usize].is_some();
+
+    // Checks that `value` exists and is available at position `pos` of `block`, the end if `None`
+    let available = |value: Value, block: BlockId, pos: Option<usize>| -> VerifyResult {
+        let (def_block, def_pos) = match placement.get(value.0 as usize) {
+            None => return Err(VerifyError::UndefinedValue(name(), value)),
+            Some(None) => return Err(VerifyError::UnplacedValue(name(), value)),
+            Some(Some(placed)) => *placed,
+        };
+
+        let ok = !reachable(block) || match pos {
+            Some(pos) if def_block == block => def_pos < pos,
+            _ => dominates(&idom, def_block, block),
+        };
+
+        if ok { Ok(()) } else { Err(VerifyError::NotDominated(name(), value)) }
+    };
+
+    for (id, block) in func.block_ids().zip(&func.blocks) {
+        let mut leading = true;
+
+        for (pos, value) in block.insts.iter().enumerate() {
+            let inst = func.inst(*value);
+
+            match inst {
+                Inst::Phi(incoming) => {
+                    if !leading {
+                        return Err(VerifyError::MisplacedPhi(name(), *value))
+                    }
+
+                    let mut from = incoming.iter().map(|(b, _)| *b).collect::<Vec<_>>();
+                    let mut expected = preds[id.0 as usize].clone();
+                    from.sort();
+                    expected.sort();
+
+                    if from != expected {
+                        return Err(VerifyError::PhiPredecessors(name(), *value))
+                    }
+
+                    // An incoming value only has to be available at the end of its predecessor
+                    for (pred, incoming) in incoming {
+                        available(*incoming, *pred, None)?;
+                    }
+                },
+                _ => {
+                    leading = false;
+
+                    for op in inst.operands() {
+                        available(op, id, Some(pos))?;
+                    }
+                },
+            }
+
+            match inst {
+                Inst::Param(i) if id != Function::ENTRY || *i >= func.params =>
+                    return Err(VerifyError::MisplacedParam(name(), *value)),
+                Inst::Call(Callee::Direct(callee), args) => match module.function(callee) {
+                    None => return Err(VerifyError::UndefinedFunction(name(), callee.clone())),
+                    Some(f) if f.params != args.len() => return Err(VerifyError::ArityMismatch(name(), callee.clone())),
+                    _ => (),
+                },
+                Inst::Const(Constant::Func(callee)) if module.function(callee).is_none() =>
+                    return Err(VerifyError::UndefinedFunction(name(), callee.clone())),
+                Inst::GetGlobal(global) | Inst::SetGlobal(global, _) if !module.globals.contains(global) =>
+                    return Err(VerifyError::UndefinedGlobal(name(), global.clone())),
+                _ => (),
+            }
+        }
+
+        match &block.terminator {
+            Terminator::Branch(v, ..) | Terminator::Return(v) => available(*v, id, None)?,
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
\ No newline at end of file
@@ -6,6 +6,7 @@ type LnPos = (i32, i32);
 
 pub struct Lexer {
     tokens: Vec<Token>,
+    spans: Vec<Span>,
     source: String,
 }
 
@@ -15,9 +16,15 @@ impl<'l> Lexer {
             panic!("No file path given");
         }
 
+        Self::from_source(std::fs::read_to_string(fname.unwrap()).unwrap())
+    }
+
+    /// A lexer over source text rather than a file
+    pub fn from_source(source: String) -> Lexer {
         Lexer {
             tokens: vec![],
-            source: std::fs::read_to_string(fname.unwrap()).unwrap(),
+            spans: vec![],
+            source,
         }
     }
 
@@ -25,11 +32,20 @@ impl<'l> Lexer {
         &self.tokens
     }
 
+    /// Start of each token, parallel to `tokens`
+    pub fn spans(&'l self) -> &'l Vec<Span> {
+        &self.spans
+    }
+
     pub fn lex(&'l mut self) -> Result<Vec<Token>, TokenError> {
         let mut src = Source::new(&self.source);
         let mut tokens = vec![];
+        let mut spans = vec![];
 
         while src.has_next() {
+            let (line, column) = src.lp();
+            let count = tokens.len();
+
             match src.next()? {
                 '\0' => break,
 
@@ -55,7 +71,7 @@ impl<'l> Lexer {
                     let mut num = String::from(d);
 
                     while src.peek().is_ascii_digit() ||
-                        src.peek() == '.' || 
+                        (src.peek() == '.' && src.peek_next() != '.') ||
                         src.peek() == '_' {
                             match src.peek() {
                                 '_' => { src.next()?; },
@@ -91,9 +107,19 @@ impl<'l> Lexer {
                 }
 
                 ';' => tokens.push(Token::Semicolon),
-                '/' => tokens.push(Token::Divide),
-                '%' => tokens.push(Token::Modulo),
-                '.' => tokens.push(Token::Dot),
+                '.' => {
+                    match src.peek() {
+                        '.' => {
+                            src.next()?;
+                            match src.peek() {
+                                '.' => { tokens.push(Token::Spread); src.next()?; },
+                                '=' => { tokens.push(Token::RangeInclusive); src.next()?; },
+                                _ => tokens.push(Token::Range)
+                            }
+                        },
+                        _ => tokens.push(Token::Dot)
+                    }
+                },
                 ',' => tokens.push(Token::Comma),
                 '{' => tokens.push(Token::LeftBrace),
                 '}' => tokens.push(Token::RightBrace),
@@ -101,7 +127,6 @@ impl<'l> Lexer {
                 ')' => tokens.push(Token::RightParenthesis),
                 '[' => tokens.push(Token::LeftBracket),
                 ']' => tokens.push(Token::RightBracket),
-                '^' => tokens.push(Token::Xor),
                 '~' => tokens.push(Token::BinaryNegate),
                 
                 '=' => {
@@ -110,8 +135,18 @@ impl<'l> Lexer {
                 },
                 
                 ':' => {
-                    src.expect('=')?;
-                    tokens.push(Token::Assign);
+                    match src.peek() {
+                        '=' => { tokens.push(Token::Assign); src.next()?; },
+                        _ => tokens.push(Token::Colon)
+                    }
+                },
+
+                '?' => {
+                    match src.peek() {
+                        '?' => { tokens.push(Token::Coalesce); src.next()?; },
+                        '.' => { tokens.push(Token::Optional); src.next()?; },
+                        _ => tokens.push(Token::Question)
+                    }
                 },
                 
                 '+' => {
@@ -132,11 +167,39 @@ impl<'l> Lexer {
                 
                 '*' => {
                     match src.peek() {
-                        '*' => { tokens.push(Token::Exponentiate); src.next()?; },
+                        '*' => {
+                            src.next()?;
+                            match src.peek() {
+                                '=' => { tokens.push(Token::ExponentiateEquals); src.next()?; },
+                                _ => tokens.push(Token::Exponentiate)
+                            }
+                        },
+                        '=' => { tokens.push(Token::MultiplyEquals); src.next()?; },
                         _ => tokens.push(Token::Multiply)
                     }
                 },
 
+                '/' => {
+                    match src.peek() {
+                        '=' => { tokens.push(Token::DivideEquals); src.next()?; },
+                        _ => tokens.push(Token::Divide)
+                    }
+                },
+
+                '%' => {
+                    match src.peek() {
+                        '=' => { tokens.push(Token::ModuloEquals); src.next()?; },
+                        _ => tokens.push(Token::Modulo)
+                    }
+                },
+
+                '^' => {
+                    match src.peek() {
+                        '=' => { tokens.push(Token::XorEquals); src.next()?; },
+                        _ => tokens.push(Token::Xor)
+                    }
+                },
+
                 '!' => {
                     match src.peek() {
                         '=' => { tokens.push(Token::NotEquals); src.next()?; },
@@ -147,7 +210,13 @@ impl<'l> Lexer {
                 '>' => {
                     match src.peek() {
                         '=' => { tokens.push(Token::GreaterEquals); src.next()?; },
-                        '>' => { tokens.push(Token::ShiftRight); src.next()?; },
+                        '>' => {
+                            src.next()?;
+                            match src.peek() {
+                                '=' => { tokens.push(Token::ShiftRightEquals); src.next()?; },
+                                _ => tokens.push(Token::ShiftRight)
+                            }
+                        },
                         _ => tokens.push(Token::GreaterThan)
                     }
                 },
@@ -155,7 +224,13 @@ impl<'l> Lexer {
                 '<' => {
                     match src.peek() {
                         '=' => { tokens.push(Token::LessEquals); src.next()?; },
-                        '<' => { tokens.push(Token::ShiftLeft); src.next()?; },
+                        '<' => {
+                            src.next()?;
+                            match src.peek() {
+                                '=' => { tokens.push(Token::ShiftLeftEquals); src.next()?; },
+                                _ => tokens.push(Token::ShiftLeft)
+                            }
+                        },
                         _ => tokens.push(Token::LessThan) 
                     }
                 },
@@ -163,6 +238,7 @@ impl<'l> Lexer {
                 '&' => {
                     match src.peek() {
                         '&' => { tokens.push(Token::LogicalAnd); src.next()?; },
+                        '=' => { tokens.push(Token::BinaryAndEquals); src.next()?; },
                         _ => tokens.push(Token::BinaryAnd)
                     }
                 },
@@ -170,17 +246,25 @@ impl<'l> Lexer {
                 '|' => {
                     match src.peek() {
                         '|' => { tokens.push(Token::LogicalOr); src.next()?; },
+                        '=' => { tokens.push(Token::BinaryOrEquals); src.next()?; },
                         _ => tokens.push(Token::BinaryOr)
                     }
                 }
 
                 u => return Err(TokenError::Unknown(u, src.lp())),
             }
+
+            if tokens.len() > count {
+                spans.push(Span { line, column });
+            }
         }
 
+        let (line, column) = src.lp();
         tokens.push(Token::EOF);
+        spans.push(Span { line, column });
 
         self.tokens = tokens.clone();
+        self.spans = spans;
         Ok(tokens)
     }
 }
@@ -206,6 +290,12 @@ fn match_keyword(word: &str) -> Token {
         "for" => For,
         "break" => Break,
         "continue" => Continue,
+        "in" => In,
+        "throw" => Throw,
+        "try" => Try,
+        "catch" => Catch,
+        "finally" => Finally,
+        "assert" => Assert,
         "null" => Null,
         "true" => BooleanLiteral(true),
         "false" => BooleanLiteral(false),
@@ -1,4 +1,6 @@
 pub mod lexer;
 pub mod token;
 mod error;
-mod source;
\ No newline at end of file
+mod source;
+
+pub use error::TokenError;
\ No newline at end of file
@@ -1,12 +1,12 @@
 use super::error::*;
-use std::{ iter, vec };
 
 pub const EOF: char = '\0';
 
 /// A source code iterator. \
 /// Code `position` and `line` are handled internally
 pub struct Source {
-    source: iter::Peekable<vec::IntoIter<char>>,
+    source: Vec<char>,
+    index: usize,
     pos: i32,
     line: i32,
 }
@@ -15,10 +15,8 @@ impl<'s> Source {
     /// Creates a new source code iterator from a string
     pub fn new(src: &'s str) -> Source {
         Source {
-            source: src.chars()
-                .collect::<Vec<char>>()
-                .into_iter()
-                .peekable(),
+            source: src.chars().collect(),
+            index: 0,
             pos: 0,
             line: 1,
         }
@@ -26,7 +24,9 @@ impl<'s> Source {
 
     /// Consumes and returns the next character in the iterator, or `TokenError` if there is none
     pub fn next(&mut self) -> Result<char, TokenError> {
-        if let Some(c) = self.source.next() {
+        if let Some(&c) = self.source.get(self.index) {
+            self.index += 1;
+
             if c == '\n' { 
                 self.line += 1; 
                 self.pos = 0;
@@ -44,12 +44,12 @@ impl<'s> Source {
 
     /// Checks what the next character is in the source iterator without consuming
     pub fn peek(&mut self) -> char {
-        if let Some(c) = self.source.peek() {
-            *c
-        } 
-        else {
-            '\0'
-        }
+        self.source.get(self.index).copied().unwrap_or(EOF)
+    }
+
+    /// Checks the character after the next one without consuming, for two character lookahead
+    pub fn peek_next(&mut self) -> char {
+        self.source.get(self.index + 1).copied().unwrap_or(EOF)
     }
 
     /// A tuple of the current `(line, position)` in the source code
@@ -59,7 +59,7 @@ impl<'s> Source {
 
     /// Checks if the source iterator has more characters left in it
     pub fn has_next(&mut self) -> bool {
-        self.source.size_hint().0 > 0
+        self.index < self.source.len()
     }
 
     /// Consumes the next character and returns an error if it is not the given expected character
@@ -1,3 +1,10 @@
+/// Where a token starts in the source: a 1-based line and 0-based column
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub line: i32,
+    pub column: i32,
+}
+
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
@@ -11,6 +18,12 @@ pub enum Token {
     For,                // for
     Break,              // break
     Continue,           // continue
+    In,                 // in
+    Throw,              // throw
+    Try,                // try
+    Catch,              // catch
+    Finally,            // finally
+    Assert,             // assert
     
     // Ambiguous symbols
     Plus,               // +
@@ -20,25 +33,42 @@ pub enum Token {
     Decrement,          // --
     MinusEquals,        // -=
     Multiply,           // *
+    MultiplyEquals,     // *=
     Exponentiate,       // **
+    ExponentiateEquals, // **=
     Not,                // !
     NotEquals,          // !=
     GreaterThan,        // >
     GreaterEquals,      // >=
     ShiftRight,         // >>
+    ShiftRightEquals,   // >>=
     LessThan,           // <
     LessEquals,         // <=
     ShiftLeft,          // <<
+    ShiftLeftEquals,    // <<=
     BinaryAnd,          // &
+    BinaryAndEquals,    // &=
     LogicalAnd,         // &&
     BinaryOr,           // |
+    BinaryOrEquals,     // |=
     LogicalOr,          // ||
+    Divide,             // /
+    DivideEquals,       // /=
+    Modulo,             // %
+    ModuloEquals,       // %=
+    Xor,                // ^
+    XorEquals,          // ^=
+    Question,           // ?
+    Coalesce,           // ??
+    Optional,           // ?.
+    Colon,              // :
+    Range,              // ..
+    RangeInclusive,     // ..=
+    Spread,             // ...
 
     // Unambiguous symbols
     Assign,             // :=
     Equals,             // ==
-    Divide,             // /
-    Modulo,             // %
     Dot,                // .
     Comma,              // ,
     Semicolon,          // ;
@@ -48,7 +78,6 @@ pub enum Token {
     RightBracket,       // ]
     LeftBrace,          // {
     RightBrace,         // }
-    Xor,                // ^
     BinaryNegate,       // ~
                 
     // Literals
@@ -78,7 +107,6 @@ impl Token {
             LogicalAnd          => 2,
             LogicalOr           => 2,
 
-            Exponentiate        => 3,
             LessThan            => 3,
             LessEquals          => 3,
             GreaterThan         => 3,
@@ -89,15 +117,18 @@ impl Token {
             BinaryOr            => 3,
             ShiftRight          => 3,
             ShiftLeft           => 3,
+            In                  => 3,
 
             // Unary
             Xor                 => 4,
             Not                 => 4,
             BinaryNegate        => 4,
 
+            // Binary, right associative
+            Exponentiate        => 5,
+
             // Other
             LeftParenthesis     => 10,
-            RightBracket        => 10,
             Dot                 => 10,
 
             _ => 0
@@ -0,0 +1,76 @@
+//! Ult as a library: lexing, parsing, checking and evaluating Ult programs. \
+//! The functions and re-exports at the crate root are the stable API and follow semver.
+//! The modules underneath are public for tools built on the tree, like the `parse::visit` traits,
+//! but the compiler internals (`codegen`, `ir`, `backend`) may still change in minor releases
+//!
+//! ```
+//! let tokens = ult::lex("func main() { return 6 * 7 }")?;
+//! let ast = ult::parse(&tokens)?;
+//!
+//! assert!(ult::check(&ast).is_empty());
+//! assert_eq!(ult::eval(&ast)?.to_string(), "42");
+//! # Ok::<(), ult::Error>(())
+//! ```
+//!
+//! Each native and source-to-source backend sits behind a `backend-*` cargo feature, all enabled by
+//! default. Features only ever add code, and `Target` has every variant whatever is enabled, so turning
+//! one off never breaks a build, it makes `backend::build` refuse that target instead
+
+pub mod lex;
+pub mod parse;
+pub mod analysis;
+pub mod lint;
+pub mod codegen;
+pub mod backend;
+pub mod ir;
+pub mod engine;
+pub mod stdlib;
+mod error;
+
+pub use analysis::{ Diagnostic, Severity };
+pub use codegen::value::Value;
+pub use engine::Engine;
+pub use error::Error;
+pub use lex::token::{ Span, Token };
+pub use parse::ast;
+pub use parse::ast::AST;
+
+use codegen::compiler::Compiler;
+use codegen::vm::VM;
+use lex::lexer::Lexer;
+use parse::parser::Parser;
+
+/// The tokens of a source, ending with `Token::EOF`, and where each of them starts
+#[derive(Debug, Clone)]
+pub struct Tokens {
+    pub tokens: Vec<Token>,
+    pub spans: Vec<Span>,
+}
+
+pub fn lex(source: &str) -> Result<Tokens, Error> {
+    let mut lexer = Lexer::from_source(source.to_string());
+    let tokens = lexer.lex()?;
+
+    Ok(Tokens { tokens, spans: lexer.spans().clone() })
+}
+
+pub fn parse(tokens: &Tokens) -> Result<AST, Error> {
+    Ok(Parser::new(&tokens.tokens, &tokens.spans).parse()?)
+}
+
+/// Compiles a program to bytecode and runs it, returning what its `main` returns,
+/// or `null` without a `main`
+pub fn eval(ast: &AST) -> Result<Value, Error> {
+    let mut compiler = Compiler::new(ast);
+    compiler.compile()?;
+
+    let mut vm = VM::new();
+    stdlib::install(&mut vm);
+
+    Ok(vm.run(compiler.program().unwrap())?)
+}
+
+/// The diagnostics of `ult check`, sorted by position
+pub fn check(ast: &AST) -> Vec<Diagnostic> {
+    analysis::check(ast)
+}
@@ -0,0 +1,170 @@
+use super::Level;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{ Display, Result, Formatter };
+
+#[derive(Debug)]
+pub enum LintError {
+    /// Line number and rule name
+    UnknownRule(i32, String),
+    UnknownLevel(i32, String),
+    Malformed(i32),
+}
+
+impl Display for LintError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        use LintError::*;
+        match self {
+            UnknownRule(line, name) => write!(f, "line {}: unknown lint rule '{}'", line, name),
+            UnknownLevel(line, name) => write!(f, "line {}: unknown lint level '{}', expected allow, warn or deny", line, name),
+            Malformed(line) => write!(f, "line {}: expected `rule = \"level\"`", line),
+        }
+    }
+}
+
+impl Error for LintError {}
+
+/// Rule levels from a `lint.toml` file:
+/// ```toml
+/// # Comments and a `[rules]` header are allowed
+/// [rules]
+/// shadowing = "allow"
+/// unused-variable = "deny"
+/// ```
+#[derive(Debug, Default)]
+pub struct Config {
+    levels: HashMap<String, Level>,
+}
+
+impl Config {
+    pub const FILE_NAME: &'static str = "lint.toml";
+
+    /// Parses a config, rejecting rules not in `rules`
+    pub fn parse(text: &str, rules: &[&str]) -> std::result::Result<Config, LintError> {
+        let mut levels = HashMap::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i as i32 + 1;
+            let line = line.split('#').next().unwrap().trim();
+
+            if line.is_empty() || line == "[rules]" {
+                continue
+            }
+
+            let (rule, level) = line.split_once('=').ok_or(LintError::Malformed(line_number))?;
+            let (rule, level) = (rule.trim(), level.trim().trim_matches('"'));
+
+            let level = Level::from_name(level).ok_or_else(|| LintError::UnknownLevel(line_number, level.to_string()))?;
+
+            if !rules.contains(&rule) {
+                return Err(LintError::UnknownRule(line_number, rule.to_string()))
+            }
+
+            levels.insert(rule.to_string(), level);
+        }
+
+        Ok(Config { levels })
+    }
+
+    pub fn level(&self, rule: &str) -> Option<Level> {
+        self.levels.get(rule).copied()
+    }
+}
+
+/// Levels set by comments in the source: \
+/// `# lint: allow(rule, ...)` after code applies to its own line, on a line by itself to the next line
+/// with code. `# lint-file: deny(rule, ...)` applies to the whole file
+#[derive(Debug, Default)]
+pub struct Pragmas {
+    file: HashMap<String, Level>,
+    lines: HashMap<(i32, String), Level>,
+}
+
+impl Pragmas {
+    pub fn parse(source: &str, rules: &[&str]) -> std::result::Result<Pragmas, LintError> {
+        let mut pragmas = Pragmas::default();
+        // Pragmas on lines of their own, waiting for the next line with code
+        let mut pending: Vec<(String, Level)> = vec![];
+
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i as i32 + 1;
+            let (code, comment) = split_comment(line);
+            let has_code = !code.trim().is_empty();
+
+            if has_code {
+                for (rule, level) in pending.drain(..) {
+                    pragmas.lines.insert((line_number, rule), level);
+                }
+            }
+
+            let comment = match comment {
+                Some(comment) => comment.trim(),
+                None => continue,
+            };
+
+            let (file_wide, directive) = if let Some(rest) = comment.strip_prefix("lint-file:") {
+                (true, rest)
+            }
+            else if let Some(rest) = comment.strip_prefix("lint:") {
+                (false, rest)
+            }
+            else {
+                continue
+            };
+
+            for (rule, level) in parse_directive(directive.trim(), line_number, rules)? {
+                if file_wide {
+                    pragmas.file.insert(rule, level);
+                }
+                else if has_code {
+                    pragmas.lines.insert((line_number, rule), level);
+                }
+                else {
+                    pending.push((rule, level));
+                }
+            }
+        }
+
+        Ok(pragmas)
+    }
+
+    /// The level set for `rule` on `line`, if any
+    pub fn level(&self, rule: &str, line: i32) -> Option<Level> {
+        self.lines.get(&(line, rule.to_string())).or_else(|| self.file.get(rule)).copied()
+    }
+}
+
+/// `allow(a, b)` into its rules and level
+fn parse_directive(directive: &str, line: i32, rules: &[&str]) -> std::result::Result<Vec<(String, Level)>, LintError> {
+    let (level, rest) = directive.split_once('(').ok_or(LintError::Malformed(line))?;
+    let names = rest.strip_suffix(')').ok_or(LintError::Malformed(line))?;
+
+    let level = Level::from_name(level.trim()).ok_or_else(|| LintError::UnknownLevel(line, level.trim().to_string()))?;
+
+    names.split(',')
+        .map(str::trim)
+        .map(|name| match rules.contains(&name) {
+            true => Ok((name.to_string(), level)),
+            false => Err(LintError::UnknownRule(line, name.to_string())),
+        })
+        .collect()
+}
+
+/// Splits a line at its `#` comment, skipping `#` inside string and character literals
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return (&line[..i], Some(&line[i + 1..])),
+            _ => (),
+        }
+    }
+
+    (line, None)
+}
\ No newline at end of file
@@ -0,0 +1,347 @@
+pub mod config;
+pub mod rules;
+
+pub use config::{ Config, Pragmas };
+
+use super::analysis::{ Diagnostic, Severity };
+use super::lex::token::Span;
+use super::parse::ast::*;
+use super::parse::visit::{ walk_block, walk_expression, walk_statement, Visitor };
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+pub type SymbolId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Global,
+    Function,
+    Argument,
+    Local,
+}
+
+/// A declared name and how often it's used, complete once the walk is done
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub span: Span,
+    pub reads: usize,
+    pub writes: usize,
+}
+
+/// A check over the program. The linter walks the tree once, resolving names as it goes,
+/// and calls every rule's hooks along the way
+pub trait Rule {
+    /// Kebab case name used in configs and pragmas
+    fn name(&self) -> &'static str;
+
+    fn default_level(&self) -> Level {
+        Level::Warn
+    }
+
+    /// Called when `symbol` is declared, with the symbol the name referred to until then
+    fn declare(&mut self, _cx: &mut Context, _symbol: SymbolId, _shadowed: Option<SymbolId>) {}
+
+    fn statement(&mut self, _cx: &mut Context, _stmt: &Statement) {}
+
+    fn expression(&mut self, _cx: &mut Context, _expr: &Expression) {}
+
+    /// Called after the walk, when every use is known
+    fn finish(&mut self, _cx: &mut Context) {}
+}
+
+/// What rules see of the walk: declared symbols, names in scope and the current position
+pub struct Context {
+    pub symbols: Vec<Symbol>,
+    scopes: Vec<HashMap<String, SymbolId>>,
+    /// Enclosing function names, innermost last
+    functions: Vec<String>,
+    span: Span,
+    findings: Vec<(Span, String)>,
+}
+
+impl Context {
+    fn new() -> Self {
+        Self {
+            symbols: vec![],
+            scopes: vec![HashMap::new()],
+            functions: vec![],
+            span: Span::default(),
+            findings: vec![],
+        }
+    }
+
+    /// The symbol `name` refers to at the current position
+    pub fn resolve(&self, name: &str) -> Option<SymbolId> {
+        self.scopes.iter().rev().find_map(|s| s.get(name)).copied()
+    }
+
+    /// Name of the function being walked, `None` at the top level
+    pub fn function(&self) -> Option<&str> {
+        self.functions.last().map(String::as_str)
+    }
+
+    /// Where the statement being walked starts
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn report(&mut self, span: Span, message: impl Into<String>) {
+        self.findings.push((span, message.into()));
+    }
+}
+
+/// Runs rules over a program and turns their findings into diagnostics according to
+/// the configured levels, with in-source pragmas taking precedence over the config
+pub struct Linter {
+    rules: Vec<Box<dyn Rule>>,
+    config: Config,
+}
+
+impl Linter {
+    /// A linter with every built in rule
+    pub fn new(config: Config) -> Self {
+        Self { rules: rules::all(), config }
+    }
+
+    pub fn rule_names(&self) -> Vec<&'static str> {
+        self.rules.iter().map(|r| r.name()).collect()
+    }
+
+    pub fn lint(&mut self, ast: &AST, pragmas: &Pragmas) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+
+        for i in 0..self.rules.len() {
+            let mut cx = Context::new();
+            let mut walker = Walker { rule: self.rules[i].as_mut(), cx: &mut cx };
+
+            walker.visit_ast(ast);
+            walker.rule.finish(walker.cx);
+
+            let name = self.rules[i].name();
+            let default = self.config.level(name).unwrap_or(self.rules[i].default_level());
+
+            for (span, message) in cx.findings {
+                let severity = match pragmas.level(name, span.line).unwrap_or(default) {
+                    Level::Allow => continue,
+                    Level::Warn => Severity::Warning,
+                    Level::Deny => Severity::Error,
+                };
+
+                diagnostics.push(Diagnostic { severity, span, message, rule: Some(name) });
+            }
+        }
+
+        diagnostics.sort_by_key(|d| (d.span.line, d.span.column));
+        diagnostics
+    }
+}
+
+/// Walks the tree for one rule, keeping `Context` up to date
+struct Walker<'r, 'c> {
+    rule: &'r mut dyn Rule,
+    cx: &'c mut Context,
+}
+
+impl Walker<'_, '_> {
+    fn declare(&mut self, name: String, kind: SymbolKind, span: Span) -> SymbolId {
+        let shadowed = self.cx.resolve(&name);
+
+        self.cx.symbols.push(Symbol { name: name.clone(), kind, span, reads: 0, writes: 0 });
+        let symbol = self.cx.symbols.len() - 1;
+        let hidden = name.starts_with('@');
+        self.cx.scopes.last_mut().unwrap().insert(name, symbol);
+
+        // Names starting with `@` come from the parser desugaring patterns, not from the program
+        if !hidden {
+            self.rule.declare(self.cx, symbol, shadowed);
+        }
+        symbol
+    }
+
+    fn function(&mut self, identifier: &Identifier, arguments: &[Identifier], body: &Block, span: Span) {
+        self.cx.functions.push(identifier.name());
+        self.cx.scopes.push(HashMap::new());
+
+        for arg in arguments {
+            self.declare(arg.name(), SymbolKind::Argument, span);
+        }
+
+        self.visit_block(body, span);
+
+        self.cx.scopes.pop();
+        self.cx.functions.pop();
+    }
+
+    fn write(&mut self, identifier: &Identifier) {
+        if let Some(symbol) = self.cx.resolve(&identifier.name()) {
+            self.cx.symbols[symbol].writes += 1;
+        }
+    }
+}
+
+impl Visitor for Walker<'_, '_> {
+    fn visit_ast(&mut self, ast: &AST) {
+        // Top level names are visible everywhere, so they are declared first
+        for (decl, span) in ast.program().iter().zip(ast.spans()) {
+            match decl {
+                Declaration::Variable { identifier, .. } => {
+                    if self.cx.resolve(&identifier.name()).is_none() {
+                        self.declare(identifier.name(), SymbolKind::Global, *span);
+                    }
+                },
+                Declaration::Destructure { pattern, .. } => {
+                    for identifier in pattern.identifiers() {
+                        if self.cx.resolve(&identifier.name()).is_none() {
+                            self.declare(identifier.name(), SymbolKind::Global, *span);
+                        }
+                    }
+                },
+                Declaration::Function { identifier, .. } => { self.declare(identifier.name(), SymbolKind::Function, *span); },
+            }
+        }
+
+        for (decl, span) in ast.program().iter().zip(ast.spans()) {
+            self.cx.span = *span;
+
+            match decl {
+                Declaration::Variable { value: Some(value), .. } => self.visit_expression(value),
+                Declaration::Variable { value: None, .. } => (),
+                Declaration::Destructure { value, .. } => self.visit_expression(value),
+                Declaration::Function { identifier, arguments, body } =>
+                    self.function(identifier, arguments.as_deref().unwrap_or_default(), body, *span),
+            }
+        }
+
+        // Tests are walked like functions without arguments, so what they use counts as used
+        for test in ast.tests() {
+            self.cx.span = test.span;
+
+            let identifier = Identifier::new(format!("test {:?}", test.name), Scope::Global);
+            self.function(&identifier, &[], &test.body, test.span);
+        }
+    }
+
+    fn visit_block(&mut self, block: &Block, span: Span) {
+        self.cx.scopes.push(HashMap::new());
+        walk_block(self, block, span);
+        self.cx.scopes.pop();
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement, span: Span) {
+        self.cx.span = span;
+        self.rule.statement(self.cx, stmt);
+
+        match stmt {
+            Statement::Declaration(Declaration::Variable { identifier, value }) => {
+                if let Some(value) = value {
+                    self.visit_expression(value);
+                }
+                self.declare(identifier.name(), SymbolKind::Local, span);
+            },
+            Statement::Declaration(Declaration::Destructure { pattern, value }) => {
+                self.visit_expression(value);
+
+                for identifier in pattern.identifiers() {
+                    self.declare(identifier.name(), SymbolKind::Local, span);
+                }
+            },
+            Statement::Declaration(Declaration::Function { identifier, arguments, body }) => {
+                // Declared before the body so recursive calls resolve
+                self.declare(identifier.name(), SymbolKind::Function, span);
+                self.function(identifier, arguments.as_deref().unwrap_or_default(), body, span);
+            },
+            Statement::For { variable, condition, step, body } => {
+                self.cx.scopes.push(HashMap::new());
+
+                if let Some(stmt) = variable {
+                    self.visit_statement(stmt, span);
+                }
+                if let Some(condition) = condition {
+                    self.visit_expression(condition);
+                }
+
+                self.visit_block(body, span);
+
+                if let Some(step) = step {
+                    self.cx.span = span;
+                    self.visit_expression(step);
+                }
+
+                self.cx.scopes.pop();
+            },
+            Statement::ForEach { variable, iterable, body } => {
+                self.visit_expression(iterable);
+
+                self.cx.scopes.push(HashMap::new());
+                self.declare(variable.name(), SymbolKind::Local, span);
+                self.visit_block(body, span);
+                self.cx.scopes.pop();
+            },
+            Statement::Try { body, catch, finally } => {
+                self.visit_block(body, span);
+
+                if let Some((identifier, block)) = catch {
+                    self.cx.scopes.push(HashMap::new());
+                    self.declare(identifier.name(), SymbolKind::Local, span);
+                    self.visit_block(block, span);
+                    self.cx.scopes.pop();
+                }
+                if let Some(block) = finally {
+                    self.visit_block(block, span);
+                }
+            },
+            stmt => walk_statement(self, stmt, span),
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        self.rule.expression(self.cx, expr);
+
+        match expr {
+            Expression::Value(identifier) => {
+                if let Some(symbol) = self.cx.resolve(&identifier.name()) {
+                    self.cx.symbols[symbol].reads += 1;
+                }
+            },
+            // Compound assignments and `x++` also read their target
+            Expression::Assignment { lhs, operation, rhs } => match lhs.as_ref() {
+                Expression::Value(identifier) => {
+                    if operation.is_some() {
+                        self.visit_expression(lhs);
+                    }
+
+                    self.visit_expression(rhs);
+                    self.write(identifier);
+                },
+                _ => walk_expression(self, expr),
+            },
+            Expression::Postfix { operand, .. } => {
+                self.visit_expression(operand);
+
+                if let Expression::Value(identifier) = operand.as_ref() {
+                    self.write(identifier);
+                }
+            },
+            expr => walk_expression(self, expr),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use super::super::lex::token::{ Span, Token };
+use super::super::parse::ast::*;
+use super::{ Context, Rule, SymbolId, SymbolKind };
+
+/// Every built in rule, in the order their findings are reported on the same spot
+pub fn all() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(Unused { name: "unused-variable", kinds: &[SymbolKind::Global, SymbolKind::Local] }),
+        Box::new(Unused { name: "unused-function", kinds: &[SymbolKind::Function] }),
+        Box::new(Unused { name: "unused-argument", kinds: &[SymbolKind::Argument] }),
+        Box::new(Shadowing),
+        Box::new(ConstantCondition),
+        Box::new(SelfAssignment),
+        Box::new(NullComparison),
+        Box::new(UnreadGlobalAssignment { assignments: vec![] }),
+    ]
+}
+
+/// Symbols of the given kinds that are never read. Names starting with `_` and `main` are exempt
+pub struct Unused {
+    name: &'static str,
+    kinds: &'static [SymbolKind],
+}
+
+impl Rule for Unused {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn finish(&mut self, cx: &mut Context) {
+        let unused = cx.symbols.iter()
+            .filter(|s| self.kinds.contains(&s.kind) && s.reads == 0)
+            .filter(|s| !s.name.starts_with('_'))
+            .filter(|s| !(s.kind == SymbolKind::Function && s.name == "main"))
+            .map(|s| (s.span, s.kind, s.name.clone()))
+            .collect::<Vec<_>>();
+
+        for (span, kind, name) in unused {
+            let what = match kind {
+                SymbolKind::Global | SymbolKind::Local => "variable",
+                SymbolKind::Function => "function",
+                SymbolKind::Argument => "argument",
+            };
+            cx.report(span, format!("{} '{}' is never used", what, name));
+        }
+    }
+}
+
+/// Locals and arguments that hide another declaration of the same name
+pub struct Shadowing;
+
+impl Rule for Shadowing {
+    fn name(&self) -> &'static str {
+        "shadowing"
+    }
+
+    fn declare(&mut self, cx: &mut Context, symbol: SymbolId, shadowed: Option<SymbolId>) {
+        let (symbol, shadowed) = match shadowed {
+            Some(shadowed) if matches!(cx.symbols[symbol].kind, SymbolKind::Local | SymbolKind::Argument) =>
+                (&cx.symbols[symbol], &cx.symbols[shadowed]),
+            _ => return,
+        };
+
+        let message = format!("'{}' shadows the declaration on line {}", symbol.name, shadowed.span.line);
+        cx.report(symbol.span, message);
+    }
+}
+
+/// `if` and `while` conditions made only of literals. `while (true)` is the idiomatic endless loop and allowed
+pub struct ConstantCondition;
+
+impl Rule for ConstantCondition {
+    fn name(&self) -> &'static str {
+        "constant-condition"
+    }
+
+    fn statement(&mut self, cx: &mut Context, stmt: &Statement) {
+        let condition = match stmt {
+            Statement::If { condition, .. } => condition,
+            Statement::While { condition: Expression::Literal(Literal::Boolean(true)), .. } => return,
+            Statement::While { condition, .. } => condition,
+            _ => return,
+        };
+
+        if is_constant(condition) {
+            cx.report(cx.span(), "condition is always the same");
+        }
+    }
+}
+
+fn is_constant(expr: &Expression) -> bool {
+    match expr {
+        Expression::Literal(_) => true,
+        Expression::Unary { operand, .. } => is_constant(operand),
+        Expression::Binary { lhs, rhs, .. } | Expression::Logical { lhs, rhs, .. } => is_constant(lhs) && is_constant(rhs),
+        _ => false,
+    }
+}
+
+/// `x := x`
+pub struct SelfAssignment;
+
+impl Rule for SelfAssignment {
+    fn name(&self) -> &'static str {
+        "self-assignment"
+    }
+
+    fn expression(&mut self, cx: &mut Context, expr: &Expression) {
+        if let Expression::Assignment { lhs, operation: None, rhs } = expr {
+            if let (Expression::Value(a), Expression::Value(b)) = (lhs.as_ref(), rhs.as_ref()) {
+                if a.name() == b.name() {
+                    cx.report(cx.span(), format!("'{}' is assigned to itself", a.name()));
+                }
+            }
+        }
+    }
+}
+
+/// `x == null` and `x != null`, which are easy to confuse with a truthiness test
+/// since `0`, `false` and `0.0` are falsy but not `null`
+pub struct NullComparison;
+
+impl Rule for NullComparison {
+    fn name(&self) -> &'static str {
+        "null-comparison"
+    }
+
+    fn expression(&mut self, cx: &mut Context, expr: &Expression) {
+        if let Expression::Binary { lhs, operation: Token::Equals | Token::NotEquals, rhs } = expr {
+            let null = |e: &Expression| matches!(e, Expression::Literal(Literal::Null));
+
+            if null(lhs) || null(rhs) {
+                cx.report(cx.span(), "comparison with 'null' using '==' or '!='");
+            }
+        }
+    }
+}
+
+/// A function assigning a global that nothing ever reads, usually a missing `let`.
+/// `main.ult`'s `x := 27` inside `main` is one
+pub struct UnreadGlobalAssignment {
+    assignments: Vec<(SymbolId, String, Span)>,
+}
+
+impl Rule for UnreadGlobalAssignment {
+    fn name(&self) -> &'static str {
+        "unread-global-assignment"
+    }
+
+    fn expression(&mut self, cx: &mut Context, expr: &Expression) {
+        let Expression::Assignment { lhs, .. } = expr else { return };
+        let Expression::Value(identifier) = lhs.as_ref() else { return };
+
+        match (cx.resolve(&identifier.name()), cx.function()) {
+            (Some(symbol), Some(function)) if cx.symbols[symbol].kind == SymbolKind::Global =>
+                self.assignments.push((symbol, function.to_string(), cx.span())),
+            _ => (),
+        }
+    }
+
+    fn finish(&mut self, cx: &mut Context) {
+        for (symbol, function, span) in std::mem::take(&mut self.assignments) {
+            let symbol = &cx.symbols[symbol];
+
+            if symbol.reads == 0 {
+                let message = format!("global '{}' is assigned in '{}' but never read, did you mean `let {}`?",
+                    symbol.name, function, symbol.name);
+                cx.report(span, message);
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -1,38 +1,405 @@
-mod lex; mod parse; mod codegen; /* mod eval; */
-
-use lex::lexer::Lexer;
-use parse::parser::Parser;
-use codegen::compiler::Compiler;
+use ult::{ backend, ir, AST, Severity, Value };
+use ult::codegen::compiler::Compiler;
+use ult::codegen::gc::GcMode;
+use ult::codegen::vm::VM;
+use ult::lint::{ Config, Linter, Pragmas };
+use ult::backend::Target;
+use ult::ir::opt::{ OptLevel, PassManager };
 use std::error::Error;
+use std::path::{ Path, PathBuf };
 use std::env;
+use std::time::{ Duration, Instant };
+
+const USAGE: &str = "Usage:
+    ult [--gc-stats] [--gc-stress] [--debug] <file>
+                    run a program, with --gc-stats printing garbage collector statistics to
+                    stderr, --gc-stress collecting garbage on every allocation and --debug
+                    printing the syntax tree, the bytecode, the result and the time to stderr
+    ult build --target <target> [-o <output>] [--emit-only] <file>
+    ult check <file>
+                    report unreachable code, missing returns, break and continue outside
+                    of loops, and variables read before they are assigned
+    ult lint [--config <lint.toml>] <file>
+                    run the lint rules, with levels from the config (default lint.toml next
+                    to the file) and `# lint: allow(rule)` or `# lint-file: deny(rule)` comments
+    ult ir [-O0|-O1|-O2|-O3] [--dump-passes] <file>
+                    print the verified SSA IR of a program, optimized at the given level
+                    (default -O0), with --dump-passes printing it around each pass to stderr
+    ult test [--filter <text>] [<file or directory>...]
+                    run the `test` blocks of the files, and of the .ult files under the
+                    directories (default the current one), each on a fresh run of its file,
+                    with --filter only running tests whose name contains the text
+
+Targets:
+    x86_64-linux    native executable via GNU as and ld
+    c               GNU C99 source compiled with $CC (default cc)
+    wasm32          WebAssembly binary module (.wat text with --emit-only)
+    llvm            LLVM IR, from the SSA IR at -O2, compiled with $CLANG (default clang)
+    js              ES2020 JavaScript for Node with a source map";
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args = env::args().collect::<Vec<String>>();
-    let filepath = args.get(1);
+    let args = env::args().skip(1).collect::<Vec<String>>();
+
+    match args.first().map(String::as_str) {
+        Some("build") => build(&args[1..]),
+        Some("check") => check(args.get(1)),
+        Some("lint") => lint(&args[1..]),
+        Some("ir") => dump_ir(&args[1..]),
+        Some("test") => test(&args[1..]),
+        Some("-h") | Some("--help") | None => {
+            println!("{}", USAGE);
+            Ok(())
+        },
+        Some(_) => run(&args),
+    }
+}
 
-    let mut lexer = Lexer::new(filepath);
+fn parse_file(filepath: Option<&String>) -> Result<AST, Box<dyn Error>> {
+    let source = std::fs::read_to_string(filepath.ok_or(USAGE)?)?;
+
+    Ok(ult::parse(&ult::lex(&source)?)?)
+}
+
+fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut filepath = None;
+    let mut gc_stats = false;
+    let mut gc_mode = GcMode::Normal;
+    let mut debug = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--gc-stats" => gc_stats = true,
+            "--debug" => debug = true,
+            "--gc-stress" => gc_mode = GcMode::Stress,
+            _ => filepath = Some(arg),
+        }
+    }
 
     let start = std::time::Instant::now();  // Begin program
 
-    lexer.lex()?;
+    let filepath = filepath.ok_or(USAGE)?;
+    let source = std::fs::read_to_string(filepath)?;
+    let ast = ult::parse(&ult::lex(&source)?)?;
+
+    let mut compiler = Compiler::new(&ast);
 
-    let mut parser = Parser::new(lexer.tokens());
+    compiler.compile()?;
 
-    let ast = parser.parse()?;
+    let mut vm = VM::new().with_gc(gc_mode);
+    ult::stdlib::install(&mut vm);
 
-    let compiler = Compiler::new(&ast);
+    let result = vm.run(compiler.program().unwrap());
 
-    compiler.compile();
+    if gc_stats {
+        // A last collection so the counts cover everything the program left behind
+        vm.collect();
+        eprintln!("{}", vm.gc_stats());
+    }
+
+    let result = match result {
+        Ok(result) => result,
+        Err(error) => {
+            eprintln!("Runtime error: {}", error);
+            eprint!("{}", format_trace(&vm, filepath, &source));
+            std::process::exit(1);
+        },
+    };
     
     let end = std::time::Instant::now();    // End program
 
-    for node in ast.program() {
-        println!("{:?}", node);
+    if debug {
+        for node in ast.program() {
+            eprintln!("{:?}", node);
+        }
+
+        compiler.dump();
+
+        eprintln!("Result: {}", result);
+
+        eprintln!("Done in {:?}", (end - start));
     }
 
-    compiler.dump();
-    
-    println!("Done in {:?}", (end - start));
+    Ok(())
+}
+
+/// Where an uncaught error was thrown from, innermost call first, with the line of each call
+fn format_trace(vm: &VM, filepath: &str, source: &str) -> String {
+    let mut out = String::new();
+
+    for entry in vm.trace() {
+        match entry.line {
+            Some(line) => {
+                out += &format!("    at {} ({}:{})\n", entry.function, filepath, line);
+
+                if let Some(text) = (line as usize).checked_sub(1).and_then(|i| source.lines().nth(i)) {
+                    out += &format!("        {}\n", text.trim());
+                }
+            },
+            None => out += &format!("    at {} ({})\n", entry.function, filepath),
+        }
+    }
+
+    out
+}
+
+fn build(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut target = None;
+    let mut output = None;
+    let mut input = None;
+    let mut emit_only = false;
+
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--target" => target = Some(Target::from_name(args.next().ok_or(USAGE)?)?),
+            "-o" => output = Some(PathBuf::from(args.next().ok_or(USAGE)?)),
+            "--emit-only" => emit_only = true,
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let target = target.ok_or("No target given, expected --target <target>")?;
+    let input = input.ok_or(USAGE)?;
+    let output = output.unwrap_or_else(|| PathBuf::from(&input).with_extension(""));
+
+    if output == Path::new(&input) {
+        return Err("Output would overwrite the input file, use -o <output>".into())
+    }
+
+    let ast = parse_file(Some(&input))?;
+
+    backend::build(&ast, target, Path::new(&input), &output, emit_only)?;
+
+    Ok(())
+}
+
+fn check(filepath: Option<&String>) -> Result<(), Box<dyn Error>> {
+    let filepath = filepath.ok_or(USAGE)?;
+    let ast = parse_file(Some(filepath))?;
+    let diagnostics = ult::check(&ast);
+
+    for diagnostic in &diagnostics {
+        eprintln!("{}:{}", filepath, diagnostic);
+    }
+
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        std::process::exit(1);
+    }
 
     Ok(())
 }
+
+fn lint(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut config_path = None;
+    let mut input = None;
+
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_path = Some(PathBuf::from(args.next().ok_or(USAGE)?)),
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let input = input.ok_or(USAGE)?;
+    let ast = parse_file(Some(&input))?;
+    let source = std::fs::read_to_string(&input)?;
+
+    let mut linter = Linter::new(Config::default());
+    let rules = linter.rule_names();
+
+    // Without --config a lint.toml next to the file is used if there is one
+    let config_path = config_path.or_else(|| {
+        let path = Path::new(&input).with_file_name(Config::FILE_NAME);
+        path.exists().then_some(path)
+    });
+
+    if let Some(path) = config_path {
+        let text = std::fs::read_to_string(&path)?;
+        let config = Config::parse(&text, &rules).map_err(|e| format!("{}: {}", path.display(), e))?;
+        linter = Linter::new(config);
+    }
+
+    let pragmas = Pragmas::parse(&source, &rules).map_err(|e| format!("{}: {}", input, e))?;
+    let diagnostics = linter.lint(&ast, &pragmas);
+
+    for diagnostic in &diagnostics {
+        eprintln!("{}:{}", input, diagnostic);
+    }
+
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn dump_ir(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut level = OptLevel::O0;
+    let mut dump_passes = false;
+    let mut input = None;
+
+    for arg in args {
+        match arg.as_str() {
+            "--dump-passes" => dump_passes = true,
+            flag if flag.starts_with("-O") => level = OptLevel::from_flag(flag).ok_or(USAGE)?,
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let ast = parse_file(Some(&input.ok_or(USAGE)?))?;
+    let mut module = ir::lower::lower(&ast)?;
+
+    ir::verify::verify(&module)?;
+
+    let mut stderr = std::io::stderr();
+    let dump: Option<&mut dyn std::io::Write> = if dump_passes { Some(&mut stderr) } else { None };
+
+    PassManager::for_level(level).run(&mut module, dump)?;
+
+    print!("{}", module);
+
+    Ok(())
+}
+
+fn test(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut filter = None;
+    let mut paths = vec![];
+
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--filter" => filter = Some(args.next().ok_or(USAGE)?.as_str()),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    if paths.is_empty() {
+        paths.push(PathBuf::from("."));
+    }
+
+    let mut files = vec![];
+
+    for path in &paths {
+        test_files(path, &mut files)?;
+    }
+
+    let start = Instant::now();
+    let mut suites = vec![];
+    let mut failures = vec![];
+
+    // A file that doesn't compile fails as a whole, before any test runs
+    for file in files {
+        let source = std::fs::read_to_string(&file)?;
+        let name = file.display().to_string();
+
+        let compiled = ult::lex(&source).map_err(|e| e.to_string())
+            .and_then(|tokens| ult::parse(&tokens).map_err(|e| e.to_string()))
+            .and_then(|ast| {
+                let mut compiler = Compiler::new(&ast).without_main();
+                compiler.compile().map_err(|e| e.to_string())?;
+                Ok(compiler.program().cloned().unwrap())
+            });
+
+        match compiled {
+            Ok(program) if !program.tests.is_empty() => suites.push((name, source, program)),
+            Ok(_) => {},
+            Err(error) => failures.push((name, format!("{}\n", error))),
+        }
+    }
+
+    let (mut passed, mut filtered) = (0, 0);
+    let count = suites.iter().flat_map(|(_, _, program)| &program.tests)
+        .filter(|(name, _)| filter.is_none_or(|filter| name.contains(filter)))
+        .count();
+
+    println!("running {} test{}", count, if count == 1 { "" } else { "s" });
+
+    for (file, source, program) in &suites {
+        for (name, function) in &program.tests {
+            if filter.is_some_and(|filter| !name.contains(filter)) {
+                filtered += 1;
+                continue;
+            }
+
+            // Every test gets its own VM, so globals it changes don't leak into the next one
+            let mut vm = VM::new();
+            ult::stdlib::install(&mut vm);
+
+            let started = Instant::now();
+            let result = vm.run(program).and_then(|_| vm.call_value(Value::Function(function.clone()), vec![]));
+            let elapsed = started.elapsed();
+
+            let label = format!("{}: {}", file, name);
+
+            match result {
+                Ok(_) => {
+                    println!("test {} ... ok ({})", label, duration(elapsed));
+                    passed += 1;
+                },
+                Err(error) => {
+                    println!("test {} ... FAILED ({})", label, duration(elapsed));
+                    failures.push((label, format!("{}\n{}", error, format_trace(&vm, file, source))));
+                },
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+
+        for (label, message) in &failures {
+            print!("\n---- {} ----\n{}", label, message);
+        }
+    }
+
+    println!(
+        "\ntest result: {}. {} passed; {} failed; {} filtered out; finished in {}",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        passed,
+        failures.len(),
+        filtered,
+        duration(start.elapsed()),
+    );
+
+    if !failures.is_empty() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// The files `ult test` runs for a path: the path itself if it's a file, \
+/// otherwise the .ult files under it in name order, skipping hidden directories
+fn test_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(path)?.map(|entry| entry.map(|e| e.path())).collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    for entry in entries {
+        let hidden = entry.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+
+        if entry.is_dir() && !hidden {
+            test_files(&entry, files)?;
+        }
+        else if entry.extension().is_some_and(|ext| ext == "ult") {
+            files.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+fn duration(elapsed: Duration) -> String {
+    format!("{:.2?}", elapsed)
+}
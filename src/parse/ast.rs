@@ -1,21 +1,22 @@
 #![allow(dead_code)]
-use super::super::lex::token::Token;
+use super::super::lex::token::{ Span, Token };
 
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct AST {
-    program: Vec<Declaration>
+    program: Vec<Declaration>,
+    spans: Vec<Span>,
+    tests: Vec<Test>,
 }
 
 impl AST {
     pub fn new() -> AST {
-        AST {
-            program: Vec::<Declaration>::new()
-        }
+        AST::default()
     }
 
-    pub fn push(&mut self, dec: Declaration) {
+    pub fn push(&mut self, dec: Declaration, span: Span) {
         self.program.push(dec);
+        self.spans.push(span);
     }
 
     pub fn get(&self) -> &Self {
@@ -25,6 +26,41 @@ impl AST {
     pub fn program(&self) -> &Vec<Declaration> {
         &self.program
     }
+
+    pub fn program_mut(&mut self) -> &mut Vec<Declaration> {
+        &mut self.program
+    }
+
+    /// Where each declaration of `program` starts
+    pub fn spans(&self) -> &Vec<Span> {
+        &self.spans
+    }
+
+    pub fn into_parts(self) -> (Vec<Declaration>, Vec<Span>) {
+        (self.program, self.spans)
+    }
+
+    pub fn push_test(&mut self, test: Test) {
+        self.tests.push(test);
+    }
+
+    /// The `test` blocks, which only `ult test` runs
+    pub fn tests(&self) -> &Vec<Test> {
+        &self.tests
+    }
+
+    pub fn tests_mut(&mut self) -> &mut Vec<Test> {
+        &mut self.tests
+    }
+}
+
+/// `test "name" { }` at the top level. \
+/// Tests aren't part of the program, `ult test` runs each of them after the program's top level
+#[derive(Debug, Clone)]
+pub struct Test {
+    pub name: String,
+    pub body: Block,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -48,9 +84,13 @@ impl Identifier {
     pub fn scope(&self) -> Scope {
         self.scope.clone()
     }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Declaration {
     Function {
         identifier: Identifier,
@@ -61,6 +101,42 @@ pub enum Declaration {
         identifier: Identifier,
         value:      Option<Expression>
     },
+    /// `let (a, [b, c]) := value`, binding every name in `pattern` to the part of `value` it stands for. \
+    /// Patterns in parameters and `for` loops are parsed into one of these at the start of the body
+    Destructure {
+        pattern:    Pattern,
+        value:      Expression,
+    },
+}
+
+/// What a destructuring declaration binds, matched against a value's shape at runtime
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Identifier(Identifier),
+    /// `(a, b)`, matching a tuple with exactly as many elements
+    Tuple(Vec<Pattern>),
+    /// `[a, b]`, matching an array with exactly as many elements
+    Array(Vec<Pattern>),
+}
+
+impl Pattern {
+    /// Every name the pattern binds, left to right
+    pub fn identifiers(&self) -> Vec<&Identifier> {
+        match self {
+            Pattern::Identifier(identifier) => vec![identifier],
+            Pattern::Tuple(patterns) | Pattern::Array(patterns) =>
+                patterns.iter().flat_map(Pattern::identifiers).collect(),
+        }
+    }
+
+    /// How the pattern is described in errors, like `a tuple of 2 elements`
+    pub fn shape(&self) -> Option<String> {
+        match self {
+            Pattern::Identifier(_) => None,
+            Pattern::Tuple(patterns) => Some(format!("a tuple of {} elements", patterns.len())),
+            Pattern::Array(patterns) => Some(format!("an array of {} elements", patterns.len())),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -75,8 +151,19 @@ pub enum Expression {
         scope: Scope,
         elements: Vec<Expression>
     },
+    /// `(a, b)`, `(a,)` or `()`, an immutable sequence
+    Tuple(Vec<Expression>),
+    /// `{key: value, ...}`, or `{}` when empty, entries keep the order they were inserted in
+    Map(Vec<(Expression, Expression)>),
+    /// `{a, b, ...}`, or `{,}` when empty, an immutable collection of distinct values. \
+    /// Its elements may be spread like an array's
+    Set(Vec<Expression>),
+    /// `lhs := rhs`, or with `operation` the compound `lhs op= rhs`, which evaluates `lhs`'s target once. \
+    /// `lhs` is always a `Place`. `++x` and `--x` are `x += 1` and `x -= 1`
     Assignment {
         lhs: Box<Expression>,
+        /// The binary operator of a compound assignment, like `Token::Plus` for `+=`
+        operation: Option<Token>,
         rhs: Box<Expression>
     },
     Call {
@@ -92,9 +179,98 @@ pub enum Expression {
         operation:  Token,
         rhs:        Box<Expression>,
     },
+    /// `lhs && rhs` or `lhs || rhs`, a boolean. `rhs` is only evaluated when `lhs` doesn't decide it
+    Logical {
+        lhs:        Box<Expression>,
+        operation:  Token,
+        rhs:        Box<Expression>,
+    },
+    /// `x++` or `x--`, which evaluate to the value from before the update
+    Postfix {
+        operand:    Box<Expression>,
+        operation:  Token,
+    },
+    /// `condition ? then : otherwise`, which only evaluates the branch it takes
+    Conditional {
+        condition:  Box<Expression>,
+        then:       Box<Expression>,
+        otherwise:  Box<Expression>,
+    },
+    /// `lhs ?? rhs`, which only evaluates `rhs` when `lhs` is null
+    Coalesce {
+        lhs:        Box<Expression>,
+        rhs:        Box<Expression>,
+    },
+    /// `target?.[property]`, null without evaluating `property` when `target` is null. \
+    /// Only this link is skipped, so chains need `?.` at every step that can be null
+    OptionalMember {
+        target:     Box<Expression>,
+        property:   Box<Expression>,
+    },
+    /// `target?.(args)`, null without evaluating `args` when `target` is null
+    OptionalCall {
+        target:     Box<Expression>,
+        args:       Option<Vec<Expression>>,
+    },
+    /// `start..end`, or `start..=end` when `inclusive`, the integers from `start` up to `end`
+    Range {
+        start:      Box<Expression>,
+        end:        Box<Expression>,
+        inclusive:  bool,
+    },
+    /// `...iterable`, which is only parsed as an array or set element or call argument. \
+    /// Expands to every element of `iterable` in its place
+    Spread(Box<Expression>),
+    /// `[element for pattern in iterable if condition]`, with any number of `for` and `if` clauses. \
+    /// The parser turns the clauses into `loops`, nested `ForEach` and `If` statements around an `Append`
+    /// of the element to `result`, a hidden variable only bound while they run. Parsed anywhere an expression is
+    Comprehension {
+        result:     Identifier,
+        loops:      Box<Statement>,
+        /// Where the first `for` starts
+        span:       Span,
+    },
+    /// Appends `value` to the array in `target`, the innermost statement of a comprehension's loops
+    Append {
+        target:     Identifier,
+        value:      Box<Expression>,
+    },
+}
+
+impl Expression {
+    /// A `Binary`, or a `Logical` for `&&` and `||`
+    pub fn binary(lhs: Expression, operation: Token, rhs: Expression) -> Expression {
+        let (lhs, rhs) = (Box::new(lhs), Box::new(rhs));
+
+        match operation {
+            Token::LogicalAnd | Token::LogicalOr => Expression::Logical { lhs, operation, rhs },
+            operation => Expression::Binary { lhs, operation, rhs },
+        }
+    }
+
+    /// What the expression names as the left side of an assignment, `None` if it can't be assigned to
+    pub fn place(&self) -> Option<Place<'_>> {
+        match self {
+            Expression::Value(identifier) => Some(Place::Variable(identifier)),
+            Expression::Member { target, property } => Some(Place::Element { target, index: property }),
+            _ => None,
+        }
+    }
+}
+
+/// An lvalue: what assignments and `++`/`--` store into, fields will join these once there are structs. \
+/// The target of an element is any expression, so `grid[i][j]` stores into the array `grid[i]` evaluates to
+#[derive(Debug, Clone, Copy)]
+pub enum Place<'e> {
+    Variable(&'e Identifier),
+    /// `target[index]`
+    Element {
+        target: &'e Expression,
+        index:  &'e Expression,
+    },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Statement {
     If {
         condition: Expression,
@@ -111,6 +287,12 @@ pub enum Statement {
         step: Option<Expression>,
         body: Block
     },
+    /// `for (variable in iterable)`, with `iterable` evaluated once before the first iteration
+    ForEach {
+        variable: Identifier,
+        iterable: Expression,
+        body: Block
+    },
     Else {
         body: Block
     },
@@ -118,6 +300,21 @@ pub enum Statement {
     Continue,
     Block(Block),
     Return(Expression),
+    /// `throw value`, unwinding to the nearest enclosing `catch`. Runtime errors are thrown as their message
+    Throw(Expression),
+    /// `try { } catch (e) { } finally { }`, with a `catch`, a `finally` or both. \
+    /// `finally` runs however the rest is left, by falling off the end, a `throw`, `return`, `break` or `continue`
+    Try {
+        body: Block,
+        catch: Option<(Identifier, Block)>,
+        finally: Option<Block>,
+    },
+    /// `assert condition, message`, failing with the message and the value of the condition, or of both
+    /// operands if it's a binary operation. The message is optional and only evaluated on failure
+    Assert {
+        condition: Expression,
+        message: Option<Expression>,
+    },
     Expression(Expression),
     Declaration(Declaration)
 }
@@ -147,17 +344,40 @@ impl Scope {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Block {
     scope: Scope,
-    statements: Vec<Statement>
+    statements: Vec<Statement>,
+    spans: Vec<Span>,
 }
 
 impl Block {
-    pub fn new(scope: Scope, statements: Vec<Statement>) -> Self {
+    pub fn new(scope: Scope, statements: Vec<Statement>, spans: Vec<Span>) -> Self {
         Block {
             scope,
-            statements
+            statements,
+            spans
         }
     }
+
+    pub fn scope(&self) -> Scope {
+        self.scope.clone()
+    }
+
+    pub fn statements(&self) -> &Vec<Statement> {
+        &self.statements
+    }
+
+    pub fn statements_mut(&mut self) -> &mut Vec<Statement> {
+        &mut self.statements
+    }
+
+    /// Where each of `statements` starts
+    pub fn spans(&self) -> &Vec<Span> {
+        &self.spans
+    }
+
+    pub fn into_parts(self) -> (Scope, Vec<Statement>, Vec<Span>) {
+        (self.scope, self.statements, self.spans)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,209 @@
+use super::ast::*;
+
+/// Rewrites the tree by value: every method takes a node and returns its replacement, which may be
+/// any node of the same type. \
+/// The defaults rebuild the node from its folded children with the `fold_*` functions, bottom up,
+/// so overriding `fold_expression` and calling `walk_expression` first sees already folded operands
+pub trait Folder {
+    fn fold_ast(&mut self, ast: AST) -> AST {
+        walk_ast(self, ast)
+    }
+
+    fn fold_declaration(&mut self, decl: Declaration) -> Declaration {
+        walk_declaration(self, decl)
+    }
+
+    fn fold_statement(&mut self, stmt: Statement) -> Statement {
+        walk_statement(self, stmt)
+    }
+
+    fn fold_block(&mut self, block: Block) -> Block {
+        walk_block(self, block)
+    }
+
+    fn fold_expression(&mut self, expr: Expression) -> Expression {
+        walk_expression(self, expr)
+    }
+
+    fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+        walk_pattern(self, pattern)
+    }
+
+    fn fold_identifier(&mut self, identifier: Identifier) -> Identifier {
+        identifier
+    }
+
+    fn fold_literal(&mut self, literal: Literal) -> Literal {
+        literal
+    }
+}
+
+pub fn walk_ast<F: Folder + ?Sized>(folder: &mut F, mut ast: AST) -> AST {
+    let tests = std::mem::take(ast.tests_mut());
+    let (program, spans) = ast.into_parts();
+    let mut folded = AST::new();
+
+    for (decl, span) in program.into_iter().zip(spans) {
+        folded.push(folder.fold_declaration(decl), span);
+    }
+
+    for test in tests {
+        folded.push_test(Test { body: folder.fold_block(test.body), ..test });
+    }
+
+    folded
+}
+
+pub fn walk_declaration<F: Folder + ?Sized>(folder: &mut F, decl: Declaration) -> Declaration {
+    match decl {
+        Declaration::Function { identifier, arguments, body } => Declaration::Function {
+            identifier: folder.fold_identifier(identifier),
+            arguments: arguments.map(|args| args.into_iter().map(|a| folder.fold_identifier(a)).collect()),
+            body: folder.fold_block(body),
+        },
+        Declaration::Variable { identifier, value } => {
+            let value = value.map(|v| folder.fold_expression(v));
+            Declaration::Variable { identifier: folder.fold_identifier(identifier), value }
+        },
+        Declaration::Destructure { pattern, value } => {
+            let value = folder.fold_expression(value);
+            Declaration::Destructure { pattern: folder.fold_pattern(pattern), value }
+        },
+    }
+}
+
+pub fn walk_pattern<F: Folder + ?Sized>(folder: &mut F, pattern: Pattern) -> Pattern {
+    match pattern {
+        Pattern::Identifier(identifier) => Pattern::Identifier(folder.fold_identifier(identifier)),
+        Pattern::Tuple(patterns) => Pattern::Tuple(patterns.into_iter().map(|p| folder.fold_pattern(p)).collect()),
+        Pattern::Array(patterns) => Pattern::Array(patterns.into_iter().map(|p| folder.fold_pattern(p)).collect()),
+    }
+}
+
+pub fn walk_statement<F: Folder + ?Sized>(folder: &mut F, stmt: Statement) -> Statement {
+    match stmt {
+        Statement::If { condition, body, else_stmt } => Statement::If {
+            condition: folder.fold_expression(condition),
+            body: folder.fold_block(body),
+            else_stmt: else_stmt.map(|s| Box::new(folder.fold_statement(*s))),
+        },
+        Statement::While { condition, body } => Statement::While {
+            condition: folder.fold_expression(condition),
+            body: folder.fold_block(body),
+        },
+        Statement::For { variable, condition, step, body } => {
+            let variable = variable.map(|s| Box::new(folder.fold_statement(*s)));
+            let condition = condition.map(|c| folder.fold_expression(c));
+            let body = folder.fold_block(body);
+            let step = step.map(|s| folder.fold_expression(s));
+
+            Statement::For { variable, condition, step, body }
+        },
+        Statement::ForEach { variable, iterable, body } => {
+            let iterable = folder.fold_expression(iterable);
+            let variable = folder.fold_identifier(variable);
+
+            Statement::ForEach { variable, iterable, body: folder.fold_block(body) }
+        },
+        Statement::Else { body } => Statement::Else { body: folder.fold_block(body) },
+        Statement::Block(block) => Statement::Block(folder.fold_block(block)),
+        Statement::Return(expr) => Statement::Return(folder.fold_expression(expr)),
+        Statement::Throw(expr) => Statement::Throw(folder.fold_expression(expr)),
+        Statement::Assert { condition, message } => Statement::Assert {
+            condition: folder.fold_expression(condition),
+            message: message.map(|m| folder.fold_expression(m)),
+        },
+        Statement::Try { body, catch, finally } => {
+            let body = folder.fold_block(body);
+            let catch = catch.map(|(identifier, block)| (folder.fold_identifier(identifier), folder.fold_block(block)));
+
+            Statement::Try { body, catch, finally: finally.map(|block| folder.fold_block(block)) }
+        },
+        Statement::Expression(expr) => Statement::Expression(folder.fold_expression(expr)),
+        Statement::Declaration(decl) => Statement::Declaration(folder.fold_declaration(decl)),
+        Statement::Break => Statement::Break,
+        Statement::Continue => Statement::Continue,
+    }
+}
+
+pub fn walk_block<F: Folder + ?Sized>(folder: &mut F, block: Block) -> Block {
+    let (scope, statements, spans) = block.into_parts();
+    let statements = statements.into_iter().map(|s| folder.fold_statement(s)).collect();
+
+    Block::new(scope, statements, spans)
+}
+
+pub fn walk_expression<F: Folder + ?Sized>(folder: &mut F, expr: Expression) -> Expression {
+    match expr {
+        Expression::Literal(literal) => Expression::Literal(folder.fold_literal(literal)),
+        Expression::Value(identifier) => Expression::Value(folder.fold_identifier(identifier)),
+        Expression::Member { target, property } => {
+            let target = boxed(folder, target);
+            Expression::Member { target, property: boxed(folder, property) }
+        },
+        Expression::Array { scope, elements } => Expression::Array {
+            scope,
+            elements: elements.into_iter().map(|e| folder.fold_expression(e)).collect(),
+        },
+        Expression::Assignment { lhs, operation, rhs } => {
+            let rhs = boxed(folder, rhs);
+            Expression::Assignment { lhs: boxed(folder, lhs), operation, rhs }
+        },
+        Expression::Call { target, args } => Expression::Call {
+            target: boxed(folder, target),
+            args: args.map(|args| args.into_iter().map(|a| folder.fold_expression(a)).collect()),
+        },
+        Expression::Unary { prefix, operand } => Expression::Unary { prefix, operand: boxed(folder, operand) },
+        Expression::Binary { lhs, operation, rhs } => {
+            let lhs = boxed(folder, lhs);
+            Expression::Binary { lhs, operation, rhs: boxed(folder, rhs) }
+        },
+        Expression::Logical { lhs, operation, rhs } => {
+            let lhs = boxed(folder, lhs);
+            Expression::Logical { lhs, operation, rhs: boxed(folder, rhs) }
+        },
+        Expression::Postfix { operand, operation } => Expression::Postfix { operand: boxed(folder, operand), operation },
+        Expression::Conditional { condition, then, otherwise } => {
+            let condition = boxed(folder, condition);
+            let then = boxed(folder, then);
+            Expression::Conditional { condition, then, otherwise: boxed(folder, otherwise) }
+        },
+        Expression::Coalesce { lhs, rhs } => {
+            let lhs = boxed(folder, lhs);
+            Expression::Coalesce { lhs, rhs: boxed(folder, rhs) }
+        },
+        Expression::OptionalMember { target, property } => {
+            let target = boxed(folder, target);
+            Expression::OptionalMember { target, property: boxed(folder, property) }
+        },
+        Expression::OptionalCall { target, args } => Expression::OptionalCall {
+            target: boxed(folder, target),
+            args: args.map(|args| args.into_iter().map(|a| folder.fold_expression(a)).collect()),
+        },
+        Expression::Range { start, end, inclusive } => {
+            let start = boxed(folder, start);
+            Expression::Range { start, end: boxed(folder, end), inclusive }
+        },
+        Expression::Tuple(elements) => Expression::Tuple(elements.into_iter().map(|e| folder.fold_expression(e)).collect()),
+        Expression::Map(entries) => Expression::Map(entries.into_iter()
+            .map(|(key, value)| (folder.fold_expression(key), folder.fold_expression(value)))
+            .collect()),
+        Expression::Set(elements) => Expression::Set(elements.into_iter().map(|e| folder.fold_expression(e)).collect()),
+        Expression::Spread(operand) => Expression::Spread(boxed(folder, operand)),
+        Expression::Comprehension { result, loops, span } => {
+            let result = folder.fold_identifier(result);
+            Expression::Comprehension { result, loops: Box::new(folder.fold_statement(*loops)), span }
+        },
+        Expression::Append { target, value } => {
+            let value = boxed(folder, value);
+            Expression::Append { target: folder.fold_identifier(target), value }
+        },
+    }
+}
+
+/// Folds a boxed operand, reusing its allocation
+fn boxed<F: Folder + ?Sized>(folder: &mut F, mut expr: Box<Expression>) -> Box<Expression> {
+    let taken = std::mem::replace(expr.as_mut(), Expression::Literal(Literal::Null));
+    *expr = folder.fold_expression(taken);
+    expr
+}
@@ -1,4 +1,9 @@
 pub mod parser;
 pub mod ast;
+pub mod visit;
+pub mod visit_mut;
+pub mod fold;
 mod error;
-mod util;
\ No newline at end of file
+pub(crate) mod util;
+
+pub use error::ParseError;
\ No newline at end of file
@@ -1,4 +1,4 @@
-use super::super::lex::token::Token;
+use super::super::lex::token::{ Span, Token };
 use super::error::ParseError;
 use super::ast::*;
 use super::util::*;
@@ -9,7 +9,9 @@ pub type StatementResult = Result<Statement, ParseError>;
 
 #[derive(Clone)]
 pub struct Parser<'p> {
-    tok: Peekable<Iter<'p, Token>>
+    tok: Peekable<Iter<'p, Token>>,
+    spans: &'p [Span],
+    index: usize,
 }
 
 // TODO list:
@@ -17,21 +19,25 @@ pub struct Parser<'p> {
 // - Parse if, while, else, for, etc. so that body is just a single statement (which includes blocks)
 
 impl<'p, 's> Parser<'p> {
-    pub fn new(tokens: &'p [Token]) -> Parser<'p> {
+    /// `spans` are parallel to `tokens` and record where declarations and statements start
+    pub fn new(tokens: &'p [Token], spans: &'p [Span]) -> Parser<'p> {
         Parser {
-            tok: tokens.iter().peekable()
+            tok: tokens.iter().peekable(),
+            spans,
+            index: 0,
         }
     }
 
-    pub fn next(&mut self) -> Result<Token, ParseError> {
+    fn next(&mut self) -> Result<Token, ParseError> {
         if let Some(next) = self.tok.next() {
+            self.index += 1;
             return Ok((*next).clone())
         }
 
         Err(ParseError::UnexpectedEOF)
     }
 
-    pub fn peek(&mut self) -> Option<Token> {
+    fn peek(&mut self) -> Option<Token> {
         if let Some(next) = self.tok.peek() {
             return Some((*next).clone())
         }
@@ -39,6 +45,16 @@ impl<'p, 's> Parser<'p> {
         None
     }
 
+    /// Span of the next token
+    fn span(&self) -> Span {
+        self.spans.get(self.index).copied().unwrap_or_default()
+    }
+
+    /// Whether the next token is on the same line as the last one
+    fn same_line(&self) -> bool {
+        self.index > 0 && self.spans.get(self.index - 1).map(|s| s.line) == Some(self.span().line)
+    }
+
     fn maybe(&mut self, token: Token) -> bool {
         if self.peek() == Some(token) {
             return self.next().is_ok()
@@ -61,9 +77,12 @@ impl<'p, 's> Parser<'p> {
 
         // Parse program 
         while let Some(tok) = self.peek() {
+            let span = self.span();
+
             match tok {
-                Token::Func  => ast.push(self.parse_func_decl(&Scope::Global)?),
-                Token::Let   => ast.push(self.parse_variable_decl(&Scope::Global)?),
+                Token::Func  => ast.push(self.parse_func_decl(&Scope::Global)?, span),
+                Token::Let   => ast.push(self.parse_variable_decl(&Scope::Global)?, span),
+                Token::Identifier(name) if name == "test" => ast.push_test(self.parse_test(span)?),
                 Token::EOF => break,
 
                 e => return Err(ParseError::UnexpectedToken(e)),
@@ -75,15 +94,49 @@ impl<'p, 's> Parser<'p> {
         Ok(ast)
     }
 
+    /// `test "name" { }`, where `test` is only a keyword at the top level
+    fn parse_test(&mut self, span: Span) -> Result<Test, ParseError> {
+        self.next()?;
+
+        let name = match self.next()? {
+            Token::StringLiteral(name) => name,
+            tok => return Err(ParseError::UnexpectedToken(tok)),
+        };
+
+        let body = self.parse_block(&Scope::Local(String::from("test")))?;
+
+        Ok(Test { name, body, span })
+    }
+
     fn parse_func_decl(&mut self, scope: &'s Scope) -> Result<Declaration, ParseError> {
         self.expect(Token::Func)?;
 
         let identifier = self.parse_identifier(scope)?;
-        let name = identifier.name();
+        let local = &Scope::Local(identifier.name());
         
-        let arguments = self.parse_args_decl(&Scope::Local(name))?;
+        let patterns = self.parse_args_decl(local)?;
+
+        let body = self.parse_block(local)?;
 
-        let body = self.parse_block(&Scope::Local(identifier.name()))?;
+        // A destructured parameter is passed under a hidden name, then destructured by the body
+        let mut arguments = vec![];
+        let mut destructures = vec![];
+
+        for (i, (pattern, span)) in patterns.into_iter().enumerate() {
+            match pattern {
+                Pattern::Identifier(arg) => arguments.push(arg),
+                pattern => {
+                    let hidden = Identifier::new(format!("@{}", i), local.clone());
+                    let value = Expression::Value(hidden.clone());
+
+                    arguments.push(hidden);
+                    destructures.push((Statement::Declaration(Declaration::Destructure { pattern, value }), span));
+                },
+            }
+        }
+
+        let arguments = (!arguments.is_empty()).then_some(arguments);
+        let body = prepend(body, destructures);
         
         let decl = Declaration::Function { identifier, arguments, body };
         
@@ -94,16 +147,18 @@ impl<'p, 's> Parser<'p> {
         self.expect(Token::LeftBrace)?;
 
         let mut statements = vec![];
+        let mut spans = vec![];
         
         while let Some(tok) = self.peek() {
             if tok == Token::RightBrace { break }
             
+            spans.push(self.span());
             statements.push(self.parse_stmt(scope)?);
         }
 
         self.expect(Token::RightBrace)?;
 
-        Ok(Block::new(scope.clone(), statements))
+        Ok(Block::new(scope.clone(), statements, spans))
     }
 
     fn parse_stmt(&mut self, scope: &'s Scope) -> StatementResult {
@@ -121,6 +176,9 @@ impl<'p, 's> Parser<'p> {
             Some(Token::LeftBrace) => Statement::Block(self.parse_block(scope)?),
 
             Some(Token::Return) => Statement::Return(self.parse_return(scope)?),
+            Some(Token::Throw) => Statement::Throw(self.parse_throw(scope)?),
+            Some(Token::Try) => self.parse_try(scope)?,
+            Some(Token::Assert) => self.parse_assert(scope)?,
 
             Some(e) => return Err(ParseError::UnexpectedToken(e)),
 
@@ -150,6 +208,10 @@ impl<'p, 's> Parser<'p> {
         for_scope.push_str("_for");
         let for_scope = &Scope::Local(for_scope);
 
+        if self.is_for_each() {
+            return self.parse_for_each(scope, for_scope)
+        }
+
         let variable = match self.peek() {
             Some(Token::Let) => Some(
                 Box::new(
@@ -191,6 +253,33 @@ impl<'p, 's> Parser<'p> {
         })
     }
 
+    /// Whether the loop head is `ident in` or starts with a pattern, which needs more than one token of lookahead
+    fn is_for_each(&self) -> bool {
+        let mut ahead = self.tok.clone();
+
+        match ahead.next() {
+            Some(Token::Identifier(_)) => ahead.next() == Some(&Token::In),
+            Some(Token::LeftParenthesis | Token::LeftBracket) => true,
+            _ => false,
+        }
+    }
+
+    /// The rest of `for (pattern in iterable) { }` after the opening parenthesis
+    fn parse_for_each(&mut self, scope: &'s Scope, for_scope: &'s Scope) -> StatementResult {
+        let span = self.span();
+        let pattern = self.parse_pattern(scope)?;
+
+        self.expect(Token::In)?;
+
+        let iterable = self.parse_expr(scope)?;
+
+        self.expect(Token::RightParenthesis)?;
+
+        let body = self.parse_block(scope)?;
+
+        Ok(for_each(pattern, iterable, body, for_scope, span))
+    }
+
     fn parse_if(&mut self, scope: &'s Scope) -> StatementResult {
         self.expect(Token::If)?;
         
@@ -258,8 +347,72 @@ impl<'p, 's> Parser<'p> {
         self.parse_expr(scope)
     }
 
+    fn parse_throw(&mut self, scope: &'s Scope) -> ExpressionResult {
+        self.expect(Token::Throw)?;
+
+        self.parse_expr(scope)
+    }
+
+    /// `assert condition` or `assert condition, message`
+    fn parse_assert(&mut self, scope: &'s Scope) -> StatementResult {
+        self.expect(Token::Assert)?;
+
+        let condition = self.parse_expr(scope)?;
+
+        let message = match self.maybe(Token::Comma) {
+            true => Some(self.parse_expr(scope)?),
+            false => None,
+        };
+
+        Ok(Statement::Assert { condition, message })
+    }
+
+    /// `try { } catch (e) { } finally { }`, where either clause can be left out but not both
+    fn parse_try(&mut self, scope: &'s Scope) -> StatementResult {
+        self.expect(Token::Try)?;
+
+        let mut local_scope = scope.name();
+        local_scope.push_str("_try");
+        let try_scope = &Scope::Local(local_scope);
+
+        let body = self.parse_block(try_scope)?;
+
+        let catch = match self.maybe(Token::Catch) {
+            true => {
+                self.expect(Token::LeftParenthesis)?;
+                let identifier = self.parse_identifier(try_scope)?;
+                self.expect(Token::RightParenthesis)?;
+
+                Some((identifier, self.parse_block(try_scope)?))
+            },
+            false => None,
+        };
+
+        let finally = match self.maybe(Token::Finally) {
+            true => Some(self.parse_block(try_scope)?),
+            false => None,
+        };
+
+        if catch.is_none() && finally.is_none() {
+            return Err(match self.peek() {
+                Some(tok) => ParseError::UnexpectedToken(tok),
+                None => ParseError::UnexpectedEOF,
+            })
+        }
+
+        Ok(Statement::Try {
+            body,
+            catch,
+            finally
+        })
+    }
+
     fn parse_variable_decl(&mut self, scope: &'s Scope) -> Result<Declaration, ParseError> {
         self.expect(Token::Let)?;
+
+        if let Some(Token::LeftParenthesis | Token::LeftBracket) = self.peek() {
+            return self.parse_destructure(scope)
+        }
         
         let identifier = self.parse_identifier(scope)?;        
 
@@ -267,12 +420,7 @@ impl<'p, 's> Parser<'p> {
             Some(Token::Semicolon) => None,
             Some(Token::Assign) => { 
                 self.next()?; 
-
-                match self.peek() {
-                    Some(Token::LeftBracket) => Some(self.parse_array_decl(scope)?),
-                    None => return Err(ParseError::UnexpectedEOF),
-                    _ => Some(self.parse_expr(scope)?),
-                }
+                Some(self.parse_expr(scope)?)
             },
             _ => return Err(ParseError::BadAssignment)
         };
@@ -282,14 +430,60 @@ impl<'p, 's> Parser<'p> {
         Ok(decl)
     }
 
-    fn parse_args_decl(&mut self, scope: &'s Scope) -> Result<Option<Vec<Identifier>>, ParseError> {
+    /// `let pattern := value` after the `let`, where the value can't be left out
+    fn parse_destructure(&mut self, scope: &'s Scope) -> Result<Declaration, ParseError> {
+        let pattern = self.parse_pattern(scope)?;
+
+        if !self.maybe(Token::Assign) {
+            return Err(ParseError::BadAssignment)
+        }
+
+        let value = self.parse_expr(scope)?;
+
+        Ok(Declaration::Destructure { pattern, value })
+    }
+
+    /// A name, `(a, b)` or `[a, b]`, nesting freely. \
+    /// Like in expressions `(a)` is just `a` and a one element tuple needs a trailing comma, `(a,)`
+    fn parse_pattern(&mut self, scope: &'s Scope) -> Result<Pattern, ParseError> {
+        let close = match self.peek() {
+            Some(Token::LeftParenthesis) => Token::RightParenthesis,
+            Some(Token::LeftBracket) => Token::RightBracket,
+            Some(_) => return Ok(Pattern::Identifier(self.parse_identifier(scope)?)),
+            None => return Err(ParseError::UnexpectedEOF)
+        };
+
+        self.next()?;
+
+        let mut patterns = vec![];
+        let mut trailing = false;
+
+        while self.peek() != Some(close.clone()) {
+            patterns.push(self.parse_pattern(scope)?);
+
+            trailing = self.maybe(Token::Comma);
+            if !trailing { break }
+        }
+
+        self.expect(close.clone())?;
+
+        match close {
+            Token::RightParenthesis if patterns.len() == 1 && !trailing => Ok(patterns.pop().unwrap()),
+            Token::RightParenthesis => Ok(Pattern::Tuple(patterns)),
+            _ => Ok(Pattern::Array(patterns)),
+        }
+    }
+
+    /// Parameters are patterns, each with where it starts
+    fn parse_args_decl(&mut self, scope: &'s Scope) -> Result<Vec<(Pattern, Span)>, ParseError> {
         self.expect(Token::LeftParenthesis)?;
 
         let mut args = vec![];
         
         if self.peek() != Some(Token::RightParenthesis) {
             // Initial
-            args.push(self.parse_identifier(scope)?);
+            let span = self.span();
+            args.push((self.parse_pattern(scope)?, span));
             
             // Args+
             while let Some(tok) = self.peek() {
@@ -297,21 +491,19 @@ impl<'p, 's> Parser<'p> {
                 
                 self.expect(Token::Comma)?;
                 
+                let span = self.span();
                 let arg = match self.peek() {
-                    Some(_) => self.parse_identifier(scope)?,
+                    Some(_) => self.parse_pattern(scope)?,
                     _ => return Err(ParseError::SyntaxError)
                 };
                 
-                args.push(arg);
+                args.push((arg, span));
             }
         }
 
         self.expect(Token::RightParenthesis)?;
 
-        match args.len() {
-            0 => Ok(None),
-            _ => Ok(Some(args))
-        }
+        Ok(args)
     }    
 
     fn parse_array_decl(&mut self, scope: &'s Scope) -> ExpressionResult {
@@ -319,9 +511,13 @@ impl<'p, 's> Parser<'p> {
 
         self.expect(Token::LeftBracket)?;
 
+        if self.is_comprehension() {
+            return self.parse_comprehension(scope)
+        }
+
         if self.peek() != Some(Token::RightBracket) {
             // Initial
-            elements.push(self.parse_expr(scope)?);
+            elements.push(self.parse_element(scope)?);
             
             // Args+
             while let Some(tok) = self.peek() {
@@ -330,7 +526,7 @@ impl<'p, 's> Parser<'p> {
                 self.expect(Token::Comma)?;
                 
                 let arg = match self.peek() {
-                    Some(_) => self.parse_expr(scope)?,
+                    Some(_) => self.parse_element(scope)?,
                     _ => return Err(ParseError::SyntaxError)
                 };
                 
@@ -346,7 +542,147 @@ impl<'p, 's> Parser<'p> {
         })
     }
 
+    /// Whether the first element of an array literal is followed by a `for`, which needs lookahead past the element
+    fn is_comprehension(&self) -> bool {
+        let mut depth = 0;
+
+        for tok in self.tok.clone() {
+            match tok {
+                Token::LeftParenthesis | Token::LeftBracket | Token::LeftBrace => depth += 1,
+                Token::RightParenthesis | Token::RightBracket | Token::RightBrace if depth == 0 => return false,
+                Token::RightParenthesis | Token::RightBracket | Token::RightBrace => depth -= 1,
+                Token::Comma if depth == 0 => return false,
+                Token::For if depth == 0 => return true,
+                _ => (),
+            }
+        }
+
+        false
+    }
+
+    /// The rest of `[element for pattern in iterable if condition ...]` after the opening bracket. \
+    /// Clauses nest left to right like the loops they become, so each sees the names bound before it.
+    /// Everything inside gets its own scope, the bound names don't outlive the brackets
+    fn parse_comprehension(&mut self, scope: &'s Scope) -> ExpressionResult {
+        let mut local_scope = scope.name();
+        local_scope.push_str("_comprehension");
+        let inner = &Scope::Local(local_scope);
+
+        let element_span = self.span();
+        let element = self.parse_expr(inner)?;
+        let span = self.span();
+
+        let mut clauses = vec![];
+
+        while self.peek() != Some(Token::RightBracket) {
+            let clause_span = self.span();
+
+            match self.next()? {
+                Token::For => {
+                    let pattern = self.parse_pattern(inner)?;
+                    self.expect(Token::In)?;
+                    clauses.push((Some(pattern), self.parse_expr(inner)?, clause_span));
+                },
+                Token::If if !clauses.is_empty() => clauses.push((None, self.parse_expr(inner)?, clause_span)),
+                tok => return Err(ParseError::UnexpectedToken(tok)),
+            }
+        }
+
+        self.expect(Token::RightBracket)?;
+
+        // The loops are built from the innermost statement out
+        let result = Identifier::new(String::from("@comprehension"), inner.clone());
+        let append = Expression::Append { target: result.clone(), value: Box::new(element) };
+        let (mut stmt, mut stmt_span) = (Statement::Expression(append), element_span);
+
+        for (pattern, expr, clause_span) in clauses.into_iter().rev() {
+            let body = Block::new(inner.clone(), vec![stmt], vec![stmt_span]);
+
+            stmt = match pattern {
+                Some(pattern) => for_each(pattern, expr, body, inner, clause_span),
+                None => Statement::If { condition: expr, body, else_stmt: None },
+            };
+            stmt_span = clause_span;
+        }
+
+        Ok(Expression::Comprehension {
+            result,
+            loops: Box::new(stmt),
+            span
+        })
+    }
+
+    /// An array element or call argument, which may be spread with `...`
+    fn parse_element(&mut self, scope: &'s Scope) -> ExpressionResult {
+        match self.maybe(Token::Spread) {
+            true => Ok(Expression::Spread(Box::new(self.parse_expr(scope)?))),
+            false => self.parse_expr(scope),
+        }
+    }
+
     fn parse_expr(&mut self, scope: &'s Scope) -> ExpressionResult {
+        let expr = self.parse_range(scope)?;
+
+        match self.peek() {
+            Some(Token::Question) => self.parse_conditional(expr, scope),
+            _ => Ok(expr)
+        }
+    }
+
+    /// `cond ? a : b`, right associative as `b` is a whole expression
+    fn parse_conditional(&mut self, condition: Expression, scope: &'s Scope) -> ExpressionResult {
+        self.expect(Token::Question)?;
+
+        let then = self.parse_expr(scope)?;
+
+        self.expect(Token::Colon)?;
+
+        let otherwise = self.parse_expr(scope)?;
+
+        Ok(Expression::Conditional {
+            condition: Box::new(condition),
+            then: Box::new(then),
+            otherwise: Box::new(otherwise)
+        })
+    }
+
+    /// `a..b` and `a..=b` bind looser than `??`, and don't chain
+    fn parse_range(&mut self, scope: &'s Scope) -> ExpressionResult {
+        let start = self.parse_coalesce(scope)?;
+
+        let inclusive = match self.peek() {
+            Some(Token::Range) => false,
+            Some(Token::RangeInclusive) => true,
+            _ => return Ok(start)
+        };
+
+        self.next()?;
+        let end = self.parse_coalesce(scope)?;
+
+        Ok(Expression::Range {
+            start: Box::new(start),
+            end: Box::new(end),
+            inclusive
+        })
+    }
+
+    /// `??` binds looser than every binary operator
+    fn parse_coalesce(&mut self, scope: &'s Scope) -> ExpressionResult {
+        let mut expr = self.parse_operation(scope)?;
+
+        while self.maybe(Token::Coalesce) {
+            let rhs = self.parse_operation(scope)?;
+
+            expr = Expression::Coalesce {
+                lhs: Box::new(expr),
+                rhs: Box::new(rhs)
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_operation(&mut self, scope: &'s Scope) -> ExpressionResult {
         let mut expr = match self.peek() {
             // Everything should start with smallest piece: unary op
             Some(tok) if is_unop_start(&tok) => self.parse_unary(scope)?,
@@ -356,10 +692,10 @@ impl<'p, 's> Parser<'p> {
         
         // Next is start to binary expr ? parse : return unary expr
         while let Some(next) = self.peek() {
-            if !is_binop(&next) { break }
+            if !is_binop(&next) && !is_reassignment_op(&next) { break }
             
             expr = match next {
-                Token::Assign => self.parse_reassignment(expr, scope)?,
+                op if is_reassignment_op(&op) => self.parse_reassignment(expr, scope)?,
                 _ => self.parse_binary(expr, scope)?
             };
         }
@@ -367,30 +703,23 @@ impl<'p, 's> Parser<'p> {
         Ok(expr)
     }
 
+    /// `**` is right associative, every other operator left associative
     fn parse_binary(&mut self, lhs: Expression, scope: &'s Scope) -> ExpressionResult {
         let op = self.next()?;
         let mut rhs = self.parse_unary(scope)?;
 
-        if let Some(next) = self.peek() {
+        if let Some(next) = self.peek().filter(is_binop) {
             match next.prec() {
-                n if n > op.prec() => 
+                n if n > op.prec() || (n == op.prec() && op == Token::Exponentiate) => 
                     rhs = self.parse_binary(rhs, scope)?,
                 n if n == op.prec() => {
-                    return self.parse_binary(Expression::Binary {
-                        operation: op,
-                        lhs: Box::new(lhs),
-                        rhs: Box::new(rhs),
-                    }, scope);
+                    return self.parse_binary(Expression::binary(lhs, op, rhs), scope);
                 },
                 _ => ()
             }
         }
 
-        Ok(Expression::Binary {
-            operation: op,
-            lhs: Box::new(lhs),
-            rhs: Box::new(rhs),
-        })
+        Ok(Expression::binary(lhs, op, rhs))
     }
 
     fn parse_unary(&mut self, scope: &'s Scope) -> ExpressionResult {
@@ -403,14 +732,32 @@ impl<'p, 's> Parser<'p> {
             op = self.parse_postfix(op, scope)?;
         }
 
-        if prefix == None {
-            return Ok(op)
+        // A `++` starting the next line belongs to the next statement
+        if let Some(operation @ (Token::Increment | Token::Decrement)) = self.peek() {
+            if self.same_line() {
+                self.next()?;
+
+                if op.place().is_none() {
+                    return Err(ParseError::BadAssignment)
+                }
+
+                op = Expression::Postfix { operand: Box::new(op), operation };
+            }
         }
 
-        Ok(Expression::Unary {
-            prefix,
-            operand: Box::new(op)
-        })
+        match prefix.as_ref().and_then(update_op) {
+            Some(_) if op.place().is_none() => Err(ParseError::BadAssignment),
+            Some(operation) => Ok(Expression::Assignment {
+                lhs: Box::new(op),
+                operation: Some(operation),
+                rhs: Box::new(Expression::Literal(Literal::Integer(1))),
+            }),
+            None if prefix.is_none() => Ok(op),
+            None => Ok(Expression::Unary {
+                prefix,
+                operand: Box::new(op)
+            }),
+        }
     }
 
     fn parse_prefix(&mut self) -> Option<Token> {
@@ -424,6 +771,8 @@ impl<'p, 's> Parser<'p> {
         use Expression :: { Literal, Value };
         match self.peek() {
             Some(Token::LeftParenthesis)    => self.parse_group(scope),
+            Some(Token::LeftBrace)          => self.parse_collection(scope),
+            Some(Token::LeftBracket)        => self.parse_array_decl(scope),
             Some(lit) if is_literal(&lit)   => Ok(Literal(self.parse_literal()?)),
             Some(ident) if is_ident(&ident) => Ok(Value(self.parse_identifier(scope)?)),
             Some(un) if is_unop_start(&un)  => self.parse_unary(scope),
@@ -436,11 +785,29 @@ impl<'p, 's> Parser<'p> {
         match self.peek() {
             Some(Token::LeftParenthesis) => self.parse_call(target, scope),
             Some(Token::LeftBracket)     => self.parse_index(target, scope),
+            Some(Token::Optional)        => self.parse_optional(target, scope),
             Some(e) => Err(ParseError::BadExpression(e)),
             None => Err(ParseError::UnexpectedEOF)
         }
     }
 
+    /// `?.[index]` or `?.(args)`, as there are no fields to name after `?.`
+    fn parse_optional(&mut self, target: Expression, scope: &'s Scope) -> ExpressionResult {
+        self.expect(Token::Optional)?;
+
+        match self.peek() {
+            Some(Token::LeftBracket | Token::LeftParenthesis) => (),
+            Some(e) => return Err(ParseError::BadExpression(e)),
+            None => return Err(ParseError::UnexpectedEOF)
+        }
+
+        match self.parse_postfix(target, scope)? {
+            Expression::Member { target, property } => Ok(Expression::OptionalMember { target, property }),
+            Expression::Call { target, args } => Ok(Expression::OptionalCall { target, args }),
+            _ => Err(ParseError::SyntaxError)
+        }
+    }
+
     fn parse_identifier(&mut self, scope: &'s Scope) -> Result<Identifier, ParseError> {
         let name = match self.next()? {
             Token::Identifier(id) => id,
@@ -465,14 +832,77 @@ impl<'p, 's> Parser<'p> {
         Ok(lit) // It's lit
     }
 
+    /// `(expr)`, or a tuple once there's a comma. `()` is the empty tuple
     fn parse_group(&mut self, scope: &'s Scope) -> ExpressionResult {
         self.expect(Token::LeftParenthesis)?;
+
+        if self.maybe(Token::RightParenthesis) {
+            return Ok(Expression::Tuple(vec![]))
+        }
         
         let group = self.parse_expr(scope)?;
+
+        if self.peek() != Some(Token::Comma) {
+            self.expect(Token::RightParenthesis)?;
+            return Ok(group)
+        }
+
+        let mut elements = vec![group];
+
+        while self.maybe(Token::Comma) {
+            if self.peek() == Some(Token::RightParenthesis) { break }
+
+            elements.push(self.parse_expr(scope)?);
+        }
         
         self.expect(Token::RightParenthesis)?;
 
-        Ok(group)
+        Ok(Expression::Tuple(elements))
+    }
+
+    /// `{key: value, ...}` is a map and `{a, b, ...}` a set, told apart by the first entry. \
+    /// `{}` is the empty map and `{,}` the empty set
+    fn parse_collection(&mut self, scope: &'s Scope) -> ExpressionResult {
+        self.expect(Token::LeftBrace)?;
+
+        if self.maybe(Token::RightBrace) {
+            return Ok(Expression::Map(vec![]))
+        }
+
+        if self.maybe(Token::Comma) {
+            self.expect(Token::RightBrace)?;
+            return Ok(Expression::Set(vec![]))
+        }
+
+        let first = self.parse_element(scope)?;
+
+        if !matches!(first, Expression::Spread(_)) && self.maybe(Token::Colon) {
+            let mut entries = vec![(first, self.parse_expr(scope)?)];
+
+            while self.maybe(Token::Comma) {
+                if self.peek() == Some(Token::RightBrace) { break }
+
+                let key = self.parse_expr(scope)?;
+                self.expect(Token::Colon)?;
+                entries.push((key, self.parse_expr(scope)?));
+            }
+
+            self.expect(Token::RightBrace)?;
+
+            return Ok(Expression::Map(entries))
+        }
+
+        let mut elements = vec![first];
+
+        while self.maybe(Token::Comma) {
+            if self.peek() == Some(Token::RightBrace) { break }
+
+            elements.push(self.parse_element(scope)?);
+        }
+
+        self.expect(Token::RightBrace)?;
+
+        Ok(Expression::Set(elements))
     }
 
     fn parse_call(&mut self, target: Expression, scope: &'s Scope) -> ExpressionResult {        
@@ -491,7 +921,7 @@ impl<'p, 's> Parser<'p> {
 
         if self.peek() != Some(Token::RightParenthesis) {
             // Initial
-            args.push(self.parse_expr(scope)?);
+            args.push(self.parse_element(scope)?);
             
             // Args+
             while let Some(tok) = self.peek() {
@@ -500,7 +930,7 @@ impl<'p, 's> Parser<'p> {
                 self.expect(Token::Comma)?;
                 
                 let arg = match self.peek() {
-                    Some(_) => self.parse_expr(scope)?,
+                    Some(_) => self.parse_element(scope)?,
                     _ => return Err(ParseError::SyntaxError)
                 };
                 
@@ -530,19 +960,50 @@ impl<'p, 's> Parser<'p> {
     }   
 
     fn parse_reassignment(&mut self, lhs: Expression, scope: &'s Scope) -> ExpressionResult {
-        // Only allow assignment to identifiers
-        match lhs {
-            Expression::Value(Identifier {..}) => (),
-            _ => return Err(ParseError::BadAssignment)
-        }
+        let operation = compound_op(&self.next()?);
 
-        self.expect(Token::Assign)?;
+        if lhs.place().is_none() {
+            return Err(ParseError::BadAssignment)
+        }
 
         let rhs = self.parse_expr(scope)?;
 
         Ok(Expression::Assignment {
             lhs: Box::new(lhs),
+            operation,
             rhs: Box::new(rhs)
         })
     }
+}
+
+/// A loop binding each element of `iterable` to `pattern`. \
+/// Unless the pattern is a plain name, elements are bound to a hidden variable the body starts by destructuring
+fn for_each(pattern: Pattern, iterable: Expression, body: Block, for_scope: &Scope, span: Span) -> Statement {
+    let (variable, body) = match pattern {
+        Pattern::Identifier(identifier) => (Identifier::new(identifier.name(), for_scope.clone()), body),
+        pattern => {
+            let hidden = Identifier::new(String::from("@element"), for_scope.clone());
+            let value = Expression::Value(hidden.clone());
+            let destructure = Statement::Declaration(Declaration::Destructure { pattern, value });
+
+            (hidden, prepend(body, vec![(destructure, span)]))
+        },
+    };
+
+    Statement::ForEach {
+        variable,
+        iterable,
+        body
+    }
+}
+
+/// `body` with `statements` run before its own
+fn prepend(body: Block, statements: Vec<(Statement, Span)>) -> Block {
+    let (scope, rest, rest_spans) = body.into_parts();
+    let (mut all, mut spans): (Vec<_>, Vec<_>) = statements.into_iter().unzip();
+
+    all.extend(rest);
+    spans.extend(rest_spans);
+
+    Block::new(scope, all, spans)
 }
\ No newline at end of file
@@ -2,8 +2,9 @@ use super::super::lex::token::Token;
 
 // Utility functions
 
+/// A `{` starting a statement opens a block, not a map or set
 pub fn is_expr_start(tok: &Token) -> bool {
-    is_unop_start(tok)
+    is_unop_start(tok) && *tok != Token::LeftBrace
 }
 
 pub fn is_ident(tok: &Token) -> bool {
@@ -17,29 +18,84 @@ pub fn is_literal(tok: &Token) -> bool {
 }
 
 pub fn is_unop_start(tok: &Token) -> bool {
-    *tok == Token::LeftParenthesis || is_unop_prefix(tok) || is_ident(tok) || is_literal(tok)
+    matches!(tok, Token::LeftParenthesis | Token::LeftBracket | Token::LeftBrace) || is_unop_prefix(tok) || is_ident(tok) || is_literal(tok)
 }
 
-// Increment and Decrement operators temporarily unimplemented because they are annoying
 pub fn is_unop_prefix(tok: &Token) -> bool {
     use Token::*;
-    matches!(tok, BinaryNegate | Minus | Not)
+    matches!(tok, BinaryNegate | Minus | Not | Increment | Decrement)
 }
 
 pub fn is_unop_postfix(tok: &Token) -> bool {
     use Token::*;
-    matches!(tok, LeftParenthesis | LeftBracket)
+    matches!(tok, LeftParenthesis | LeftBracket | Optional)
 }
 
 pub fn is_binop(tok: &Token) -> bool {
     use Token::*;
     matches!(tok, Plus | Minus | NotEquals | Equals | GreaterThan | GreaterEquals | LessThan |
         LessEquals | BinaryAnd | LogicalAnd | BinaryOr | LogicalOr | Multiply | Divide | Modulo |
-        Xor | ShiftRight | ShiftLeft | Assign)
+        Exponentiate | Xor | ShiftRight | ShiftLeft | In | Assign)
+}
+
+/// How a binary operator is written, `?` for tokens that aren't one
+pub fn binop_symbol(tok: &Token) -> &'static str {
+    use Token::*;
+    match tok {
+        Plus => "+",
+        Minus => "-",
+        Multiply => "*",
+        Divide => "/",
+        Modulo => "%",
+        Exponentiate => "**",
+        BinaryAnd => "&",
+        BinaryOr => "|",
+        Xor => "^",
+        ShiftLeft => "<<",
+        ShiftRight => ">>",
+        Equals => "==",
+        NotEquals => "!=",
+        LessThan => "<",
+        LessEquals => "<=",
+        GreaterThan => ">",
+        GreaterEquals => ">=",
+        LogicalAnd => "&&",
+        LogicalOr => "||",
+        In => "in",
+        _ => "?",
+    }
 }
 
-#[allow(dead_code)]
 pub fn is_reassignment_op(tok: &Token) -> bool {
+    *tok == Token::Assign || compound_op(tok).is_some()
+}
+
+/// The binary operator a compound assignment applies, `Token::Plus` for `+=`
+pub fn compound_op(tok: &Token) -> Option<Token> {
     use Token::*;
-    matches!(tok, PlusEquals | MinusEquals)
-}
\ No newline at end of file
+    let op = match tok {
+        PlusEquals => Plus,
+        MinusEquals => Minus,
+        MultiplyEquals => Multiply,
+        DivideEquals => Divide,
+        ModuloEquals => Modulo,
+        ExponentiateEquals => Exponentiate,
+        BinaryAndEquals => BinaryAnd,
+        BinaryOrEquals => BinaryOr,
+        XorEquals => Xor,
+        ShiftLeftEquals => ShiftLeft,
+        ShiftRightEquals => ShiftRight,
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+/// `++` and `--` update by adding or subtracting one
+pub fn update_op(tok: &Token) -> Option<Token> {
+    match tok {
+        Token::Increment => Some(Token::Plus),
+        Token::Decrement => Some(Token::Minus),
+        _ => None,
+    }
+}
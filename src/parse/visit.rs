@@ -0,0 +1,191 @@
+use super::super::lex::token::Span;
+use super::ast::*;
+
+/// Read only traversal of the tree. \
+/// Every method defaults to the matching `walk_*` function, which visits the node's children in
+/// evaluation order. Override the nodes of interest and call `walk_*` from them to keep descending
+///
+/// Statements carry the span of their block entry. Nested statements without one of their own,
+/// like an `else` or the initializer of a `for`, get the span of the statement containing them
+pub trait Visitor {
+    fn visit_ast(&mut self, ast: &AST) {
+        walk_ast(self, ast)
+    }
+
+    fn visit_declaration(&mut self, decl: &Declaration, span: Span) {
+        walk_declaration(self, decl, span)
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement, span: Span) {
+        walk_statement(self, stmt, span)
+    }
+
+    /// `span` stands in for statements the block has no span for
+    fn visit_block(&mut self, block: &Block, span: Span) {
+        walk_block(self, block, span)
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        walk_expression(self, expr)
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        walk_pattern(self, pattern)
+    }
+
+    /// Called for declared names, arguments and variable reads alike
+    fn visit_identifier(&mut self, _identifier: &Identifier) {}
+
+    fn visit_literal(&mut self, _literal: &Literal) {}
+}
+
+pub fn walk_ast<V: Visitor + ?Sized>(visitor: &mut V, ast: &AST) {
+    for (decl, span) in ast.program().iter().zip(ast.spans()) {
+        visitor.visit_declaration(decl, *span);
+    }
+
+    for test in ast.tests() {
+        visitor.visit_block(&test.body, test.span);
+    }
+}
+
+/// A variable's value is visited before its name, a function's name and arguments before its body
+pub fn walk_declaration<V: Visitor + ?Sized>(visitor: &mut V, decl: &Declaration, span: Span) {
+    match decl {
+        Declaration::Function { identifier, arguments, body } => {
+            visitor.visit_identifier(identifier);
+            arguments.iter().flatten().for_each(|arg| visitor.visit_identifier(arg));
+            visitor.visit_block(body, span);
+        },
+        Declaration::Variable { identifier, value } => {
+            if let Some(value) = value {
+                visitor.visit_expression(value);
+            }
+            visitor.visit_identifier(identifier);
+        },
+        Declaration::Destructure { pattern, value } => {
+            visitor.visit_expression(value);
+            visitor.visit_pattern(pattern);
+        },
+    }
+}
+
+pub fn walk_pattern<V: Visitor + ?Sized>(visitor: &mut V, pattern: &Pattern) {
+    match pattern {
+        Pattern::Identifier(identifier) => visitor.visit_identifier(identifier),
+        Pattern::Tuple(patterns) | Pattern::Array(patterns) => patterns.iter().for_each(|p| visitor.visit_pattern(p)),
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Statement, span: Span) {
+    match stmt {
+        Statement::If { condition, body, else_stmt } => {
+            visitor.visit_expression(condition);
+            visitor.visit_block(body, span);
+
+            if let Some(stmt) = else_stmt {
+                visitor.visit_statement(stmt, span);
+            }
+        },
+        Statement::While { condition, body } => {
+            visitor.visit_expression(condition);
+            visitor.visit_block(body, span);
+        },
+        Statement::For { variable, condition, step, body } => {
+            if let Some(stmt) = variable {
+                visitor.visit_statement(stmt, span);
+            }
+            if let Some(condition) = condition {
+                visitor.visit_expression(condition);
+            }
+
+            visitor.visit_block(body, span);
+
+            if let Some(step) = step {
+                visitor.visit_expression(step);
+            }
+        },
+        Statement::ForEach { variable, iterable, body } => {
+            visitor.visit_expression(iterable);
+            visitor.visit_identifier(variable);
+            visitor.visit_block(body, span);
+        },
+        Statement::Else { body: block } | Statement::Block(block) => visitor.visit_block(block, span),
+        Statement::Return(expr) | Statement::Expression(expr) | Statement::Throw(expr) => visitor.visit_expression(expr),
+        Statement::Assert { condition, message } => {
+            visitor.visit_expression(condition);
+
+            if let Some(message) = message {
+                visitor.visit_expression(message);
+            }
+        },
+        Statement::Try { body, catch, finally } => {
+            visitor.visit_block(body, span);
+
+            if let Some((identifier, block)) = catch {
+                visitor.visit_identifier(identifier);
+                visitor.visit_block(block, span);
+            }
+            if let Some(block) = finally {
+                visitor.visit_block(block, span);
+            }
+        },
+        Statement::Declaration(decl) => visitor.visit_declaration(decl, span),
+        Statement::Break | Statement::Continue => (),
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block, span: Span) {
+    for (i, stmt) in block.statements().iter().enumerate() {
+        visitor.visit_statement(stmt, block.spans().get(i).copied().unwrap_or(span));
+    }
+}
+
+/// The right hand side of an assignment is visited before its target
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expression) {
+    match expr {
+        Expression::Literal(literal) => visitor.visit_literal(literal),
+        Expression::Value(identifier) => visitor.visit_identifier(identifier),
+        Expression::Member { target, property } | Expression::OptionalMember { target, property } => {
+            visitor.visit_expression(target);
+            visitor.visit_expression(property);
+        },
+        Expression::Array { elements, .. } | Expression::Tuple(elements) | Expression::Set(elements) =>
+            elements.iter().for_each(|e| visitor.visit_expression(e)),
+        Expression::Map(entries) => entries.iter().for_each(|(key, value)| {
+            visitor.visit_expression(key);
+            visitor.visit_expression(value);
+        }),
+        Expression::Assignment { lhs, rhs, .. } => {
+            visitor.visit_expression(rhs);
+            visitor.visit_expression(lhs);
+        },
+        Expression::Call { target, args } | Expression::OptionalCall { target, args } => {
+            visitor.visit_expression(target);
+            args.iter().flatten().for_each(|a| visitor.visit_expression(a));
+        },
+        Expression::Unary { operand, .. } | Expression::Postfix { operand, .. } => visitor.visit_expression(operand),
+        Expression::Binary { lhs, rhs, .. } | Expression::Logical { lhs, rhs, .. } | Expression::Coalesce { lhs, rhs } => {
+            visitor.visit_expression(lhs);
+            visitor.visit_expression(rhs);
+        },
+        Expression::Conditional { condition, then, otherwise } => {
+            visitor.visit_expression(condition);
+            visitor.visit_expression(then);
+            visitor.visit_expression(otherwise);
+        },
+        Expression::Range { start, end, .. } => {
+            visitor.visit_expression(start);
+            visitor.visit_expression(end);
+        },
+        Expression::Spread(operand) => visitor.visit_expression(operand),
+        Expression::Comprehension { result, loops, span } => {
+            visitor.visit_identifier(result);
+            visitor.visit_statement(loops, *span);
+        },
+        Expression::Append { target, value } => {
+            visitor.visit_expression(value);
+            visitor.visit_identifier(target);
+        },
+    }
+}
@@ -0,0 +1,188 @@
+use super::super::lex::token::Span;
+use super::ast::*;
+
+/// In place traversal of the tree, the mutable counterpart of `Visitor`, visiting nodes in the
+/// same order. Nodes can be edited but not replaced by ones of a different kind, use a `Folder` for that
+pub trait VisitorMut {
+    fn visit_ast_mut(&mut self, ast: &mut AST) {
+        walk_ast_mut(self, ast)
+    }
+
+    fn visit_declaration_mut(&mut self, decl: &mut Declaration, span: Span) {
+        walk_declaration_mut(self, decl, span)
+    }
+
+    fn visit_statement_mut(&mut self, stmt: &mut Statement, span: Span) {
+        walk_statement_mut(self, stmt, span)
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block, span: Span) {
+        walk_block_mut(self, block, span)
+    }
+
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        walk_expression_mut(self, expr)
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        walk_pattern_mut(self, pattern)
+    }
+
+    fn visit_identifier_mut(&mut self, _identifier: &mut Identifier) {}
+
+    fn visit_literal_mut(&mut self, _literal: &mut Literal) {}
+}
+
+pub fn walk_ast_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut AST) {
+    let spans = ast.spans().clone();
+
+    for (decl, span) in ast.program_mut().iter_mut().zip(spans) {
+        visitor.visit_declaration_mut(decl, span);
+    }
+
+    for test in ast.tests_mut() {
+        visitor.visit_block_mut(&mut test.body, test.span);
+    }
+}
+
+pub fn walk_declaration_mut<V: VisitorMut + ?Sized>(visitor: &mut V, decl: &mut Declaration, span: Span) {
+    match decl {
+        Declaration::Function { identifier, arguments, body } => {
+            visitor.visit_identifier_mut(identifier);
+            arguments.iter_mut().flatten().for_each(|arg| visitor.visit_identifier_mut(arg));
+            visitor.visit_block_mut(body, span);
+        },
+        Declaration::Variable { identifier, value } => {
+            if let Some(value) = value {
+                visitor.visit_expression_mut(value);
+            }
+            visitor.visit_identifier_mut(identifier);
+        },
+        Declaration::Destructure { pattern, value } => {
+            visitor.visit_expression_mut(value);
+            visitor.visit_pattern_mut(pattern);
+        },
+    }
+}
+
+pub fn walk_pattern_mut<V: VisitorMut + ?Sized>(visitor: &mut V, pattern: &mut Pattern) {
+    match pattern {
+        Pattern::Identifier(identifier) => visitor.visit_identifier_mut(identifier),
+        Pattern::Tuple(patterns) | Pattern::Array(patterns) =>
+            patterns.iter_mut().for_each(|p| visitor.visit_pattern_mut(p)),
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Statement, span: Span) {
+    match stmt {
+        Statement::If { condition, body, else_stmt } => {
+            visitor.visit_expression_mut(condition);
+            visitor.visit_block_mut(body, span);
+
+            if let Some(stmt) = else_stmt {
+                visitor.visit_statement_mut(stmt, span);
+            }
+        },
+        Statement::While { condition, body } => {
+            visitor.visit_expression_mut(condition);
+            visitor.visit_block_mut(body, span);
+        },
+        Statement::For { variable, condition, step, body } => {
+            if let Some(stmt) = variable {
+                visitor.visit_statement_mut(stmt, span);
+            }
+            if let Some(condition) = condition {
+                visitor.visit_expression_mut(condition);
+            }
+
+            visitor.visit_block_mut(body, span);
+
+            if let Some(step) = step {
+                visitor.visit_expression_mut(step);
+            }
+        },
+        Statement::ForEach { variable, iterable, body } => {
+            visitor.visit_expression_mut(iterable);
+            visitor.visit_identifier_mut(variable);
+            visitor.visit_block_mut(body, span);
+        },
+        Statement::Else { body: block } | Statement::Block(block) => visitor.visit_block_mut(block, span),
+        Statement::Return(expr) | Statement::Expression(expr) | Statement::Throw(expr) => visitor.visit_expression_mut(expr),
+        Statement::Assert { condition, message } => {
+            visitor.visit_expression_mut(condition);
+
+            if let Some(message) = message {
+                visitor.visit_expression_mut(message);
+            }
+        },
+        Statement::Try { body, catch, finally } => {
+            visitor.visit_block_mut(body, span);
+
+            if let Some((identifier, block)) = catch {
+                visitor.visit_identifier_mut(identifier);
+                visitor.visit_block_mut(block, span);
+            }
+            if let Some(block) = finally {
+                visitor.visit_block_mut(block, span);
+            }
+        },
+        Statement::Declaration(decl) => visitor.visit_declaration_mut(decl, span),
+        Statement::Break | Statement::Continue => (),
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block, span: Span) {
+    let spans = block.spans().clone();
+
+    for (i, stmt) in block.statements_mut().iter_mut().enumerate() {
+        visitor.visit_statement_mut(stmt, spans.get(i).copied().unwrap_or(span));
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expression) {
+    match expr {
+        Expression::Literal(literal) => visitor.visit_literal_mut(literal),
+        Expression::Value(identifier) => visitor.visit_identifier_mut(identifier),
+        Expression::Member { target, property } | Expression::OptionalMember { target, property } => {
+            visitor.visit_expression_mut(target);
+            visitor.visit_expression_mut(property);
+        },
+        Expression::Array { elements, .. } | Expression::Tuple(elements) | Expression::Set(elements) =>
+            elements.iter_mut().for_each(|e| visitor.visit_expression_mut(e)),
+        Expression::Map(entries) => entries.iter_mut().for_each(|(key, value)| {
+            visitor.visit_expression_mut(key);
+            visitor.visit_expression_mut(value);
+        }),
+        Expression::Assignment { lhs, rhs, .. } => {
+            visitor.visit_expression_mut(rhs);
+            visitor.visit_expression_mut(lhs);
+        },
+        Expression::Call { target, args } | Expression::OptionalCall { target, args } => {
+            visitor.visit_expression_mut(target);
+            args.iter_mut().flatten().for_each(|a| visitor.visit_expression_mut(a));
+        },
+        Expression::Unary { operand, .. } | Expression::Postfix { operand, .. } => visitor.visit_expression_mut(operand),
+        Expression::Binary { lhs, rhs, .. } | Expression::Logical { lhs, rhs, .. } | Expression::Coalesce { lhs, rhs } => {
+            visitor.visit_expression_mut(lhs);
+            visitor.visit_expression_mut(rhs);
+        },
+        Expression::Conditional { condition, then, otherwise } => {
+            visitor.visit_expression_mut(condition);
+            visitor.visit_expression_mut(then);
+            visitor.visit_expression_mut(otherwise);
+        },
+        Expression::Range { start, end, .. } => {
+            visitor.visit_expression_mut(start);
+            visitor.visit_expression_mut(end);
+        },
+        Expression::Spread(operand) => visitor.visit_expression_mut(operand),
+        Expression::Comprehension { result, loops, span } => {
+            visitor.visit_identifier_mut(result);
+            visitor.visit_statement_mut(loops, *span);
+        },
+        Expression::Append { target, value } => {
+            visitor.visit_expression_mut(value);
+            visitor.visit_identifier_mut(target);
+        },
+    }
+}
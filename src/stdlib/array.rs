@@ -0,0 +1,76 @@
+use super::super::codegen::value::{ Native, Value };
+use super::super::codegen::vm::VM;
+use super::array;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+pub(super) fn install(vm: &mut VM) {
+    vm.register(Native::new(String::from("push"), Some(2), |_, mut args| {
+        let value = args.pop().unwrap();
+        array("push", 1, &args[0])?.borrow_mut().push(value);
+        Ok(Value::Null)
+    }));
+
+    vm.register(Native::new(String::from("pop"), Some(1), |_, args| {
+        Ok(array("pop", 1, &args[0])?.borrow_mut().pop().unwrap_or(Value::Null))
+    }));
+
+    // The callbacks run on a copy of the elements, so they can change the array itself
+    vm.register(Native::new(String::from("map"), Some(2), |vm, args| {
+        let elements = array("map", 1, &args[0])?.borrow().clone();
+
+        let mapped = elements.into_iter()
+            .map(|element| vm.call_value(args[1].clone(), vec![element]))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Value::Array(Rc::new(RefCell::new(mapped))))
+    }));
+
+    vm.register(Native::new(String::from("filter"), Some(2), |vm, args| {
+        let elements = array("filter", 1, &args[0])?.borrow().clone();
+        let mut kept = vec![];
+
+        for element in elements {
+            if vm.call_value(args[1].clone(), vec![element.clone()])?.truthy() {
+                kept.push(element);
+            }
+        }
+
+        Ok(Value::Array(Rc::new(RefCell::new(kept))))
+    }));
+
+    vm.register(Native::new(String::from("sort"), Some(1), |_, args| {
+        let elements = array("sort", 1, &args[0])?;
+        let mut elements = elements.borrow_mut();
+
+        // Anything `<` can't compare is an error before the array is touched
+        if let Some(first) = elements.first() {
+            for element in elements.iter() {
+                first.less(element)?;
+            }
+        }
+
+        elements.sort_by(order);
+        Ok(Value::Null)
+    }));
+}
+
+/// Total order agreeing with `<`, with NaN after every other number
+fn order(a: &Value, b: &Value) -> Ordering {
+    let decimal = |v: &Value| match v {
+        Value::Integer(i) => *i as f64,
+        Value::Decimal(d) => *d,
+        _ => 0.0,
+    };
+
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Character(a), Value::Character(b)) => a.cmp(b),
+        (a, b) => match (decimal(a), decimal(b)) {
+            (a, b) if a.is_nan() || b.is_nan() => a.is_nan().cmp(&b.is_nan()),
+            (a, b) => a.total_cmp(&b),
+        },
+    }
+}
@@ -0,0 +1,39 @@
+use super::super::codegen::value::{ Native, Value };
+use super::super::codegen::vm::VM;
+use super::register;
+use std::io::{ self, BufRead, Write };
+
+pub(super) fn install(vm: &mut VM) {
+    vm.register(Native::new(String::from("print"), None, |_, args| {
+        write(&args, "");
+        Ok(Value::Null)
+    }));
+
+    vm.register(Native::new(String::from("println"), None, |_, args| {
+        write(&args, "\n");
+        Ok(Value::Null)
+    }));
+
+    register(vm, "read_line", read_line);
+    register(vm, "read_file", |path: String| std::fs::read_to_string(path));
+    register(vm, "write_file", |path: String, value: Value| std::fs::write(path, value.to_string()));
+}
+
+fn write(values: &[Value], end: &str) {
+    let line = values.iter().map(Value::to_string).collect::<Vec<_>>().join(" ");
+
+    let mut stdout = io::stdout().lock();
+    let _ = write!(stdout, "{}{}", line, end);
+    let _ = stdout.flush();
+}
+
+fn read_line() -> io::Result<Option<String>> {
+    let mut line = String::new();
+
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Ok(None)
+    }
+
+    let trimmed = line.strip_suffix('\n').map(|l| l.strip_suffix('\r').unwrap_or(l));
+    Ok(Some(trimmed.unwrap_or(&line).to_string()))
+}
@@ -0,0 +1,30 @@
+use super::super::codegen::value::{ Native, Value };
+use super::super::codegen::vm::VM;
+use super::super::codegen::RuntimeError;
+use super::{ expected, register };
+
+pub(super) fn install(vm: &mut VM) {
+    register(vm, "sqrt", f64::sqrt);
+    register(vm, "sin", f64::sin);
+    register(vm, "cos", f64::cos);
+    register(vm, "tan", f64::tan);
+
+    vm.register(Native::new(String::from("pow"), Some(2), |_, args| args[0].power(&args[1])));
+
+    // Comparing like `<` keeps the type of whichever argument is picked
+    vm.register(Native::new(String::from("min"), Some(2), |_, args| {
+        let pick = args[1].less(&args[0])?.truthy() as usize;
+        Ok(args[pick].clone())
+    }));
+
+    vm.register(Native::new(String::from("max"), Some(2), |_, args| {
+        let pick = args[0].less(&args[1])?.truthy() as usize;
+        Ok(args[pick].clone())
+    }));
+
+    vm.register(Native::new(String::from("abs"), Some(1), |_, args| match &args[0] {
+        Value::Integer(i) => i.checked_abs().map(Value::Integer).ok_or(RuntimeError::IntegerOverflow),
+        Value::Decimal(d) => Ok(Value::Decimal(d.abs())),
+        v => Err(expected("abs", 1, "Integer or Decimal", v)),
+    }));
+}
@@ -0,0 +1,103 @@
+//! The standard library: functions every program can call without declaring them. \
+//! The VM gets them from `install`, the C and JavaScript backends from their runtimes, which implement
+//! the same functions under the names in `BUILTINS`. The native backends only generate the math functions
+//! their values allow. A program declaring a global of the same name replaces the builtin
+
+mod array;
+mod io;
+mod math;
+mod string;
+
+use super::codegen::value::Value;
+use super::codegen::vm::VM;
+use super::codegen::RuntimeError;
+use super::engine::NativeFn;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A standard library function, as documented for users
+#[derive(Debug, Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    /// Number of arguments, `None` takes any number
+    pub arity: Option<usize>,
+    pub signature: &'static str,
+    pub doc: &'static str,
+}
+
+const fn builtin(name: &'static str, arity: Option<usize>, signature: &'static str, doc: &'static str) -> Builtin {
+    Builtin { name, arity, signature, doc }
+}
+
+/// Every standard library function, grouped like the modules implementing them
+pub const BUILTINS: &[Builtin] = &[
+    // IO
+    builtin("print", None, "print(values...)", "Writes the values to stdout separated by spaces"),
+    builtin("println", None, "println(values...)", "Like `print`, then ends the line"),
+    builtin("read_line", Some(0), "read_line()", "Reads a line from stdin without its line ending, `null` at the end of input"),
+    builtin("read_file", Some(1), "read_file(path)", "Reads a whole file as a string"),
+    builtin("write_file", Some(2), "write_file(path, value)", "Writes a value to a file as `print` would, replacing its contents"),
+
+    // Strings
+    builtin("len", Some(1), "len(value)", "Number of characters in a string or elements in an array or tuple"),
+    builtin("slice", Some(3), "slice(value, start, end)", "Characters or elements from `start` up to but not including `end`, as a new string or array"),
+    builtin("split", Some(2), "split(string, separator)", "Array of the parts of a string between separators, its characters if the separator is empty"),
+    builtin("find", Some(2), "find(string, needle)", "Character index of the first occurrence of `needle`, -1 if there is none"),
+    builtin("upper", Some(1), "upper(string)", "Copy of a string in upper case"),
+    builtin("lower", Some(1), "lower(string)", "Copy of a string in lower case"),
+
+    // Math
+    builtin("sqrt", Some(1), "sqrt(x)", "Square root, as a decimal"),
+    builtin("pow", Some(2), "pow(x, y)", "`x` to the power of `y`, like `x ** y`"),
+    builtin("sin", Some(1), "sin(x)", "Sine of an angle in radians"),
+    builtin("cos", Some(1), "cos(x)", "Cosine of an angle in radians"),
+    builtin("tan", Some(1), "tan(x)", "Tangent of an angle in radians"),
+    builtin("min", Some(2), "min(a, b)", "The smaller of two values, `a` if they're equal"),
+    builtin("max", Some(2), "max(a, b)", "The larger of two values, `a` if they're equal"),
+    builtin("abs", Some(1), "abs(x)", "Absolute value of an integer or decimal"),
+
+    // Arrays
+    builtin("push", Some(2), "push(array, value)", "Appends a value to an array"),
+    builtin("pop", Some(1), "pop(array)", "Removes and returns the last element of an array, `null` if it's empty"),
+    builtin("map", Some(2), "map(array, f)", "New array of `f` called on each element"),
+    builtin("filter", Some(2), "filter(array, f)", "New array of the elements for which `f` returns a truthy value"),
+    builtin("sort", Some(1), "sort(array)", "Sorts an array in place, in ascending order"),
+];
+
+/// The standard library function called `name`
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+/// Registers the whole standard library with a VM
+pub fn install(vm: &mut VM) {
+    io::install(vm);
+    string::install(vm);
+    math::install(vm);
+    array::install(vm);
+}
+
+fn register<Args>(vm: &mut VM, name: &str, function: impl NativeFn<Args>) {
+    vm.register(function.into_native(name));
+}
+
+/// Type error for an argument of a builtin accepting more than one type
+fn expected(name: &str, position: usize, what: &str, value: &Value) -> RuntimeError {
+    RuntimeError::TypeError(format!("argument {} of '{}': expected {}, got {}", position, name, what, value.type_name()))
+}
+
+fn array(name: &str, position: usize, value: &Value) -> Result<Rc<RefCell<Vec<Value>>>, RuntimeError> {
+    match value {
+        Value::Array(elements) => Ok(elements.clone()),
+        v => Err(expected(name, position, "Array", v)),
+    }
+}
+
+/// Strings and characters, which string functions accept alike
+fn text(name: &str, position: usize, value: &Value) -> Result<String, RuntimeError> {
+    match value {
+        Value::String(s) => Ok(s.to_string()),
+        Value::Character(c) => Ok(c.to_string()),
+        v => Err(expected(name, position, "String", v)),
+    }
+}
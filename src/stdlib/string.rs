@@ -0,0 +1,69 @@
+use super::super::codegen::value::{ Native, Value };
+use super::super::codegen::vm::VM;
+use super::super::codegen::RuntimeError;
+use super::{ expected, register, text };
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub(super) fn install(vm: &mut VM) {
+    vm.register(Native::new(String::from("len"), Some(1), |_, args| match &args[0] {
+        Value::String(s) => Ok(Value::Integer(s.chars().count() as i64)),
+        Value::Array(elements) => Ok(Value::Integer(elements.borrow().len() as i64)),
+        Value::Tuple(items) => Ok(Value::Integer(items.len() as i64)),
+        Value::Map(map) => Ok(Value::Integer(map.borrow().len() as i64)),
+        Value::Set(set) => Ok(Value::Integer(set.len() as i64)),
+        v => Err(expected("len", 1, "String, Array, Tuple, Map or Set", v)),
+    }));
+
+    vm.register(Native::new(String::from("slice"), Some(3), |_, args| slice(&args)));
+
+    vm.register(Native::new(String::from("split"), Some(2), |_, args| {
+        let (s, separator) = (text("split", 1, &args[0])?, text("split", 2, &args[1])?);
+
+        let parts: Vec<Value> = match separator.is_empty() {
+            true => s.chars().map(|c| Value::String(c.to_string().into())).collect(),
+            false => s.split(separator.as_str()).map(|part| Value::String(part.into())).collect(),
+        };
+
+        Ok(Value::Array(Rc::new(RefCell::new(parts))))
+    }));
+
+    vm.register(Native::new(String::from("find"), Some(2), |_, args| {
+        let (s, needle) = (text("find", 1, &args[0])?, text("find", 2, &args[1])?);
+
+        let idx = s.find(needle.as_str()).map_or(-1, |byte| s[..byte].chars().count() as i64);
+        Ok(Value::Integer(idx))
+    }));
+
+    register(vm, "upper", |s: String| s.to_uppercase());
+    register(vm, "lower", |s: String| s.to_lowercase());
+}
+
+/// Copies `start..end` out of a string, by character, or an array
+fn slice(args: &[Value]) -> Result<Value, RuntimeError> {
+    let bound = |position: usize| match &args[position] {
+        Value::Integer(i) => Ok(*i),
+        v => Err(expected("slice", position + 1, "Integer", v)),
+    };
+
+    let (start, end) = (bound(1)?, bound(2)?);
+
+    let range = |len: usize| match (usize::try_from(start), usize::try_from(end)) {
+        (Ok(s), Ok(e)) if s <= e && e <= len => Ok(s..e),
+        _ => Err(RuntimeError::Native(String::from("slice"), format!("range {}..{} out of bounds for length {}", start, end, len))),
+    };
+
+    match &args[0] {
+        Value::String(s) => {
+            let range = range(s.chars().count())?;
+            let sliced: String = s.chars().skip(range.start).take(range.len()).collect();
+            Ok(Value::String(sliced.into()))
+        },
+        Value::Array(elements) => {
+            let elements = elements.borrow();
+            let range = range(elements.len())?;
+            Ok(Value::Array(Rc::new(RefCell::new(elements[range].to_vec()))))
+        },
+        v => Err(expected("slice", 1, "String or Array", v)),
+    }
+}
@@ -0,0 +1,67 @@
+use ult::ast::{ Declaration, Expression, Statement };
+use ult::parse::visit::{ walk_expression, Visitor };
+use ult::{ Error, Severity, Token };
+
+#[test]
+fn lex_parse_and_eval() {
+    let tokens = ult::lex("func main() {\n    let n := 6\n    return n * 7\n}\n").unwrap();
+
+    assert_eq!(tokens.tokens.first(), Some(&Token::Func));
+    assert_eq!(tokens.tokens.last(), Some(&Token::EOF));
+    assert_eq!(tokens.tokens.len(), tokens.spans.len());
+    assert_eq!((tokens.spans[5].line, tokens.spans[5].column), (2, 4));
+
+    let ast = ult::parse(&tokens).unwrap();
+
+    match &ast.program()[0] {
+        Declaration::Function { identifier, body, .. } => {
+            assert_eq!(identifier.name(), "main");
+            assert!(matches!(body.statements()[1], Statement::Return(Expression::Binary { .. })));
+        },
+        decl => panic!("expected a function, got {:?}", decl),
+    }
+
+    assert_eq!(ult::eval(&ast).unwrap().to_string(), "42");
+}
+
+#[test]
+fn errors_come_from_their_stage() {
+    assert!(matches!(ult::lex("let s := \"unterminated"), Err(Error::Lex(_))));
+
+    let tokens = ult::lex("let := 1").unwrap();
+    assert!(matches!(ult::parse(&tokens), Err(Error::Parse(_))));
+
+    let ast = ult::parse(&ult::lex("func main() { return 1 / 0 }").unwrap()).unwrap();
+    let error = ult::eval(&ast).unwrap_err();
+
+    assert!(matches!(error, Error::Runtime(_)));
+    assert_eq!(error.to_string(), "Division by zero");
+}
+
+#[test]
+fn diagnostics_and_visitors_work_on_parsed_trees() {
+    let ast = ult::parse(&ult::lex("func f() {\n    return 1\n    f()\n}\n").unwrap()).unwrap();
+    let diagnostics = ult::check(&ast);
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+    assert_eq!(diagnostics[0].to_string(), "3:5: warning: unreachable code after 'return'");
+
+    struct Calls(Vec<String>);
+
+    impl Visitor for Calls {
+        fn visit_expression(&mut self, expr: &Expression) {
+            if let Expression::Call { target, .. } = expr {
+                if let Expression::Value(identifier) = target.as_ref() {
+                    self.0.push(identifier.name());
+                }
+            }
+            walk_expression(self, expr);
+        }
+    }
+
+    let mut calls = Calls(vec![]);
+    calls.visit_ast(&ast);
+
+    assert_eq!(calls.0, ["f"]);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::*;
+
+/// Runs `ult test` with `args` followed by the scratch directory
+fn ult_test(scratch: &Scratch, args: &[&str]) -> (Option<i32>, String) {
+    let dir = scratch.dir.to_str().unwrap().to_string();
+    let args = ["test"].iter().chain(args).copied().chain([dir.as_str()]).collect::<Vec<_>>();
+    let out = ult(&args);
+
+    (out.status.code(), stdout(&out).replace(&format!("{}/", dir), ""))
+}
+
+#[test]
+fn failed_assertions_show_the_values() {
+    assert_eq!(eval("func main() { assert (1 + 1) == 3 }").unwrap_err(), "Assertion failed: 2 == 3");
+    assert_eq!(eval("func main() { assert \"a\" in \"xyz\" }").unwrap_err(), "Assertion failed: \"a\" in \"xyz\"");
+    assert_eq!(eval("func main() { let xs := [1]\nassert len(xs) > 1, \"too short\" }").unwrap_err(), "Assertion failed: too short (1 > 1)");
+    assert_eq!(eval("func main() { assert null }").unwrap_err(), "Assertion failed: got null");
+    assert_eq!(eval("func main() { assert 0 || false, 42 }").unwrap_err(), "Assertion failed: 42 (got false)");
+}
+
+#[test]
+fn passing_assertions_do_nothing() {
+    assert_eq!(value("assert 1 < 2\nassert \"x\", \"strings are truthy\"\nreturn 1"), "1");
+}
+
+#[test]
+fn messages_are_only_evaluated_on_failure() {
+    assert_eq!(eval("let n := 0\nfunc count() { n += 1\nreturn n }\nfunc main() { assert true, count()\nassert 1 == 1, count()\nreturn n }").unwrap(), "0");
+    assert_eq!(eval("let n := 0\nfunc count() { n += 1\nreturn n }\nfunc main() { assert false, count() }").unwrap_err(), "Assertion failed: 1 (got false)");
+}
+
+#[test]
+fn assertions_can_be_caught() {
+    assert_eq!(value("try { assert 1 == 2, \"no\" } catch (e) { return e }"), "Assertion failed: no (1 == 2)");
+}
+
+#[test]
+fn test_blocks_are_not_run_by_the_program() {
+    assert_eq!(eval("test \"never\" { throw 1 }\nfunc main() { return 2 }").unwrap(), "2");
+    assert!(eval("test never { }").is_err());
+    assert!(eval("func main() { test \"inner\" { } }").is_err());
+    assert!(eval("func main() { assert }").is_err());
+}
+
+#[test]
+fn tests_are_discovered_run_and_reported() {
+    let scratch = Scratch::new("ult_test_report");
+    scratch.source("a", "func double(x) { return x * 2 }\n\ntest \"doubles\" {\n    assert double(2) == 4\n}\n\ntest \"fails\" {\n    assert double(2) == 5, \"wrong\"\n}\n");
+    std::fs::create_dir_all(scratch.path("nested")).unwrap();
+    std::fs::write(scratch.path("nested/b.ult"), "test \"errors\" {\n    let x := 1 / 0\n}\n").unwrap();
+    std::fs::write(scratch.path("notes.txt"), "test \"ignored\" { }").unwrap();
+
+    let (code, out) = ult_test(&scratch, &[]);
+    let lines = out.lines().map(|l| l.split(" (").next().unwrap()).collect::<Vec<_>>();
+
+    assert_eq!(code, Some(1), "{}", out);
+    assert_eq!(lines[..4], [
+        "running 3 tests",
+        "test a.ult: doubles ... ok",
+        "test a.ult: fails ... FAILED",
+        "test nested/b.ult: errors ... FAILED",
+    ]);
+    assert!(out.contains("---- a.ult: fails ----\nAssertion failed: wrong (4 == 5)\n    at test \"fails\" (a.ult:8)\n        assert double(2) == 5, \"wrong\"\n"), "{}", out);
+    assert!(out.contains("---- nested/b.ult: errors ----\nDivision by zero\n"), "{}", out);
+    assert!(out.contains("\ntest result: FAILED. 1 passed; 2 failed; 0 filtered out; finished in "), "{}", out);
+}
+
+#[test]
+fn tests_are_filtered_by_name() {
+    let scratch = Scratch::new("ult_test_filter");
+    scratch.source("a", "test \"adds\" { assert (1 + 1) == 2 }\ntest \"fails\" { assert false }\ntest \"adds more\" { assert (1 + 2) == 3 }\n");
+
+    let (code, out) = ult_test(&scratch, &["--filter", "add"]);
+
+    assert_eq!(code, Some(0), "{}", out);
+    assert!(out.starts_with("running 2 tests\ntest a.ult: adds ... ok ("), "{}", out);
+    assert!(out.contains("\ntest result: ok. 2 passed; 0 failed; 1 filtered out; "), "{}", out);
+}
+
+#[test]
+fn tests_run_in_isolation() {
+    let scratch = Scratch::new("ult_test_isolation");
+    scratch.source("a", "let runs := 0\nlet xs := [0]\nfunc main() { throw \"main is not run\" }\n\
+                         test \"first\" { runs += 1\npush(xs, 1)\nassert runs == 1\nassert len(xs) == 2 }\n\
+                         test \"second\" { runs += 1\npush(xs, 2)\nassert runs == 1\nassert len(xs) == 2 }\n");
+
+    let (code, out) = ult_test(&scratch, &[]);
+
+    assert_eq!(code, Some(0), "{}", out);
+    assert!(out.contains("2 passed; 0 failed"), "{}", out);
+}
+
+#[test]
+fn files_that_do_not_compile_fail() {
+    let scratch = Scratch::new("ult_test_broken");
+    scratch.source("a", "test \"ok\" { assert true }\n");
+    scratch.source("b", "test \"broken\" { assert ( }\n");
+
+    let (code, out) = ult_test(&scratch, &[]);
+
+    assert_eq!(code, Some(1), "{}", out);
+    assert!(out.contains("---- b.ult ----\n"), "{}", out);
+    assert!(out.contains("1 passed; 1 failed"), "{}", out);
+}
+
+#[test]
+fn ult_unit_tests() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/ult");
+    let out = ult(&["test", dir]);
+
+    assert!(out.status.success(), "{}", stdout(&out));
+    assert!(stdout(&out).contains("; 0 failed; 0 filtered out;"), "{}", stdout(&out));
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn every_compound_operator_updates_in_place() {
+    let update = |init: &str, op: &str, rhs: &str| value(&format!("let x := {}\nx {}= {}\nreturn x", init, op, rhs));
+
+    assert_eq!(update("7", "+", "3"), "10");
+    assert_eq!(update("7", "-", "10"), "-3");
+    assert_eq!(update("7", "*", "-2"), "-14");
+    assert_eq!(update("7", "/", "2"), "3");
+    assert_eq!(update("-7", "%", "3"), "-1");
+    assert_eq!(update("3", "**", "3"), "27");
+    assert_eq!(update("5", "|", "10"), "15");
+    assert_eq!(update("6", "&", "3"), "2");
+    assert_eq!(update("6", "^", "3"), "5");
+    assert_eq!(update("3", "<<", "4"), "48");
+    assert_eq!(update("-16", ">>", "2"), "-4");
+}
+
+#[test]
+fn compound_assignment_follows_the_operand_types() {
+    assert_eq!(value("let s := \"ab\"\ns += 'c'\ns += 1\nreturn s"), "abc1");
+    assert_eq!(value("let d := 1.5\nd *= 2\nreturn d"), "3.0");
+    assert_eq!(value("let n := 7\nn /= 2.0\nreturn n"), "3.5");
+    assert_eq!(eval("func main() { let x := true\nx += 1 }").unwrap_err(), "Type error: cannot apply '+' to Boolean and Integer");
+}
+
+#[test]
+fn globals_are_updated_from_functions() {
+    assert_eq!(eval("let calls := 0\nfunc count() { calls += 1\ncalls++ }\nfunc main() { count()\ncount()\nreturn calls }").unwrap(), "4");
+}
+
+#[test]
+fn operands_are_evaluated_left_to_right() {
+    assert_eq!(value("let x := 5\nreturn x-- - --x"), "2");
+    assert_eq!(value("let x := 5\nreturn ++x + x--"), "12");
+}
+
+#[test]
+fn elements_are_stored_into() {
+    assert_eq!(value("let xs := [1, 2, 3]\nxs[0] := 10\nreturn xs"), "[10, 2, 3]");
+    assert_eq!(value("let xs := [1, 2, 3]\nlet i := 1\nxs[2] := xs[i] * 2\nreturn xs"), "[1, 2, 4]");
+}
+
+#[test]
+fn nested_elements_are_stored_into() {
+    assert_eq!(value("let grid := [[0, 0], [0, 0]]\ngrid[1][0] := 7\nreturn grid"), "[[0, 0], [7, 0]]");
+    assert_eq!(value("let xs := [0]\nlet grid := [[0, 0]]\ngrid[0][1] := xs[0] := 7\nlet a := [grid, xs]\nreturn a"), "[[[0, 7]], [7]]");
+}
+
+#[test]
+fn the_target_index_and_value_run_in_order() {
+    let source = "
+        let log := \"\"
+
+        func at(v, tag) {
+            log := log + tag
+            return v
+        }
+
+        func main() {
+            let grid := [[0, 0]]
+            grid[at(0, \"t\")][at(1, \"i\")] := at(5, \"v\")
+            return log
+        }
+    ";
+
+    assert_eq!(eval(source).unwrap(), "tiv");
+}
+
+#[test]
+fn exponents_are_right_associative_and_bind_tightest() {
+    assert_eq!(value("return 2 ** 3 ** 2"), "512");
+    assert_eq!(value("return (2 ** 3) ** 2"), "64");
+    assert_eq!(value("return 2 * 3 ** 2 + 1"), "19");
+    assert_eq!(value("let x := 3\nreturn x ** 2 == 9"), "true");
+    assert_eq!(value("return 2 ** -1"), "0.5");
+    assert_eq!(value("return 4 ** 0.5"), "2.0");
+    assert_eq!(value("let x := 2\nx **= 3 ** 2\nreturn x"), "512");
+    assert_eq!(value("return pow(2, 10) == 2 ** 10"), "true");
+    assert_eq!(eval("func main() { return 2 ** 63 }").unwrap_err(), "Integer overflow");
+}
+
+#[test]
+fn stores_evaluate_to_the_stored_value() {
+    assert_eq!(value("let xs := [1, 2]\nlet a := xs[1] := 5\nreturn a + xs[1]"), "10");
+    assert_eq!(value("let xs := [1, 2]\nlet ys := xs\nys[0] := \"shared\"\nreturn xs"), "[\"shared\", 2]");
+    assert_eq!(value("let row := [0]\nlet grid := [row]\ngrid[0][0] += 3\ngrid[0][0]++\nreturn row"), "[4]");
+}
+
+#[test]
+fn prefix_and_postfix_values() {
+    assert_eq!(value("let x := 1\nlet a := [x++, x, ++x, x--, --x]\nreturn a"), "[1, 2, 3, 3, 1]");
+    assert_eq!(value("let x := 1\nreturn x++ + x++ * 10"), "21");
+    assert_eq!(value("let x := 0.5\nx++\nreturn x"), "1.5");
+    assert_eq!(value("let xs := [5]\nlet old := xs[0]--\nlet a := [old, xs[0]]\nreturn a"), "[5, 4]");
+}
+
+#[test]
+fn targets_and_indexes_are_evaluated_once() {
+    let source = "
+        let log := []
+
+        func at(value, i) {
+            push(log, i)
+            return value
+        }
+
+        func main() {
+            let xs := [1, 2]
+            at(xs, 0)[at(1, 1)] += 10
+            at(xs, 2)[0]++
+            push(log, xs)
+            return log
+        }
+    ";
+
+    assert_eq!(eval(source).unwrap(), "[0, 1, 2, [2, 12]]");
+}
+
+#[test]
+fn the_target_is_read_before_the_right_hand_side() {
+    assert_eq!(value("let x := 1\nx += (x := 10)\nreturn x"), "11");
+    assert_eq!(value("let xs := [1]\nxs[0] += (xs[0] += 5)\nreturn xs"), "[7]");
+}
+
+#[test]
+fn increments_on_the_next_line_start_a_statement() {
+    assert_eq!(value("let x := 1\nlet y := x\n++y\nlet a := [x, y]\nreturn a"), "[1, 2]");
+}
+
+#[test]
+fn only_variables_and_elements_are_assignable() {
+    assert_eq!(eval("func main() { 1 += 2 }").unwrap_err(), "Bad assignment!");
+    assert_eq!(eval("func main() { return f()++ }").unwrap_err(), "Bad assignment!");
+    assert_eq!(eval("func main() { let x := 1\nreturn --(x + 1) }").unwrap_err(), "Bad assignment!");
+    assert_eq!(eval("func main() { let s := \"ab\"\ns[0] += \"c\" }").unwrap_err(), "Type error: cannot assign into String");
+    assert_eq!(eval("func main() { let s := \"ab\"\ns[0] := 'c' }").unwrap_err(), "Type error: cannot assign into String");
+    assert_eq!(eval("func main() { let xs := [1]\nxs[\"0\"] := 2 }").unwrap_err(), "Type error: cannot index with String");
+    assert_eq!(eval("func main() { let xs := [1]\nxs[1]++ }").unwrap_err(), "Index 1 out of bounds for length 1");
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use common::*;
+use std::process::Command;
+
+/// Transpiles `source` to C, compiles it with the system compiler and runs it
+fn build_and_run(name: &str, source: &str) -> std::process::Output {
+    let scratch = Scratch::new(name);
+    let input = scratch.source(name, source);
+    let exe = scratch.path(name);
+
+    let build = ult(&["build", "--target", "c", "-o", exe.to_str().unwrap(), input.to_str().unwrap()]);
+    assert!(build.status.success(), "build failed: {}", stderr(&build));
+
+    Command::new(&exe).output().unwrap()
+}
+
+#[test]
+fn dynamic_values() {
+    let out = build_and_run("c_values", r#"
+        let x := 2 * 7 + 2
+        let int := "a C keyword"
+
+        func apply(f, v) { return f(v) }
+
+        func main() {
+            func square(v) { return v * v }
+            let arr := [1, 2.5, "three", 'c', null, true]
+            println(x, int, arr, arr[2][1], apply(square, 12))
+            println(0.1 + 0.2, 7 / 2.0, "n = " + 3, 2 ** 3 ** 2, 2 * 3 ** 2, 2 ** 0.5 > 1.4)
+            return 3
+        }
+    "#);
+
+    assert_eq!(stdout(&out), "16 a C keyword [1, 2.5, \"three\", 'c', null, true] h 144\n0.30000000000000004 3.5 n = 3 512 18 true\n");
+    assert_eq!(out.status.code(), Some(3));
+}
+
+#[test]
+fn shadowing_and_loops() {
+    let out = build_and_run("c_scopes", r#"
+        let x := 10
+
+        func main() {
+            let total := 0
+            for (let i := 0; i < 10; i := i + 1) {
+                if (i == 3) { continue }
+                let x := x + i
+                if (x > 15) { break }
+                total := total + x
+            }
+            let x := "shadowed"
+            println(total, x)
+        }
+    "#);
+
+    assert_eq!(stdout(&out), "62 shadowed\n");
+}
+
+#[test]
+fn runtime_errors() {
+    let out = build_and_run("c_errors", r#"
+        func main() {
+            let arr := [1, 2]
+            println(arr[2])
+        }
+    "#);
+
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(stderr(&out), "Runtime error: Index 2 out of bounds for length 2\n");
+
+    let out = build_and_run("c_pattern_error", "func main() { let xs := [1, 2, 3]\nlet [a, b] := xs }");
+
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(stderr(&out), "Runtime error: Cannot destructure Array of 3 elements as an array of 2 elements\n");
+
+    let out = build_and_run("c_key_error", "func main() { let m := {\"a\": 1}\nreturn m[\"b\"] }");
+
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(stderr(&out), "Runtime error: Key \"b\" not found in map\n");
+
+    let out = build_and_run("c_unhashable", "func main() { let m := {}\nm[(1, [2])] := 3 }");
+
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(stderr(&out), "Runtime error: Type error: unhashable Array\n");
+}
+
+#[test]
+fn standard_library() {
+    let out = build_and_run("c_stdlib", STDLIB_PROGRAM);
+
+    assert_eq!(stderr(&out), "");
+    assert_eq!(stdout(&out), STDLIB_OUTPUT);
+    assert_eq!(out.status.code(), Some(3));
+
+    let out = build_and_run("c_stdlib_error", "func main() { return slice(\"abc\", 2, 5) }");
+
+    assert_eq!(stderr(&out), "Runtime error: Error in native function 'slice': range 2..5 out of bounds for length 3\n");
+    assert_eq!(out.status.code(), Some(1));
+}
+
+#[test]
+fn shared_programs() {
+    // C has no exceptions
+    for (name, program, output, code) in PROGRAMS.iter().filter(|(name, ..)| *name != "exception") {
+        let out = build_and_run(&format!("c_{}", name), program);
+
+        assert_eq!(stderr(&out), "", "{}", name);
+        assert_eq!(stdout(&out), *output, "{}", name);
+        assert_eq!(out.status.code(), Some(*code), "{}", name);
+    }
+}
+
+#[test]
+fn comprehensions_in_expressions() {
+    let out = build_and_run("c_comprehension_expressions", r#"
+        let calls := 0
+
+        func count(x) {
+            calls += 1
+            return x
+        }
+
+        func evens(n) { return [x for x in 0..n if (x % 2) == 0] }
+
+        func main() {
+            println([x * 2 for x in 1..=3], evens(5), [[y for y in 0..x] for x in 1..3], (1, [2, 3]))
+            let a := false && len([count(x) for x in 0..3]) > 0
+            let b := true ? [count(x) for x in 0..2] : [count(x) for x in 0..3]
+            let c := [1] ?? [count(x) for x in 0..3]
+            println(a, b, c, calls)
+            return 1 + len([x for x in 0..3])
+        }
+    "#);
+
+    assert_eq!(stderr(&out), "");
+    assert_eq!(stdout(&out), "[2, 4, 6] [0, 2, 4] [[0], [0, 1]] (1, [2, 3])\nfalse [0, 1] [1] 2\n");
+    assert_eq!(out.status.code(), Some(4));
+}
@@ -0,0 +1,187 @@
+mod common;
+
+use common::*;
+
+fn check(name: &str, source: &str) -> (bool, Vec<String>) {
+    let scratch = Scratch::new(name);
+    let input = scratch.source(name, source);
+
+    let output = ult(&["check", input.to_str().unwrap()]);
+    let prefix = format!("{}:", input.display());
+
+    let diagnostics = stderr(&output).lines()
+        .map(|l| l.strip_prefix(&prefix).unwrap_or(l).to_string())
+        .collect();
+
+    (output.status.success(), diagnostics)
+}
+
+#[test]
+fn control_flow_problems_are_reported() {
+    let (ok, diagnostics) = check("check_flow", r#"func sign(n) {
+    if (n > 0) {
+        return 1;
+        println("positive");
+    }
+    else if (n < 0) { return -1; }
+}
+
+func scan() {
+    for (let i := 0; i < 3; i := i + 1) {
+        continue;
+        println(i);
+    }
+    break;
+}
+"#);
+
+    assert!(!ok);
+    assert_eq!(diagnostics, [
+        "1:1: warning: function 'sign' does not return a value on every path",
+        "4:9: warning: unreachable code after 'return'",
+        "12:9: warning: unreachable code after 'continue'",
+        "14:5: error: 'break' used outside of a loop",
+    ]);
+}
+
+#[test]
+fn reads_before_assignment_follow_every_path() {
+    let (ok, diagnostics) = check("check_reads", r#"func main() {
+    let a;
+    let b;
+    if (true) { a := 1; } else { a := 2; }
+    while (a < 3) { b := a; a := a + 1; }
+    println(a, b);
+    return 0;
+}
+"#);
+
+    assert!(ok);
+    assert_eq!(diagnostics, ["6:5: warning: variable 'b' may be read before it is assigned"]);
+}
+
+#[test]
+fn compound_assignments_read_their_target() {
+    let (ok, diagnostics) = check("check_compound", r#"func main() {
+    let a;
+    let b;
+    a += 1;
+    b++;
+    return 0;
+}
+"#);
+
+    assert!(ok);
+    assert_eq!(diagnostics, [
+        "4:5: warning: variable 'a' may be read before it is assigned",
+        "5:5: warning: variable 'b' may be read before it is assigned",
+    ]);
+}
+
+#[test]
+fn infinite_loops_only_end_by_returning() {
+    let (ok, diagnostics) = check("check_loops", r#"func first(items) {
+    while (true) {
+        if (items) { return items[0]; }
+    }
+    println("done");
+}
+
+func forever() {
+    for (;;) { func inner() { continue; } }
+}
+"#);
+
+    assert!(!ok);
+    assert_eq!(diagnostics, [
+        "5:5: warning: unreachable code",
+        "9:31: error: 'continue' used outside of a loop",
+    ]);
+}
+
+#[test]
+fn assignments_in_branches_may_not_happen() {
+    let (ok, diagnostics) = check("check_branches", r#"func main(flag) {
+    let a;
+    let b;
+    let c;
+    let d;
+    flag ? (a := 1) : 0;
+    flag ?? (b := 2);
+    c := flag ? 1 : 2;
+    flag && (d := 3);
+    return a + b + c + d;
+}
+"#);
+
+    assert!(ok);
+    assert_eq!(diagnostics, [
+        "10:5: warning: variable 'a' may be read before it is assigned",
+        "10:5: warning: variable 'b' may be read before it is assigned",
+        "10:5: warning: variable 'd' may be read before it is assigned",
+    ]);
+}
+
+#[test]
+fn patterns_that_can_never_match_are_errors() {
+    let (ok, diagnostics) = check("check_patterns", r#"func main(t) {
+    let (a, b) := t;
+    let (c, d) := (1, 2, 3);
+    let [e] := "e";
+    let [f, g] := (1, 2);
+    let (h, i) := {1: 2};
+    let (j, k) := [x for x in t];
+    return a + b + c + d + e + f + g + h + i + j + k;
+}
+"#);
+
+    assert!(!ok);
+    assert_eq!(diagnostics, [
+        "3:5: error: pattern can never match, it destructures a tuple of 3 elements as a tuple of 2 elements",
+        "4:5: error: pattern can never match, it destructures String as an array of 1 elements",
+        "5:5: error: pattern can never match, it destructures a tuple of 2 elements as an array of 2 elements",
+        "6:5: error: pattern can never match, it destructures Map as a tuple of 2 elements",
+        "7:5: error: pattern can never match, it destructures an array as a tuple of 2 elements",
+    ]);
+}
+
+#[test]
+fn try_statements_are_followed_through_their_handlers() {
+    let (ok, diagnostics) = check("check_try", r#"func f(n) {
+    let x;
+    try { x := n / 2; } catch (e) { println(x, e); }
+    let y;
+    try { y := 1; } finally { println(n); }
+    throw x + y;
+    println(n);
+}
+
+func g() {
+    try { return 1; } catch (e) { return e; }
+}
+"#);
+
+    assert!(ok);
+    assert_eq!(diagnostics, [
+        "3:37: warning: variable 'x' may be read before it is assigned",
+        "7:5: warning: unreachable code after 'throw'",
+    ]);
+}
+
+#[test]
+fn test_blocks_are_checked() {
+    let (ok, diagnostics) = check("check_tests", r#"test "flow" {
+    let x;
+    assert x == 1;
+    break;
+    println(x);
+}
+"#);
+
+    assert!(!ok);
+    assert_eq!(diagnostics, [
+        "3:5: warning: variable 'x' may be read before it is assigned",
+        "4:5: error: 'break' used outside of a loop",
+        "5:5: warning: unreachable code after 'break'",
+    ]);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn entries_are_added_and_updated_in_place() {
+    assert_eq!(eval("let ages := {\"ann\": 31}\nfunc main() { ages[\"cy\"] := 40\nages[\"ann\"] += 1\nreturn ages }").unwrap(),
+        "{\"ann\": 32, \"cy\": 40}");
+    assert_eq!(value("let counts := {}\nfor (w in [\"a\", \"b\", \"a\"]) { if (w in counts) { counts[w] += 1 } else { counts[w] := 1 } }\nreturn counts"),
+        "{\"a\": 2, \"b\": 1}");
+}
+
+#[test]
+fn sets_keep_the_first_of_equal_elements() {
+    assert_eq!(value("return {1, 2.0, 2, \"2\"}"), "{1, 2.0, \"2\"}");
+    assert_eq!(value("return {1: \"x\", 1.0: \"y\"}"), "{1: \"y\"}");
+}
+
+#[test]
+fn literals_keep_insertion_order_and_drop_duplicates() {
+    assert_eq!(value("return {\"b\": 1, \"a\": 2, \"b\": 3}"), "{\"b\": 3, \"a\": 2}");
+    assert_eq!(value("return {3, 1, 3, 2, 1}"), "{3, 1, 2}");
+    assert_eq!(value("return ({}, {,}, {1,}, {'a': \"b\",})"), "({}, {,}, {1}, {'a': \"b\"})");
+    assert_eq!(value("let xs := [2, 3]\nreturn {1, ...xs, ...(3..5)}"), "{1, 2, 3, 4}");
+    assert_eq!(value("let m := {}\nm[2] := 'x'\nm[1] := 'y'\nm[2] := 'z'\nreturn m"), "{2: 'z', 1: 'y'}");
+}
+
+#[test]
+fn keys_match_like_equality() {
+    assert_eq!(value("let m := {1: \"int\"}\nreturn m[1.0]"), "int");
+    assert_eq!(value("let m := {0.0: 1}\nreturn m[-0.0]"), "1");
+    assert_eq!(value("let nan := 0.0 / 0.0\nlet m := {nan: 1}\nm[nan] += 1\nreturn (m[nan], len(m))"), "(2, 1)");
+    assert_eq!(value("let m := {(1, (2, 3)): 1}\nreturn m[(1.0, (2, 3.0))]"), "1");
+    assert_eq!(value("let m := {{1, 2}: 's', (1, {3}): 't'}\nreturn (m[{2, 1}], m[(1.0, {3})])"), "('s', 't')");
+    assert_eq!(value("let m := {null: 1, true: 2, 'c': 3, \"c\": 4, 0..2: 5}\nreturn len(m) + m[0..2]"), "10");
+    assert_eq!(value("func f() { return 1 }\nlet m := {f: 'f'}\nreturn m[f]"), "f");
+}
+
+#[test]
+fn collections_compare_by_content() {
+    assert_eq!(value("return {1: 2, 3: 4} == {3: 4, 1: 2}"), "true");
+    assert_eq!(value("return {1: 2} == {1: 2.0}"), "true");
+    assert_eq!(value("return {1: 2} != {1: 3}"), "true");
+    assert_eq!(value("return {1, 2, 3} == {3, 2, 1}"), "true");
+    assert_eq!(value("return {1, 2} == {1, 2, 3}"), "false");
+    assert_eq!(value("return {1: 2} == {1, 2}"), "false");
+    assert_eq!(value("return {} == {,}"), "false");
+}
+
+#[test]
+fn in_tests_membership() {
+    assert_eq!(value("let m := {\"a\": null}\nreturn (\"a\" in m, \"b\" in m, null in m)"), "(true, false, false)");
+    assert_eq!(value("let s := {1, (2, 3)}\nreturn (1.0 in s, (2, 3) in s, 2 in s)"), "(true, true, false)");
+    assert_eq!(value("let inner := [2]\nlet xs := [1, inner]\nlet ys := [2]\nreturn (ys in xs, 3 in xs, 'b' in ('a', 'b'))"), "(true, false, true)");
+    assert_eq!(value("return ('e' in \"hello\", \"ll\" in \"hello\", \"lo!\" in \"hello\")"), "(true, true, false)");
+    assert_eq!(value("return (0 in (0..3), 3 in (0..3), 3 in (0..=3), 1.5 in (0..3))"), "(true, false, true, false)");
+    assert_eq!(value("let x := 2\nreturn (x + 1) in {3}"), "true");
+    assert_eq!(eval("func main() { return 1 in 5 }").unwrap_err(), "Type error: cannot apply 'in' to Integer and Integer");
+    assert_eq!(eval("func main() { return 1 in \"1\" }").unwrap_err(), "Type error: cannot apply 'in' to Integer and String");
+}
+
+#[test]
+fn maps_iterate_as_entries_and_sets_as_elements() {
+    assert_eq!(value("let m := {'a': 1, 'b': 2}\nlet s := \"\"\nfor ((k, v) in m) { s := s + k + v }\nreturn s"), "a1b2");
+    assert_eq!(value("let s := {3, 1, 2}\nlet t := 0\nfor (x in s) { t := t * 10 + x }\nreturn t"), "312");
+    assert_eq!(value("let m := {1: 1}\nfor ((k, v) in m) { m[k + 1] := v }\nreturn m"), "{1: 1, 2: 1}");
+    assert_eq!(value("let m := {1: 'a'}\nlet xs := [...m, ...{2, 3}]\nreturn xs"), "[(1, 'a'), 2, 3]");
+    assert_eq!(value("let m := {1: 2, 3: 4}\nlet s := {...m}\nreturn (1, 2) in s"), "true");
+}
+
+#[test]
+fn misuse_is_a_runtime_error() {
+    assert_eq!(eval("func main() { let m := {\"a\": 1}\nreturn m[\"b\"] }").unwrap_err(), "Key \"b\" not found in map");
+    assert_eq!(eval("func main() { let m := {}\nreturn m['c'] }").unwrap_err(), "Key 'c' not found in map");
+    assert_eq!(eval("func main() { let s := {1}\ns[0] := 2 }").unwrap_err(), "Type error: cannot assign into Set");
+    assert_eq!(eval("func main() { let s := {1}\nreturn s[0] }").unwrap_err(), "Type error: cannot index into Set");
+}
+
+#[test]
+fn maps_may_hold_themselves() {
+    assert_eq!(value("let m := {}\nm[\"self\"] := m\nreturn (len(m), \"self\" in m, m[\"self\"][\"self\"] == m)"), "(1, true, true)");
+}
+
+#[test]
+fn arrays_and_maps_are_unhashable() {
+    for body in ["let k := [1]\nlet m := {}\nm[k] := 1", "let k := [1]\nreturn {k: 1}", "let m := {}\nreturn m[([1], 2)]",
+                 "return [1] in {1: 2}", "return {1, [2]}", "let xs := [[1]]\nreturn {1, ...xs}"] {
+        assert_eq!(eval(&format!("func main() {{\n{}\n}}", body)).unwrap_err(), "Type error: unhashable Array", "{}", body);
+    }
+
+    for body in ["let m := {}\nm[m] := 1", "return {} in {,}", "return {(1, {}): 2}"] {
+        assert_eq!(eval(&format!("func main() {{\n{}\n}}", body)).unwrap_err(), "Type error: unhashable Map", "{}", body);
+    }
+}
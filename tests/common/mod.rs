@@ -0,0 +1,503 @@
+#![allow(dead_code)]
+use std::path::PathBuf;
+use std::process::{ Command, Output };
+
+/// A scratch directory unique to one test, removed when dropped
+pub struct Scratch {
+    pub dir: PathBuf,
+}
+
+impl Scratch {
+    pub fn new(name: &str) -> Scratch {
+        let dir = std::env::temp_dir().join(format!("ult-test-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Scratch { dir }
+    }
+
+    /// Writes `source` to `<name>.ult` and returns its path
+    pub fn source(&self, name: &str, source: &str) -> PathBuf {
+        let path = self.dir.join(format!("{}.ult", name));
+        std::fs::write(&path, source).unwrap();
+        path
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Runs the `ult` binary with the given arguments
+pub fn ult(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ult")).args(args).output().unwrap()
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// Runs `source` on the VM, giving what `main` returns or the error as text
+pub fn eval(source: &str) -> Result<String, String> {
+    let ast = ult::parse(&ult::lex(source).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+    ult::eval(&ast).map(|v| v.to_string()).map_err(|e| e.to_string())
+}
+
+/// What `body` returns as the body of `main`
+pub fn value(body: &str) -> String {
+    eval(&format!("func main() {{\n{}\n}}", body)).unwrap()
+}
+
+/// Runs `body` as the body of `main` after `at(v, tag)`, which appends `tag` to `log` and returns `v`,
+/// has been declared
+pub fn logged(body: &str) -> String {
+    eval(&format!("let log := \"\"\nfunc at(v, tag) {{ log := log + tag\nreturn v }}\nfunc main() {{\n{}\n}}", body)).unwrap()
+}
+
+/// A program using most of the standard library, printing `STDLIB_OUTPUT` whatever runs it
+pub const STDLIB_PROGRAM: &str = r#"
+func double(x) { return x * 2 }
+func odd(x) { return x % 2 }
+
+func main() {
+    let xs := [3, 1, 2]
+    push(xs, 0)
+    sort(xs)
+    println(xs, len("héllo"), slice("héllo", 1, 3), split("a,b", ","), split("ab", ""), find("héllo", 'l'))
+    println(upper("abc"), lower("ABC"), sqrt(16), pow(2, 10), min(3, 2.5), max(1, 2), abs(0 - 5), sin(0))
+    println(map(xs, double), filter(xs, odd), pop(xs), xs, slice(xs, 0, 2))
+    print("no", "newline")
+    println()
+    println(read_line())
+    return len(xs)
+}
+"#;
+
+pub const STDLIB_OUTPUT: &str = "[0, 1, 2, 3] 5 él [\"a\", \"b\"] [\"a\", \"b\"] 2\n\
+    ABC abc 4.0 1024 2.5 2 5 0.0\n\
+    [0, 2, 4, 6] [1, 3] 3 [0, 1, 2] [0, 1]\n\
+    no newline\n\
+    null\n";
+
+/// Every compound assignment and `++`/`--`, with operands that count their calls, printing `UPDATE_OUTPUT`
+/// and returning 58
+pub const UPDATE_PROGRAM: &str = r#"
+let calls := 0
+
+func next(i) {
+    calls += 1
+    return i
+}
+
+func main() {
+    let x := 7
+    x += 3
+    x -= 1
+    x *= 4
+    x /= 6
+    x %= 4
+    x **= 3
+    x |= 12
+    x &= 10
+    x ^= 7
+    x <<= 3
+    x >>= 1
+    let y := x++
+    let z := ++x
+    println(x, y, z, x--, --x, x)
+
+    let xs := [10, 20, 30]
+    xs[next(0)] += 5
+    xs[next(1)]++
+    --xs[next(2)]
+    println(xs, calls, xs[2]--, xs, ++xs[0] * 2)
+
+    let s := "ab"
+    s += 'c'
+    let d := 1.5
+    d++
+    d *= 2
+    println(s, d, x-- - --x)
+    return x
+}
+"#;
+
+pub const UPDATE_OUTPUT: &str = "62 60 62 62 60 60\n\
+    [16, 21, 28] 3 29 [16, 21, 28] 32\n\
+    abc 5.0 2\n";
+
+/// The updates of `UPDATE_PROGRAM` on integer variables, for the backends without arrays or strings.
+/// Prints `INT_UPDATE_OUTPUT` and returns 1
+pub const INT_UPDATE_PROGRAM: &str = r#"
+let total := 1
+
+func main() {
+    let x := 6
+    x += 3
+    x *= 4
+    x **= 2
+    x %= 100
+    x <<= 2
+    x >>= 1
+    x ^= 5
+    x |= 1
+    x &= 45
+    x -= 1
+    x /= 2
+    total += x++
+    let y := ++x + x--
+    for (let i := 0; i < 3; i++) { total *= 2 }
+    println(x, y, total, x-- - --x)
+    return x
+}
+"#;
+
+pub const INT_UPDATE_OUTPUT: &str = "3 8 24 2\n";
+
+/// Stores into elements of arrays and nested arrays, printing `STORE_OUTPUT` and returning 11
+pub const STORE_PROGRAM: &str = r#"
+let log := ""
+
+func at(v, tag) {
+    log := log + tag
+    return v
+}
+
+func main() {
+    let xs := [1, 2, 3]
+    xs[0] := 10
+    let top := [0, 0]
+    let bottom := [0, 0]
+    let grid := [top, bottom]
+    grid[1][0] := xs[0] := 7
+    grid[at(0, "t")][at(1, "i")] := at(5, "v")
+    let i := 1
+    xs[2] := xs[i] * 2
+    println(xs, grid, top, log)
+    return grid[1][0] + xs[2]
+}
+"#;
+
+pub const STORE_OUTPUT: &str = "[7, 2, 4] [[0, 5], [7, 0]] [0, 5] tiv\n";
+
+/// `?:`, `??` and `?.` on values that may be null, printing `CONDITIONAL_OUTPUT` and returning 6. `log`
+/// records which of the guarded operands ran
+pub const CONDITIONAL_PROGRAM: &str = r#"
+let log := ""
+
+func at(v, tag) {
+    log := log + tag
+    return v
+}
+
+func pick(n) {
+    return n < 0 ? "negative" : n == 0 ? "zero" : "positive"
+}
+
+func twice(v) { return v * 2 }
+
+func main() {
+    let xs := [1, null, 3]
+    let grid := [xs, null]
+    let missing := null
+    let f := twice
+    let g := null
+    println(pick(-3), pick(0), pick(8))
+    println(xs[1] ?? at("default", "a"), xs[0] ?? at(0, "b"), false ?? 1, 0 ?? 1)
+    println(grid?.[0]?.[2], grid?.[1]?.[at(0, "c")] ?? "none", missing?.[at(0, "d")])
+    println(f?.(at(21, "e")), g?.(at(1, "f")), twice?.(4))
+    println(xs[0 == 0 ? 2 : 0], true ? 1 : 2 + 10, (false ? 1 : 2) + 10)
+    println(missing ?? null ?? "last", log)
+    let n := 1
+    n := n > 0 ? n + 5 : at(0, "g")
+    return n ?? 0
+}
+"#;
+
+pub const CONDITIONAL_OUTPUT: &str = "negative zero positive\n\
+    default 1 false 0\n\
+    3 none null\n\
+    42 null 8\n\
+    3 1 12\n\
+    last ae\n";
+
+/// The conditionals of `CONDITIONAL_PROGRAM` that only need integers, printing `INT_CONDITIONAL_OUTPUT`
+/// and returning 3
+pub const INT_CONDITIONAL_PROGRAM: &str = r#"
+let calls := 0
+
+func sign(n) {
+    return n < 0 ? -1 : n == 0 ? 0 : 1
+}
+
+func count(v) {
+    calls += 1
+    return v
+}
+
+func main() {
+    let a := 5
+    let b := a > 3 ? a * 2 : count(a - 1)
+    let c := a > 10 ? count(7) : a < 0 ? count(1) : 2
+    println(sign(-7), sign(0), sign(9), b, c, true ? 1 : 0, calls)
+    return b == 10 ? 3 : 4
+}
+"#;
+
+pub const INT_CONDITIONAL_OUTPUT: &str = "-1 0 1 10 2 1 0\n";
+
+/// `&&` and `||` whose right sides record themselves in `log` when they run, printing `LOGICAL_OUTPUT` and
+/// returning 7. `xs[i]` would be out of bounds if it ran
+pub const LOGICAL_PROGRAM: &str = r#"
+let log := ""
+
+func at(v, tag) {
+    log := log + tag
+    return v
+}
+
+func main() {
+    let xs := [1, 2]
+    let i := 5
+    println(false && at(true, "a"), true || at(false, "b"), null && at(1, "c"), 1 || at(0, "d"))
+    println(true && at(0, "e"), false || at("x", "f"), at(1, "g") && at(2, "h"), at(0, "i") || at(null, "j"))
+    println((i < 2) && xs[i] > 0, (i > 2) || xs[i] > 0, false && at(1, "k") || at(true, "l"), log)
+    return len(log)
+}
+"#;
+
+pub const LOGICAL_OUTPUT: &str = "false true false true\n\
+    false true true false\n\
+    false true true efghijl\n";
+
+/// The logical operators of `LOGICAL_PROGRAM` that only need integers, printing `INT_LOGICAL_OUTPUT`
+/// and returning 5
+pub const INT_LOGICAL_PROGRAM: &str = r#"
+let calls := 0
+
+func count(v) {
+    calls += 1
+    return v
+}
+
+func main() {
+    println(false && count(true), true || count(false), count(1) && count(0), count(0) || count(5))
+    println(calls, 0 && count(1), 3 || count(0))
+    return calls == 4 && (calls > 10 || count(true)) ? calls : 0
+}
+"#;
+
+pub const INT_LOGICAL_OUTPUT: &str = "false true false true\n4 false true\n";
+
+/// Loops over ranges, arrays and strings, one of them growing the array it walks, and spreads into arrays
+/// and calls, printing `FOREACH_OUTPUT` and returning 27
+pub const FOREACH_PROGRAM: &str = r#"
+func add(a, b, c) { return a + b + c }
+
+func main() {
+    let xs := [1, 2, 3]
+    let ys := [0, ...xs, ...1..=2, 9]
+    let total := 0
+    for (x in 0..10) {
+        if (x == 7) { break }
+        if ((x % 2) == 0) { continue }
+        total += x
+    }
+    for (x in ys) { total += x }
+    let word := ""
+    for (c in "hé!") { word := word + c + "," }
+    println(ys, total, word, 1..4, add(...xs), add(1, ...1..3))
+    for (i in 3..=1) { println("never") }
+    for (x in xs) { push(xs, x) }
+    println(xs)
+    return total
+}
+"#;
+
+pub const FOREACH_OUTPUT: &str = "[0, 1, 2, 3, 1, 2, 9] 27 h,é,!, 1..4 6 4\n[1, 2, 3, 1, 2, 3]\n";
+
+/// The loops of `FOREACH_PROGRAM` over literal ranges, printing `INT_FOREACH_OUTPUT` and returning 69
+pub const INT_FOREACH_PROGRAM: &str = r#"
+func main() {
+    let total := 0
+    for (x in 0..10) {
+        if (x == 7) { break }
+        if ((x % 2) == 0) { continue }
+        total += x
+    }
+    for (i in 1..=3) {
+        i *= 10
+        total += i
+    }
+    for (i in 3..=1) { total := 0 }
+    println(total)
+    return total
+}
+"#;
+
+pub const INT_FOREACH_OUTPUT: &str = "69\n";
+
+/// Takes tuples and arrays apart in globals, lets, parameters and `for` loops, printing `TUPLE_OUTPUT` and
+/// returning 14
+pub const TUPLE_PROGRAM: &str = r#"
+let rest := [2, 3]
+let (g, [h, i]) := (1, rest)
+
+func swap((a, b)) { return (b, a) }
+
+func main() {
+    let (x, y) := swap((1, "two"))
+    println(x, y, swap((1, 'c')), (5,), ())
+    let pair := (i, 4)
+    let ps := [g + h, pair]
+    let [p, (q, r)] := ps
+    let n := 0
+    let pairs := [(1, 2), (3, 4)]
+    for ((k, v) in pairs) { n += k * v }
+    println(p, q, r, n, len((1, 2, 3)), (1, (2, 3)) == (1, (2, 3)), (1, 2)[1])
+    for (c in (7, 8)) { print(c) }
+    println()
+    return n
+}
+"#;
+
+pub const TUPLE_OUTPUT: &str = "two 1 ('c', 1) (5,) ()\n3 3 4 14 3 true 2\n78\n";
+
+/// Builds, updates, searches and iterates maps and sets, printing `MAP_OUTPUT` and returning 11
+pub const MAP_PROGRAM: &str = r#"
+let ages := {"ann": 31, "bob": 27}
+
+func count(words) {
+    let counts := {}
+    for (w in words) {
+        if (w in counts) { counts[w] += 1 } else { counts[w] := 1 }
+    }
+    return counts
+}
+
+func main() {
+    ages["cy"] := 40
+    ages["ann"] += 1
+    let words := ["a", "b", "a", 'a', "c", "a"]
+    let counts := count(words)
+    println(ages, counts, len(counts))
+    let seen := {1, 2.0, 2, "2", (1, 2), ...words}
+    println(seen, {,}, {}, 2 in seen, 'a' in seen, "bob" in ages, 3 in (1, 2), "ell" in "hello")
+    let total := 0
+    for ((name, age) in ages) { total += age }
+    println(total, {1: "x", 1.0: "y"}, {(1, 2): 3}[(1.0, 2)], {1, 2} == {2, 1}, {1: 2} == {1: 3})
+    return counts["a"] + len(seen)
+}
+"#;
+
+pub const MAP_OUTPUT: &str = "{\"ann\": 32, \"bob\": 27, \"cy\": 40} {\"a\": 3, \"b\": 1, 'a': 1, \"c\": 1} 4\n\
+{1, 2.0, \"2\", (1, 2), \"a\", \"b\", 'a', \"c\"} {,} {} true true true false true\n\
+99 {1: \"y\"} 3 true false\n";
+
+/// Comprehensions that filter, nest, destructure and shadow, printing `COMPREHENSION_OUTPUT` and returning 10
+pub const COMPREHENSION_PROGRAM: &str = r#"
+let squares := [n * n for n in 1..=4]
+
+func main() {
+    let xs := [3, -1, 4, -1, 5]
+    let positive := [x * 2 for x in xs if x > 0]
+    let pairs := [(x, y) for x in 1..=3 for y in x..=3 if ((x + y) % 2) == 0]
+    println(squares, positive, pairs)
+    let ages := {"ann": 31, "bob": 27}
+    let names := [name + "!" for (name, age) in ages if age > 30]
+    let x := "outer"
+    let chars := [c for x in ("ab", "c") for c in x]
+    let empty := [x for x in xs if x > 10]
+    println(names, chars, empty, x)
+    return len(pairs) + len(positive) + len(chars)
+}
+"#;
+
+pub const COMPREHENSION_OUTPUT: &str = "[1, 4, 9, 16] [6, 8, 10] [(1, 1), (1, 3), (2, 2), (3, 3)]\n\
+[\"ann!\"] ['a', 'b', 'c'] [] outer\n";
+
+/// Values and runtime errors thrown through calls into `try`/`catch`/`finally`, and `break` and `continue`
+/// out of a `try`, printing `EXCEPTION_OUTPUT` and returning 142
+pub const EXCEPTION_PROGRAM: &str = r#"
+func divide(a, b) {
+    if (b == 0) { throw ("divide", a) }
+    return a / b
+}
+
+func attempt(f, x) {
+    try {
+        return f(x)
+    } catch (e) {
+        println("caught", e)
+        return null
+    } finally {
+        println("done", x)
+    }
+}
+
+func pick(i) {
+    let xs := [1, 2]
+    return xs[i]
+}
+
+func six(b) { return divide(6, b) }
+
+func main() {
+    attempt(pick, 5)
+    attempt(six, 0)
+    println(attempt(six, 3))
+    let total := 0
+    for (x in 0..5) {
+        try {
+            if (x == 1) { continue }
+            if (x == 3) { break }
+            total += x
+        } finally {
+            total += 10
+        }
+    }
+    try {
+        try { throw "inner" } finally { total += 100 }
+    } catch (e) {
+        println(e, total)
+    }
+    return total
+}
+"#;
+
+pub const EXCEPTION_OUTPUT: &str = "caught Index 5 out of bounds for length 2\n\
+done 5\n\
+caught (\"divide\", 6)\n\
+done 0\n\
+done 3\n\
+2\n\
+inner 142\n";
+
+/// The programs above by name, with what they print and return, for the backends with dynamic values to run
+pub const PROGRAMS: [(&str, &str, &str, i32); 9] = [
+    ("update", UPDATE_PROGRAM, UPDATE_OUTPUT, 58),
+    ("store", STORE_PROGRAM, STORE_OUTPUT, 11),
+    ("conditional", CONDITIONAL_PROGRAM, CONDITIONAL_OUTPUT, 6),
+    ("logical", LOGICAL_PROGRAM, LOGICAL_OUTPUT, 7),
+    ("foreach", FOREACH_PROGRAM, FOREACH_OUTPUT, 27),
+    ("tuple", TUPLE_PROGRAM, TUPLE_OUTPUT, 14),
+    ("map", MAP_PROGRAM, MAP_OUTPUT, 11),
+    ("comprehension", COMPREHENSION_PROGRAM, COMPREHENSION_OUTPUT, 10),
+    ("exception", EXCEPTION_PROGRAM, EXCEPTION_OUTPUT, 142),
+];
+
+/// The integer versions of the programs, for the backends with only numbers
+pub const INT_PROGRAMS: [(&str, &str, &str, i32); 4] = [
+    ("update", INT_UPDATE_PROGRAM, INT_UPDATE_OUTPUT, 1),
+    ("conditional", INT_CONDITIONAL_PROGRAM, INT_CONDITIONAL_OUTPUT, 3),
+    ("logical", INT_LOGICAL_PROGRAM, INT_LOGICAL_OUTPUT, 5),
+    ("foreach", INT_FOREACH_PROGRAM, INT_FOREACH_OUTPUT, 69),
+];
@@ -0,0 +1,78 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn filters_may_leave_the_array_empty() {
+    assert_eq!(value("let xs := [3, -1, 4]\nreturn [x * 2 for x in xs if x > 0]"), "[6, 8]");
+    assert_eq!(value("let xs := [3, -1, 4]\nreturn [x for x in xs if x > 10]"), "[]");
+}
+
+#[test]
+fn later_clauses_see_earlier_bindings() {
+    assert_eq!(value("return [(x, y) for x in 1..=3 for y in x..=3 if ((x + y) % 2) == 0]"), "[(1, 1), (1, 3), (2, 2), (3, 3)]");
+    assert_eq!(value("let x := \"outer\"\nlet chars := [c for x in (\"ab\", \"c\") for c in x]\nreturn (chars, x)"), "(['a', 'b', 'c'], \"outer\")");
+    assert_eq!(value("let ages := {\"ann\": 31, \"bob\": 27}\nreturn [name + \"!\" for (name, age) in ages if age > 30]"), "[\"ann!\"]");
+}
+
+#[test]
+fn clauses_run_left_to_right() {
+    assert_eq!(value("let xs := [x for x in 0..3]\nreturn xs"), "[0, 1, 2]");
+    assert_eq!(value("let xs := [(x, y) for x in 0..2 for y in 0..2]\nreturn xs"), "[(0, 0), (0, 1), (1, 0), (1, 1)]");
+    assert_eq!(value("let xs := [x for x in 0..10 if (x % 2) == 0 if (x % 3) == 0]\nreturn xs"), "[0, 6]");
+    assert_eq!(value("let xs := [y for x in 0..4 if x > 1 for y in 0..x]\nreturn xs"), "[0, 1, 0, 1, 2]");
+    assert_eq!(value("let s := {3, 1}\nlet xs := [(k, v) for (k, v) in {'a': 1} for z in s]\nreturn xs"), "[('a', 1), ('a', 1)]");
+}
+
+#[test]
+fn bound_names_do_not_leak() {
+    assert_eq!(value("let x := 5\nlet xs := [x for x in 0..3]\nreturn (x, xs)"), "(5, [0, 1, 2])");
+    assert_eq!(value("let x := 5\nlet xs := [x for y in 0..3]\nreturn xs"), "[5, 5, 5]");
+    assert_eq!(value("let a := [x for x in 0..2]\nlet b := [x * 10 for x in a]\nreturn (a, b)"), "([0, 1], [0, 10])");
+    assert_eq!(eval("func main() { let xs := [x for x in 0..3]\nreturn x }").unwrap_err(), "Undefined variable 'x'");
+}
+
+#[test]
+fn comprehensions_are_ordinary_arrays() {
+    assert_eq!(value("let xs := [x for x in \"abc\"]\npush(xs, 'd')\nreturn xs"), "['a', 'b', 'c', 'd']");
+    assert_eq!(value("let [a, b] := [x + 1 for x in (1, 2)]\nreturn a * b"), "6");
+    assert_eq!(value("for (let xs := [x for x in 0..2]; len(xs) < 4; push(xs, 0)) { }\nreturn 1"), "1");
+    assert_eq!(eval("let xs := [x * x for x in 0..4]\nfunc main() { return xs }").unwrap(), "[0, 1, 4, 9]");
+    assert_eq!(eval("func main() { let xs := [x for x in 5] }").unwrap_err(), "Type error: cannot iterate over Integer");
+}
+
+#[test]
+fn comprehensions_are_expressions() {
+    assert_eq!(value("let xs := [1, 2]\nreturn [x * 2 for x in xs]"), "[2, 4]");
+    assert_eq!(value("let ys := []\nys := [x for x in 0..3 if x > 0]\nreturn ys"), "[1, 2]");
+    assert_eq!(value("return [[y for y in 0..x] for x in 1..3]"), "[[0], [0, 1]]");
+    assert_eq!(value("return (1, [2, 3], [x for x in 4..5])"), "(1, [2, 3], [4])");
+    assert_eq!(value("return [1, 2][1] + len([x for x in 0..3])"), "5");
+    assert_eq!(eval("func f(a, b) { return (a, b) }\nfunc main() { return f(1, [x for x in 0..2]) }").unwrap(), "(1, [0, 1])");
+}
+
+#[test]
+fn loops_only_run_when_the_comprehension_is_evaluated() {
+    let calls = "let calls := 0\nfunc count(x) { calls += 1\nreturn x }\n";
+    let run = |body: &str| eval(&format!("{}func main() {{\n{}\n}}", calls, body)).unwrap();
+
+    assert_eq!(run("let a := false && len([count(x) for x in 0..3]) > 0\nreturn (a, calls)"), "(false, 0)");
+    assert_eq!(run("let a := true || len([count(x) for x in 0..3]) > 0\nreturn (a, calls)"), "(true, 0)");
+    assert_eq!(run("let a := false ? [count(x) for x in 0..3] : [count(x) for x in 0..2]\nreturn (a, calls)"), "([0, 1], 2)");
+    assert_eq!(run("let a := [1] ?? [count(x) for x in 0..3]\nreturn (a, calls)"), "([1], 0)");
+    assert_eq!(run("let n := 0\nwhile (len([x for x in 0..n]) < 3) { n += 1 }\nreturn n"), "3");
+}
+
+#[test]
+fn errors_inside_comprehensions_unwind_the_expression() {
+    assert_eq!(value("let a := 1\nlet b := 0\ntry { b := a + len([1 / x for x in (1, 0)]) } catch (e) { b := (a, e) }\nreturn (a, b, [x for x in 0..1])"),
+        "(1, (1, \"Division by zero\"), [0])");
+}
+
+#[test]
+fn malformed_comprehensions_do_not_parse() {
+    assert!(eval("func main() { let xs := [x for x] }").is_err());
+    assert!(eval("func main() { let xs := [x if x for x in 0..3] }").is_err());
+    assert!(eval("func main() { let xs := [x for x in 0..3, 4] }").is_err());
+    assert!(eval("func main() { let xs := [...x for x in 0..3] }").is_err());
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn conditionals_only_run_the_chosen_branch() {
+    assert_eq!(logged("let n := true ? at(1, \"a\") : at(2, \"b\")\nreturn (n, log)"), "(1, \"a\")");
+    assert_eq!(logged("let n := 0 ? at(1, \"a\") : at(2, \"b\")\nreturn (n, log)"), "(2, \"b\")");
+    assert_eq!(value("let xs := [4, 5, 6]\nreturn xs[0 == 0 ? 2 : 0]"), "6");
+}
+
+#[test]
+fn coalescing_only_replaces_null() {
+    assert_eq!(value("return false ?? 1"), "false");
+    assert_eq!(value("return 0 ?? 1"), "0");
+    assert_eq!(value("let xs := [1, null]\nreturn xs[1] ?? \"default\""), "default");
+    assert_eq!(logged("let n := 1 ?? at(2, \"a\")\nreturn (n, log)"), "(1, \"\")");
+    assert_eq!(logged("let n := null ?? at(2, \"a\")\nreturn (n, log)"), "(2, \"a\")");
+}
+
+#[test]
+fn optional_chains_skip_the_rest_on_null() {
+    assert_eq!(value("let grid := [[1, 2, 3], null]\nreturn grid?.[0]?.[2]"), "3");
+    assert_eq!(logged("let grid := [[1], null]\nlet n := grid?.[1]?.[at(0, \"a\")]\nreturn (n, log)"), "(null, \"\")");
+    assert_eq!(logged("let g := null\nlet n := g?.(at(1, \"a\"))\nreturn (n, log)"), "(null, \"\")");
+    assert_eq!(eval("func twice(v) { return v * 2 }\nfunc main() { let f := twice\nreturn (f?.(21), twice?.(4)) }").unwrap(), "(42, 8)");
+}
+
+#[test]
+fn conditionals_nest_to_the_right() {
+    assert_eq!(value("return true ? 1 : false ? 2 : 3"), "1");
+    assert_eq!(value("return false ? 1 : false ? 2 : 3"), "3");
+    assert_eq!(value("return true ? false ? 1 : 2 : 3"), "2");
+    assert_eq!(value("let x := 0\nx := true ? 4 : 5\nreturn x"), "4");
+}
+
+#[test]
+fn coalescing_binds_looser_than_binary_operators() {
+    assert_eq!(value("return 1 + 2 ?? 5"), "3");
+    assert_eq!(value("return null ?? 2 * 3"), "6");
+    assert_eq!(value("return null ?? false ? 1 : 2"), "2");
+    assert_eq!(value("let a := null\nlet b := null\nreturn a ?? b ?? 'c'"), "c");
+}
+
+#[test]
+fn binary_operators_stop_at_closing_brackets() {
+    assert_eq!(value("let xs := [4, 5, 6]\nreturn xs[3 - 1]"), "6");
+    assert_eq!(value("let xs := [4, 5, 6]\nreturn xs[1 == 1 && true ? 1 : 0]"), "5");
+    assert_eq!(value("let xs := [1 + 1, 2 * 3]\nreturn xs"), "[2, 6]");
+}
+
+#[test]
+fn optional_links_only_guard_themselves() {
+    assert_eq!(value("let xs := null\nreturn xs?.[0]?.[1]"), "null");
+    assert_eq!(eval("func main() { let xs := null\nreturn xs?.[0][1] }").unwrap_err(), "Type error: cannot index into Null");
+    assert_eq!(eval("func main() { let f := 3\nreturn f?.() }").unwrap_err(), "Value of type Integer is not callable");
+}
+
+#[test]
+fn malformed_operators_are_rejected() {
+    assert_eq!(eval("func main() { let x := [1]\nreturn x?.y }").unwrap_err(), "Bad expression! Identifier(\"y\")");
+    assert_eq!(eval("func main() { return true ? 1 }").unwrap_err(), "Unexpected token! RightBrace");
+    assert_eq!(eval("func main() { let x := [1]\nx?.[0] := 2 }").unwrap_err(), "Bad assignment!");
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn globals_are_destructured_before_main() {
+    assert_eq!(eval("let rest := [2, 3]\nlet (g, [h, i]) := (1, rest)\nfunc main() { return g * 100 + h * 10 + i }").unwrap(), "123");
+}
+
+#[test]
+fn parameters_are_destructured_per_call() {
+    assert_eq!(eval("func swap((a, b)) { return (b, a) }\nfunc main() { return (swap((1, \"two\")), swap((1, 'c'))) }").unwrap(),
+        "((\"two\", 1), ('c', 1))");
+}
+
+#[test]
+fn loop_variables_are_destructured_each_iteration() {
+    assert_eq!(value("let pairs := [(1, 2), (3, 4)]\nlet n := 0\nfor ((k, v) in pairs) { n += k * v }\nreturn n"), "14");
+    assert_eq!(value("let n := 0\nfor (c in (7, 8)) { n := n * 10 + c }\nreturn n"), "78");
+}
+
+#[test]
+fn tuples_are_immutable_sequences() {
+    assert_eq!(value("return (1, \"a\", 'b')"), "(1, \"a\", 'b')");
+    assert_eq!(value("return ((1,), ())"), "((1,), ())");
+    assert_eq!(value("return (1 + 2)"), "3");
+    assert_eq!(value("let t := (1, 2, 3)\nreturn t[2] + len(t)"), "6");
+    assert_eq!(value("return (1, 2) == (1, 2.0)"), "true");
+    assert_eq!(eval("func main() { let t := (1, 2)\nt[0] := 5 }").unwrap_err(), "Type error: cannot assign into Tuple");
+}
+
+#[test]
+fn patterns_nest_and_shadow() {
+    assert_eq!(value("let a := 1\nlet b := 2\nlet (a, b) := (b, a)\nreturn a * 10 + b"), "21");
+    assert_eq!(value("let t := (1, (2, (3, 4)))\nlet (a, (b, (c, d))) := t\nreturn a * 1000 + b * 100 + c * 10 + d"), "1234");
+    assert_eq!(value("let x := [1, 2]\nlet y := [3, 4]\nlet xs := [x, y]\nlet n := 0\nfor ([a, b] in xs) { n += a * b }\nreturn n"), "14");
+    assert_eq!(value("func f(x, (y, z)) { return x + y + z }\nreturn f(1, (2, 3))"), "6");
+}
+
+#[test]
+fn patterns_that_do_not_fit_are_runtime_errors() {
+    assert_eq!(eval("func main() { let (a, b) := 5 }").unwrap_err(), "Cannot destructure Integer as a tuple of 2 elements");
+    assert_eq!(eval("func main() { let xs := [1, 2, 3]\nlet [a, b] := xs }").unwrap_err(),
+        "Cannot destructure Array of 3 elements as an array of 2 elements");
+    assert_eq!(eval("func main() { let t := (1, 2)\nlet [a, b] := t }").unwrap_err(),
+        "Cannot destructure Tuple as an array of 2 elements");
+}
@@ -0,0 +1,84 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use ult::{ Engine, Error, Value };
+
+const SCRIPT: &str = r#"
+let calls := 0
+
+func area(w, h) {
+    calls := calls + 1
+    return w * h
+}
+
+func describe(name, sizes) {
+    return name + " has " + count(sizes) + " sizes"
+}
+
+func checked(x) {
+    return half(x)
+}
+
+func main() {
+    return record("main ran")
+}
+"#;
+
+fn engine(log: &Rc<RefCell<Vec<String>>>) -> Engine {
+    let mut engine = Engine::new();
+    let log = log.clone();
+
+    engine
+        .register("count", |items: Vec<Value>| items.len())
+        .register("record", move |message: String| log.borrow_mut().push(message))
+        .register("half", |x: i64| match x % 2 {
+            0 => Ok(x / 2),
+            _ => Err(format!("{} is odd", x)),
+        });
+
+    engine.load(SCRIPT).unwrap();
+    engine
+}
+
+#[test]
+fn script_functions_are_called_with_converted_values() {
+    let log = Rc::new(RefCell::new(vec![]));
+    let mut engine = engine(&log);
+
+    // Loading runs the top level but not main
+    assert!(log.borrow().is_empty());
+
+    let area: i64 = engine.call("area", (6, 7)).unwrap();
+    let decimal: f64 = engine.call("area", (1.5, 3)).unwrap();
+    let text: String = engine.call("describe", ("box", vec![1, 2, 3])).unwrap();
+
+    assert_eq!(area, 42);
+    assert_eq!(decimal, 4.5);
+    assert_eq!(text, "box has 3 sizes");
+
+    // Globals keep their values between calls
+    assert_eq!(engine.call::<i64>("area", (1, 1)).unwrap(), 1);
+
+    let () = engine.call("main", ()).unwrap();
+    assert_eq!(*log.borrow(), ["main ran"]);
+}
+
+#[test]
+fn errors_propagate_to_the_host() {
+    let log = Rc::new(RefCell::new(vec![]));
+    let mut engine = engine(&log);
+
+    assert_eq!(engine.call::<i64>("checked", (8,)).unwrap(), 4);
+
+    let error = engine.call::<i64>("checked", (7,)).unwrap_err();
+    assert_eq!(error.to_string(), "Error in native function 'half': 7 is odd");
+
+    let error = engine.call::<i64>("checked", ("eight",)).unwrap_err();
+    assert_eq!(error.to_string(), "Type error: argument 1 of 'half': expected Integer, got String");
+
+    let error = engine.call::<bool>("area", (2, 3)).unwrap_err();
+    assert_eq!(error.to_string(), "Type error: expected Boolean, got Integer");
+
+    assert!(matches!(engine.call::<i64>("calls", ()), Err(Error::UndefinedFunction(_))));
+    assert!(matches!(engine.call::<i64>("area", (1,)), Err(Error::Runtime(_))));
+    assert!(matches!(Engine::new().load("func ("), Err(Error::Parse(_))));
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn finally_runs_after_the_try_or_catch_returns() {
+    let source = "let log := \"\"\n\
+                  func attempt(x) { try { if (x == 0) { throw \"zero\" }\nreturn 6 / x } catch (e) { log += e\nreturn null } finally { log += '.' } }\n\
+                  func main() { return (attempt(3), attempt(0), log) }";
+    assert_eq!(eval(source).unwrap(), "(2, null, \".zero.\")");
+}
+
+#[test]
+fn errors_from_function_values_are_caught_by_the_caller() {
+    let source = "func pick(i) { let xs := [1, 2]\nreturn xs[i] }\n\
+                  func attempt(f, x) { try { return f(x) } catch (e) { return e } }\n\
+                  func main() { return (attempt(pick, 1), attempt(pick, 5)) }";
+    assert_eq!(eval(source).unwrap(), "(2, \"Index 5 out of bounds for length 2\")");
+}
+
+#[test]
+fn runtime_errors_are_caught_as_their_message() {
+    assert_eq!(value("try { return 1 / 0 } catch (e) { return e }"), "Division by zero");
+    assert_eq!(value("let xs := [1]\ntry { return xs[1] } catch (e) { return e }"), "Index 1 out of bounds for length 1");
+    assert_eq!(value("try { return undefined } catch (e) { return e }"), "Undefined variable 'undefined'");
+    assert_eq!(value("try { return len(1) } catch (e) { return len(e) > 0 }"), "true");
+    assert_eq!(eval("func f() { return f() }\nfunc main() { try { f() } catch (e) { return e } }").unwrap(), "Stack overflow");
+}
+
+#[test]
+fn any_value_can_be_thrown() {
+    assert_eq!(value("let xs := [2]\ntry { throw (1, xs) } catch (e) { return e }"), "(1, [2])");
+    assert_eq!(value("try { throw null } catch (e) { return e == null }"), "true");
+    assert_eq!(value("let e := 1\ntry { throw 2 } catch (e) { e += 1 }\nreturn e"), "1");
+    assert_eq!(eval("func main() { throw \"oops\" }").unwrap_err(), "oops");
+    assert_eq!(eval("func main() { throw {1: 2} }").unwrap_err(), "{1: 2}");
+}
+
+#[test]
+fn errors_unwind_through_calls_and_natives() {
+    let source = "func check(x) { if (x > 1) { throw x }\nreturn x }\n\
+                  func main() { let xs := [1, 2, 3]\ntry { map(xs, check) } catch (e) { return e } }";
+    assert_eq!(eval(source).unwrap(), "2");
+
+    let source = "func deep(n) { if (n == 0) { throw \"bottom\" }\nlet local := n\nreturn deep(n - 1) + local }\n\
+                  func main() { let a := 1\ntry { deep(10) } catch (e) { return (a, e) } }";
+    assert_eq!(eval(source).unwrap(), "(1, \"bottom\")");
+}
+
+#[test]
+fn nested_handlers_and_rethrow() {
+    assert_eq!(value("try { try { throw 1 } catch (e) { throw e + 1 } } catch (e) { return e }"), "2");
+    assert_eq!(value("let log := \"\"\ntry { try { throw 'x' } finally { log += \"f\" } } catch (e) { log += e }\nreturn log"), "fx");
+    assert_eq!(value("let log := \"\"\ntry { try { throw 1 } catch (e) { throw 2 } finally { log += \"f\" } } catch (e) { log += e }\nreturn log"), "f2");
+    assert_eq!(value("try { throw 1 } catch (e) { }\ntry { throw 2 } catch (e) { return e }"), "2");
+}
+
+#[test]
+fn finally_runs_on_every_way_out() {
+    assert_eq!(eval("let n := 0\nfunc f() { try { return 1 } finally { n += 10 } }\nfunc main() { return (f(), n) }").unwrap(), "(1, 10)");
+    assert_eq!(value("try { return 1 } finally { return 2 }"), "2");
+    assert_eq!(value("let s := \"\"\nfor (x in 0..3) { try { if (x == 1) { continue }\ns += x } finally { s += '.' } }\nreturn s"), "0..2.");
+    assert_eq!(value("let s := \"\"\nwhile (true) { try { try { break } finally { s += 'a' } } finally { s += 'b' } }\nreturn s"), "ab");
+    assert_eq!(value("let s := 0\nfor (x in 0..3) { let y := x\ntry { for (z in 0..2) { try { break } finally { s += 1 } } } finally { s += y } }\nreturn s"), "6");
+}
+
+#[test]
+fn uncaught_errors_print_a_trace() {
+    let scratch = Scratch::new("exception_trace");
+    let input = scratch.source("trace", "func inner(x) {\n    return x / 0\n}\n\nfunc main() {\n    let y := 1\n    return inner(y)\n}\n");
+
+    let out = ult(&[input.to_str().unwrap()]);
+    let path = input.display();
+
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(stderr(&out), format!("Runtime error: Division by zero\n    \
+        at inner ({path}:2)\n        return x / 0\n    \
+        at main ({path}:7)\n        return inner(y)\n    \
+        at <script> ({path}:5)\n        func main() {{\n"));
+}
+
+#[test]
+fn malformed_try_does_not_parse() {
+    assert!(eval("func main() { try { } }").is_err());
+    assert!(eval("func main() { try { } catch { } }").is_err());
+    assert!(eval("func main() { try { } catch (e) }").is_err());
+    assert!(eval("func main() { throw }").is_err());
+}
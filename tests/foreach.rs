@@ -0,0 +1,57 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn ranges_arrays_and_strings_are_iterable() {
+    assert_eq!(value("let xs := []\nfor (i in 0..3) { push(xs, i) }\nfor (i in 1..=2) { push(xs, i) }\nreturn xs"), "[0, 1, 2, 1, 2]");
+    assert_eq!(value("let n := 0\nfor (x in [1, 2, 3]) { n += x }\nreturn n"), "6");
+    assert_eq!(value("let word := \"\"\nfor (c in \"hé!\") { word := word + c + \",\" }\nreturn word"), "h,é,!,");
+}
+
+#[test]
+fn empty_ranges_run_no_iterations() {
+    assert_eq!(value("let n := 0\nfor (i in 3..=1) { n += 1 }\nfor (i in 2..2) { n += 1 }\nreturn n"), "0");
+}
+
+#[test]
+fn break_and_continue_in_for_each() {
+    assert_eq!(value("let n := 0\nfor (x in 0..10) { if (x == 7) { break }\nif ((x % 2) == 0) { continue }\nn += x }\nreturn n"), "9");
+}
+
+#[test]
+fn loops_walk_arrays_and_maps_as_they_were_when_they_started() {
+    assert_eq!(value("let xs := [1, 2, 3]\nfor (x in xs) { push(xs, x) }\nreturn xs"), "[1, 2, 3, 1, 2, 3]");
+    assert_eq!(value("let xs := [1, 2]\nlet seen := []\nfor (x in xs) { xs[1] := 9\npush(seen, x) }\nreturn (seen, xs)"), "([1, 2], [1, 9])");
+    assert_eq!(value("let xs := [1, 2, 3]\nlet n := 0\nfor (x in xs) { pop(xs)\nn += x }\nreturn (n, xs)"), "(6, [])");
+    assert_eq!(value("let m := {0: 0}\nfor ((k, v) in m) { m[k + 1] := v + 1\nm[k] := 5 }\nreturn m"), "{0: 5, 1: 1}");
+}
+
+#[test]
+fn ranges_are_values() {
+    assert_eq!(value("let r := 2..=4\nlet n := 0\nfor (i in r) { n := n * 10 + i }\nreturn n"), "234");
+    assert_eq!(value("return 1 + 1..5"), "2..5");
+    assert_eq!(value("return (0..3) == (0..=2)"), "true");
+    assert_eq!(value("let xs := [...5..2]\nreturn len(xs)"), "0");
+    assert_eq!(eval("func main() { return 1..'c' }").unwrap_err(), "Type error: cannot apply '..' to Integer and Character");
+}
+
+#[test]
+fn the_loop_variable_is_scoped_to_the_body() {
+    assert_eq!(value("let x := 9\nfor (x in 0..3) { x += 1 }\nreturn x"), "9");
+    assert_eq!(value("let n := 0\nfor (x in 0..3) { x := 10\nn += x }\nreturn n"), "30");
+}
+
+#[test]
+fn spread_flattens_into_arrays_and_arguments() {
+    assert_eq!(value("let xs := [1, 2]\nlet ys := [...xs, ...xs, ...\"ab\"]\nreturn ys"), "[1, 2, 1, 2, 'a', 'b']");
+    assert_eq!(value("let xs := [3, 4]\nreturn max(...xs)"), "4");
+    assert_eq!(eval("func add(a, b, c) { return a + b + c }\nfunc main() { return add(1, ...1..3) }").unwrap(), "4");
+    assert_eq!(eval("func main() { return len(...\"abc\") }").unwrap_err(), "Function 'len' expected 1 arguments but got 3");
+}
+
+#[test]
+fn only_iterables_can_be_iterated() {
+    assert_eq!(eval("func main() { for (x in 5) { } }").unwrap_err(), "Type error: cannot iterate over Integer");
+    assert_eq!(eval("func main() { let xs := [...null] }").unwrap_err(), "Type error: cannot iterate over Null");
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use common::*;
+use ult::codegen::compiler::Compiler;
+use ult::codegen::gc::GcMode;
+use ult::codegen::vm::VM;
+
+const CYCLES: &str = "
+func wrap(x) {
+    let text := \"wrapped \" + x
+    let w := [x, text]
+    return w
+}
+
+func main() {
+    for (let i := 0; i < 3000; i := i + 1) {
+        let a := []
+        let b := [a]
+        push(a, b)
+    }
+
+    let xs := [1, 2, 3]
+    let wrapped := map(xs, wrap)
+    push(wrapped, wrapped)
+    println(len(wrapped), wrapped[1])
+    return 0
+}
+";
+
+/// Maps holding arrays holding them, directly and through tuples
+const MAP_CYCLES: &str = "
+func main() {
+    for (let i := 0; i < 1000; i := i + 1) {
+        let m := {\"i\": i}
+        let a := [m]
+        m[\"a\"] := a
+        m[\"t\"] := (a, i)
+    }
+
+    let kept := {}
+    kept[1] := (1, kept)
+    println(len(kept))
+    return 0
+}
+";
+
+/// The number before `label` in `--gc-stats` output
+fn stat(stats: &str, label: &str) -> usize {
+    let line = stats.lines().find(|l| l.starts_with(label)).unwrap();
+    line[label.len()..].split_whitespace().next().unwrap().parse().unwrap()
+}
+
+#[test]
+fn cycles_are_collected() {
+    let scratch = Scratch::new("gc_cycles");
+    let input = scratch.source("cycles", CYCLES);
+
+    let out = ult(&["--gc-stats", input.to_str().unwrap()]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert_eq!(stdout(&out), "4 [2, \"wrapped 2\"]\n");
+
+    let stats = stderr(&out);
+    assert!(stat(&stats, "GC collections:") >= 3, "{}", stats);
+    // Two arrays per iteration, then `wrapped` and the three arrays in it
+    assert!(stats.contains("(6004 in cycles)"), "{}", stats);
+    assert_eq!(stat(&stats, "GC live:"), 0, "{}", stats);
+    assert_eq!(stat(&stats, "GC allocations:"), 6008, "{}", stats);
+}
+
+#[test]
+fn cycles_through_maps_are_collected() {
+    let scratch = Scratch::new("gc_map_cycles");
+    let input = scratch.source("map_cycles", MAP_CYCLES);
+
+    let out = ult(&["--gc-stats", input.to_str().unwrap()]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert_eq!(stdout(&out), "1\n");
+
+    // A map and an array per iteration, then `kept`
+    let stats = stderr(&out);
+    assert!(stats.contains("(2001 in cycles)"), "{}", stats);
+}
+
+#[test]
+fn stress_mode_changes_nothing_but_the_collections() {
+    let scratch = Scratch::new("gc_stress");
+
+    for (name, source) in [("cycles", CYCLES), ("map_cycles", MAP_CYCLES), ("stdlib", STDLIB_PROGRAM)] {
+        let input = scratch.source(name, source);
+
+        let normal = ult(&["--gc-stats", input.to_str().unwrap()]);
+        let stressed = ult(&["--gc-stress", "--gc-stats", input.to_str().unwrap()]);
+        assert!(stressed.status.success(), "{}", stderr(&stressed));
+
+        assert_eq!(stdout(&normal), stdout(&stressed));
+
+        let collections = |out| stat(&stderr(out), "GC collections:");
+        assert!(collections(&stressed) > collections(&normal), "{}", stderr(&stressed));
+    }
+}
+
+#[test]
+fn values_held_outside_the_vm_survive() {
+    let source = "
+        let kept := [1]
+
+        func make() {
+            let inner := [0]
+            let outer := [inner, 2]
+            push(inner, outer)
+            return outer
+        }
+
+        func main() { return make() }
+    ";
+
+    let ast = ult::parse(&ult::lex(source).unwrap()).unwrap();
+    let mut compiler = Compiler::new(&ast);
+    compiler.compile().unwrap();
+
+    let mut vm = VM::new().with_gc(GcMode::Manual);
+    ult::stdlib::install(&mut vm);
+
+    let result = vm.run(compiler.program().unwrap()).unwrap();
+    vm.collect();
+
+    // The result is a cycle only the host holds, and the global is still a root
+    assert_eq!(vm.gc_stats().cycles, 0);
+    assert_eq!(result.index(&ult::Value::Integer(1)).unwrap().to_string(), "2");
+    assert_eq!(vm.global("kept").unwrap().to_string(), "[1]");
+
+    drop(result);
+    vm.collect();
+
+    assert_eq!(vm.gc_stats().cycles, 2);
+    assert_eq!(vm.gc_stats().live, 1);
+}
+
+#[test]
+fn values_holding_themselves_compare_and_print() {
+    let source = "
+        func main() {
+            let a := [1]
+            a[0] := a
+            let b := [1]
+            b[0] := b
+            let m := {\"k\": 1}
+            m[\"k\"] := m
+            m[\"a\"] := a
+            println(a == b, [a] == [b], a != [a], m == m, [a, a], (a,))
+            println(m)
+            return a
+        }
+    ";
+
+    let scratch = Scratch::new("gc_self_references");
+    let input = scratch.source("self_references", source);
+
+    let out = ult(&["--debug", input.to_str().unwrap()]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert_eq!(stdout(&out), "true true false true [[[...]], [[...]]] ([[...]],)\n{\"k\": {...}, \"a\": [[...]]}\n");
+    assert!(stderr(&out).contains("Result: [[...]]\n"), "{}", stderr(&out));
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use common::*;
+
+fn ir(name: &str, source: &str) -> String {
+    optimized(name, source, &[]).0
+}
+
+/// Lowers `source` with extra `ult ir` flags, returning the final IR and the per pass dumps
+fn optimized(name: &str, source: &str, flags: &[&str]) -> (String, String) {
+    let scratch = Scratch::new(name);
+    let input = scratch.source(name, source);
+
+    let mut args = vec!["ir"];
+    args.extend(flags);
+    args.push(input.to_str().unwrap());
+
+    let output = ult(&args);
+    assert!(output.status.success(), "lowering failed: {}", stderr(&output));
+
+    (stdout(&output), stderr(&output))
+}
+
+#[test]
+fn loops_merge_values_with_phis() {
+    let dump = ir("ir_loop", r#"
+        func main() {
+            let sum := 0
+            for (let i := 0; i < 10; i := i + 1) {
+                if (i == 3) { continue }
+                sum := sum + i
+            }
+            return sum
+        }
+    "#);
+
+    assert!(dump.contains("fn @main/0 {\nbb0:\n    %0: int = const 0\n    %1: int = const 0\n    jump bb1\n"), "{}", dump);
+    assert!(dump.contains("bb1:    ; preds: bb0, bb3\n    %2: int = phi [bb0: %1], [bb3: %"), "{}", dump);
+    assert!(dump.contains("%3: int = phi [bb0: %0], [bb3: %"), "{}", dump);
+}
+
+#[test]
+fn straight_line_code_needs_no_phis() {
+    let dump = ir("ir_straight", r#"
+        let limit := 3
+
+        func twice(x) {
+            let y := x
+            y := y * 2
+            return y
+        }
+
+        func main() {
+            if (limit > 2) { println("big") } else { println("small") }
+            return twice(limit)
+        }
+    "#);
+
+    let expected = "global @limit
+
+fn @__init/0 {
+bb0:
+    %0: int = const 3
+    %1: null = set_global @limit, %0
+    %2: null = const null
+    ret %2
+}
+
+fn @twice/1 {
+bb0:
+    %0: any = param 0
+    %1: int = const 2
+    %2: any = mul %0, %1
+    ret %2
+}
+";
+
+    assert!(dump.starts_with(expected), "{}", dump);
+    assert!(!dump.contains("phi"), "{}", dump);
+    assert!(dump.contains("call builtin println(%"), "{}", dump);
+    assert!(dump.contains("%8: any = call @twice(%7)\n    ret %8\n}"), "{}", dump);
+}
+
+#[test]
+fn lowering_errors_are_reported() {
+    let scratch = Scratch::new("ir_errors");
+    let input = scratch.source("ir_errors", "func main() { break }");
+
+    let output = ult(&["ir", input.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("BreakOutsideLoop"), "{}", stderr(&output));
+}
+
+#[test]
+fn constants_fold_and_dead_branches_disappear() {
+    let (dump, _) = optimized("ir_fold", r#"
+        let x := 2 * 7 + 2
+        let big := 9223372036854775807 + 1
+
+        func main() {
+            if (x > (100 * 0)) { return "folded " + 1.5 }
+            if ((1 + 1) == 3) { println("dead") }
+            return 0
+        }
+    "#, &["-O1"]);
+
+    assert!(dump.contains("%0: int = const 16\n    %1: null = set_global @x, %0\n"), "{}", dump);
+    // Overflow is left for the runtime to report
+    assert!(dump.contains("add %"), "{}", dump);
+    assert!(dump.contains("fn @main/0 {\nbb0:\n    %0: any = get_global @x\n"), "{}", dump);
+    assert!(dump.contains("const \"folded 1.5\""), "{}", dump);
+    assert!(!dump.contains("dead") && !dump.contains("println"), "{}", dump);
+}
+
+#[test]
+fn small_calls_are_inlined_and_invariants_hoisted() {
+    let source = r#"
+        func square(v) { return v * v }
+
+        func run(scale) {
+            let total := 0
+            for (let i := 0; i < 10; i := i + 1) {
+                if (scale == 3) { total := total + square(i) }
+            }
+            return total
+        }
+    "#;
+
+    let (o1, _) = optimized("ir_inline_o1", source, &["-O1"]);
+    assert!(o1.contains("call @square("), "{}", o1);
+
+    let (o2, _) = optimized("ir_inline_o2", source, &["-O2"]);
+    let run = &o2[o2.find("fn @run/1").unwrap()..];
+
+    assert!(!run.contains("call"), "{}", o2);
+    // `scale == 3` and the loop's constants are computed once before entering it
+    let entry = &run[..run.find("bb1:").unwrap()];
+    assert!(entry.contains("eq %0, "), "{}", o2);
+    assert!(entry.contains("const 10"), "{}", o2);
+}
+
+#[test]
+fn global_reads_that_may_fail_stay_put() {
+    let (dump, _) = optimized("ir_globals", r#"
+        func run(n) {
+            let unused := limit
+            let total := 0
+            for (let i := 0; i < n; i := i + 1) {
+                if (i > 5) { total := total + limit }
+            }
+            for (let i := 0; i < limit; i := i + 1) { total := total + i }
+            return total
+        }
+
+        let limit := run(3)
+    "#, &["-O2"]);
+
+    let run = &dump[dump.find("fn @run/1").unwrap()..];
+    let entry = &run[..run.find("bb1:").unwrap()];
+
+    // `limit` is read before it's defined, so the unused read still has to fail
+    assert!(entry.contains("get_global @limit"), "{}", dump);
+    // The conditional read stays in the first loop, the second loop's header reads it on entry anyway
+    let body = &run[run.find("bb4:").unwrap()..];
+    assert!(body[..body.find("jump").unwrap()].contains("get_global @limit"), "{}", dump);
+    assert_eq!(run.matches("get_global @limit").count(), 3, "{}", dump);
+}
+
+#[test]
+fn passes_can_be_dumped() {
+    let (_, dumps) = optimized("ir_dump", "func main() { return 1 + 2 }", &["-O1", "--dump-passes"]);
+
+    let headers = dumps.lines().filter(|l| l.starts_with("; IR")).collect::<Vec<_>>();
+    assert_eq!(headers, [
+        "; IR before fold",
+        "; IR after fold (changed)",
+        "; IR before copy-prop",
+        "; IR after copy-prop (unchanged)",
+        "; IR before dce",
+        "; IR after dce (changed)",
+    ]);
+    assert!(dumps.contains("%2: int = add %0, %1"), "{}", dumps);
+}
@@ -0,0 +1,211 @@
+mod common;
+
+use common::*;
+use std::path::PathBuf;
+use std::process::{ Command, Output };
+
+fn node_available() -> bool {
+    Command::new("node").arg("--version").output().is_ok_and(|o| o.status.success())
+}
+
+/// Transpiles `source` to JavaScript, returning the script path and the scratch dir holding it
+fn build(name: &str, source: &str) -> (Scratch, PathBuf) {
+    let scratch = Scratch::new(name);
+    let input = scratch.source(name, source);
+    let script = scratch.path(&format!("{}.js", name));
+
+    let build = ult(&["build", "--target", "js", "-o", script.to_str().unwrap(), input.to_str().unwrap()]);
+    assert!(build.status.success(), "build failed: {}", stderr(&build));
+
+    (scratch, script)
+}
+
+fn run(script: &PathBuf) -> Output {
+    assert!(node_available(), "node not found");
+
+    Command::new("node").arg(script).output().unwrap()
+}
+
+#[test]
+#[ignore = "needs node, run with --include-ignored"]
+fn dynamic_values_and_shadowing() {
+    let (_scratch, script) = build("js_values", r#"
+        let x := 2 * 7 + 2
+        let class := "a JS keyword"
+
+        func apply(f, v) { return f(v) }
+
+        func main() {
+            func square(v) { return v * v }
+            let arr := [1, 2.5, "three", 'c', null, true]
+            println(x, class, arr, arr[2][1], apply(square, 12))
+            println(0.1 + 0.2, 7 / 2.0, "n = " + 3, 10 / 3, 2.0, 2 ** 3 ** 2, 2 * 3 ** 2)
+
+            let total := 0
+            for (let i := 0; i < 10; i := i + 1) {
+                if (i == 3) { continue }
+                let x := x + i
+                if (x > 22) { break }
+                total := total + x
+            }
+            let x := "shadowed"
+            println(total, x, square)
+            return 3
+        }
+    "#);
+
+    let out = run(&script);
+
+    assert_eq!(stderr(&out), "");
+    assert_eq!(stdout(&out), "16 a JS keyword [1, 2.5, \"three\", 'c', null, true] h 144\n\
+        0.30000000000000004 3.5 n = 3 3 2.0 512 18\n114 shadowed <func square>\n");
+    assert_eq!(out.status.code(), Some(3));
+}
+
+#[test]
+#[ignore = "needs node, run with --include-ignored"]
+fn integer_overflow_is_checked() {
+    let (_scratch, script) = build("js_overflow", r#"
+        func main() {
+            let big := 9223372036854775807
+            println(big - 1)
+            println(big + 1)
+        }
+    "#);
+
+    let out = run(&script);
+
+    assert_eq!(stdout(&out), "9223372036854775806\n");
+    assert_eq!(stderr(&out), "Runtime error: Integer overflow\n");
+    assert_eq!(out.status.code(), Some(1));
+}
+
+#[test]
+#[ignore = "needs node, run with --include-ignored"]
+fn arrays_and_maps_are_unhashable() {
+    let (_scratch, script) = build("js_unhashable", "func main() {\nlet m := {}\nprintln({1, {}} == m)\n}");
+    let out = run(&script);
+
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(stderr(&out), "Runtime error: Type error: unhashable Map\n");
+}
+
+#[test]
+#[ignore = "needs node, run with --include-ignored"]
+fn standard_library() {
+    let (_scratch, script) = build("js_stdlib", STDLIB_PROGRAM);
+    let out = run(&script);
+
+    assert_eq!(stderr(&out), "");
+    assert_eq!(stdout(&out), STDLIB_OUTPUT);
+    assert_eq!(out.status.code(), Some(3));
+
+    let (_scratch, script) = build("js_stdlib_error", "func main() { return len(5) }");
+    let out = run(&script);
+
+    assert_eq!(stderr(&out), "Runtime error: Type error: argument 1 of 'len': expected String, Array, Tuple, Map or Set, got Integer\n");
+    assert_eq!(out.status.code(), Some(1));
+}
+
+#[test]
+fn source_map_points_at_statements() {
+    let (_scratch, script) = build("js_map", "func main() {\n    let a := 1\n    println(a)\n}\n");
+
+    let map = std::fs::read_to_string(script.with_extension("js.map")).unwrap();
+    assert!(map.starts_with("{\"version\":3,\"file\":\"js_map.js\",\"sources\":[\"js_map.ult\"]"));
+    assert!(std::fs::read_to_string(&script).unwrap().ends_with("//# sourceMappingURL=js_map.js.map\n"));
+}
+
+#[test]
+#[ignore = "needs node, run with --include-ignored"]
+fn source_map_decodes_with_node() {
+    let (_scratch, script) = build("js_map_node", "func main() {\n    let a := 1\n    println(a)\n}\n");
+    assert!(node_available(), "node not found");
+
+    // Look up where the generated `println` line came from with Node's own decoder
+    let lookup = r#"
+        const fs = require("fs");
+        const { SourceMap } = require("module");
+        const [script] = process.argv.slice(1);
+        const map = new SourceMap(JSON.parse(fs.readFileSync(script + ".map", "utf8")));
+        const line = fs.readFileSync(script, "utf8").split("\n").findIndex(l => l.includes("$.std.println(a)"));
+        const entry = map.findEntry(line, 8);
+        console.log(entry.originalSource, entry.originalLine, entry.originalColumn);
+    "#;
+
+    let out = Command::new("node").arg("-e").arg(lookup).arg(&script).output().unwrap();
+    assert_eq!(stdout(&out), "js_map_node.ult 2 4\n");
+}
+
+#[test]
+#[ignore = "needs node, run with --include-ignored"]
+fn shared_programs() {
+    for (name, program, output, code) in PROGRAMS {
+        let (_scratch, script) = build(&format!("js_{}", name), program);
+        let out = run(&script);
+
+        assert_eq!(stderr(&out), "", "{}", name);
+        assert_eq!(stdout(&out), output, "{}", name);
+        assert_eq!(out.status.code(), Some(code), "{}", name);
+    }
+}
+
+#[test]
+#[ignore = "needs node, run with --include-ignored"]
+fn missing_keys_are_runtime_errors() {
+    let (_scratch, script) = build("js_key_error", "func main() { let m := {(1, 2): 1}\nreturn m[(2, 1)] }");
+    let out = run(&script);
+
+    assert_eq!(stderr(&out), "Runtime error: Key (2, 1) not found in map\n");
+}
+
+#[test]
+#[ignore = "needs node, run with --include-ignored"]
+fn comprehensions_in_expressions() {
+    let (_scratch, script) = build("js_comprehension_expressions", r#"
+        let calls := 0
+
+        func count(x) {
+            calls += 1
+            return x
+        }
+
+        func evens(n) { return [x for x in 0..n if (x % 2) == 0] }
+
+        func main() {
+            println([x * 2 for x in 1..=3], evens(5), [[y for y in 0..x] for x in 1..3], (1, [2, 3]))
+            let a := false && len([count(x) for x in 0..3]) > 0
+            let b := true ? [count(x) for x in 0..2] : [count(x) for x in 0..3]
+            let c := [1] ?? [count(x) for x in 0..3]
+            println(a, b, c, calls)
+            return 1 + len([x for x in 0..3])
+        }
+    "#);
+    let out = run(&script);
+
+    assert_eq!(stderr(&out), "");
+    assert_eq!(stdout(&out), "[2, 4, 6] [0, 2, 4] [[0], [0, 1]] (1, [2, 3])\nfalse [0, 1] [1] 2\n");
+    assert_eq!(out.status.code(), Some(4));
+}
+
+#[test]
+#[ignore = "needs node, run with --include-ignored"]
+fn uncaught_throws_are_runtime_errors() {
+    let (_scratch, script) = build("js_uncaught", "func main() { try { throw (1, 'a') } finally { println(\"cleanup\") } }");
+    let out = run(&script);
+
+    assert_eq!(stdout(&out), "cleanup\n");
+    assert_eq!(stderr(&out), "Runtime error: (1, 'a')\n");
+    assert_eq!(out.status.code(), Some(1));
+}
+
+#[test]
+#[ignore = "needs node, run with --include-ignored"]
+fn failed_assertions_show_the_values() {
+    let (_scratch, script) = build("js_assert", "func main() { let xs := [1]\nassert len(xs) == 1\ntry { assert \"a\" in \"xyz\" } catch (e) { println(e) }\nassert len(xs) > 1, \"too short\" }");
+    let out = run(&script);
+
+    assert_eq!(stdout(&out), "Assertion failed: \"a\" in \"xyz\"\n");
+    assert_eq!(stderr(&out), "Runtime error: Assertion failed: too short (1 > 1)\n");
+    assert_eq!(out.status.code(), Some(1));
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::*;
+use std::path::Path;
+
+fn lint(input: &Path, extra: &[&str]) -> (bool, Vec<String>) {
+    let mut args = vec!["lint"];
+    args.extend(extra);
+    args.push(input.to_str().unwrap());
+
+    let output = ult(&args);
+    let prefix = format!("{}:", input.display());
+
+    let diagnostics = stderr(&output).lines()
+        .map(|l| l.strip_prefix(&prefix).unwrap_or(l).to_string())
+        .collect();
+
+    (output.status.success(), diagnostics)
+}
+
+#[test]
+fn assignment_to_unread_global_is_reported() {
+    let scratch = Scratch::new("lint_global");
+    let input = scratch.source("main", "let x := 2 * 7 + 2
+let y := 2 + 7 * 2
+
+func main() {
+    x := 27
+}
+");
+
+    let (ok, diagnostics) = lint(&input, &[]);
+
+    assert!(ok);
+    assert_eq!(diagnostics, [
+        "1:1: warning: variable 'x' is never used [unused-variable]",
+        "2:1: warning: variable 'y' is never used [unused-variable]",
+        "5:5: warning: global 'x' is assigned in 'main' but never read, did you mean `let x`? [unread-global-assignment]",
+    ]);
+}
+
+#[test]
+fn pragmas_override_levels() {
+    let scratch = Scratch::new("lint_pragmas");
+    let input = scratch.source("pragmas", r##"# lint-file: deny(null-comparison)
+func main(_unused) {
+    let a := 1;
+    # lint: allow(shadowing)
+    if (a == 1) { let a := 2; println(a); }
+    if (a == 1) { let a := 3; println(a); }
+    let b := 0; # lint: allow(unused-variable)
+    if (a == null) { println("#"); }
+}
+"##);
+
+    let (ok, diagnostics) = lint(&input, &[]);
+
+    assert!(!ok);
+    assert_eq!(diagnostics, [
+        "6:19: warning: 'a' shadows the declaration on line 3 [shadowing]",
+        "8:5: error: comparison with 'null' using '==' or '!=' [null-comparison]",
+    ]);
+}
+
+#[test]
+fn config_next_to_the_file_sets_levels() {
+    let scratch = Scratch::new("lint_config");
+    let input = scratch.source("config", "func main() {\n    let unused := 1;\n    if (1 < 2) { println(0); }\n}\n");
+    std::fs::write(scratch.path("lint.toml"), "[rules]\nunused-variable = \"deny\" # no dead locals\nconstant-condition = \"allow\"\n").unwrap();
+
+    let (ok, diagnostics) = lint(&input, &[]);
+
+    assert!(!ok);
+    assert_eq!(diagnostics, ["2:5: error: variable 'unused' is never used [unused-variable]"]);
+}
+
+#[test]
+fn unknown_rules_are_rejected() {
+    let scratch = Scratch::new("lint_unknown");
+    let input = scratch.source("unknown", "func main() { println(1); }\n");
+    let config = scratch.path("custom.toml");
+    std::fs::write(&config, "no-such-rule = \"deny\"\n").unwrap();
+
+    let output = ult(&["lint", "--config", config.to_str().unwrap(), input.to_str().unwrap()]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("line 1: unknown lint rule 'no-such-rule'"), "{}", stderr(&output));
+}
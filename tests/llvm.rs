@@ -0,0 +1,193 @@
+mod common;
+
+use common::*;
+use std::process::{ Command, Output };
+
+/// Major version of the local `llc`, if there is one
+fn llc_version() -> Option<u32> {
+    let out = Command::new("llc").arg("--version").output().ok()?;
+    let text = stdout(&out);
+    let version = text.split("LLVM version ").nth(1)?;
+
+    version.split('.').next()?.trim().parse().ok()
+}
+
+/// Emits `.ll` for `source`, then compiles it with `llc` and links it with `cc`
+fn build_and_run(name: &str, source: &str) -> Output {
+    let scratch = Scratch::new(name);
+    let input = scratch.source(name, source);
+    let ll = scratch.path(&format!("{}.ll", name));
+    let obj = scratch.path(&format!("{}.o", name));
+    let exe = scratch.path(name);
+
+    let build = ult(&["build", "--target", "llvm", "--emit-only", "-o", ll.to_str().unwrap(), input.to_str().unwrap()]);
+    assert!(build.status.success(), "build failed: {}", stderr(&build));
+
+    let version = llc_version().expect("llc not found");
+
+    let mut llc = Command::new("llc");
+    // Opaque pointers became the default in LLVM 15
+    if version < 15 {
+        llc.arg("-opaque-pointers");
+    }
+
+    let compile = llc.args(["-relocation-model=pic", "-filetype=obj", "-o"]).arg(&obj).arg(&ll).output().unwrap();
+    assert!(compile.status.success(), "llc failed: {}", stderr(&compile));
+
+    let link = Command::new("cc").arg(&obj).arg("-o").arg(&exe).output().unwrap();
+    assert!(link.status.success(), "link failed: {}", stderr(&link));
+
+    Command::new(&exe).output().unwrap()
+}
+
+#[test]
+#[ignore = "needs llc, run with --include-ignored"]
+fn control_flow_and_printing() {
+    let out = build_and_run("llvm_flow", r#"
+        let scale := 2
+
+        func fib(n) {
+            if (n < 2) { return n }
+            return fib(n - 1) + fib(n - 2)
+        }
+
+        func main() {
+            let total := 0
+            for (let i := 0; i < 10; i := i + 1) {
+                if (i == 3) { continue }
+                if (i == 8) { break }
+                total := total + i
+            }
+            let k := 0
+            while (k < 3) { k := k + 1 }
+            let total := total * 2
+            println("fib 100%", fib(20), total, k > 2, 'z', -scale, ~5, 17 % 5, !k, 2 ** 3 ** 2, 2 * 3 ** 2)
+            return fib(10) * scale
+        }
+    "#);
+
+    assert_eq!(stdout(&out), "fib 100% 6765 50 true z -2 -6 2 false 512 18\n");
+    assert_eq!(out.status.code(), Some(110));
+}
+
+#[test]
+#[ignore = "needs llc, run with --include-ignored"]
+fn division_by_zero_exits_with_error() {
+    let out = build_and_run("llvm_div", r#"
+        func main() {
+            let zero := 0
+            println("before")
+            println(1 / zero)
+        }
+    "#);
+
+    assert_eq!(stdout(&out), "before\n");
+    assert_eq!(stderr(&out), "Division by zero\n");
+    assert_eq!(out.status.code(), Some(1));
+}
+
+/// Emits `.ll` for `source`, returning the text or the build's error output
+fn emit(scratch: &Scratch, name: &str, source: &str) -> Result<String, String> {
+    let input = scratch.source(name, source);
+    let ll = scratch.path(&format!("{}.ll", name));
+
+    let build = ult(&["build", "--target", "llvm", "--emit-only", "-o", ll.to_str().unwrap(), input.to_str().unwrap()]);
+
+    match build.status.success() {
+        true => Ok(std::fs::read_to_string(ll).unwrap()),
+        false => Err(stderr(&build)),
+    }
+}
+
+/// Runs `body` in `main` after `min` and `neg` are bound to the minimum integer and -1
+fn run_with_extremes(name: &str, body: &str) -> Output {
+    build_and_run(name, &format!("func main() {{\nlet min := -9223372036854775807 - 1\nlet neg := -1\n{}\n}}", body))
+}
+
+#[test]
+#[ignore = "needs llc, run with --include-ignored"]
+fn overflow_exits_with_error() {
+    for (i, body) in ["println(min + neg)", "println(min - 1)", "println(min * neg)", "println(-min)", "println(abs(min))",
+                      "println(min / neg)", "println(min % neg)", "println(2 ** 63)", "println(1 << 70)", "println(1 >> neg)"].iter().enumerate() {
+        let out = run_with_extremes(&format!("llvm_overflow_{}", i), body);
+
+        assert_eq!(out.status.code(), Some(1), "{}", body);
+        assert_eq!(stderr(&out), "Integer overflow\n", "{}", body);
+    }
+}
+
+#[test]
+#[ignore = "needs llc, run with --include-ignored"]
+fn checked_arithmetic_in_range() {
+    let out = run_with_extremes("llvm_in_range", "println(7 / neg, 7 % neg, (min + 1) / neg, 2 ** 62, (-2) ** 63, 1 << 63, -8 >> 63)");
+
+    assert_eq!(stdout(&out), "-7 0 9223372036854775807 4611686018427387904 -9223372036854775808 -9223372036854775808 -1\n");
+    assert!(out.status.success(), "{}", stderr(&out));
+}
+
+#[test]
+#[ignore = "needs llc, run with --include-ignored"]
+fn negative_exponents_are_errors() {
+    // A global isn't folded, so the exponent is only known at runtime
+    let out = build_and_run("llvm_negative_exponent", "let e := -1\nfunc main() { println(2 ** 0)\nprintln(2 ** e) }");
+
+    assert_eq!(stdout(&out), "1\n");
+    assert_eq!(stderr(&out), "Negative exponents need decimal arithmetic\n");
+    assert_eq!(out.status.code(), Some(1));
+}
+
+#[test]
+fn locals_are_values_of_the_optimized_ir() {
+    let scratch = Scratch::new("llvm_text");
+    let text = emit(&scratch, "sum", "func sum(n) {\n    let total := 0\n    for (i in 0..n) { total += i * (2 + 3) }\n    return total\n}").unwrap();
+
+    // Locals are phis instead of memory, and `2 + 3` was folded at `-O2`
+    assert!(text.contains("define internal i64 @ult_fn_sum(i64 %p0) {\nbb0:\n"), "{}", text);
+    assert!(text.contains(" = phi i64 [ 0, %bb0 ], "), "{}", text);
+    assert!(text.contains(" = call i64 @ult_mul(i64 %v5, i64 5)\n"), "{}", text);
+    assert!(!text.contains("alloca"), "{}", text);
+}
+
+#[test]
+fn values_other_than_integers_are_rejected() {
+    let scratch = Scratch::new("llvm_unsupported");
+
+    let err = emit(&scratch, "array", "func main() { let xs := [1]\nreturn xs[0] }").unwrap_err();
+    assert!(err.contains("Unsupported(\"arrays\")"), "{}", err);
+
+    let err = emit(&scratch, "len", "func main() { return len(\"ab\") }").unwrap_err();
+    assert!(err.contains("Unsupported(\"strings outside of print\")"), "{}", err);
+
+    let err = emit(&scratch, "exponent", "func main() { return 2 ** -1 }").unwrap_err();
+    assert!(err.contains("Unsupported(\"decimals\")"), "{}", err);
+
+    let err = emit(&scratch, "arity", "func main() { return abs(1, 2) }").unwrap_err();
+    assert!(err.contains("ArityMismatch(\"abs\", 1, 2)"), "{}", err);
+}
+
+#[test]
+#[ignore = "needs llc, run with --include-ignored"]
+fn shared_programs() {
+    for (name, program, output, code) in INT_PROGRAMS {
+        let out = build_and_run(&format!("llvm_{}", name), program);
+
+        assert_eq!(stdout(&out), output, "{}", name);
+        assert_eq!(out.status.code(), Some(code), "{}", name);
+    }
+}
+
+#[test]
+#[ignore = "needs llc, run with --include-ignored"]
+fn math_builtins() {
+    let out = build_and_run("llvm_math", r#"
+        func main() {
+            let a := -7
+            println(abs(a), abs(5), min(3, a), max(3, a), min(2, 2), pow(2, 10), pow(3, 0))
+            println(max(min(10, 4 * 3), abs(-2)) + pow(2, 3 ** 1))
+            return abs(a) + max(1, 2)
+        }
+    "#);
+
+    assert_eq!(stdout(&out), "7 5 -7 3 2 1024 1\n18\n");
+    assert_eq!(out.status.code(), Some(9));
+}
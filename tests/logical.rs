@@ -0,0 +1,43 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn decided_right_sides_are_skipped() {
+    assert_eq!(logged("let r := false && at(true, \"a\")\nreturn (r, log)"), "(false, \"\")");
+    assert_eq!(logged("let r := null && at(1, \"a\")\nreturn (r, log)"), "(false, \"\")");
+    assert_eq!(logged("let r := true || at(false, \"a\")\nreturn (r, log)"), "(true, \"\")");
+    assert_eq!(logged("let r := 1 || at(0, \"a\")\nreturn (r, log)"), "(true, \"\")");
+}
+
+#[test]
+fn undecided_right_sides_run() {
+    assert_eq!(logged("let r := true && at(0, \"a\")\nreturn (r, log)"), "(false, \"a\")");
+    assert_eq!(logged("let r := false || at(\"x\", \"a\")\nreturn (r, log)"), "(true, \"a\")");
+}
+
+#[test]
+fn operands_run_left_to_right() {
+    assert_eq!(logged("let r := [at(1, \"a\") && at(2, \"b\"), at(0, \"c\") || at(null, \"d\")]\nreturn (r, log)"), "([true, false], \"abcd\")");
+    assert_eq!(logged("let r := false && at(1, \"a\") || at(true, \"b\")\nreturn (r, log)"), "(true, \"b\")");
+}
+
+#[test]
+fn skipped_sides_cannot_fail() {
+    assert_eq!(value("return false && (1 / 0)"), "false");
+    assert_eq!(value("let xs := [1]\nreturn true || xs[4]"), "true");
+    assert_eq!(eval("func main() { return true && (1 / 0) }").unwrap_err(), "Division by zero");
+}
+
+#[test]
+fn skipped_sides_do_not_assign() {
+    assert_eq!(value("let n := 0\nfalse && (n := 1)\ntrue || (n += 2)\nreturn n"), "0");
+    assert_eq!(value("let n := 0\ntrue && (n := 1)\nfalse || (n += 2)\nreturn n"), "3");
+}
+
+#[test]
+fn results_are_booleans() {
+    assert_eq!(value("return 2 && 'x'"), "true");
+    assert_eq!(value("return null || 0"), "false");
+    assert_eq!(value("return false || true && false"), "false");
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use common::*;
+use std::io::Write;
+use std::process::{ Command, Stdio };
+use ult::stdlib::BUILTINS;
+
+#[test]
+fn strings() {
+    assert_eq!(value("return len(\"héllo\")"), "5");
+    assert_eq!(value("return slice(\"héllo\", 1, 3)"), "él");
+    assert_eq!(value("return split(\"a,b,,c\", \",\")"), r#"["a", "b", "", "c"]"#);
+    assert_eq!(value("return split(\"ab\", \"\")"), r#"["a", "b"]"#);
+    assert_eq!(value("return find(\"héllo\", 'l')"), "2");
+    assert_eq!(value("return find(\"hello\", \"z\")"), "-1");
+    assert_eq!(value("return upper(\"Ult\") + lower(\"ULT\")"), "ULTult");
+}
+
+#[test]
+fn math() {
+    assert_eq!(value("return sqrt(16)"), "4.0");
+    assert_eq!(value("return pow(2, 10)"), "1024");
+    assert_eq!(value("return pow(2, 0.5) == sqrt(2)"), "true");
+    assert_eq!(value("return sin(0) + cos(0) + tan(0)"), "1.0");
+    assert_eq!(value("return min(3, 2.5)"), "2.5");
+    assert_eq!(value("return max(\"a\", \"b\")"), "b");
+    assert_eq!(value("return abs(0 - 7)"), "7");
+    assert_eq!(value("return abs(0.0 - 1.5)"), "1.5");
+}
+
+#[test]
+fn arrays() {
+    let prelude = "
+        func double(x) { return x * 2 }
+        func odd(x) { return x % 2 }
+
+        func sorted() {
+            let xs := [3, 1.5, 2, 0.5]
+            sort(xs)
+            return xs
+        }
+
+        func stack() {
+            let xs := [1]
+            push(xs, 2)
+            let top := pop(xs)
+            let empty := []
+            let popped := [top, pop(xs), pop(xs), pop(empty), len(xs)]
+            return popped
+        }
+    ";
+    let returned = |prelude: &str, expr: &str| eval(&format!("{}\nfunc main() {{ return {} }}", prelude, expr)).unwrap();
+
+    assert_eq!(returned(prelude, "sorted()"), "[0.5, 1.5, 2, 3]");
+    assert_eq!(returned(prelude, "stack()"), "[2, 1, null, null, 0]");
+
+    let prelude = format!("{}\nlet xs := [1, 2, 3, 4]", prelude);
+    assert_eq!(returned(&prelude, "map(xs, double)"), "[2, 4, 6, 8]");
+    assert_eq!(returned(&prelude, "filter(xs, odd)"), "[1, 3]");
+    assert_eq!(returned(&prelude, "slice(xs, 1, 3)"), "[2, 3]");
+    assert_eq!(returned(&prelude, "len(xs)"), "4");
+}
+
+#[test]
+fn errors_name_the_function() {
+    assert_eq!(eval("func main() { return len(5) }").unwrap_err(),
+        "Type error: argument 1 of 'len': expected String, Array, Tuple, Map or Set, got Integer");
+    assert_eq!(eval("func main() { return slice(\"abc\", 2, 5) }").unwrap_err(),
+        "Error in native function 'slice': range 2..5 out of bounds for length 3");
+    assert_eq!(eval("func main() { return upper(1) }").unwrap_err(),
+        "Type error: argument 1 of 'upper': expected String, got Integer");
+    assert_eq!(eval("func main() { return abs(0 - 9223372036854775807 - 1) }").unwrap_err(), "Integer overflow");
+    assert_eq!(eval("func main() { return sqrt(1, 2) }").unwrap_err(), "Function 'sqrt' expected 1 arguments but got 2");
+    assert_eq!(eval("let xs := [1, \"a\"]\nfunc main() { return sort(xs) }").unwrap_err(),
+        "Type error: cannot apply '<' to Integer and String");
+    assert!(eval("func main() { return read_file(\"/nonexistent/ult\") }").unwrap_err()
+        .starts_with("Error in native function 'read_file': "));
+}
+
+#[test]
+fn every_builtin_is_installed() {
+    for builtin in BUILTINS {
+        assert_eq!(value(&format!("return {}", builtin.name)), format!("<native func {}>", builtin.name));
+        assert!(builtin.signature.starts_with(&format!("{}(", builtin.name)));
+        assert!(!builtin.doc.is_empty());
+    }
+}
+
+#[test]
+fn globals_replace_builtins() {
+    assert_eq!(eval("func len(x) { return 42 }\nfunc main() { return len(\"abc\") }").unwrap(), "42");
+}
+
+#[test]
+fn shared_program() {
+    let scratch = Scratch::new("stdlib_shared");
+    let input = scratch.source("shared", STDLIB_PROGRAM);
+
+    let out = ult(&[input.to_str().unwrap()]);
+
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert_eq!(stdout(&out), STDLIB_OUTPUT);
+}
+
+#[test]
+fn io_through_stdin_stdout_and_files() {
+    let scratch = Scratch::new("stdlib_io");
+    let file = scratch.path("out.txt");
+    let input = scratch.source("io", &format!(r#"
+        func main() {{
+            let first := read_line()
+            let second := read_line()
+            let lines := [first, 2]
+            write_file("{path}", lines)
+            print("read", first, second)
+            println("", read_file("{path}"), read_line())
+            return 0
+        }}
+    "#, path = file.display()));
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ult"))
+        .arg(&input)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(b"one\r\ntwo").unwrap();
+    let out = child.wait_with_output().unwrap();
+
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert_eq!(stdout(&out), "read one two [\"one\", 2] null\n");
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "[\"one\", 2]");
+}
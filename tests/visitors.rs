@@ -0,0 +1,113 @@
+use ult::ast::{ Declaration, Expression, Identifier, Literal, Statement, AST };
+use ult::parse::fold::{ walk_expression, Folder };
+use ult::parse::visit::Visitor;
+use ult::parse::visit_mut::VisitorMut;
+use ult::Token;
+
+fn parse(source: &str) -> AST {
+    ult::parse(&ult::lex(source).unwrap()).unwrap()
+}
+
+/// Every identifier in the tree, in visiting order
+fn names(ast: &AST) -> Vec<String> {
+    struct Names(Vec<String>);
+
+    impl Visitor for Names {
+        fn visit_identifier(&mut self, identifier: &Identifier) {
+            self.0.push(identifier.name());
+        }
+    }
+
+    let mut names = Names(vec![]);
+    names.visit_ast(ast);
+    names.0
+}
+
+/// Renames a variable wherever it's bound or used
+struct Rename(&'static str, &'static str);
+
+impl VisitorMut for Rename {
+    fn visit_identifier_mut(&mut self, identifier: &mut Identifier) {
+        if identifier.name() == self.0 {
+            identifier.set_name(self.1.to_string());
+        }
+    }
+}
+
+/// Folds `+` and `*` of integer literals into a literal, bottom up
+struct Arithmetic;
+
+impl Folder for Arithmetic {
+    fn fold_expression(&mut self, expr: Expression) -> Expression {
+        use Expression::Literal as Lit;
+        use Literal::Integer;
+
+        match walk_expression(self, expr) {
+            Expression::Binary { lhs, operation, rhs } => match (*lhs, operation, *rhs) {
+                (Lit(Integer(a)), Token::Plus, Lit(Integer(b))) => Lit(Integer(a + b)),
+                (Lit(Integer(a)), Token::Multiply, Lit(Integer(b))) => Lit(Integer(a * b)),
+                (lhs, operation, rhs) => Expression::binary(lhs, operation, rhs),
+            },
+            expr => expr,
+        }
+    }
+}
+
+#[test]
+fn visitor_mut_renames_in_place() {
+    let source = "let n := 2\n\
+                  func double(n) { return n * 2 }\n\
+                  func main() {\n\
+                      let (a, n) := (1, n)\n\
+                      for (n in 0..n) { a += n }\n\
+                      try { throw n } catch (n) { a += n }\n\
+                      return double(a)\n\
+                  }\n\
+                  test \"uses n\" { assert n == 2 }\n";
+
+    let mut ast = parse(source);
+    let before = names(&ast);
+
+    Rename("n", "count").visit_ast_mut(&mut ast);
+
+    let after = names(&ast);
+    let renamed = before.iter().map(|name| if name == "n" { "count" } else { name }).collect::<Vec<_>>();
+
+    assert_eq!(before.iter().filter(|name| *name == "n").count(), 12);
+    assert_eq!(after, renamed);
+    assert_eq!(ast.spans(), parse(source).spans());
+    assert_eq!(ult::eval(&ast).unwrap().to_string(), ult::eval(&parse(source)).unwrap().to_string());
+}
+
+#[test]
+fn folder_replaces_nodes() {
+    let source = "let x := 1 + 2\n\
+                  func main() {\n\
+                      let y := (1 + 2) * 4 + x\n\
+                      let ys := [y * (2 + 3) for y in 0..2]\n\
+                      return ys\n\
+                  }\n\
+                  test \"folds\" { assert (2 * 3) == 6 }\n";
+
+    let ast = Arithmetic.fold_ast(parse(source));
+
+    let Declaration::Variable { value: Some(x), .. } = &ast.program()[0] else { panic!("{:?}", ast.program()[0]) };
+    assert!(matches!(x, Expression::Literal(Literal::Integer(3))), "{:?}", x);
+
+    let Declaration::Function { body, .. } = &ast.program()[1] else { panic!("{:?}", ast.program()[1]) };
+    let Statement::Declaration(Declaration::Variable { value: Some(y), .. }) = &body.statements()[0] else { panic!("{:?}", body) };
+    let Expression::Binary { lhs, operation: Token::Plus, rhs } = y else { panic!("{:?}", y) };
+    assert!(matches!(**lhs, Expression::Literal(Literal::Integer(12))), "{:?}", y);
+    assert!(matches!(&**rhs, Expression::Value(x) if x.name() == "x"), "{:?}", y);
+
+    // The comprehension's loop is folded too, and only `2 + 3` in it is constant
+    let comprehension = format!("{:?}", body.statements()[1]);
+    assert!(comprehension.contains("rhs: Literal(Integer(5))"), "{}", comprehension);
+    assert!(!comprehension.contains("Plus"), "{}", comprehension);
+
+    let test = format!("{:?}", ast.tests()[0].body);
+    assert!(test.contains("condition: Binary { lhs: Literal(Integer(6)), operation: Equals, rhs: Literal(Integer(6)) }"), "{}", test);
+
+    assert_eq!(ast.spans(), parse(source).spans());
+    assert_eq!(ult::eval(&ast).unwrap().to_string(), "[0, 5]");
+}
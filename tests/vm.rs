@@ -0,0 +1,152 @@
+mod common;
+
+use common::*;
+use ult::codegen::compiler::Compiler;
+
+fn disassemble(source: &str) -> String {
+    let ast = ult::parse(&ult::lex(source).unwrap()).unwrap();
+    let mut compiler = Compiler::new(&ast);
+
+    compiler.compile().unwrap();
+
+    // Instructions without operands are padded to the operand column
+    compiler.program().unwrap().disassemble().lines().map(|l| format!("{}\n", l.trim_end())).collect()
+}
+
+const LOOP_PROGRAM: &str = "func main() {
+    let i := 0
+    while (i < 3) {
+        i += 1
+        if (i == 2) { continue }
+    }
+    return i
+}
+";
+
+const LOOP_DISASSEMBLY: &str = "== globals ==
+   0 main
+
+== <script> (0 args) ==
+0000 CONSTANT          0 <func main>
+0003 DEFINE_GLOBAL     0 (main)
+0006 GET_GLOBAL        0 (main)
+0009 CALL              0
+0011 RETURN
+
+== main (0 args) ==
+0000 CONSTANT          0 0
+0003 GET_LOCAL         0
+0006 CONSTANT          1 3
+0009 LESS
+0010 JUMP_IF_FALSE    27 -> 0040
+0013 GET_LOCAL         0
+0016 CONSTANT          2 1
+0019 ADD
+0020 SET_LOCAL         0
+0023 POP
+0024 GET_LOCAL         0
+0027 CONSTANT          3 2
+0030 EQUAL
+0031 JUMP_IF_FALSE     3 -> 0037
+0034 LOOP             34 -> 0003
+0037 LOOP             37 -> 0003
+0040 GET_LOCAL         0
+0043 RETURN
+0044 NULL
+0045 RETURN
+";
+
+#[test]
+fn locals_live_in_slots_after_the_arguments() {
+    let source = "func add(a, b) {\n    let sum := a + b\n    {\n        let sum := sum * 10\n        b := sum\n    }\n    return sum + b\n}\n\
+                  func main() { return add(1, 2) }";
+
+    assert_eq!(eval(source).unwrap(), "33");
+
+    let code = disassemble(source);
+    let add = &code[code.find("== add (2 args) ==").unwrap()..code.find("== main").unwrap()];
+
+    // `a` and `b` are slots 0 and 1, the outer `sum` 2 and the inner one 3
+    assert_eq!(add.lines().filter(|l| l.contains("_LOCAL")).map(|l| &l[5..]).collect::<Vec<_>>(), [
+        "GET_LOCAL         0",
+        "GET_LOCAL         1",
+        "GET_LOCAL         2",
+        "GET_LOCAL         3",
+        "SET_LOCAL         1",
+        "GET_LOCAL         2",
+        "GET_LOCAL         1",
+    ]);
+}
+
+#[test]
+fn shadowing_reuses_names_but_not_slots() {
+    assert_eq!(value("let x := 1\n{ let x := x + 1\nx *= 10 }\nreturn x"), "1");
+    assert_eq!(value("let x := 1\nlet x := x + 1\nreturn x"), "2");
+    assert_eq!(value("let x := 1\nif (x == 1) { let x := \"inner\"\nreturn x }\nreturn x"), "inner");
+    assert_eq!(eval("let x := 1\nfunc main() { let x := 2\nreturn x }").unwrap(), "2");
+    assert_eq!(eval("let x := 1\nfunc main() { { let x := 2 }\nreturn x }").unwrap(), "1");
+}
+
+#[test]
+fn break_and_continue_in_for_and_while() {
+    assert_eq!(value("let s := 0\nfor (let i := 0; i < 10; i += 1) { if (i == 3) { continue }\nif (i == 6) { break }\ns += i }\nreturn s"), "12");
+    assert_eq!(value("let s := 0\nlet i := 0\nwhile (i < 10) { i += 1\nif ((i % 2) == 0) { continue }\nif (i > 7) { break }\ns += i }\nreturn (s, i)"), "(16, 9)");
+    // A `break` only leaves the innermost loop, and pops the locals declared inside it
+    assert_eq!(value("let n := 0\nfor (let i := 0; i < 3; i += 1) { let a := i\nwhile (true) { let b := a\nn += b\nbreak } }\nreturn n"), "3");
+    assert_eq!(value("let n := 0\nfor (let i := 0; i < 3; i += 1) { let a := 1\nfor (let j := 0; j < 3; j += 1) { if (j == i) { continue }\nn += a } }\nreturn n"), "6");
+}
+
+#[test]
+fn loops_compile_to_jumps() {
+    assert_eq!(disassemble(LOOP_PROGRAM), LOOP_DISASSEMBLY);
+    assert_eq!(eval(LOOP_PROGRAM).unwrap(), "3");
+}
+
+#[test]
+fn debug_prints_the_disassembly() {
+    let scratch = Scratch::new("vm_dump");
+    let input = scratch.source("dump", LOOP_PROGRAM);
+
+    let out = ult(&["--debug", input.to_str().unwrap()]);
+
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert_eq!(stdout(&out), "");
+    let dump = stderr(&out).lines().map(|l| format!("{}\n", l.trim_end())).collect::<String>();
+    assert!(dump.contains(LOOP_DISASSEMBLY), "{}", dump);
+    assert!(dump.contains("Result: 3\n"), "{}", dump);
+
+    let out = ult(&[input.to_str().unwrap()]);
+
+    assert_eq!(stdout(&out), "");
+    assert_eq!(stderr(&out), "");
+}
+
+#[test]
+fn calls_check_their_arity() {
+    let source = "func add(a, b) { return a + b }\nfunc main() { return add(1) }";
+    assert_eq!(eval(source).unwrap_err(), "Function 'add' expected 2 arguments but got 1");
+
+    let source = "func none() { return 1 }\nfunc main() { return none(1, 2) }";
+    assert_eq!(eval(source).unwrap_err(), "Function 'none' expected 0 arguments but got 2");
+
+    assert_eq!(eval("func main() { return len(\"ab\", 1) }").unwrap_err(), "Function 'len' expected 1 arguments but got 2");
+}
+
+#[test]
+fn runtime_errors_stop_the_program() {
+    assert_eq!(eval("func main() { return 1 + true }").unwrap_err(), "Type error: cannot apply '+' to Integer and Boolean");
+    assert_eq!(eval("func main() { return missing }").unwrap_err(), "Undefined variable 'missing'");
+    assert_eq!(eval("func main() { let x := 1\nreturn x() }").unwrap_err(), "Value of type Integer is not callable");
+    assert_eq!(eval("func main() { return 1 % 0 }").unwrap_err(), "Division by zero");
+    assert_eq!(eval("func main() { return 9223372036854775807 + 1 }").unwrap_err(), "Integer overflow");
+    assert_eq!(eval("func f(n) { return f(n + 1) }\nfunc main() { return f(0) }").unwrap_err(), "Stack overflow");
+
+    let scratch = Scratch::new("vm_error");
+    let input = scratch.source("error", "func main() {\n    println(\"before\")\n    return 1 / 0\n}\n");
+
+    let out = ult(&[input.to_str().unwrap()]);
+
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(stdout(&out), "before\n");
+    assert!(stderr(&out).starts_with("Runtime error: Division by zero\n"), "{}", stderr(&out));
+}
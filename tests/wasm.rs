@@ -0,0 +1,190 @@
+mod common;
+
+use common::*;
+use std::process::{ Command, Output };
+
+/// Validates the module, instantiates it with the `env` imports and calls `main`, exiting with
+/// its result
+const HOST: &str = r#"
+const bytes = require('fs').readFileSync(process.argv[2]);
+if (!WebAssembly.validate(bytes)) { console.error('invalid module'); process.exit(100); }
+let memory;
+const out = s => process.stdout.write(s);
+const env = {
+    print_i64: v => out(v.toString()),
+    print_f64: v => out(Number.isInteger(v) ? v.toFixed(1) : String(v)),
+    print_bool: v => out(v !== 0n ? 'true' : 'false'),
+    print_char: v => out(String.fromCodePoint(Number(v))),
+    print_str: (p, n) => out(Buffer.from(memory.buffer, p, n).toString()),
+    fail: (p, n) => { process.stderr.write(Buffer.from(memory.buffer, p, n).toString() + '\n'); process.exit(1); },
+};
+WebAssembly.instantiate(bytes, { env }).then(({ instance }) => {
+    memory = instance.exports.memory;
+    process.exitCode = Number(instance.exports.main()) & 0xff;
+});
+"#;
+
+fn node_available() -> bool {
+    Command::new("node").arg("--version").output().is_ok_and(|o| o.status.success())
+}
+
+/// Builds `source` to a `.wasm` module and runs it under node, or `None` without node
+fn build_and_run(name: &str, source: &str) -> Option<Output> {
+    if !node_available() {
+        eprintln!("skipping {}: node not found", name);
+        return None
+    }
+
+    let scratch = Scratch::new(name);
+    let input = scratch.source(name, source);
+    let module = scratch.path(&format!("{}.wasm", name));
+    let host = scratch.path("host.js");
+    std::fs::write(&host, HOST).unwrap();
+
+    let build = ult(&["build", "--target", "wasm32", "-o", module.to_str().unwrap(), input.to_str().unwrap()]);
+    assert!(build.status.success(), "build failed: {}", stderr(&build));
+
+    Some(Command::new("node").arg(&host).arg(&module).output().unwrap())
+}
+
+#[test]
+fn integers_and_control_flow() {
+    let Some(out) = build_and_run("wasm_ints", r#"
+        let scale := 2
+
+        func fib(n) {
+            if (n < 2) { return n }
+            return fib(n - 1) + fib(n - 2)
+        }
+
+        func main() {
+            let total := 0
+            for (let i := 0; i < 10; i := i + 1) {
+                if (i == 3) { continue }
+                if (i == 8) { break }
+                total := total + i
+            }
+            let k := 0
+            while (k < 3) { k := k + 1 }
+            println("fib", fib(20), total, k > 2, 'z', -scale, ~5, 17 % 5, 2 ** 3 ** 2, 2 * 3 ** 2)
+            return fib(10) * scale
+        }
+    "#) else { return };
+
+    assert_eq!(stderr(&out), "");
+    assert_eq!(stdout(&out), "fib 6765 25 true z -2 -6 2 512 18\n");
+    assert_eq!(out.status.code(), Some(110));
+}
+
+#[test]
+fn decimals_are_inferred() {
+    let Some(out) = build_and_run("wasm_decimals", r#"
+        func half(v) { return v / 2.0 }
+
+        func main() {
+            let avg := 1
+            avg := half(25)
+            let x := 3
+            x := x + 0.5
+            println(avg, x, half(4), 1.5 < 2)
+            return 0
+        }
+    "#) else { return };
+
+    assert_eq!(stderr(&out), "");
+    assert_eq!(stdout(&out), "12.5 3.5 2.0 true\n");
+}
+
+#[test]
+fn emits_text_format() {
+    let scratch = Scratch::new("wasm_text");
+    let input = scratch.source("wasm_text", "func main() { return 1 }");
+    let wat = scratch.path("wasm_text.wat");
+
+    let build = ult(&["build", "--target", "wasm32", "--emit-only", "-o", wat.to_str().unwrap(), input.to_str().unwrap()]);
+    assert!(build.status.success(), "build failed: {}", stderr(&build));
+
+    let text = std::fs::read_to_string(wat).unwrap();
+    assert!(text.starts_with("(module"));
+    assert!(text.contains("(export \"main\" (func $main))"));
+}
+
+#[test]
+fn shared_programs() {
+    for (name, program, output, code) in INT_PROGRAMS {
+        let Some(out) = build_and_run(&format!("wasm_{}", name), program) else { return };
+
+        assert_eq!(stderr(&out), "", "{}", name);
+        assert_eq!(stdout(&out), output, "{}", name);
+        assert_eq!(out.status.code(), Some(code), "{}", name);
+    }
+}
+
+#[test]
+fn conditionals_join_their_types() {
+    let Some(out) = build_and_run("wasm_conditional_types", "func main() { let x := 2\nprintln(x > 1 ? 0.5 : x, x > 3 ? 0.5 : x) }") else { return };
+
+    assert_eq!(stdout(&out), "0.5 2.0\n");
+}
+
+#[test]
+fn math_builtins() {
+    let Some(out) = build_and_run("wasm_math", r#"
+        func main() {
+            let a := -7
+            println(abs(a), abs(5), min(3, a), max(3, a), min(2, 2), pow(2, 10), pow(3, 0))
+            println(max(min(10, 4 * 3), abs(-2)) + pow(2, 3 ** 1))
+            println(abs(-2.5), min(1.5, 0.5), max(1.5, 0.5), sqrt(16), sqrt(2.25), min('a', 'b'))
+            return abs(a) + max(1, 2)
+        }
+    "#) else { return };
+
+    assert_eq!(stderr(&out), "");
+    assert_eq!(stdout(&out), "7 5 -7 3 2 1024 1\n18\n2.5 0.5 1.5 4.0 1.5 a\n");
+    assert_eq!(out.status.code(), Some(9));
+}
+
+fn run_with_extremes(name: &str, body: &str) -> Option<Output> {
+    build_and_run(name, &format!("func main() {{\nlet min := -9223372036854775807 - 1\nlet neg := -1\n{}\n}}", body))
+}
+
+#[test]
+fn overflow_is_a_runtime_error() {
+    for (i, body) in ["println(min + neg)", "println(min - 1)", "println(min * neg)", "println(neg * min)", "println(-min)",
+                      "println(abs(min))", "println(min / neg)", "println(min % neg)", "println(2 ** 63)", "println(3037000500 * 3037000500)",
+                      "let x := min\nx--", "let x := min\nx -= 1"].iter().enumerate() {
+        let Some(out) = run_with_extremes(&format!("wasm_overflow_{}", i), body) else { return };
+
+        assert_eq!(stderr(&out), "Integer overflow\n", "{}", body);
+        assert_eq!(out.status.code(), Some(1), "{}", body);
+    }
+}
+
+#[test]
+fn checked_arithmetic_in_range() {
+    let Some(out) = run_with_extremes("wasm_in_range", r#"
+        println(7 / neg, 7 % neg, (min + 1) / neg, min * 1, -3 * 3037000499, 2 ** 62, (-2) ** 63)
+        println(-7 / 2, -7 % 2, 1 << 63, -8 >> 63, 1 << 0)
+    "#) else { return };
+
+    assert_eq!(stderr(&out), "");
+    assert_eq!(stdout(&out), "-7 0 9223372036854775807 -9223372036854775808 -9111001497 4611686018427387904 -9223372036854775808\n\
+                              -3 -1 -9223372036854775808 -1 1\n");
+}
+
+#[test]
+fn invalid_operands_are_runtime_errors() {
+    for (i, (body, error)) in [
+        ("println(1 / (neg + 1))", "Division by zero"),
+        ("println(1 % (neg + 1))", "Division by zero"),
+        ("println(1 << 70)", "Integer overflow"),
+        ("println(1 >> 64)", "Integer overflow"),
+        ("println(1 << neg)", "Integer overflow"),
+        ("println(2 ** neg)", "Negative exponents need decimal arithmetic"),
+    ].iter().enumerate() {
+        let Some(out) = run_with_extremes(&format!("wasm_invalid_{}", i), body) else { return };
+
+        assert_eq!(stderr(&out), format!("{}\n", error), "{}", body);
+        assert_eq!(out.status.code(), Some(1), "{}", body);
+    }
+}
@@ -0,0 +1,208 @@
+mod common;
+
+use common::*;
+use std::process::Command;
+
+/// Builds `source` into a native executable, runs it and returns its output
+fn build_and_run(name: &str, source: &str) -> std::process::Output {
+    let scratch = Scratch::new(name);
+    let input = scratch.source(name, source);
+    let exe = scratch.path(name);
+
+    let build = ult(&["build", "--target", "x86_64-linux", "-o", exe.to_str().unwrap(), input.to_str().unwrap()]);
+    assert!(build.status.success(), "build failed: {}", stderr(&build));
+
+    Command::new(&exe).output().unwrap()
+}
+
+#[test]
+fn arithmetic_and_printing() {
+    let out = build_and_run("x86_arith", r#"
+        let x := 2 * 7 + 2
+        let y := 2 + 7 * 2
+
+        func main() {
+            println("x is", x, "and y is", y)
+            println(-7 / 2, -7 % 2, 1 << 4, 6 & 3, 6 | 3, 6 ^ 3, ~0, 2 ** 3 ** 2, 2 * 3 ** 2)
+            println(x == 16, x != 16, !true)
+            print("no newline")
+        }
+    "#);
+
+    assert!(out.status.success());
+    assert_eq!(stdout(&out), "x is 16 and y is 16\n-3 -1 16 2 7 5 -1 512 18\ntrue false false\nno newline");
+}
+
+#[test]
+fn control_flow_and_recursion() {
+    let out = build_and_run("x86_control", r#"
+        func fib(n) {
+            if (n < 2) { return n }
+            return fib(n - 1) + fib(n - 2)
+        }
+
+        func main() {
+            let total := 0
+            for (let i := 0; i < 10; i := i + 1) {
+                if (i == 3) { continue }
+                if ((i * i) > 50) { break }
+                total := total + i * i
+            }
+
+            let j := 0
+            while (true) {
+                j := j + 1
+                if (j == 5) { break }
+            }
+
+            println(total, j, fib(20))
+        }
+    "#);
+
+    assert_eq!(stdout(&out), "131 5 6765\n");
+}
+
+#[test]
+fn calling_convention() {
+    let out = build_and_run("x86_abi", r#"
+        func weigh(a, b, c, d, e, f, g, h) {
+            return a + b * 2 + c * 3 + d * 4 + e * 5 + f * 6 + g * 7 + h * 8
+        }
+
+        func main() {
+            func twice(v) { return v * 2 }
+            println(weigh(1, 2, 3, 4, 5, 6, 7, twice(4)))
+            return 42
+        }
+    "#);
+
+    assert_eq!(stdout(&out), "204\n");
+    assert_eq!(out.status.code(), Some(42));
+}
+
+#[test]
+fn division_by_zero_exits_with_error() {
+    let out = build_and_run("x86_div_zero", r#"
+        func main() {
+            let zero := 0
+            println(1 / zero)
+        }
+    "#);
+
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(stderr(&out), "Division by zero\n");
+}
+
+/// Runs `body` in `main` after `min` and `neg` are bound to the minimum integer and -1
+fn run_with_extremes(name: &str, body: &str) -> std::process::Output {
+    build_and_run(name, &format!("func main() {{\nlet min := -9223372036854775807 - 1\nlet neg := -1\n{}\n}}", body))
+}
+
+#[test]
+fn overflow_exits_with_error() {
+    for (i, body) in ["println(min + neg)", "println(min - 1)", "println(min * neg)", "println(-min)", "println(abs(min))",
+                      "println(min / neg)", "println(min % neg)", "println(2 ** 63)", "let x := min\nx--", "let x := min\nx -= 1"].iter().enumerate() {
+        let out = run_with_extremes(&format!("x86_overflow_{}", i), body);
+
+        assert_eq!(out.status.code(), Some(1), "{}", body);
+        assert_eq!(stderr(&out), "Integer overflow\n", "{}", body);
+    }
+}
+
+#[test]
+fn dividing_by_minus_one_does_not_fault() {
+    let out = run_with_extremes("x86_minus_one", "println(7 / neg, 7 % neg, (min + 1) / neg, 2 ** 62, (-2) ** 63)");
+
+    assert_eq!(stdout(&out), "-7 0 9223372036854775807 4611686018427387904 -9223372036854775808\n");
+    assert!(out.status.success(), "{}", stderr(&out));
+}
+
+#[test]
+fn shift_counts_outside_the_word_are_errors() {
+    let out = run_with_extremes("x86_shift", "println(1 << 63, -8 >> 63, 1 << 0)");
+    assert_eq!(stdout(&out), "-9223372036854775808 -1 1\n");
+
+    for (i, body) in ["println(1 << 70)", "println(1 >> 64)", "println(1 << neg)"].iter().enumerate() {
+        let out = run_with_extremes(&format!("x86_shift_{}", i), body);
+
+        assert_eq!(out.status.code(), Some(1), "{}", body);
+        assert_eq!(stderr(&out), "Integer overflow\n", "{}", body);
+    }
+}
+
+#[test]
+fn negative_exponents_are_errors() {
+    let out = run_with_extremes("x86_negative_exponent", "println(2 ** 0)\nprintln(2 ** neg)");
+
+    assert_eq!(stdout(&out), "1\n");
+    assert_eq!(stderr(&out), "Negative exponents need decimal arithmetic\n");
+    assert_eq!(out.status.code(), Some(1));
+}
+
+#[test]
+fn shared_programs() {
+    for (name, program, output, code) in INT_PROGRAMS {
+        let out = build_and_run(&format!("x86_{}", name), program);
+
+        assert_eq!(stdout(&out), output, "{}", name);
+        assert_eq!(out.status.code(), Some(code), "{}", name);
+    }
+}
+
+#[test]
+fn null_coalescing_is_rejected() {
+    // Null is zero, so `??` can't tell them apart
+    let scratch = Scratch::new("x86_coalesce");
+    let input = scratch.source("x86_coalesce", "func main() { let x := 0\nreturn x ?? 1 }");
+    let build = ult(&["build", "--target", "x86_64-linux", "-o", scratch.path("out").to_str().unwrap(), input.to_str().unwrap()]);
+
+    assert!(!build.status.success());
+    assert!(stderr(&build).contains("null coalescing"), "{}", stderr(&build));
+}
+
+#[test]
+fn math_builtins() {
+    let out = build_and_run("x86_math", r#"
+        func main() {
+            let a := -7
+            println(abs(a), abs(5), min(3, a), max(3, a), min(2, 2), pow(2, 10), pow(3, 0))
+            println(max(min(10, 4 * 3), abs(-2)) + pow(2, 3 ** 1))
+            return abs(a) + max(1, 2)
+        }
+    "#);
+
+    assert_eq!(stdout(&out), "7 5 -7 3 2 1024 1\n18\n");
+    assert_eq!(out.status.code(), Some(9));
+}
+
+#[test]
+fn builtins_needing_other_values_are_rejected() {
+    let scratch = Scratch::new("x86_builtins");
+    let build = |name: &str, source: &str| {
+        let input = scratch.source(name, source);
+        ult(&["build", "--target", "x86_64-linux", "-o", scratch.path(name).to_str().unwrap(), input.to_str().unwrap()])
+    };
+
+    let out = build("len", "func main() { return len(\"ab\") }");
+    assert!(stderr(&out).contains("standard library function 'len'"), "{}", stderr(&out));
+
+    let out = build("arity", "func main() { return abs(1, 2) }");
+    assert!(stderr(&out).contains("ArityMismatch(\"abs\", 1, 2)"), "{}", stderr(&out));
+
+    // A function of the same name replaces the builtin
+    let out = build("own", "func abs(x) { return 1 }\nfunc main() { return abs(-5) }");
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert_eq!(Command::new(scratch.path("own")).output().unwrap().status.code(), Some(1));
+}
+
+#[test]
+fn only_literal_ranges_are_iterated() {
+    let scratch = Scratch::new("x86_foreach_values");
+    let input = scratch.source("values", "func main() {\nlet n := 3\nfor (x in n) { }\nreturn 0\n}");
+    let output = scratch.path("values");
+
+    let build = ult(&["build", "--target", "x86_64-linux", "--emit-only", "-o", output.to_str().unwrap(), input.to_str().unwrap()]);
+
+    assert!(!build.status.success());
+    assert!(stderr(&build).contains("iterating over values other than ranges"), "{}", stderr(&build));
+}